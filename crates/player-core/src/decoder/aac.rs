@@ -0,0 +1,493 @@
+//! AAC configuration and ADTS framing.
+//!
+//! Reads the AudioSpecificConfig (ISO/IEC 14496-3, 1.6.2.1) stored in MP4
+//! `esds` boxes and Matroska `CodecPrivate`, including explicit and
+//! backward-compatible SBR/PS signalling, and the ADTS headers MPEG-TS and
+//! raw `.aac` streams frame every access unit with. Raw access units can be
+//! wrapped in ADTS and ADTS frames unwrapped, for decoders that expect the
+//! other framing.
+
+use super::bits::BitReader;
+use crate::demuxer::AudioInfo;
+use crate::error::{PlayerError, Result};
+
+/// Sampling frequencies indexed by `samplingFrequencyIndex`.
+const SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// Audio object type of AAC LC.
+pub const AOT_AAC_LC: u8 = 2;
+/// Audio object type of SBR (HE-AAC).
+pub const AOT_SBR: u8 = 5;
+/// Audio object type of PS (HE-AAC v2).
+pub const AOT_PS: u8 = 29;
+
+/// Sync extension types of backward-compatible SBR and PS signalling.
+const SYNC_EXTENSION_SBR: u32 = 0x2B7;
+const SYNC_EXTENSION_PS: u32 = 0x548;
+
+/// Length of an ADTS header without CRC.
+pub const ADTS_HEADER_LEN: usize = 7;
+
+/// Returns the sampling frequency of a `samplingFrequencyIndex`.
+fn sample_rate(index: u8) -> Result<u32> {
+    SAMPLE_RATES
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| PlayerError::decoder("Invalid AAC sampling frequency index"))
+}
+
+/// Returns the number of output channels of a `channelConfiguration`.
+fn config_channels(channel_config: u8) -> u16 {
+    match channel_config {
+        1..=6 => channel_config as u16,
+        7 => 8,
+        _ => 0,
+    }
+}
+
+/// Reads an `audioObjectType` with its escape value.
+fn read_object_type(r: &mut BitReader<'_>) -> Result<u8> {
+    match r.bits(5)? as u8 {
+        31 => Ok(32 + r.bits(6)? as u8),
+        object_type => Ok(object_type),
+    }
+}
+
+/// Reads a `samplingFrequencyIndex`, or the explicit frequency after the
+/// escape index 15. Returns the index and frequency.
+fn read_sample_rate(r: &mut BitReader<'_>) -> Result<(u8, u32)> {
+    match r.bits(4)? as u8 {
+        15 => Ok((15, r.bits(24)?)),
+        index => Ok((index, sample_rate(index)?)),
+    }
+}
+
+/// Counts the channels of a `program_config_element()`.
+fn read_program_config_channels(r: &mut BitReader<'_>) -> Result<u16> {
+    r.skip(4 + 2 + 4)?; // element_instance_tag, object_type, sampling index
+    let front = r.bits(4)?;
+    let side = r.bits(4)?;
+    let back = r.bits(4)?;
+    let lfe = r.bits(2)?;
+    let assoc_data = r.bits(3)?;
+    let cc = r.bits(4)?;
+    for _ in 0..3 {
+        // mono, stereo and matrix mixdown
+        if r.flag()? {
+            r.skip(4)?;
+        }
+    }
+    let mut channels = lfe as u16;
+    for _ in 0..front + side + back {
+        channels += if r.flag()? { 2 } else { 1 };
+        r.skip(4)?;
+    }
+    r.skip((lfe * 4 + assoc_data * 4 + cc * 5) as usize)?;
+    Ok(channels)
+}
+
+/// Decoded AudioSpecificConfig fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+    /// The first signalled audio object type, as used in codec strings:
+    /// 5 or 29 for hierarchically signalled HE-AAC.
+    pub object_type: u8,
+    /// The core audio object type (e.g. 2 for AAC LC).
+    pub base_object_type: u8,
+    /// `samplingFrequencyIndex` of the core, 15 for an explicit frequency.
+    pub sampling_index: u8,
+    /// Core sampling frequency in Hz.
+    pub sample_rate: u32,
+    /// `channelConfiguration`, 0 when a program config element is used.
+    pub channel_config: u8,
+    /// Number of coded channels.
+    pub channels: u16,
+    /// Whether spectral band replication is present.
+    pub sbr: bool,
+    /// Whether parametric stereo is present.
+    pub ps: bool,
+    /// SBR output sampling frequency in Hz, if SBR is present.
+    pub extension_sample_rate: Option<u32>,
+    /// Samples per channel in one core frame (1024 or 960).
+    pub frame_length: u32,
+}
+
+impl AudioSpecificConfig {
+    /// Parses an AudioSpecificConfig.
+    ///
+    /// # Errors
+    /// Returns an error if the config is truncated or uses a reserved
+    /// sampling frequency index.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        let object_type = read_object_type(&mut r)?;
+        let (sampling_index, sample_rate) = read_sample_rate(&mut r)?;
+        let mut channel_config = r.bits(4)? as u8;
+        let mut config = Self {
+            object_type,
+            base_object_type: object_type,
+            sampling_index,
+            sample_rate,
+            channel_config,
+            channels: config_channels(channel_config),
+            sbr: false,
+            ps: false,
+            extension_sample_rate: None,
+            frame_length: 1024,
+        };
+
+        if matches!(object_type, AOT_SBR | AOT_PS) {
+            config.sbr = true;
+            config.ps = object_type == AOT_PS;
+            config.extension_sample_rate = Some(read_sample_rate(&mut r)?.1);
+            config.base_object_type = read_object_type(&mut r)?;
+            if config.base_object_type == 22 {
+                channel_config = r.bits(4)? as u8;
+                config.channel_config = channel_config;
+                config.channels = config_channels(channel_config);
+            }
+        }
+
+        if matches!(
+            config.base_object_type,
+            1..=4 | 6 | 7 | 17 | 19..=23
+        ) {
+            // GASpecificConfig()
+            if r.flag()? {
+                config.frame_length = 960;
+            }
+            if r.flag()? {
+                r.skip(14)?; // coreCoderDelay
+            }
+            let extension_flag = r.flag()?;
+            if channel_config == 0 {
+                config.channels = read_program_config_channels(&mut r)?;
+            }
+            if matches!(config.base_object_type, 6 | 20) {
+                r.skip(3)?; // layerNr
+            }
+            if extension_flag {
+                if config.base_object_type == 22 {
+                    r.skip(5 + 11)?;
+                }
+                if matches!(config.base_object_type, 17 | 19..=23) {
+                    r.skip(3)?;
+                }
+                r.skip(1)?; // extensionFlag3
+            }
+            if matches!(config.base_object_type, 17 | 19..=27) {
+                r.skip(2)?; // epConfig
+            }
+        }
+
+        // Backward-compatible signalling appended after the core config.
+        if !config.sbr && r.remaining() >= 16 {
+            let mut ext = r.clone();
+            if ext.bits(11)? == SYNC_EXTENSION_SBR && read_object_type(&mut ext)? == AOT_SBR {
+                config.sbr = ext.flag()?;
+                if config.sbr {
+                    config.extension_sample_rate = Some(read_sample_rate(&mut ext)?.1);
+                    if ext.remaining() >= 12 && ext.bits(11)? == SYNC_EXTENSION_PS {
+                        config.ps = ext.flag()?;
+                    }
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// Builds the config an ADTS header describes.
+    pub fn from_adts(header: &AdtsHeader) -> Self {
+        Self {
+            object_type: header.object_type,
+            base_object_type: header.object_type,
+            sampling_index: header.sampling_index,
+            sample_rate: header.sample_rate,
+            channel_config: header.channel_config,
+            channels: config_channels(header.channel_config),
+            sbr: false,
+            ps: false,
+            extension_sample_rate: None,
+            frame_length: 1024,
+        }
+    }
+
+    /// Serializes the core fields as a two-byte AudioSpecificConfig, as
+    /// needed to describe an ADTS stream to a decoder expecting raw frames.
+    ///
+    /// # Errors
+    /// Returns an error if the object type or sampling frequency needs an
+    /// escape value.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.base_object_type >= 31 || self.sampling_index >= 13 {
+            return Err(PlayerError::decoder("AAC config needs escape values"));
+        }
+        let bits = ((self.base_object_type as u16) << 11)
+            | ((self.sampling_index as u16) << 7)
+            | ((self.channel_config as u16) << 3);
+        Ok(bits.to_be_bytes().to_vec())
+    }
+
+    /// Returns the sampling frequency of the decoded output, which SBR
+    /// doubles.
+    pub fn output_sample_rate(&self) -> u32 {
+        match (self.sbr, self.extension_sample_rate) {
+            (true, Some(rate)) => rate,
+            (true, None) => self.sample_rate * 2,
+            _ => self.sample_rate,
+        }
+    }
+
+    /// Returns the number of decoded output channels; parametric stereo
+    /// turns a mono core into stereo.
+    pub fn output_channels(&self) -> u16 {
+        if self.ps && self.channels == 1 {
+            2
+        } else {
+            self.channels
+        }
+    }
+
+    /// Returns the number of output samples per channel in one frame.
+    pub fn output_frame_length(&self) -> u32 {
+        if self.output_sample_rate() > self.sample_rate {
+            self.frame_length * 2
+        } else {
+            self.frame_length
+        }
+    }
+
+    /// Returns the codec string, e.g. `mp4a.40.2`.
+    pub fn codec_string(&self) -> String {
+        format!("mp4a.40.{}", self.object_type)
+    }
+
+    /// Overwrites the sound properties with the decoded output format. The
+    /// config takes precedence, as sample entries commonly hardcode two
+    /// channels and report the core rate of HE-AAC.
+    pub fn fill_audio_info(&self, audio: &mut AudioInfo) {
+        let channels = self.output_channels();
+        if channels > 0 && channels != audio.channels {
+            *audio = AudioInfo {
+                bit_depth: audio.bit_depth,
+                ..AudioInfo::new(self.output_sample_rate(), channels)
+            };
+        }
+        audio.sample_rate = self.output_sample_rate();
+    }
+
+    /// Wraps a raw access unit in an ADTS header without CRC.
+    ///
+    /// # Errors
+    /// Returns an error if the config cannot be expressed in ADTS: object
+    /// types above 4, explicit frequencies, or frames over 8191 bytes.
+    pub fn to_adts(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let frame_length = payload.len() + ADTS_HEADER_LEN;
+        if !(1..=4).contains(&self.base_object_type)
+            || self.sampling_index >= 13
+            || self.channel_config > 7
+            || frame_length > 0x1FFF
+        {
+            return Err(PlayerError::decoder("AAC config cannot be stored in ADTS"));
+        }
+        let profile = self.base_object_type - 1;
+        let mut frame = Vec::with_capacity(frame_length);
+        frame.extend([
+            0xFF,
+            0xF1, // MPEG-4, no CRC
+            (profile << 6) | (self.sampling_index << 2) | (self.channel_config >> 2),
+            ((self.channel_config & 3) << 6) | (frame_length >> 11) as u8,
+            (frame_length >> 3) as u8,
+            ((frame_length & 7) << 5) as u8 | 0x1F,
+            0xFC, // buffer fullness 0x7FF, one raw data block
+        ]);
+        frame.extend_from_slice(payload);
+        Ok(frame)
+    }
+}
+
+/// Decoded fields of an ADTS header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdtsHeader {
+    /// Audio object type (`profile + 1`).
+    pub object_type: u8,
+    /// `sampling_frequency_index`.
+    pub sampling_index: u8,
+    /// Sampling frequency in Hz.
+    pub sample_rate: u32,
+    /// `channel_configuration`.
+    pub channel_config: u8,
+    /// Length of the header, 9 bytes when a CRC follows it.
+    pub header_len: usize,
+    /// Length of the whole frame including the header.
+    pub frame_length: usize,
+    /// Number of raw data blocks in the frame.
+    pub raw_blocks: u8,
+}
+
+impl AdtsHeader {
+    /// Parses the header at the start of `data`.
+    ///
+    /// # Errors
+    /// Returns an error if the sync word is missing, the header is
+    /// truncated, or its fields are invalid.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        if data.len() < ADTS_HEADER_LEN || r.bits(12)? != 0xFFF {
+            return Err(PlayerError::decoder("Missing ADTS sync word"));
+        }
+        r.skip(3)?; // ID, layer
+        let protection_absent = r.flag()?;
+        let object_type = r.bits(2)? as u8 + 1;
+        let sampling_index = r.bits(4)? as u8;
+        r.skip(1)?; // private_bit
+        let channel_config = r.bits(3)? as u8;
+        r.skip(4)?; // original_copy, home, copyright bits
+        let header = Self {
+            object_type,
+            sampling_index,
+            sample_rate: sample_rate(sampling_index)?,
+            channel_config,
+            header_len: if protection_absent { 7 } else { 9 },
+            frame_length: r.bits(13)? as usize,
+            raw_blocks: {
+                r.skip(11)?; // adts_buffer_fullness
+                r.bits(2)? as u8 + 1
+            },
+        };
+        if header.frame_length < header.header_len {
+            return Err(PlayerError::decoder("Invalid ADTS frame length"));
+        }
+        Ok(header)
+    }
+
+    /// Returns the AudioSpecificConfig equivalent of the header.
+    pub fn audio_specific_config(&self) -> AudioSpecificConfig {
+        AudioSpecificConfig::from_adts(self)
+    }
+}
+
+/// Returns whether `data` starts with an ADTS sync word.
+pub fn is_adts(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0xFF && data[1] & 0xF6 == 0xF0
+}
+
+/// Splits a buffer of ADTS frames into headers and raw payloads.
+///
+/// # Errors
+/// Returns an error if a header is invalid or a frame runs past the end of
+/// the data.
+pub fn split_adts(data: &[u8]) -> Result<Vec<(AdtsHeader, &[u8])>> {
+    let mut frames = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let header = AdtsHeader::parse(rest)?;
+        let frame = rest
+            .get(..header.frame_length)
+            .ok_or_else(|| PlayerError::decoder("ADTS frame exceeds packet"))?;
+        frames.push((header, &frame[header.header_len..]));
+        rest = &rest[header.frame_length..];
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// AudioSpecificConfig of 44.1 kHz stereo AAC LC.
+    pub(crate) const LC_STEREO_44100: [u8; 2] = [0x12, 0x10];
+
+    #[test]
+    fn test_parse_lc_config() {
+        let config = AudioSpecificConfig::parse(&LC_STEREO_44100).unwrap();
+        assert_eq!(config.object_type, AOT_AAC_LC);
+        assert_eq!((config.sample_rate, config.channels), (44100, 2));
+        assert!(!config.sbr);
+        assert_eq!(config.codec_string(), "mp4a.40.2");
+        assert_eq!(config.to_bytes().unwrap(), LC_STEREO_44100);
+
+        // 960-sample frames, 5.1.
+        let config = AudioSpecificConfig::parse(&[0x11, 0xB4]).unwrap();
+        assert_eq!((config.sample_rate, config.channels), (48000, 6));
+        assert_eq!(config.frame_length, 960);
+
+        assert!(AudioSpecificConfig::parse(&[0x16]).is_err());
+        assert!(AudioSpecificConfig::parse(&[0x16, 0x90]).is_err());
+    }
+
+    #[test]
+    fn test_parse_he_aac_config() {
+        // Hierarchical: SBR at 24 kHz core, 48 kHz output, AAC LC mono core
+        // with PS.
+        let config = AudioSpecificConfig::parse(&[0xEB, 0x09, 0x88, 0x00]).unwrap();
+        assert_eq!(config.object_type, AOT_PS);
+        assert_eq!(config.base_object_type, AOT_AAC_LC);
+        assert!(config.sbr && config.ps);
+        assert_eq!(config.sample_rate, 24000);
+        assert_eq!(config.output_sample_rate(), 48000);
+        assert_eq!(config.output_channels(), 2);
+        assert_eq!(config.output_frame_length(), 2048);
+        assert_eq!(config.codec_string(), "mp4a.40.29");
+
+        // Backward-compatible: AAC LC 22.05 kHz stereo, then SBR to 44.1 kHz.
+        let config = AudioSpecificConfig::parse(&[0x13, 0x90, 0x56, 0xE5, 0xA0]).unwrap();
+        assert_eq!(config.object_type, AOT_AAC_LC);
+        assert!(config.sbr && !config.ps);
+        assert_eq!(config.output_sample_rate(), 44100);
+        assert_eq!(config.codec_string(), "mp4a.40.2");
+
+        let mut audio = AudioInfo::new(22050, 2);
+        config.fill_audio_info(&mut audio);
+        assert_eq!(audio, AudioInfo::new(44100, 2));
+    }
+
+    #[test]
+    fn test_parse_program_config_element() {
+        // Channel config 0 with a PCE of one CPE in front and one LFE.
+        let mut w = crate::decoder::h264::tests::BitWriter::default();
+        w.bits(5, 2).bits(4, 3).bits(4, 0).bits(3, 0);
+        w.bits(4, 0).bits(2, 1).bits(4, 3);
+        w.bits(4, 1)
+            .bits(4, 0)
+            .bits(4, 0)
+            .bits(2, 1)
+            .bits(3, 0)
+            .bits(4, 0);
+        w.bits(3, 0).bits(1, 1).bits(4, 0).bits(4, 0);
+        let config = AudioSpecificConfig::parse(&w.finish()).unwrap();
+        assert_eq!((config.channel_config, config.channels), (0, 3));
+    }
+
+    #[test]
+    fn test_adts_round_trip() {
+        let config = AudioSpecificConfig::parse(&LC_STEREO_44100).unwrap();
+        let payload = [0x21, 0x10, 0x05];
+        let frame = config.to_adts(&payload).unwrap();
+        assert!(is_adts(&frame));
+
+        let header = AdtsHeader::parse(&frame).unwrap();
+        assert_eq!(header.frame_length, 10);
+        assert_eq!((header.sample_rate, header.channel_config), (44100, 2));
+        assert_eq!(header.raw_blocks, 1);
+        assert_eq!(header.audio_specific_config(), config);
+
+        let mut stream = frame.clone();
+        stream.extend(config.to_adts(&[0xAA]).unwrap());
+        let frames = split_adts(&stream).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, &payload);
+        assert_eq!(frames[1].1, &[0xAA]);
+
+        assert!(split_adts(&stream[..12]).is_err());
+        assert!(AdtsHeader::parse(&[0xFF, 0xF1, 0x50]).is_err());
+        assert!(!is_adts(&payload));
+
+        let he = AudioSpecificConfig::parse(&[0xEB, 0x09, 0x88, 0x00]).unwrap();
+        assert!(he.to_adts(&payload).is_ok());
+        let explicit = AudioSpecificConfig::parse(&[0x17, 0x80, 0x00, 0x00, 0x10]).unwrap();
+        assert!(explicit.to_adts(&payload).is_err());
+    }
+}
@@ -0,0 +1,646 @@
+//! AV1 bitstream parsing.
+//!
+//! Reads the `av1C` configuration record stored by MP4 and Matroska, the
+//! sequence header OBU it carries and HDR metadata OBUs. Together they give
+//! the `av01.P.LLT.DD.M.CCC.cp.tc.mc.F` codec string (AV1 ISOBMFF binding,
+//! "Codecs Parameter String") used to probe WebCodecs support.
+
+use super::bits::BitReader;
+use super::MAX_DIMENSION;
+use crate::demuxer::{ColorInfo, VideoInfo};
+use crate::error::{PlayerError, Result};
+
+/// OBU type of a sequence header.
+pub const OBU_SEQUENCE_HEADER: u8 = 1;
+/// OBU type of a metadata OBU.
+pub const OBU_METADATA: u8 = 5;
+
+/// `metadata_type` of content light level information.
+const METADATA_TYPE_HDR_CLL: u64 = 1;
+/// `metadata_type` of mastering display colour volume information.
+const METADATA_TYPE_HDR_MDCV: u64 = 2;
+
+/// A single open bitstream unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Obu<'a> {
+    /// `obu_type`.
+    pub obu_type: u8,
+    /// Payload after the header and size field.
+    pub payload: &'a [u8],
+}
+
+/// Reads a `leb128()` value from a byte-aligned reader.
+fn leb128(r: &mut BitReader<'_>) -> Result<u64> {
+    let mut value = 0u64;
+    for i in 0..8 {
+        let byte = r.bits(8)?;
+        value |= ((byte & 0x7F) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(PlayerError::decoder("Invalid leb128 value"))
+}
+
+/// Splits a sequence of OBUs in the low overhead bitstream format, as
+/// stored in MP4 and Matroska samples.
+///
+/// Only the last OBU may omit its size field.
+///
+/// # Errors
+/// Returns an error if an OBU header is malformed or a size runs past the
+/// end of the data.
+pub fn split_obus(data: &[u8]) -> Result<Vec<Obu<'_>>> {
+    let mut obus = Vec::new();
+    let mut r = BitReader::new(data);
+    while r.remaining() > 0 {
+        if r.flag()? {
+            return Err(PlayerError::decoder("OBU forbidden bit set"));
+        }
+        let obu_type = r.bits(4)? as u8;
+        let has_extension = r.flag()?;
+        let has_size = r.flag()?;
+        r.skip(1)?;
+        if has_extension {
+            r.skip(8)?;
+        }
+        let size = if has_size {
+            leb128(&mut r)? as usize
+        } else {
+            r.remaining() / 8
+        };
+        let payload = r
+            .bytes(size)
+            .map_err(|_| PlayerError::decoder("OBU exceeds packet"))?;
+        obus.push(Obu { obu_type, payload });
+    }
+    Ok(obus)
+}
+
+/// Colour properties from the sequence header `color_config()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorConfig {
+    /// Bits per sample (8, 10 or 12).
+    pub bit_depth: u8,
+    /// Whether the stream has a luma plane only.
+    pub monochrome: bool,
+    /// Whether chroma is horizontally subsampled.
+    pub subsampling_x: bool,
+    /// Whether chroma is vertically subsampled.
+    pub subsampling_y: bool,
+    /// `chroma_sample_position` (0 unknown, 1 vertical, 2 colocated).
+    pub chroma_sample_position: u8,
+    /// Colour primaries, transfer characteristics and matrix coefficients,
+    /// when `color_description_present_flag` is set.
+    pub description: Option<(u8, u8, u8)>,
+    /// Whether samples use the full range.
+    pub full_range: bool,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            bit_depth: 8,
+            monochrome: false,
+            subsampling_x: true,
+            subsampling_y: true,
+            chroma_sample_position: 0,
+            description: None,
+            full_range: false,
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Reads a `color_config()` structure.
+    fn read(r: &mut BitReader<'_>, seq_profile: u8) -> Result<Self> {
+        let mut color = Self::default();
+        let high_bitdepth = r.flag()?;
+        color.bit_depth = match (seq_profile, high_bitdepth) {
+            (2, true) if r.flag()? => 12,
+            (_, true) => 10,
+            _ => 8,
+        };
+        color.monochrome = seq_profile != 1 && r.flag()?;
+        if r.flag()? {
+            color.description = Some((r.bits(8)? as u8, r.bits(8)? as u8, r.bits(8)? as u8));
+        }
+        if color.monochrome {
+            color.full_range = r.flag()?;
+            return Ok(color);
+        }
+        // sRGB: BT.709 primaries, sRGB transfer and identity matrix.
+        if color.description == Some((1, 13, 0)) {
+            color.full_range = true;
+            color.subsampling_x = false;
+            color.subsampling_y = false;
+        } else {
+            color.full_range = r.flag()?;
+            (color.subsampling_x, color.subsampling_y) = match seq_profile {
+                0 => (true, true),
+                1 => (false, false),
+                _ if color.bit_depth == 12 => {
+                    let x = r.flag()?;
+                    (x, x && r.flag()?)
+                }
+                _ => (true, false),
+            };
+            if color.subsampling_x && color.subsampling_y {
+                color.chroma_sample_position = r.bits(2)? as u8;
+            }
+        }
+        r.skip(1)?; // separate_uv_delta_q
+        Ok(color)
+    }
+
+    /// Returns the colour description in H.273 code points.
+    pub fn color_info(&self) -> ColorInfo {
+        ColorInfo {
+            primaries: self.description.map(|(cp, _, _)| cp),
+            transfer: self.description.map(|(_, tc, _)| tc),
+            matrix: self.description.map(|(_, _, mc)| mc),
+            full_range: Some(self.full_range),
+        }
+    }
+}
+
+/// Decoded sequence header OBU fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenceHeader {
+    /// `seq_profile` (0 Main, 1 High, 2 Professional).
+    pub profile: u8,
+    /// Whether the stream contains a single intra frame.
+    pub still_picture: bool,
+    /// `seq_level_idx` of the first operating point.
+    pub level_idx: u8,
+    /// `seq_tier` of the first operating point: true for the High tier.
+    pub high_tier: bool,
+    /// Maximum frame width in pixels.
+    pub max_width: u32,
+    /// Maximum frame height in pixels.
+    pub max_height: u32,
+    /// Colour properties.
+    pub color: ColorConfig,
+    /// Whether frames carry film grain synthesis parameters.
+    pub film_grain_params_present: bool,
+}
+
+impl SequenceHeader {
+    /// Parses the payload of a sequence header OBU.
+    ///
+    /// # Errors
+    /// Returns an error if the payload is truncated or the maximum frame
+    /// size exceeds [`MAX_DIMENSION`].
+    pub fn parse(payload: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(payload);
+        let mut header = Self {
+            profile: r.bits(3)? as u8,
+            still_picture: r.flag()?,
+            ..Self::default()
+        };
+        let reduced_still_picture_header = r.flag()?;
+        if reduced_still_picture_header {
+            header.level_idx = r.bits(5)? as u8;
+        } else {
+            let mut buffer_delay_length = 0;
+            let mut decoder_model_info_present = false;
+            if r.flag()? {
+                // timing_info()
+                r.skip(64)?;
+                if r.flag()? {
+                    uvlc(&mut r)?; // num_ticks_per_picture_minus_1
+                }
+                decoder_model_info_present = r.flag()?;
+                if decoder_model_info_present {
+                    buffer_delay_length = r.bits(5)? as usize + 1;
+                    r.skip(32 + 5 + 5)?;
+                }
+            }
+            let initial_display_delay_present = r.flag()?;
+            let operating_points = r.bits(5)? + 1;
+            for i in 0..operating_points {
+                r.skip(12)?; // operating_point_idc
+                let level_idx = r.bits(5)? as u8;
+                let high_tier = level_idx > 7 && r.flag()?;
+                if i == 0 {
+                    header.level_idx = level_idx;
+                    header.high_tier = high_tier;
+                }
+                if decoder_model_info_present && r.flag()? {
+                    r.skip(2 * buffer_delay_length + 1)?;
+                }
+                if initial_display_delay_present && r.flag()? {
+                    r.skip(4)?;
+                }
+            }
+        }
+
+        let width_bits = r.bits(4)? + 1;
+        let height_bits = r.bits(4)? + 1;
+        header.max_width = r.bits(width_bits)? + 1;
+        header.max_height = r.bits(height_bits)? + 1;
+        if header.max_width > MAX_DIMENSION || header.max_height > MAX_DIMENSION {
+            return Err(PlayerError::decoder("AV1 frame size too large"));
+        }
+        if !reduced_still_picture_header && r.flag()? {
+            r.skip(4 + 3)?; // frame ID lengths
+        }
+        r.skip(3)?; // superblock size, filter intra, intra edge filter
+        if !reduced_still_picture_header {
+            r.skip(4)?; // interintra, masked compound, warped motion, dual filter
+            let enable_order_hint = r.flag()?;
+            if enable_order_hint {
+                r.skip(2)?; // jnt_comp, ref_frame_mvs
+            }
+            let force_screen_content_tools = if r.flag()? { 2 } else { r.bits(1)? };
+            if force_screen_content_tools > 0 && !r.flag()? {
+                r.skip(1)?; // seq_force_integer_mv
+            }
+            if enable_order_hint {
+                r.skip(3)?; // order_hint_bits_minus_1
+            }
+        }
+        r.skip(3)?; // superres, cdef, restoration
+        header.color = ColorConfig::read(&mut r, header.profile)?;
+        header.film_grain_params_present = r.flag()?;
+        Ok(header)
+    }
+
+    /// Returns the codec string, e.g. `av01.0.04M.08` or, when colour
+    /// properties differ from the defaults, `av01.0.09M.10.0.110.09.16.09.0`.
+    pub fn codec_string(&self) -> String {
+        codec_string(self.profile, self.level_idx, self.high_tier, &self.color)
+    }
+
+    /// Fills picture properties the container left unset.
+    pub fn fill_video_info(&self, video: &mut VideoInfo) {
+        if video.width == 0 || video.height == 0 {
+            video.width = self.max_width;
+            video.height = self.max_height;
+        }
+        video.color = video.color.or(Some(self.color.color_info()));
+        video.bit_depth = video.bit_depth.or(Some(self.color.bit_depth));
+    }
+}
+
+/// Reads a `uvlc()` code.
+fn uvlc(r: &mut BitReader<'_>) -> Result<u32> {
+    let mut leading_zeros = 0;
+    while !r.flag()? {
+        leading_zeros += 1;
+    }
+    if leading_zeros >= 32 {
+        return Ok(u32::MAX);
+    }
+    Ok(r.bits(leading_zeros)? + ((1u64 << leading_zeros) - 1) as u32)
+}
+
+/// Formats a codec string, omitting the optional fields when they all hold
+/// their default values.
+fn codec_string(profile: u8, level_idx: u8, high_tier: bool, color: &ColorConfig) -> String {
+    let mut codec = format!(
+        "av01.{}.{:02}{}.{:02}",
+        profile,
+        level_idx,
+        if high_tier { 'H' } else { 'M' },
+        color.bit_depth
+    );
+    let chroma_sample_position = if color.subsampling_x && color.subsampling_y {
+        color.chroma_sample_position
+    } else {
+        0
+    };
+    let (cp, tc, mc) = color.description.unwrap_or((1, 1, 1));
+    let defaults = !color.monochrome
+        && color.subsampling_x
+        && color.subsampling_y
+        && chroma_sample_position == 0
+        && (cp, tc, mc) == (1, 1, 1)
+        && !color.full_range;
+    if !defaults {
+        codec.push_str(&format!(
+            ".{}.{}{}{}.{:02}.{:02}.{:02}.{}",
+            color.monochrome as u8,
+            color.subsampling_x as u8,
+            color.subsampling_y as u8,
+            chroma_sample_position,
+            cp,
+            tc,
+            mc,
+            color.full_range as u8
+        ));
+    }
+    codec
+}
+
+/// Content light level information (CTA-861.3).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentLightLevel {
+    /// Maximum content light level, in cd/m².
+    pub max_cll: u16,
+    /// Maximum frame-average light level, in cd/m².
+    pub max_fall: u16,
+}
+
+/// Mastering display colour volume (SMPTE ST 2086).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MasteringDisplay {
+    /// CIE 1931 (x, y) chromaticity of the red, green and blue primaries,
+    /// as 0.16 fixed-point values.
+    pub primaries: [(u16, u16); 3],
+    /// CIE 1931 (x, y) chromaticity of the white point, as 0.16 fixed-point
+    /// values.
+    pub white_point: (u16, u16),
+    /// Maximum luminance, in cd/m² as a 24.8 fixed-point value.
+    pub max_luminance: u32,
+    /// Minimum luminance, in cd/m² as an 18.14 fixed-point value.
+    pub min_luminance: u32,
+}
+
+/// HDR metadata carried in metadata OBUs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HdrMetadata {
+    /// Content light level, if signalled.
+    pub content_light_level: Option<ContentLightLevel>,
+    /// Mastering display colour volume, if signalled.
+    pub mastering_display: Option<MasteringDisplay>,
+}
+
+impl HdrMetadata {
+    /// Updates the metadata from the payload of a metadata OBU. Other
+    /// metadata types are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if an HDR metadata payload is truncated.
+    pub fn read_obu(&mut self, payload: &[u8]) -> Result<()> {
+        let mut r = BitReader::new(payload);
+        match leb128(&mut r)? {
+            METADATA_TYPE_HDR_CLL => {
+                self.content_light_level = Some(ContentLightLevel {
+                    max_cll: r.bits(16)? as u16,
+                    max_fall: r.bits(16)? as u16,
+                });
+            }
+            METADATA_TYPE_HDR_MDCV => {
+                let mut display = MasteringDisplay::default();
+                for primary in &mut display.primaries {
+                    *primary = (r.bits(16)? as u16, r.bits(16)? as u16);
+                }
+                display.white_point = (r.bits(16)? as u16, r.bits(16)? as u16);
+                display.max_luminance = r.bits(32)?;
+                display.min_luminance = r.bits(32)?;
+                self.mastering_display = Some(display);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns whether no HDR metadata was found.
+    pub fn is_empty(&self) -> bool {
+        self.content_light_level.is_none() && self.mastering_display.is_none()
+    }
+}
+
+/// AV1 codec configuration record (`av1C`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Av1DecoderConfig {
+    /// `seq_profile`.
+    pub profile: u8,
+    /// `seq_level_idx_0`.
+    pub level_idx: u8,
+    /// `seq_tier_0`: true for the High tier.
+    pub high_tier: bool,
+    /// Colour properties signalled by the record itself.
+    pub color: ColorConfig,
+    /// The configuration OBUs following the fixed fields.
+    pub config_obus: Vec<u8>,
+    /// The sequence header from `config_obus`, if present.
+    pub sequence_header: Option<SequenceHeader>,
+    /// HDR metadata from `config_obus`.
+    pub hdr: HdrMetadata,
+}
+
+impl Av1DecoderConfig {
+    /// Parses an `av1C` record.
+    ///
+    /// # Errors
+    /// Returns an error if the record is truncated, has an unknown version,
+    /// or its configuration OBUs are malformed.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        if r.bits(8)? != 0x81 {
+            return Err(PlayerError::decoder("Unsupported av1C version"));
+        }
+        let profile = r.bits(3)? as u8;
+        let level_idx = r.bits(5)? as u8;
+        let high_tier = r.flag()?;
+        let high_bitdepth = r.flag()?;
+        let twelve_bit = r.flag()?;
+        let color = ColorConfig {
+            bit_depth: match (high_bitdepth, twelve_bit) {
+                (true, true) => 12,
+                (true, false) => 10,
+                _ => 8,
+            },
+            monochrome: r.flag()?,
+            subsampling_x: r.flag()?,
+            subsampling_y: r.flag()?,
+            chroma_sample_position: r.bits(2)? as u8,
+            ..ColorConfig::default()
+        };
+        r.skip(8)?; // initial_presentation_delay
+        let config_obus = r.bytes(r.remaining() / 8)?.to_vec();
+
+        let mut sequence_header = None;
+        let mut hdr = HdrMetadata::default();
+        for obu in split_obus(&config_obus)? {
+            match obu.obu_type {
+                OBU_SEQUENCE_HEADER => sequence_header = Some(SequenceHeader::parse(obu.payload)?),
+                OBU_METADATA => hdr.read_obu(obu.payload)?,
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            profile,
+            level_idx,
+            high_tier,
+            color,
+            config_obus,
+            sequence_header,
+            hdr,
+        })
+    }
+
+    /// Returns the codec string. The sequence header is preferred, as only
+    /// it signals the colour description and range.
+    pub fn codec_string(&self) -> String {
+        match &self.sequence_header {
+            Some(header) => header.codec_string(),
+            None => codec_string(self.profile, self.level_idx, self.high_tier, &self.color),
+        }
+    }
+
+    /// Fills picture properties the container left unset.
+    pub fn fill_video_info(&self, video: &mut VideoInfo) {
+        match &self.sequence_header {
+            Some(header) => header.fill_video_info(video),
+            None => video.bit_depth = video.bit_depth.or(Some(self.color.bit_depth)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::decoder::h264::tests::BitWriter;
+
+    /// Builds a Main profile, level 5.1 sequence header for 3840x2160
+    /// 10-bit BT.2020 PQ video.
+    pub(crate) fn hdr_sequence_header() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(3, 0).bits(1, 0).bits(1, 0); // profile, still, reduced
+        w.bits(1, 0).bits(1, 0); // timing info, initial display delay
+        w.bits(5, 0).bits(12, 0).bits(5, 13).bits(1, 0); // one operating point, level 5.1, Main tier
+        w.bits(4, 11).bits(4, 11).bits(12, 3839).bits(12, 2159);
+        w.bits(1, 0); // frame_id_numbers_present_flag
+        w.bits(3, 0b111).bits(4, 0);
+        w.bits(1, 1).bits(2, 0b11); // order hint, jnt_comp, ref_frame_mvs
+        w.bits(1, 1); // seq_choose_screen_content_tools
+        w.bits(1, 1); // seq_choose_integer_mv
+        w.bits(3, 6);
+        w.bits(3, 0b011);
+        w.bits(1, 1).bits(1, 0); // 10-bit, not monochrome
+        w.bits(1, 1).bits(8, 9).bits(8, 16).bits(8, 9);
+        w.bits(1, 0).bits(2, 0).bits(1, 0); // limited range, csp, uv delta q
+        w.bits(1, 0); // film grain
+        w.finish()
+    }
+
+    /// Wraps a payload in an OBU header with a size field.
+    fn obu(obu_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![(obu_type << 3) | 0x02, payload.len() as u8];
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Builds an `av1C` record for [`hdr_sequence_header`].
+    pub(crate) fn av1c_record() -> Vec<u8> {
+        let mut record = vec![0x81, 13, 0b0100_1100, 0];
+        record.extend(obu(OBU_SEQUENCE_HEADER, &hdr_sequence_header()));
+        record.extend(obu(OBU_METADATA, &[1, 0x03, 0xE8, 0x01, 0x90]));
+        record
+    }
+
+    #[test]
+    fn test_split_obus() {
+        let mut data = obu(2, &[]);
+        data.extend(obu(6, &[0xAA, 0xBB]));
+        // The last OBU may omit its size field.
+        data.extend([0x30, 0xCC]);
+        let obus = split_obus(&data).unwrap();
+        assert_eq!(obus.len(), 3);
+        assert_eq!(
+            obus[1],
+            Obu {
+                obu_type: 6,
+                payload: &[0xAA, 0xBB]
+            }
+        );
+        assert_eq!(
+            obus[2],
+            Obu {
+                obu_type: 6,
+                payload: &[0xCC]
+            }
+        );
+
+        assert!(split_obus(&[0x32, 5, 0]).is_err());
+        assert!(split_obus(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sequence_header() {
+        let header = SequenceHeader::parse(&hdr_sequence_header()).unwrap();
+        assert_eq!((header.profile, header.level_idx), (0, 13));
+        assert_eq!((header.max_width, header.max_height), (3840, 2160));
+        assert_eq!(header.color.bit_depth, 10);
+        assert_eq!(header.color.description, Some((9, 16, 9)));
+        assert_eq!(header.codec_string(), "av01.0.13M.10.0.110.09.16.09.0");
+
+        // Reduced still picture header, 8-bit 4:2:0 with no colour description.
+        let mut w = BitWriter::default();
+        w.bits(3, 0).bits(1, 1).bits(1, 1).bits(5, 8);
+        w.bits(4, 9).bits(4, 8).bits(10, 639).bits(9, 479);
+        w.bits(3, 0).bits(3, 0);
+        w.bits(1, 0)
+            .bits(1, 0)
+            .bits(1, 0)
+            .bits(1, 0)
+            .bits(2, 0)
+            .bits(1, 0);
+        w.bits(1, 0);
+        let still = SequenceHeader::parse(&w.finish()).unwrap();
+        assert!(still.still_picture);
+        assert_eq!((still.max_width, still.max_height), (640, 480));
+        assert_eq!(still.codec_string(), "av01.0.08M.08");
+
+        let mut w = BitWriter::default();
+        w.bits(3, 0).bits(1, 1).bits(1, 1).bits(5, 8);
+        w.bits(4, 15).bits(4, 8).bits(16, 0xFFFF).bits(9, 479);
+        w.bits(16, 0);
+        let result = SequenceHeader::parse(&w.finish());
+        assert!(result.unwrap_err().to_string().contains("too large"));
+        assert!(SequenceHeader::parse(&[0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_parse_av1c() {
+        let config = Av1DecoderConfig::parse(&av1c_record()).unwrap();
+        assert_eq!(config.color.bit_depth, 10);
+        assert_eq!(config.codec_string(), "av01.0.13M.10.0.110.09.16.09.0");
+        assert_eq!(
+            config.hdr.content_light_level,
+            Some(ContentLightLevel {
+                max_cll: 1000,
+                max_fall: 400
+            })
+        );
+        assert!(config.hdr.mastering_display.is_none());
+
+        let mut video = VideoInfo::default();
+        config.fill_video_info(&mut video);
+        assert_eq!((video.width, video.height), (3840, 2160));
+        assert_eq!(video.color.unwrap().transfer, Some(16));
+
+        // Without configuration OBUs the record fields are used.
+        let config = Av1DecoderConfig::parse(&[0x81, 0x08, 0b0000_1100, 0]).unwrap();
+        assert_eq!(config.codec_string(), "av01.0.08M.08");
+        assert!(config.sequence_header.is_none());
+
+        assert!(Av1DecoderConfig::parse(&[0x01, 0x08, 0x0C, 0]).is_err());
+        assert!(Av1DecoderConfig::parse(&[0x81, 0x08]).is_err());
+    }
+
+    #[test]
+    fn test_hdr_metadata() {
+        let mut payload = vec![2];
+        for value in [34000u16, 16000, 13250, 34500, 7500, 3000, 15635, 16450] {
+            payload.extend(value.to_be_bytes());
+        }
+        payload.extend((1000u32 << 8).to_be_bytes());
+        payload.extend(50u32.to_be_bytes());
+        let mut hdr = HdrMetadata::default();
+        assert!(hdr.is_empty());
+        hdr.read_obu(&payload).unwrap();
+        let display = hdr.mastering_display.unwrap();
+        assert_eq!(display.primaries[0], (34000, 16000));
+        assert_eq!(display.white_point, (15635, 16450));
+        assert_eq!(display.max_luminance >> 8, 1000);
+
+        // Unknown metadata types are skipped.
+        hdr.read_obu(&[4, 0xB5]).unwrap();
+        assert!(hdr.read_obu(&[1, 0]).is_err());
+    }
+}
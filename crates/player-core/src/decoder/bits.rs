@@ -0,0 +1,143 @@
+//! Bit-level reading helpers shared by the codec bitstream parsers.
+
+use crate::error::{PlayerError, Result};
+
+/// Cursor over a byte slice reading MSB-first bit fields.
+#[derive(Debug, Clone)]
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader positioned at the first bit of `data`.
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Returns the position of the cursor in bits.
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the number of unread bits.
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    /// Advances the cursor by `n` bits.
+    pub(crate) fn skip(&mut self, n: usize) -> Result<()> {
+        if self.remaining() < n {
+            return Err(PlayerError::decoder("Unexpected end of bitstream"));
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    /// Reads `len` whole bytes. The cursor must be byte-aligned.
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        debug_assert!(self.pos.is_multiple_of(8));
+        let start = self.pos / 8;
+        let bytes = self
+            .data
+            .get(start..start + len)
+            .ok_or_else(|| PlayerError::decoder("Unexpected end of bitstream"))?;
+        self.pos += len * 8;
+        Ok(bytes)
+    }
+
+    /// Reads a single bit as a flag.
+    pub(crate) fn flag(&mut self) -> Result<bool> {
+        Ok(self.bits(1)? == 1)
+    }
+
+    /// Reads an unsigned value of up to 32 bits.
+    pub(crate) fn bits(&mut self, n: u32) -> Result<u32> {
+        debug_assert!(n <= 32);
+        if self.remaining() < n as usize {
+            return Err(PlayerError::decoder("Unexpected end of bitstream"));
+        }
+        let mut value = 0u64;
+        for _ in 0..n {
+            let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | bit as u64;
+            self.pos += 1;
+        }
+        Ok(value as u32)
+    }
+
+    /// Reads a unary code: counts the zero bits before the next one bit,
+    /// consuming both.
+    pub(crate) fn unary(&mut self) -> Result<u32> {
+        let mut zeros = 0;
+        while !self.flag()? {
+            zeros += 1;
+        }
+        Ok(zeros)
+    }
+
+    /// Reads an unsigned Exp-Golomb code (`ue(v)`).
+    pub(crate) fn ue(&mut self) -> Result<u32> {
+        let mut zeros = 0;
+        while !self.flag()? {
+            zeros += 1;
+            if zeros > 31 {
+                return Err(PlayerError::decoder("Invalid Exp-Golomb code"));
+            }
+        }
+        Ok(((1u64 << zeros) - 1 + self.bits(zeros)? as u64) as u32)
+    }
+
+    /// Reads a signed Exp-Golomb code (`se(v)`).
+    pub(crate) fn se(&mut self) -> Result<i32> {
+        let code = self.ue()? as i64;
+        Ok(if code % 2 == 1 {
+            (code + 1) / 2
+        } else {
+            -(code / 2)
+        } as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_reader_fields() {
+        let mut r = BitReader::new(&[0b1011_0011, 0xFF]);
+        assert!(r.flag().unwrap());
+        assert_eq!(r.bits(3).unwrap(), 0b011);
+        assert_eq!(r.bits(8).unwrap(), 0b0011_1111);
+        assert_eq!(r.remaining(), 4);
+        assert!(r.bits(5).is_err());
+        r.skip(4).unwrap();
+        assert!(r.flag().is_err());
+
+        let mut r = BitReader::new(&[1, 2, 3]);
+        assert_eq!(r.bits(8).unwrap(), 1);
+        assert_eq!(r.bytes(2).unwrap(), &[2, 3]);
+        assert!(r.bytes(1).is_err());
+    }
+
+    #[test]
+    fn test_bit_reader_exp_golomb() {
+        // 1, 010, 011, 00100, 00101 → 0, 1, 2, 3, 4
+        let mut r = BitReader::new(&[0b1010_0110, 0b0100_0010, 0b1000_0000]);
+        let codes: Vec<u32> = (0..5).map(|_| r.ue().unwrap()).collect();
+        assert_eq!(codes, vec![0, 1, 2, 3, 4]);
+
+        // se: 1 → 0, 010 → 1, 011 → -1, 00100 → 2
+        let mut r = BitReader::new(&[0b1010_0110, 0b0100_0000]);
+        let values: Vec<i32> = (0..4).map(|_| r.se().unwrap()).collect();
+        assert_eq!(values, vec![0, 1, -1, 2]);
+
+        assert!(BitReader::new(&[0, 0, 0, 0, 0]).ue().is_err());
+
+        let mut r = BitReader::new(&[0b1001_0000, 0b0000_0001]);
+        let codes: Vec<u32> = (0..3).map(|_| r.unary().unwrap()).collect();
+        assert_eq!(codes, vec![0, 2, 11]);
+        assert!(r.unary().is_err());
+    }
+}
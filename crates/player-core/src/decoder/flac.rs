@@ -0,0 +1,821 @@
+//! FLAC parsing and decoding.
+//!
+//! Parses the STREAMINFO metadata block and frame headers (RFC 9639), and
+//! decodes frames made of constant, verbatim, fixed-predictor and LPC
+//! subframes to interleaved integer samples.
+
+use super::bits::BitReader;
+use crate::demuxer::{channel_mask_layout, AudioInfo};
+use crate::error::{PlayerError, Result};
+
+/// Marker that starts a native FLAC file and the codec initialization data
+/// of FLAC in Matroska.
+pub const STREAM_MARKER: [u8; 4] = *b"fLaC";
+
+/// Length of a metadata block header.
+pub const METADATA_HEADER_LEN: usize = 4;
+
+/// Length of the STREAMINFO block payload.
+pub const STREAMINFO_LEN: usize = 34;
+
+/// Longest frame header: sync and codes, a 7-byte coded number, 16-bit
+/// block size and sample rate, and the CRC-8.
+pub const MAX_HEADER_LEN: usize = 16;
+
+/// Metadata block type of STREAMINFO.
+pub const BLOCK_STREAMINFO: u8 = 0;
+
+/// Sample rates of the frame header codes 1 to 11.
+const SAMPLE_RATES: [u32; 11] = [
+    88200, 176400, 192000, 8000, 16000, 22050, 24000, 32000, 44100, 48000, 96000,
+];
+
+/// Sample sizes of the frame header codes; 0 means "from STREAMINFO" and
+/// code 3 is reserved.
+const SAMPLE_SIZES: [u8; 8] = [0, 8, 12, 0, 16, 20, 24, 32];
+
+/// WAVE speaker masks of the default channel orders for 1 to 8 channels.
+const CHANNEL_MASKS: [u32; 8] = [0x4, 0x3, 0x7, 0x33, 0x37, 0x3F, 0x70F, 0x63F];
+
+/// Coefficients of the fixed predictors of orders 0 to 4.
+const FIXED_COEFFICIENTS: [&[i64]; 5] = [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+
+/// Header of a metadata block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataBlockHeader {
+    /// Whether this is the last metadata block before the frames.
+    pub last: bool,
+    /// Block type (0 for STREAMINFO).
+    pub kind: u8,
+    /// Length of the payload in bytes.
+    pub len: usize,
+}
+
+impl MetadataBlockHeader {
+    /// Parses a metadata block header; returns `None` if the data is
+    /// truncated or the type is the invalid value 127.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let h = data.get(..METADATA_HEADER_LEN)?;
+        let kind = h[0] & 0x7F;
+        (kind != 0x7F).then(|| Self {
+            last: h[0] & 0x80 != 0,
+            kind,
+            len: u32::from_be_bytes([0, h[1], h[2], h[3]]) as usize,
+        })
+    }
+}
+
+/// Stream properties from the STREAMINFO block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlacStreamInfo {
+    /// Smallest block size in samples, the last frame excepted.
+    pub min_block_size: u16,
+    /// Largest block size in samples.
+    pub max_block_size: u16,
+    /// Smallest frame size in bytes, or 0 if unknown.
+    pub min_frame_size: u32,
+    /// Largest frame size in bytes, or 0 if unknown.
+    pub max_frame_size: u32,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Number of channels (1 to 8).
+    pub channels: u8,
+    /// Bits per sample (4 to 32).
+    pub bits_per_sample: u8,
+    /// Number of samples per channel, if known.
+    pub total_samples: Option<u64>,
+    /// MD5 signature of the decoded samples; all zero if unknown.
+    pub md5: [u8; 16],
+}
+
+impl FlacStreamInfo {
+    /// Parses FLAC initialization data: metadata blocks starting with
+    /// STREAMINFO, optionally preceded by the stream marker as in Matroska
+    /// `CodecPrivate`. The blocks after STREAMINFO are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if the data does not start with a valid STREAMINFO
+    /// block.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let data = data.strip_prefix(&STREAM_MARKER[..]).unwrap_or(data);
+        let payload = MetadataBlockHeader::parse(data)
+            .filter(|h| h.kind == BLOCK_STREAMINFO)
+            .and_then(|h| data.get(METADATA_HEADER_LEN..METADATA_HEADER_LEN + h.len))
+            .ok_or_else(|| PlayerError::decoder("FLAC data does not start with STREAMINFO"))?;
+        Self::parse_streaminfo(payload)
+    }
+
+    /// Parses the payload of a STREAMINFO block.
+    ///
+    /// # Errors
+    /// Returns an error if the payload is truncated or signals a zero sample
+    /// rate or a block size below 16 samples.
+    pub fn parse_streaminfo(payload: &[u8]) -> Result<Self> {
+        let p = payload
+            .get(..STREAMINFO_LEN)
+            .ok_or_else(|| PlayerError::decoder("FLAC STREAMINFO truncated"))?;
+        let mut r = BitReader::new(p);
+        let min_block_size = r.bits(16)? as u16;
+        let max_block_size = r.bits(16)? as u16;
+        let min_frame_size = r.bits(24)?;
+        let max_frame_size = r.bits(24)?;
+        let sample_rate = r.bits(20)?;
+        let channels = r.bits(3)? as u8 + 1;
+        let bits_per_sample = r.bits(5)? as u8 + 1;
+        let total_samples = (r.bits(4)? as u64) << 32 | r.bits(32)? as u64;
+        if sample_rate == 0 || max_block_size < 16 {
+            return Err(PlayerError::decoder("Invalid FLAC STREAMINFO"));
+        }
+        Ok(Self {
+            min_block_size,
+            max_block_size,
+            min_frame_size,
+            max_frame_size,
+            sample_rate,
+            channels,
+            bits_per_sample,
+            total_samples: (total_samples > 0).then_some(total_samples),
+            md5: p[18..].try_into().unwrap(),
+        })
+    }
+
+    /// Returns whether all frames but the last have the same block size.
+    pub fn is_fixed_block_size(&self) -> bool {
+        self.min_block_size == self.max_block_size
+    }
+
+    /// Fills in the sample rate, channels and bit depth, with the channel
+    /// layout FLAC assigns to the channel count.
+    pub fn fill_audio_info(&self, audio: &mut AudioInfo) {
+        *audio = AudioInfo::new(self.sample_rate, self.channels as u16);
+        audio.channel_layout = CHANNEL_MASKS
+            .get(self.channels as usize - 1)
+            .and_then(|&mask| channel_mask_layout(mask))
+            .map(str::to_string);
+        audio.bit_depth = Some(self.bits_per_sample);
+    }
+}
+
+/// Assignment of the subframes of a frame to channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelAssignment {
+    /// Independently coded channels (1 to 8).
+    Independent(u8),
+    /// Left channel and side (left minus right) channel.
+    LeftSide,
+    /// Side channel and right channel.
+    SideRight,
+    /// Mid (average) channel and side channel.
+    MidSide,
+}
+
+impl ChannelAssignment {
+    /// Returns whether subframe `index` is a side channel, coded with one
+    /// extra bit per sample.
+    fn is_side(self, index: usize) -> bool {
+        match self {
+            ChannelAssignment::Independent(_) => false,
+            ChannelAssignment::SideRight => index == 0,
+            ChannelAssignment::LeftSide | ChannelAssignment::MidSide => index == 1,
+        }
+    }
+}
+
+/// Decoded fields of a frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Whether the stream uses variable block sizes, in which case
+    /// `number` is a sample number rather than a frame number.
+    pub variable_block_size: bool,
+    /// Number of samples per channel in the frame.
+    pub block_size: u32,
+    /// Sample rate in Hz, or `None` if only given by STREAMINFO.
+    pub sample_rate: Option<u32>,
+    /// Channel assignment.
+    pub channel_assignment: ChannelAssignment,
+    /// Bits per sample, or `None` if only given by STREAMINFO.
+    pub bits_per_sample: Option<u8>,
+    /// Frame number, or the number of the first sample with variable
+    /// block sizes.
+    pub number: u64,
+    /// Length of the header in bytes, CRC-8 included.
+    pub header_len: usize,
+}
+
+impl FrameHeader {
+    /// Parses a frame header and checks its CRC-8.
+    ///
+    /// # Errors
+    /// Returns an error if the data is truncated, lacks the frame sync,
+    /// uses reserved values or fails the CRC check.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let byte = |at: usize| {
+            data.get(at)
+                .copied()
+                .ok_or_else(|| PlayerError::decoder("FLAC frame header truncated"))
+        };
+        if byte(0)? != 0xFF || byte(1)? & 0xFE != 0xF8 {
+            return Err(PlayerError::decoder("Missing FLAC frame sync"));
+        }
+        let variable_block_size = data[1] & 0x01 == 1;
+        let (block_code, rate_code) = (byte(2)? >> 4, data[2] & 0x0F);
+        let channel_code = byte(3)? >> 4;
+        let size_code = (data[3] >> 1) & 0x07;
+        let channel_assignment = match channel_code {
+            0..=7 => ChannelAssignment::Independent(channel_code + 1),
+            8 => ChannelAssignment::LeftSide,
+            9 => ChannelAssignment::SideRight,
+            10 => ChannelAssignment::MidSide,
+            _ => return Err(PlayerError::decoder("Reserved FLAC channel assignment")),
+        };
+        if size_code == 3 || data[3] & 0x01 != 0 || block_code == 0 || rate_code == 15 {
+            return Err(PlayerError::decoder("Reserved value in FLAC frame header"));
+        }
+
+        // The frame or sample number is coded like UTF-8, up to 7 bytes.
+        let lead = byte(4)?;
+        let len = match lead.leading_ones() as usize {
+            0 => 1,
+            len @ 2..=7 => len,
+            _ => return Err(PlayerError::decoder("Invalid FLAC frame number")),
+        };
+        let mut number = (lead & (0x7F >> len)) as u64;
+        for at in 5..4 + len {
+            let b = byte(at)?;
+            if b & 0xC0 != 0x80 {
+                return Err(PlayerError::decoder("Invalid FLAC frame number"));
+            }
+            number = number << 6 | (b & 0x3F) as u64;
+        }
+        let mut pos = 4 + len;
+
+        let block_size = match block_code {
+            1 => 192,
+            2..=5 => 576 << (block_code - 2),
+            6 => {
+                pos += 1;
+                byte(pos - 1)? as u32 + 1
+            }
+            7 => {
+                pos += 2;
+                u16::from_be_bytes([byte(pos - 2)?, byte(pos - 1)?]) as u32 + 1
+            }
+            _ => 256 << (block_code - 8),
+        };
+        let sample_rate = match rate_code {
+            0 => None,
+            1..=11 => Some(SAMPLE_RATES[rate_code as usize - 1]),
+            12 => {
+                pos += 1;
+                Some(byte(pos - 1)? as u32 * 1000)
+            }
+            _ => {
+                pos += 2;
+                let value = u16::from_be_bytes([byte(pos - 2)?, byte(pos - 1)?]) as u32;
+                Some(if rate_code == 14 { value * 10 } else { value })
+            }
+        };
+        if byte(pos)? != crc8(&data[..pos]) {
+            return Err(PlayerError::decoder("FLAC frame header CRC mismatch"));
+        }
+        Ok(Self {
+            variable_block_size,
+            block_size,
+            sample_rate,
+            channel_assignment,
+            bits_per_sample: (size_code != 0).then_some(SAMPLE_SIZES[size_code as usize]),
+            number,
+            header_len: pos + 1,
+        })
+    }
+
+    /// Returns the number of channels.
+    pub fn channels(&self) -> u8 {
+        match self.channel_assignment {
+            ChannelAssignment::Independent(channels) => channels,
+            _ => 2,
+        }
+    }
+
+    /// Returns the number of the first sample, given the block size of a
+    /// fixed-blocksize stream.
+    pub fn first_sample(&self, fixed_block_size: u32) -> u64 {
+        if self.variable_block_size {
+            self.number
+        } else {
+            self.number * fixed_block_size as u64
+        }
+    }
+
+    /// Returns the `number` the next frame of the stream must carry.
+    pub fn next_number(&self) -> u64 {
+        if self.variable_block_size {
+            self.number + self.block_size as u64
+        } else {
+            self.number + 1
+        }
+    }
+}
+
+/// Properties of a decoded frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedFrame {
+    /// Frame header.
+    pub header: FrameHeader,
+    /// Sample rate in Hz, from the header or STREAMINFO.
+    pub sample_rate: u32,
+    /// Bits per sample, from the header or STREAMINFO.
+    pub bits_per_sample: u8,
+    /// Length of the whole frame in bytes, CRC-16 included.
+    pub frame_len: usize,
+}
+
+/// Decodes the frame at the start of `data`, appending its samples to
+/// `out` interleaved and sign-extended to 32 bits. The sample rate and
+/// size are taken from `info` when the header does not code them.
+///
+/// # Errors
+/// Returns an error if the frame is truncated or malformed, fails its CRC
+/// checks, or needs STREAMINFO values that are not given.
+pub fn decode_frame(
+    data: &[u8],
+    info: Option<&FlacStreamInfo>,
+    out: &mut Vec<i32>,
+) -> Result<DecodedFrame> {
+    let header = FrameHeader::parse(data)?;
+    let sample_rate = header
+        .sample_rate
+        .or(info.map(|info| info.sample_rate))
+        .ok_or_else(|| PlayerError::decoder("FLAC sample rate requires STREAMINFO"))?;
+    let bits_per_sample = header
+        .bits_per_sample
+        .or(info.map(|info| info.bits_per_sample))
+        .ok_or_else(|| PlayerError::decoder("FLAC sample size requires STREAMINFO"))?;
+
+    let block_size = header.block_size as usize;
+    let assignment = header.channel_assignment;
+    let mut r = BitReader::new(&data[header.header_len..]);
+    let mut channels = (0..header.channels() as usize)
+        .map(|index| {
+            let bits = bits_per_sample as u32 + assignment.is_side(index) as u32;
+            decode_subframe(&mut r, block_size, bits)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Zero padding up to the byte boundary, then the CRC-16 of the frame.
+    r.skip((8 - r.position() % 8) % 8)?;
+    let frame_len = header.header_len + r.position() / 8 + 2;
+    let frame = data
+        .get(..frame_len)
+        .ok_or_else(|| PlayerError::decoder("FLAC frame truncated"))?;
+    if crc16(0, frame) != 0 {
+        return Err(PlayerError::decoder("FLAC frame CRC mismatch"));
+    }
+
+    if let [first, second] = channels.as_mut_slice() {
+        let pairs = first.iter_mut().zip(second.iter_mut());
+        match assignment {
+            ChannelAssignment::LeftSide => {
+                pairs.for_each(|(left, side)| *side = left.wrapping_sub(*side))
+            }
+            ChannelAssignment::SideRight => {
+                pairs.for_each(|(side, right)| *side = side.wrapping_add(*right))
+            }
+            ChannelAssignment::MidSide => pairs.for_each(|(mid, side)| {
+                let sum = (*mid << 1) | (*side & 1);
+                (*mid, *side) = (sum.wrapping_add(*side) >> 1, sum.wrapping_sub(*side) >> 1);
+            }),
+            ChannelAssignment::Independent(_) => {}
+        }
+    }
+    out.reserve(block_size * channels.len());
+    for i in 0..block_size {
+        out.extend(channels.iter().map(|channel| channel[i] as i32));
+    }
+    Ok(DecodedFrame {
+        header,
+        sample_rate,
+        bits_per_sample,
+        frame_len,
+    })
+}
+
+/// Decodes a subframe of `block_size` samples of `bits` bits.
+fn decode_subframe(r: &mut BitReader<'_>, block_size: usize, bits: u32) -> Result<Vec<i64>> {
+    if r.flag()? {
+        return Err(PlayerError::decoder("Invalid FLAC subframe header"));
+    }
+    let kind = r.bits(6)?;
+    let wasted = if r.flag()? { r.unary()? + 1 } else { 0 };
+    if wasted >= bits {
+        return Err(PlayerError::decoder("Invalid FLAC wasted bits"));
+    }
+    let bits = bits - wasted;
+
+    let mut samples = match kind {
+        0 => vec![read_signed(r, bits)?; block_size],
+        1 => (0..block_size)
+            .map(|_| read_signed(r, bits))
+            .collect::<Result<_>>()?,
+        8..=12 => {
+            let coefficients = FIXED_COEFFICIENTS[kind as usize - 8];
+            decode_predicted(r, block_size, bits, coefficients.len(), |_| {
+                Ok((coefficients.to_vec(), 0))
+            })?
+        }
+        32..=63 => decode_predicted(r, block_size, bits, kind as usize - 31, |r| {
+            let precision = r.bits(4)? + 1;
+            let shift = read_signed(r, 5)?;
+            if precision == 16 || shift < 0 {
+                return Err(PlayerError::decoder("Invalid FLAC LPC parameters"));
+            }
+            let order = kind as usize - 31;
+            let coefficients = (0..order)
+                .map(|_| read_signed(r, precision))
+                .collect::<Result<_>>()?;
+            Ok((coefficients, shift as u32))
+        })?,
+        _ => return Err(PlayerError::decoder("Reserved FLAC subframe type")),
+    };
+    if wasted > 0 {
+        samples.iter_mut().for_each(|sample| *sample <<= wasted);
+    }
+    Ok(samples)
+}
+
+/// Decodes a predicted subframe: `order` warm-up samples, the predictor
+/// coefficients and shift read by `predictor`, and the residual.
+fn decode_predicted(
+    r: &mut BitReader<'_>,
+    block_size: usize,
+    bits: u32,
+    order: usize,
+    predictor: impl FnOnce(&mut BitReader<'_>) -> Result<(Vec<i64>, u32)>,
+) -> Result<Vec<i64>> {
+    if order > block_size {
+        return Err(PlayerError::decoder(
+            "FLAC predictor order exceeds block size",
+        ));
+    }
+    let mut samples = Vec::with_capacity(block_size);
+    for _ in 0..order {
+        samples.push(read_signed(r, bits)?);
+    }
+    let (coefficients, shift) = predictor(r)?;
+    read_residual(r, block_size, order, &mut samples)?;
+    // Damaged frames, only rejected by the CRC once decoded, may overflow.
+    for i in order..block_size {
+        let prediction = coefficients
+            .iter()
+            .zip(samples[..i].iter().rev())
+            .fold(0i64, |sum, (c, s)| sum.wrapping_add(c.wrapping_mul(*s)));
+        samples[i] = samples[i].wrapping_add(prediction >> shift);
+    }
+    Ok(samples)
+}
+
+/// Reads the Rice-coded residual of a predicted subframe, appending the
+/// `block_size - order` values to `out`.
+fn read_residual(
+    r: &mut BitReader<'_>,
+    block_size: usize,
+    order: usize,
+    out: &mut Vec<i64>,
+) -> Result<()> {
+    let (param_bits, escape) = match r.bits(2)? {
+        0 => (4, 15),
+        1 => (5, 31),
+        _ => return Err(PlayerError::decoder("Reserved FLAC residual coding")),
+    };
+    let partition_order = r.bits(4)?;
+    let partition_len = block_size >> partition_order;
+    if partition_len << partition_order != block_size || partition_len < order {
+        return Err(PlayerError::decoder(
+            "Invalid FLAC residual partition order",
+        ));
+    }
+    for partition in 0..1usize << partition_order {
+        let count = partition_len - if partition == 0 { order } else { 0 };
+        let param = r.bits(param_bits)?;
+        if param == escape {
+            let bits = r.bits(5)?;
+            for _ in 0..count {
+                out.push(read_signed(r, bits)?);
+            }
+            continue;
+        }
+        for _ in 0..count {
+            let value = (r.unary()? as u64) << param | r.bits(param)? as u64;
+            out.push((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Ok(())
+}
+
+/// Reads a two's complement value of up to 33 bits.
+fn read_signed(r: &mut BitReader<'_>, bits: u32) -> Result<i64> {
+    if bits == 0 {
+        return Ok(0);
+    }
+    let value = if bits > 32 {
+        (r.bits(bits - 32)? as u64) << 32 | r.bits(32)? as u64
+    } else {
+        r.bits(bits)? as u64
+    };
+    let shift = 64 - bits;
+    Ok(((value << shift) as i64) >> shift)
+}
+
+/// CRC-8 lookup table for the polynomial x^8 + x^2 + x + 1.
+const CRC8_TABLE: [u8; 256] = {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-16 lookup table for the polynomial x^16 + x^15 + x^2 + 1.
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x8005
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC-8 that ends a frame header.
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0, |crc, &byte| CRC8_TABLE[(crc ^ byte) as usize])
+}
+
+/// Continues the frame CRC-16 `crc` over `data`; a frame checks out when
+/// the CRC over all of it, footer included, is zero.
+pub fn crc16(crc: u16, data: &[u8]) -> u16 {
+    data.iter().fold(crc, |crc, &byte| {
+        crc << 8 ^ CRC16_TABLE[((crc >> 8) as u8 ^ byte) as usize]
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::decoder::h264::tests::BitWriter;
+
+    /// Writes `value` as a Rice code with parameter `param`.
+    fn rice(w: &mut BitWriter, param: u32, value: i32) {
+        let folded = ((value << 1) ^ (value >> 31)) as u32;
+        w.bits(folded >> param, 0).bits(1, 1).bits(param, folded);
+    }
+
+    /// Builds a fixed-blocksize frame at 44.1 kHz with an explicit 16-bit
+    /// block size, the given channel and sample size codes, and subframes
+    /// written by `subframes`.
+    pub(crate) fn frame(
+        number: u8,
+        block_size: u16,
+        channel_code: u8,
+        size_code: u8,
+        subframes: impl FnOnce(&mut BitWriter),
+    ) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xF8, 0x79, channel_code << 4 | size_code << 1, number];
+        data.extend((block_size - 1).to_be_bytes());
+        data.push(crc8(&data));
+        let mut w = BitWriter::default();
+        subframes(&mut w);
+        data.extend(w.aligned());
+        data.extend(crc16(0, &data).to_be_bytes());
+        data
+    }
+
+    /// Builds a 16-bit mono frame of `block_size` samples of `value`.
+    pub(crate) fn constant_frame(number: u8, block_size: u16, value: i16) -> Vec<u8> {
+        frame(number, block_size, 0, 4, |w| {
+            w.bits(8, 0).bits(16, value as u16 as u32);
+        })
+    }
+
+    /// Builds a STREAMINFO block payload.
+    pub(crate) fn streaminfo(
+        block_size: u16,
+        rate: u32,
+        channels: u8,
+        bits: u8,
+        total: u64,
+    ) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(16, block_size as u32).bits(16, block_size as u32);
+        w.bits(24, 0).bits(24, 0).bits(20, rate);
+        w.bits(3, channels as u32 - 1).bits(5, bits as u32 - 1);
+        w.bits(4, (total >> 32) as u32).bits(32, total as u32);
+        let mut payload = w.aligned();
+        payload.resize(STREAMINFO_LEN, 0);
+        payload
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc16(0, b"123456789"), 0xFEE8);
+        assert_eq!(crc16(crc16(0, b"1234"), b"56789"), 0xFEE8);
+    }
+
+    #[test]
+    fn test_parse_stream_info() {
+        let mut data = STREAM_MARKER.to_vec();
+        data.extend([BLOCK_STREAMINFO, 0, 0, 34]);
+        data.extend(streaminfo(4096, 96000, 6, 24, 1 << 33));
+        // A last PADDING block, which is ignored.
+        data.extend([0x81, 0, 0, 8]);
+        data.extend([0; 8]);
+        let info = FlacStreamInfo::parse(&data).unwrap();
+        assert_eq!((info.min_block_size, info.max_block_size), (4096, 4096));
+        assert!(info.is_fixed_block_size());
+        assert_eq!(
+            (info.sample_rate, info.channels, info.bits_per_sample),
+            (96000, 6, 24)
+        );
+        assert_eq!(info.total_samples, Some(1 << 33));
+        // Without the marker, as in `dfLa`.
+        assert_eq!(FlacStreamInfo::parse(&data[4..]).unwrap(), info);
+
+        let mut audio = AudioInfo::default();
+        info.fill_audio_info(&mut audio);
+        assert_eq!(audio.channel_layout.as_deref(), Some("5.1(back)"));
+        assert_eq!((audio.sample_rate, audio.bit_depth), (96000, Some(24)));
+
+        assert!(FlacStreamInfo::parse(&data[..20]).is_err());
+        assert!(FlacStreamInfo::parse(&[4, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_parse_frame_header() {
+        // Variable block size, 4608 samples, 48 kHz, mid/side, 24-bit,
+        // first sample 0x2345 in a 3-byte coded number.
+        let mut data = vec![0xFF, 0xF9, 0x5A, 0xAC, 0xE0 | (0x2345 >> 12) as u8];
+        data.extend([
+            0x80 | (0x2345 >> 6 & 0x3F) as u8,
+            0x80 | (0x2345 & 0x3F) as u8,
+        ]);
+        data.push(crc8(&data));
+        let header = FrameHeader::parse(&data).unwrap();
+        assert!(header.variable_block_size);
+        assert_eq!(header.block_size, 4608);
+        assert_eq!(header.sample_rate, Some(48000));
+        assert_eq!(header.channel_assignment, ChannelAssignment::MidSide);
+        assert_eq!(header.bits_per_sample, Some(24));
+        assert_eq!(header.number, 0x2345);
+        assert_eq!(header.header_len, 8);
+        assert_eq!(header.first_sample(4096), 0x2345);
+        assert_eq!(header.next_number(), 0x2345 + 4608);
+
+        let frame = constant_frame(3, 1024, 0);
+        let header = FrameHeader::parse(&frame).unwrap();
+        assert_eq!((header.number, header.block_size), (3, 1024));
+        assert_eq!(header.first_sample(1024), 3072);
+        assert_eq!(header.next_number(), 4);
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(FrameHeader::parse(&data).is_err());
+        assert!(FrameHeader::parse(&[0xFF, 0xFA, 0x5A, 0xAC, 0]).is_err());
+        assert!(FrameHeader::parse(&[0xFF, 0xF8, 0x5A, 0xB0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_decode_constant_and_verbatim() {
+        let mut out = Vec::new();
+        let decoded = decode_frame(&constant_frame(0, 16, -300), None, &mut out).unwrap();
+        assert_eq!(out, vec![-300; 16]);
+        assert_eq!((decoded.sample_rate, decoded.bits_per_sample), (44100, 16));
+        assert_eq!(decoded.frame_len, constant_frame(0, 16, -300).len());
+
+        // Two independent 8-bit channels of verbatim samples, the second
+        // with one wasted bit.
+        let data = frame(1, 3, 1, 1, |w| {
+            w.bits(8, 0b0000_0010);
+            for sample in [1, -2, 127] {
+                w.bits(8, sample as u8 as u32);
+            }
+            w.bits(8, 0b0000_0011).bits(1, 1);
+            for sample in [-3, 2, 63] {
+                w.bits(7, (sample as u8 & 0x7F) as u32);
+            }
+        });
+        out.clear();
+        decode_frame(&data, None, &mut out).unwrap();
+        assert_eq!(out, vec![1, -6, -2, 4, 127, 126]);
+    }
+
+    #[test]
+    fn test_decode_fixed_and_lpc() {
+        // Fixed order 2 continues the ramp 10, 20 with residuals 0, 1, -2.
+        let data = frame(0, 5, 0, 4, |w| {
+            w.bits(8, 0b0001_0100);
+            w.bits(16, 10).bits(16, 20);
+            w.bits(2, 0).bits(4, 0).bits(4, 1);
+            for residual in [0, 1, -2] {
+                rice(w, 1, residual);
+            }
+        });
+        let mut out = Vec::new();
+        decode_frame(&data, None, &mut out).unwrap();
+        assert_eq!(out, vec![10, 20, 30, 41, 50]);
+
+        // LPC order 2 with coefficients 3 and -1 at 3 bits precision and a
+        // shift of 1, and residual partition order 1 with the second
+        // partition escaped to 4-bit values.
+        let data = frame(0, 6, 0, 4, |w| {
+            w.bits(8, 0b0100_0010);
+            w.bits(16, 100).bits(16, 104);
+            w.bits(4, 2).bits(5, 1).bits(3, 3).bits(3, 0b111);
+            w.bits(2, 1).bits(4, 1);
+            w.bits(5, 0);
+            rice(w, 0, -1);
+            w.bits(5, 31).bits(5, 4);
+            for residual in [3, -8, 7] {
+                w.bits(4, (residual & 0xF) as u32);
+            }
+        });
+        out.clear();
+        decode_frame(&data, None, &mut out).unwrap();
+        // p = (3 * s[n-1] - s[n-2]) >> 1
+        assert_eq!(out, vec![100, 104, 105, 108, 101, 104]);
+    }
+
+    #[test]
+    fn test_decode_stereo_decorrelation() {
+        let left = [1000i32, -20000, 32767];
+        let right = [-1000i32, 20000, -32768];
+        // Writes one verbatim subframe of 16 or 17 bits.
+        let verbatim = |w: &mut BitWriter, samples: [i32; 3], bits: u32| {
+            w.bits(8, 0b0000_0010);
+            for sample in samples {
+                w.bits(bits, sample as u32 & ((1 << bits) - 1));
+            }
+        };
+        let side: [i32; 3] = std::array::from_fn(|i| left[i] - right[i]);
+        let mid: [i32; 3] = std::array::from_fn(|i| (left[i] + right[i]) >> 1);
+        let expected: Vec<i32> = left
+            .iter()
+            .zip(&right)
+            .flat_map(|(&l, &r)| [l, r])
+            .collect();
+        for (code, first, second) in [
+            (8, (left, 16), (side, 17)),
+            (9, (side, 17), (right, 16)),
+            (10, (mid, 16), (side, 17)),
+        ] {
+            let data = frame(0, 3, code, 4, |w| {
+                verbatim(w, first.0, first.1);
+                verbatim(w, second.0, second.1);
+            });
+            let mut out = Vec::new();
+            decode_frame(&data, None, &mut out).unwrap();
+            assert_eq!(out, expected, "channel assignment {code}");
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut data = constant_frame(0, 16, 5);
+        let mut out = Vec::new();
+        assert!(decode_frame(&data[..data.len() - 1], None, &mut out).is_err());
+        let last = data.len() - 1;
+        data[last] ^= 0x10;
+        assert!(decode_frame(&data, None, &mut out).is_err());
+
+        // The sample size comes from STREAMINFO.
+        let data = frame(0, 16, 0, 0, |w| {
+            w.bits(8, 0).bits(12, 0x800);
+        });
+        assert!(decode_frame(&data, None, &mut out).is_err());
+        let info = FlacStreamInfo::parse_streaminfo(&streaminfo(16, 44100, 1, 12, 16)).unwrap();
+        decode_frame(&data, Some(&info), &mut out).unwrap();
+        assert_eq!(out, vec![-2048; 16]);
+    }
+}
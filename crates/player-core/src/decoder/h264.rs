@@ -0,0 +1,548 @@
+//! H.264 / AVC bitstream parsing.
+//!
+//! Reads the `avcC` decoder configuration record stored by MP4 and Matroska
+//! and the sequence parameter set it carries, which describe the coded
+//! picture and give the RFC 6381 codec string used to configure WebCodecs.
+
+use super::bits::BitReader;
+use super::nal::{join_annex_b, unescape_rbsp};
+use super::MAX_DIMENSION;
+use crate::demuxer::{ColorInfo, VideoInfo};
+use crate::error::{PlayerError, Result};
+
+/// NAL unit type of a sequence parameter set.
+pub const NAL_SPS: u8 = 7;
+/// NAL unit type of a picture parameter set.
+pub const NAL_PPS: u8 = 8;
+
+/// Profiles whose SPS carries chroma format and bit depth fields.
+const HIGH_PROFILES: [u8; 13] = [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135];
+
+/// Sample aspect ratios of `aspect_ratio_idc` 1 to 16 (Table E-1).
+const SAMPLE_ASPECT_RATIOS: [(u32, u32); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+/// `aspect_ratio_idc` signalling an explicit sample aspect ratio.
+const EXTENDED_SAR: u32 = 255;
+
+/// Returns the type of a NAL unit from its header byte.
+pub fn nal_unit_type(nal: &[u8]) -> Option<u8> {
+    nal.first().map(|header| header & 0x1F)
+}
+
+/// AVC decoder configuration record (`avcC`, ISO/IEC 14496-15).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvcDecoderConfig {
+    /// `AVCProfileIndication`.
+    pub profile_idc: u8,
+    /// Constraint flags between the profile and level bytes.
+    pub profile_compatibility: u8,
+    /// `AVCLevelIndication`.
+    pub level_idc: u8,
+    /// Size of the NAL unit length prefix in samples (1, 2 or 4).
+    pub nal_length_size: u8,
+    /// Sequence parameter set NAL units.
+    pub sequence_parameter_sets: Vec<Vec<u8>>,
+    /// Picture parameter set NAL units.
+    pub picture_parameter_sets: Vec<Vec<u8>>,
+    /// The first sequence parameter set, decoded.
+    pub sps: Sps,
+}
+
+impl AvcDecoderConfig {
+    /// Parses an `avcC` record.
+    ///
+    /// # Errors
+    /// Returns an error if the record is truncated, has an unknown version,
+    /// or carries no valid sequence parameter set.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        if r.bits(8)? != 1 {
+            return Err(PlayerError::decoder("Unsupported avcC version"));
+        }
+        let profile_idc = r.bits(8)? as u8;
+        let profile_compatibility = r.bits(8)? as u8;
+        let level_idc = r.bits(8)? as u8;
+        r.skip(6)?;
+        let nal_length_size = r.bits(2)? as u8 + 1;
+        if nal_length_size == 3 {
+            return Err(PlayerError::decoder("Invalid NAL length size"));
+        }
+        r.skip(3)?;
+        let sps_count = r.bits(5)?;
+        let sequence_parameter_sets = parameter_sets(&mut r, sps_count)?;
+        let pps_count = r.bits(8)?;
+        let picture_parameter_sets = parameter_sets(&mut r, pps_count)?;
+
+        let sps = sequence_parameter_sets
+            .first()
+            .ok_or_else(|| PlayerError::decoder("avcC has no SPS"))
+            .and_then(|nal| Sps::parse(nal))?;
+
+        Ok(Self {
+            profile_idc,
+            profile_compatibility,
+            level_idc,
+            nal_length_size,
+            sequence_parameter_sets,
+            picture_parameter_sets,
+            sps,
+        })
+    }
+
+    /// Returns the RFC 6381 codec string, e.g. `avc1.64001f`.
+    pub fn codec_string(&self) -> String {
+        format!(
+            "avc1.{:02x}{:02x}{:02x}",
+            self.profile_idc, self.profile_compatibility, self.level_idc
+        )
+    }
+
+    /// Returns the parameter sets as an Annex B byte stream, to prepend to
+    /// the first keyframe for decoders that expect in-band parameter sets.
+    pub fn parameter_sets_annex_b(&self) -> Vec<u8> {
+        let units: Vec<&[u8]> = self
+            .sequence_parameter_sets
+            .iter()
+            .chain(&self.picture_parameter_sets)
+            .map(Vec::as_slice)
+            .collect();
+        join_annex_b(&units)
+    }
+}
+
+/// Reads `count` parameter sets with 16-bit length prefixes, as stored in
+/// `avcC` and `hvcC` records.
+pub(super) fn parameter_sets(r: &mut BitReader<'_>, count: u32) -> Result<Vec<Vec<u8>>> {
+    let mut sets = Vec::new();
+    for _ in 0..count {
+        let len = r.bits(16)? as usize;
+        sets.push(r.bytes(len)?.to_vec());
+    }
+    Ok(sets)
+}
+
+/// Decoded sequence parameter set fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sps {
+    /// `profile_idc` (66 Baseline, 77 Main, 100 High, ...).
+    pub profile_idc: u8,
+    /// `constraint_set0_flag` to `constraint_set5_flag` and reserved bits.
+    pub constraint_flags: u8,
+    /// `level_idc`, ten times the level number.
+    pub level_idc: u8,
+    /// `seq_parameter_set_id`.
+    pub id: u32,
+    /// `chroma_format_idc` (0 monochrome, 1 4:2:0, 2 4:2:2, 3 4:4:4).
+    pub chroma_format_idc: u32,
+    /// Luma sample bit depth.
+    pub bit_depth_luma: u8,
+    /// Chroma sample bit depth.
+    pub bit_depth_chroma: u8,
+    /// Picture width in pixels, after cropping.
+    pub width: u32,
+    /// Picture height in pixels, after cropping.
+    pub height: u32,
+    /// Whether every picture is a frame (no field or MBAFF coding).
+    pub frame_mbs_only: bool,
+    /// Sample (pixel) aspect ratio from the VUI, if signalled.
+    pub sample_aspect_ratio: Option<(u32, u32)>,
+    /// Colour description from the VUI, if signalled.
+    pub color: Option<ColorInfo>,
+    /// Frame rate from the VUI timing information, if signalled.
+    pub frame_rate: Option<f64>,
+}
+
+impl Sps {
+    /// Parses a sequence parameter set NAL unit, including its header byte.
+    ///
+    /// # Errors
+    /// Returns an error if the NAL unit is not an SPS, is truncated, or has
+    /// fields out of range, including pictures larger than [`MAX_DIMENSION`].
+    pub fn parse(nal: &[u8]) -> Result<Self> {
+        if nal_unit_type(nal) != Some(NAL_SPS) {
+            return Err(PlayerError::decoder("NAL unit is not an SPS"));
+        }
+        let rbsp = unescape_rbsp(&nal[1..]);
+        let mut r = BitReader::new(&rbsp);
+
+        let mut sps = Sps {
+            profile_idc: r.bits(8)? as u8,
+            constraint_flags: r.bits(8)? as u8,
+            level_idc: r.bits(8)? as u8,
+            id: r.ue()?,
+            chroma_format_idc: 1,
+            bit_depth_luma: 8,
+            bit_depth_chroma: 8,
+            ..Sps::default()
+        };
+
+        let mut separate_colour_plane = false;
+        if HIGH_PROFILES.contains(&sps.profile_idc) {
+            sps.chroma_format_idc = r.ue()?;
+            if sps.chroma_format_idc > 3 {
+                return Err(PlayerError::decoder("Invalid SPS chroma format"));
+            }
+            if sps.chroma_format_idc == 3 {
+                separate_colour_plane = r.flag()?;
+            }
+            sps.bit_depth_luma = bit_depth(&mut r)?;
+            sps.bit_depth_chroma = bit_depth(&mut r)?;
+            r.skip(1)?; // qpprime_y_zero_transform_bypass_flag
+            if r.flag()? {
+                let lists = if sps.chroma_format_idc == 3 { 12 } else { 8 };
+                for i in 0..lists {
+                    if r.flag()? {
+                        skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        if r.ue()? > 12 {
+            return Err(PlayerError::decoder("Invalid SPS log2_max_frame_num"));
+        }
+        match r.ue()? {
+            0 => {
+                if r.ue()? > 12 {
+                    return Err(PlayerError::decoder(
+                        "Invalid SPS log2_max_pic_order_cnt_lsb",
+                    ));
+                }
+            }
+            1 => {
+                r.skip(1)?; // delta_pic_order_always_zero_flag
+                r.se()?; // offset_for_non_ref_pic
+                r.se()?; // offset_for_top_to_bottom_field
+                let cycle = r.ue()?;
+                if cycle > 255 {
+                    return Err(PlayerError::decoder("Invalid SPS pic_order_cnt cycle"));
+                }
+                for _ in 0..cycle {
+                    r.se()?;
+                }
+            }
+            2 => {}
+            _ => return Err(PlayerError::decoder("Invalid SPS pic_order_cnt_type")),
+        }
+        r.ue()?; // max_num_ref_frames
+        r.skip(1)?; // gaps_in_frame_num_value_allowed_flag
+
+        // Sizes are computed in 64 bits, as the fields can be up to 2^32 - 2.
+        let width_mbs = r.ue()? as u64 + 1;
+        let height_map_units = r.ue()? as u64 + 1;
+        sps.frame_mbs_only = r.flag()?;
+        if !sps.frame_mbs_only {
+            r.skip(1)?; // mb_adaptive_frame_field_flag
+        }
+        r.skip(1)?; // direct_8x8_inference_flag
+
+        let field_factor = 2 - sps.frame_mbs_only as u64;
+        let (mut width, mut height) = (width_mbs * 16, height_map_units * 16 * field_factor);
+        if width > MAX_DIMENSION as u64 || height > MAX_DIMENSION as u64 {
+            return Err(PlayerError::decoder("SPS picture size too large"));
+        }
+        if r.flag()? {
+            // Crop offsets count chroma samples; 4:4:4 and monochrome count pixels.
+            let (crop_x, crop_y) = match (separate_colour_plane, sps.chroma_format_idc) {
+                (false, 1) => (2, 2 * field_factor),
+                (false, 2) => (2, field_factor),
+                _ => (1, field_factor),
+            };
+            let (left, right) = (r.ue()? as u64, r.ue()? as u64);
+            let (top, bottom) = (r.ue()? as u64, r.ue()? as u64);
+            let (crop_width, crop_height) = (crop_x * (left + right), crop_y * (top + bottom));
+            if crop_width >= width || crop_height >= height {
+                return Err(PlayerError::decoder("Invalid SPS frame cropping"));
+            }
+            width -= crop_width;
+            height -= crop_height;
+        }
+        sps.width = width as u32;
+        sps.height = height as u32;
+
+        if r.flag()? {
+            sps.parse_vui(&mut r)?;
+        }
+        Ok(sps)
+    }
+
+    /// Reads the VUI fields up to the timing information.
+    fn parse_vui(&mut self, r: &mut BitReader<'_>) -> Result<()> {
+        if r.flag()? {
+            let idc = r.bits(8)?;
+            self.sample_aspect_ratio = match idc {
+                EXTENDED_SAR => Some((r.bits(16)?, r.bits(16)?)),
+                1..=16 => Some(SAMPLE_ASPECT_RATIOS[idc as usize - 1]),
+                _ => None,
+            }
+            .filter(|&(w, h)| w > 0 && h > 0);
+        }
+        if r.flag()? {
+            r.skip(1)?; // overscan_appropriate_flag
+        }
+        if r.flag()? {
+            r.skip(3)?; // video_format
+            let full_range = r.flag()?;
+            let mut color = ColorInfo {
+                full_range: Some(full_range),
+                ..ColorInfo::default()
+            };
+            if r.flag()? {
+                color.primaries = Some(r.bits(8)? as u8);
+                color.transfer = Some(r.bits(8)? as u8);
+                color.matrix = Some(r.bits(8)? as u8);
+            }
+            self.color = Some(color);
+        }
+        if r.flag()? {
+            r.ue()?; // chroma_sample_loc_type_top_field
+            r.ue()?; // chroma_sample_loc_type_bottom_field
+        }
+        if r.flag()? {
+            let num_units_in_tick = r.bits(32)?;
+            let time_scale = r.bits(32)?;
+            if num_units_in_tick > 0 && time_scale > 0 {
+                self.frame_rate = Some(time_scale as f64 / (2.0 * num_units_in_tick as f64));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the RFC 6381 codec string, e.g. `avc1.64001f`.
+    pub fn codec_string(&self) -> String {
+        format!(
+            "avc1.{:02x}{:02x}{:02x}",
+            self.profile_idc, self.constraint_flags, self.level_idc
+        )
+    }
+
+    /// Fills picture properties the container left unset.
+    pub fn fill_video_info(&self, video: &mut VideoInfo) {
+        if video.width == 0 || video.height == 0 {
+            video.width = self.width;
+            video.height = self.height;
+        }
+        if video.display_aspect_ratio.is_none() && self.height > 0 {
+            let (sar_w, sar_h) = self.sample_aspect_ratio.unwrap_or((1, 1));
+            video.display_aspect_ratio =
+                Some(self.width as f64 * sar_w as f64 / (self.height as f64 * sar_h as f64));
+        }
+        video.frame_rate = video.frame_rate.or(self.frame_rate);
+        video.color = video.color.or(self.color);
+        video.bit_depth = video.bit_depth.or(Some(self.bit_depth_luma));
+    }
+}
+
+/// Reads a `bit_depth_*_minus8` field, which may be at most 6.
+fn bit_depth(r: &mut BitReader<'_>) -> Result<u8> {
+    match r.ue()? {
+        minus8 @ 0..=6 => Ok(minus8 as u8 + 8),
+        _ => Err(PlayerError::decoder("Invalid SPS bit depth")),
+    }
+}
+
+/// Skips a `scaling_list()` of `size` coefficients.
+fn skip_scaling_list(r: &mut BitReader<'_>, size: usize) -> Result<()> {
+    let (mut last, mut next) = (8i32, 8i32);
+    for _ in 0..size {
+        if next != 0 {
+            let delta = r.se()?;
+            if !(-128..=127).contains(&delta) {
+                return Err(PlayerError::decoder("Invalid SPS scaling list delta"));
+            }
+            next = (last + delta + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Writes MSB-first bit fields and Exp-Golomb codes.
+    #[derive(Default)]
+    pub(crate) struct BitWriter {
+        bytes: Vec<u8>,
+        bits: usize,
+    }
+
+    impl BitWriter {
+        pub(crate) fn bits(&mut self, n: u32, value: u32) -> &mut Self {
+            for i in (0..n).rev() {
+                if self.bits.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                let bit = ((value >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (7 - self.bits % 8);
+                self.bits += 1;
+            }
+            self
+        }
+
+        pub(crate) fn ue(&mut self, value: u32) -> &mut Self {
+            let code = value + 1;
+            let len = 32 - code.leading_zeros();
+            self.bits(len - 1, 0).bits(len, code)
+        }
+
+        /// Returns the bytes written, zero-padded to a byte boundary.
+        pub(crate) fn aligned(&mut self) -> Vec<u8> {
+            self.bits = 0;
+            std::mem::take(&mut self.bytes)
+        }
+
+        pub(crate) fn finish(&mut self) -> Vec<u8> {
+            // rbsp_stop_one_bit and alignment.
+            self.bits(1, 1);
+            std::mem::take(&mut self.bytes)
+        }
+    }
+
+    /// Builds a High profile 1920x1080 SPS with BT.709 colour and 23.976 fps timing.
+    pub(crate) fn high_profile_sps() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(8, 100).bits(8, 0).bits(8, 0x28).ue(0);
+        w.ue(1).ue(0).ue(0).bits(1, 0).bits(1, 0); // 4:2:0, 8-bit, no scaling matrix
+        w.ue(0).ue(0).ue(2); // frame_num, POC type 0, lsb
+        w.ue(4).bits(1, 0); // ref frames, gaps
+        w.ue(119).ue(67).bits(1, 1).bits(1, 1); // 120x68 MBs, frame_mbs_only, direct_8x8
+        w.bits(1, 1).ue(0).ue(0).ue(0).ue(4); // crop 8 lines at the bottom
+        w.bits(1, 1); // vui_parameters_present_flag
+        w.bits(1, 1).bits(8, 1); // square pixels
+        w.bits(1, 0); // overscan
+        w.bits(1, 1).bits(3, 5).bits(1, 0).bits(1, 1); // video signal, limited range
+        w.bits(8, 1).bits(8, 1).bits(8, 1);
+        w.bits(1, 0); // chroma location
+        w.bits(1, 1).bits(32, 1001).bits(32, 48000).bits(1, 1);
+        let mut nal = vec![0x67];
+        nal.extend(w.finish());
+        nal
+    }
+
+    /// Wraps parameter sets in an `avcC` record.
+    pub(crate) fn avcc_record(sps: &[u8], pps: &[u8]) -> Vec<u8> {
+        let mut record = vec![1, sps[1], sps[2], sps[3], 0xFF, 0xE1];
+        record.extend((sps.len() as u16).to_be_bytes());
+        record.extend(sps);
+        record.push(1);
+        record.extend((pps.len() as u16).to_be_bytes());
+        record.extend(pps);
+        record
+    }
+
+    #[test]
+    fn test_parse_sps() {
+        let sps = Sps::parse(&high_profile_sps()).unwrap();
+        assert_eq!((sps.profile_idc, sps.level_idc), (100, 0x28));
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        assert!(sps.frame_mbs_only);
+        assert_eq!(sps.bit_depth_luma, 8);
+        assert_eq!(sps.sample_aspect_ratio, Some((1, 1)));
+        assert_eq!(
+            sps.color,
+            Some(ColorInfo {
+                primaries: Some(1),
+                transfer: Some(1),
+                matrix: Some(1),
+                full_range: Some(false),
+            })
+        );
+        assert!((sps.frame_rate.unwrap() - 23.976).abs() < 1e-3);
+        assert_eq!(sps.codec_string(), "avc1.640028");
+
+        assert!(Sps::parse(&[0x68, 0xCE]).is_err());
+        assert!(Sps::parse(&[0x67, 100]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sps_out_of_range() {
+        let sps = |bit_depth: u32, width_mbs: u32, crop_right: u32| {
+            let mut w = BitWriter::default();
+            w.bits(8, 100).bits(8, 0).bits(8, 0).ue(0);
+            w.ue(1).ue(bit_depth).ue(0).bits(1, 0).bits(1, 0);
+            w.ue(0).ue(2).ue(1).bits(1, 0);
+            w.ue(width_mbs - 1).ue(8).bits(1, 1).bits(1, 1);
+            w.bits(1, 1).ue(0).ue(crop_right).ue(0).ue(0).bits(1, 0);
+            let mut nal = vec![0x67];
+            nal.extend(w.finish());
+            Sps::parse(&nal)
+        };
+        assert_eq!(sps(2, 1024, 8).unwrap().width, 16368);
+        assert_eq!(sps(2, 1024, 8).unwrap().bit_depth_luma, 10);
+        assert!(sps(7, 1024, 8).is_err());
+        assert!(sps(0, 1025, 0).is_err());
+        assert!(sps(0, 4096, 0).is_err());
+        assert!(sps(0, 1, 8).is_err());
+        assert!(sps(0, 1, u32::MAX - 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_baseline_sps_with_emulation_prevention() {
+        // Baseline 176x144: the escaped zero run must not shift later fields.
+        let mut w = BitWriter::default();
+        w.bits(8, 66).bits(8, 0).bits(8, 0).ue(0);
+        w.ue(0).ue(0).ue(0).ue(1).bits(1, 0);
+        w.ue(10).ue(8).bits(1, 1).bits(1, 1).bits(1, 0).bits(1, 0);
+        let rbsp = w.finish();
+        assert_eq!(&rbsp[..3], &[66, 0, 0]);
+        let mut nal = vec![0x67, 66, 0, 0, 3];
+        nal.extend(&rbsp[3..]);
+
+        let sps = Sps::parse(&nal).unwrap();
+        assert_eq!((sps.width, sps.height), (176, 144));
+        assert!(sps.color.is_none());
+        assert_eq!(sps.codec_string(), "avc1.420000");
+    }
+
+    #[test]
+    fn test_parse_avcc() {
+        let sps = high_profile_sps();
+        let pps = [0x68, 0xEE, 0x3C, 0x80];
+        let config = AvcDecoderConfig::parse(&avcc_record(&sps, &pps)).unwrap();
+        assert_eq!(config.nal_length_size, 4);
+        assert_eq!(config.codec_string(), "avc1.640028");
+        assert_eq!(config.sequence_parameter_sets, vec![sps.clone()]);
+        assert_eq!(config.picture_parameter_sets, vec![pps.to_vec()]);
+        assert_eq!(config.sps.width, 1920);
+
+        let annex_b = config.parameter_sets_annex_b();
+        assert_eq!(&annex_b[..5], &[0, 0, 0, 1, 0x67]);
+        assert_eq!(
+            &annex_b[annex_b.len() - 8..],
+            &[0, 0, 0, 1, 0x68, 0xEE, 0x3C, 0x80]
+        );
+
+        let mut video = VideoInfo::default();
+        config.sps.fill_video_info(&mut video);
+        assert_eq!((video.width, video.height), (1920, 1080));
+        assert!((video.display_aspect_ratio.unwrap() - 16.0 / 9.0).abs() < 1e-9);
+        assert_eq!(video.bit_depth, Some(8));
+
+        let mut record = avcc_record(&sps, &pps);
+        record.truncate(20);
+        assert!(AvcDecoderConfig::parse(&record).is_err());
+        assert!(AvcDecoderConfig::parse(&[1, 100, 0, 40, 0xFF, 0xE0, 0]).is_err());
+        assert!(AvcDecoderConfig::parse(&[0]).is_err());
+    }
+}
@@ -0,0 +1,511 @@
+//! H.265 / HEVC bitstream parsing.
+//!
+//! Reads the `hvcC` decoder configuration record stored by MP4 and Matroska
+//! and the video and sequence parameter sets it carries. The profile, tier
+//! and level they signal make up the RFC 6381 codec string (ISO/IEC 14496-15
+//! Annex E) used to configure WebCodecs.
+
+use super::bits::BitReader;
+use super::h264::parameter_sets;
+use super::nal::{join_annex_b, unescape_rbsp};
+use super::MAX_DIMENSION;
+use crate::demuxer::VideoInfo;
+use crate::error::{PlayerError, Result};
+
+/// NAL unit type of a video parameter set.
+pub const NAL_VPS: u8 = 32;
+/// NAL unit type of a sequence parameter set.
+pub const NAL_SPS: u8 = 33;
+/// NAL unit type of a picture parameter set.
+pub const NAL_PPS: u8 = 34;
+
+/// Returns the type of a NAL unit from its two-byte header.
+pub fn nal_unit_type(nal: &[u8]) -> Option<u8> {
+    nal.first().map(|header| (header >> 1) & 0x3F)
+}
+
+/// General profile, tier and level of a coded video sequence.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProfileTierLevel {
+    /// `general_profile_space`, 0 for all current profiles.
+    pub profile_space: u8,
+    /// `general_tier_flag`: false for Main tier, true for High tier.
+    pub high_tier: bool,
+    /// `general_profile_idc` (1 Main, 2 Main 10, 3 Main Still Picture, ...).
+    pub profile_idc: u8,
+    /// `general_profile_compatibility_flag[0..32]`, flag 0 in the MSB.
+    pub profile_compatibility_flags: u32,
+    /// The 48 bits from `general_progressive_source_flag` onwards.
+    pub constraint_indicator_flags: u64,
+    /// `general_level_idc`, thirty times the level number.
+    pub level_idc: u8,
+}
+
+impl ProfileTierLevel {
+    /// Reads the general fields of a `profile_tier_level()` structure, which
+    /// share their layout with the start of an `hvcC` record.
+    fn read_general(r: &mut BitReader<'_>) -> Result<Self> {
+        Ok(Self {
+            profile_space: r.bits(2)? as u8,
+            high_tier: r.flag()?,
+            profile_idc: r.bits(5)? as u8,
+            profile_compatibility_flags: r.bits(32)?,
+            constraint_indicator_flags: ((r.bits(16)? as u64) << 32) | r.bits(32)? as u64,
+            level_idc: r.bits(8)? as u8,
+        })
+    }
+
+    /// Reads a `profile_tier_level(1, max_sub_layers_minus1)` structure,
+    /// skipping the sub-layer fields.
+    fn read(r: &mut BitReader<'_>, max_sub_layers_minus1: u32) -> Result<Self> {
+        let general = Self::read_general(r)?;
+        let mut present = Vec::new();
+        for _ in 0..max_sub_layers_minus1 {
+            present.push((r.flag()?, r.flag()?));
+        }
+        if max_sub_layers_minus1 > 0 {
+            r.skip(2 * (8 - max_sub_layers_minus1 as usize))?;
+        }
+        for (profile_present, level_present) in present {
+            if profile_present {
+                r.skip(88)?;
+            }
+            if level_present {
+                r.skip(8)?;
+            }
+        }
+        Ok(general)
+    }
+
+    /// Returns the RFC 6381 codec string for the given sample entry type,
+    /// e.g. `hvc1.1.6.L93.B0`.
+    pub fn codec_string(&self, sample_entry: &str) -> String {
+        let space = match self.profile_space {
+            1 => "A",
+            2 => "B",
+            3 => "C",
+            _ => "",
+        };
+        let mut codec = format!(
+            "{}.{}{}.{:X}.{}{}",
+            sample_entry,
+            space,
+            self.profile_idc,
+            self.profile_compatibility_flags.reverse_bits(),
+            if self.high_tier { 'H' } else { 'L' },
+            self.level_idc
+        );
+        // Constraint bytes, with trailing zero bytes omitted.
+        let constraints = &self.constraint_indicator_flags.to_be_bytes()[2..];
+        let len = constraints
+            .iter()
+            .rposition(|&b| b != 0)
+            .map_or(0, |i| i + 1);
+        for byte in &constraints[..len] {
+            codec.push_str(&format!(".{:X}", byte));
+        }
+        codec
+    }
+}
+
+/// HEVC decoder configuration record (`hvcC`, ISO/IEC 14496-15).
+#[derive(Debug, Clone, PartialEq)]
+pub struct HevcDecoderConfig {
+    /// General profile, tier and level of the stream.
+    pub profile_tier_level: ProfileTierLevel,
+    /// `chromaFormat` (0 monochrome, 1 4:2:0, 2 4:2:2, 3 4:4:4).
+    pub chroma_format_idc: u8,
+    /// Luma sample bit depth.
+    pub bit_depth_luma: u8,
+    /// Chroma sample bit depth.
+    pub bit_depth_chroma: u8,
+    /// Size of the NAL unit length prefix in samples (1, 2 or 4).
+    pub nal_length_size: u8,
+    /// Video parameter set NAL units.
+    pub video_parameter_sets: Vec<Vec<u8>>,
+    /// Sequence parameter set NAL units.
+    pub sequence_parameter_sets: Vec<Vec<u8>>,
+    /// Picture parameter set NAL units.
+    pub picture_parameter_sets: Vec<Vec<u8>>,
+    /// The first sequence parameter set, decoded.
+    pub sps: Sps,
+}
+
+impl HevcDecoderConfig {
+    /// Parses an `hvcC` record.
+    ///
+    /// # Errors
+    /// Returns an error if the record is truncated, has an unknown version,
+    /// or carries no valid sequence parameter set.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        if r.bits(8)? != 1 {
+            return Err(PlayerError::decoder("Unsupported hvcC version"));
+        }
+        let profile_tier_level = ProfileTierLevel::read_general(&mut r)?;
+        r.skip(16)?; // min_spatial_segmentation_idc
+        r.skip(8)?; // parallelismType
+        r.skip(6)?;
+        let chroma_format_idc = r.bits(2)? as u8;
+        r.skip(5)?;
+        let bit_depth_luma = r.bits(3)? as u8 + 8;
+        r.skip(5)?;
+        let bit_depth_chroma = r.bits(3)? as u8 + 8;
+        r.skip(16)?; // avgFrameRate
+        r.skip(6)?; // constantFrameRate, numTemporalLayers, temporalIdNested
+        let nal_length_size = r.bits(2)? as u8 + 1;
+        if nal_length_size == 3 {
+            return Err(PlayerError::decoder("Invalid NAL length size"));
+        }
+
+        let mut video_parameter_sets = Vec::new();
+        let mut sequence_parameter_sets = Vec::new();
+        let mut picture_parameter_sets = Vec::new();
+        for _ in 0..r.bits(8)? {
+            r.skip(2)?; // array_completeness, reserved
+            let nal_type = r.bits(6)? as u8;
+            let count = r.bits(16)?;
+            let units = parameter_sets(&mut r, count)?;
+            match nal_type {
+                NAL_VPS => video_parameter_sets.extend(units),
+                NAL_SPS => sequence_parameter_sets.extend(units),
+                NAL_PPS => picture_parameter_sets.extend(units),
+                // SEI arrays are not needed to configure the decoder.
+                _ => {}
+            }
+        }
+
+        let sps = sequence_parameter_sets
+            .first()
+            .ok_or_else(|| PlayerError::decoder("hvcC has no SPS"))
+            .and_then(|nal| Sps::parse(nal))?;
+
+        Ok(Self {
+            profile_tier_level,
+            chroma_format_idc,
+            bit_depth_luma,
+            bit_depth_chroma,
+            nal_length_size,
+            video_parameter_sets,
+            sequence_parameter_sets,
+            picture_parameter_sets,
+            sps,
+        })
+    }
+
+    /// Returns the RFC 6381 codec string for the given sample entry type
+    /// (`hvc1` or `hev1`).
+    pub fn codec_string(&self, sample_entry: &str) -> String {
+        self.profile_tier_level.codec_string(sample_entry)
+    }
+
+    /// Returns the parameter sets as an Annex B byte stream, to prepend to
+    /// the first keyframe for decoders that expect in-band parameter sets.
+    pub fn parameter_sets_annex_b(&self) -> Vec<u8> {
+        let units: Vec<&[u8]> = self
+            .video_parameter_sets
+            .iter()
+            .chain(&self.sequence_parameter_sets)
+            .chain(&self.picture_parameter_sets)
+            .map(Vec::as_slice)
+            .collect();
+        join_annex_b(&units)
+    }
+}
+
+/// Strips the two-byte header of a NAL unit of the given type and removes
+/// emulation prevention bytes.
+fn rbsp(nal: &[u8], nal_type: u8, name: &str) -> Result<Vec<u8>> {
+    if nal.len() < 2 || nal_unit_type(nal) != Some(nal_type) {
+        return Err(PlayerError::decoder(format!("NAL unit is not a {}", name)));
+    }
+    Ok(unescape_rbsp(&nal[2..]))
+}
+
+/// Decoded video parameter set fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Vps {
+    /// `vps_video_parameter_set_id`.
+    pub id: u8,
+    /// Number of temporal sub-layers.
+    pub max_sub_layers: u8,
+    /// General profile, tier and level.
+    pub profile_tier_level: ProfileTierLevel,
+}
+
+impl Vps {
+    /// Parses a video parameter set NAL unit, including its header.
+    ///
+    /// # Errors
+    /// Returns an error if the NAL unit is not a VPS or is truncated.
+    pub fn parse(nal: &[u8]) -> Result<Self> {
+        let rbsp = rbsp(nal, NAL_VPS, "VPS")?;
+        let mut r = BitReader::new(&rbsp);
+        let id = r.bits(4)? as u8;
+        r.skip(8)?; // base layer flags, vps_max_layers_minus1
+        let max_sub_layers_minus1 = r.bits(3)?;
+        r.skip(17)?; // vps_temporal_id_nesting_flag, vps_reserved_0xffff_16bits
+        Ok(Self {
+            id,
+            max_sub_layers: max_sub_layers_minus1 as u8 + 1,
+            profile_tier_level: ProfileTierLevel::read(&mut r, max_sub_layers_minus1)?,
+        })
+    }
+}
+
+/// Decoded sequence parameter set fields, up to the coded bit depths.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sps {
+    /// `sps_video_parameter_set_id`.
+    pub vps_id: u8,
+    /// `sps_seq_parameter_set_id`.
+    pub id: u32,
+    /// Number of temporal sub-layers.
+    pub max_sub_layers: u8,
+    /// General profile, tier and level.
+    pub profile_tier_level: ProfileTierLevel,
+    /// `chroma_format_idc` (0 monochrome, 1 4:2:0, 2 4:2:2, 3 4:4:4).
+    pub chroma_format_idc: u32,
+    /// Luma sample bit depth.
+    pub bit_depth_luma: u8,
+    /// Chroma sample bit depth.
+    pub bit_depth_chroma: u8,
+    /// Picture width in pixels, after the conformance window.
+    pub width: u32,
+    /// Picture height in pixels, after the conformance window.
+    pub height: u32,
+}
+
+impl Sps {
+    /// Parses a sequence parameter set NAL unit, including its header.
+    ///
+    /// # Errors
+    /// Returns an error if the NAL unit is not an SPS, is truncated, or has
+    /// fields out of range, including pictures larger than [`MAX_DIMENSION`].
+    pub fn parse(nal: &[u8]) -> Result<Self> {
+        let rbsp = rbsp(nal, NAL_SPS, "SPS")?;
+        let mut r = BitReader::new(&rbsp);
+        let vps_id = r.bits(4)? as u8;
+        let max_sub_layers_minus1 = r.bits(3)?;
+        r.skip(1)?; // sps_temporal_id_nesting_flag
+        let profile_tier_level = ProfileTierLevel::read(&mut r, max_sub_layers_minus1)?;
+        let id = r.ue()?;
+        let chroma_format_idc = r.ue()?;
+        if chroma_format_idc > 3 {
+            return Err(PlayerError::decoder("Invalid HEVC SPS chroma format"));
+        }
+        let separate_colour_plane = chroma_format_idc == 3 && r.flag()?;
+        let mut width = r.ue()?;
+        let mut height = r.ue()?;
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(PlayerError::decoder("HEVC SPS picture size too large"));
+        }
+        if r.flag()? {
+            // Conformance window offsets count chroma samples.
+            let (sub_width, sub_height) = match (separate_colour_plane, chroma_format_idc) {
+                (false, 1) => (2, 2),
+                (false, 2) => (2, 1),
+                _ => (1, 1),
+            };
+            let (left, right) = (r.ue()? as u64, r.ue()? as u64);
+            let (top, bottom) = (r.ue()? as u64, r.ue()? as u64);
+            let (crop_width, crop_height) =
+                (sub_width * (left + right), sub_height * (top + bottom));
+            if crop_width >= width as u64 || crop_height >= height as u64 {
+                return Err(PlayerError::decoder("Invalid HEVC SPS conformance window"));
+            }
+            width -= crop_width as u32;
+            height -= crop_height as u32;
+        }
+        let bit_depth_luma = bit_depth(&mut r)?;
+        let bit_depth_chroma = bit_depth(&mut r)?;
+
+        Ok(Self {
+            vps_id,
+            id,
+            max_sub_layers: max_sub_layers_minus1 as u8 + 1,
+            profile_tier_level,
+            chroma_format_idc,
+            bit_depth_luma,
+            bit_depth_chroma,
+            width,
+            height,
+        })
+    }
+
+    /// Fills picture properties the container left unset.
+    pub fn fill_video_info(&self, video: &mut VideoInfo) {
+        if video.width == 0 || video.height == 0 {
+            video.width = self.width;
+            video.height = self.height;
+        }
+        video.bit_depth = video.bit_depth.or(Some(self.bit_depth_luma));
+    }
+}
+
+/// Reads a `bit_depth_*_minus8` field, which may be at most 8.
+fn bit_depth(r: &mut BitReader<'_>) -> Result<u8> {
+    match r.ue()? {
+        minus8 @ 0..=8 => Ok(minus8 as u8 + 8),
+        _ => Err(PlayerError::decoder("Invalid HEVC SPS bit depth")),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::decoder::h264::tests::BitWriter;
+
+    /// Writes a `profile_tier_level()` for Main profile, Main tier, level 4
+    /// with the progressive, non-packed and frame-only constraints.
+    fn main_profile_tier_level(w: &mut BitWriter) {
+        w.bits(2, 0).bits(1, 0).bits(5, 1);
+        w.bits(32, 0x6000_0000);
+        w.bits(16, 0xB000).bits(32, 0);
+        w.bits(8, 120);
+    }
+
+    /// Prepends a two-byte NAL header of the given type.
+    fn nal(nal_type: u8, rbsp: Vec<u8>) -> Vec<u8> {
+        let mut nal = vec![nal_type << 1, 1];
+        nal.extend(rbsp);
+        nal
+    }
+
+    pub(crate) fn main_profile_vps() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(4, 0)
+            .bits(2, 3)
+            .bits(6, 0)
+            .bits(3, 0)
+            .bits(1, 1)
+            .bits(16, 0xFFFF);
+        main_profile_tier_level(&mut w);
+        nal(NAL_VPS, w.finish())
+    }
+
+    /// Builds a Main profile SPS coding 1920x1088 and cropping to 1080 lines.
+    pub(crate) fn main_profile_sps() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(4, 0).bits(3, 0).bits(1, 1);
+        main_profile_tier_level(&mut w);
+        w.ue(0).ue(1); // sps_seq_parameter_set_id, 4:2:0
+        w.ue(1920).ue(1088);
+        w.bits(1, 1).ue(0).ue(0).ue(0).ue(4);
+        w.ue(0).ue(0); // 8-bit
+        nal(NAL_SPS, w.finish())
+    }
+
+    /// Wraps parameter sets in an `hvcC` record.
+    pub(crate) fn hvcc_record(vps: &[u8], sps: &[u8], pps: &[u8]) -> Vec<u8> {
+        let mut record = vec![1, 0x01, 0x60, 0, 0, 0, 0xB0, 0, 0, 0, 0, 0, 120];
+        record.extend([0xF0, 0x00, 0xFC, 0xFD, 0xF8, 0xF8, 0, 0, 0x0F, 3]);
+        for (nal_type, unit) in [(NAL_VPS, vps), (NAL_SPS, sps), (NAL_PPS, pps)] {
+            record.push(0x80 | nal_type);
+            record.extend(1u16.to_be_bytes());
+            record.extend((unit.len() as u16).to_be_bytes());
+            record.extend(unit);
+        }
+        record
+    }
+
+    #[test]
+    fn test_codec_string() {
+        let main = ProfileTierLevel {
+            profile_idc: 1,
+            profile_compatibility_flags: 0x6000_0000,
+            constraint_indicator_flags: 0xB000_0000_0000,
+            level_idc: 93,
+            ..ProfileTierLevel::default()
+        };
+        assert_eq!(main.codec_string("hvc1"), "hvc1.1.6.L93.B0");
+
+        let main10_high = ProfileTierLevel {
+            high_tier: true,
+            profile_idc: 2,
+            profile_compatibility_flags: 0x2000_0000,
+            constraint_indicator_flags: 0x9000_0000_0000,
+            level_idc: 153,
+            ..ProfileTierLevel::default()
+        };
+        assert_eq!(main10_high.codec_string("hev1"), "hev1.2.4.H153.90");
+
+        let range_extensions = ProfileTierLevel {
+            profile_space: 1,
+            profile_idc: 4,
+            profile_compatibility_flags: 0x0800_0000,
+            constraint_indicator_flags: 0x0000_8800_0000,
+            level_idc: 90,
+            ..ProfileTierLevel::default()
+        };
+        assert_eq!(
+            range_extensions.codec_string("hvc1"),
+            "hvc1.A4.10.L90.0.0.88"
+        );
+    }
+
+    #[test]
+    fn test_parse_parameter_sets() {
+        let vps = Vps::parse(&main_profile_vps()).unwrap();
+        assert_eq!(vps.max_sub_layers, 1);
+        assert_eq!(
+            vps.profile_tier_level.codec_string("hvc1"),
+            "hvc1.1.6.L120.B0"
+        );
+
+        let sps = Sps::parse(&main_profile_sps()).unwrap();
+        assert_eq!((sps.width, sps.height), (1920, 1080));
+        assert_eq!(sps.chroma_format_idc, 1);
+        assert_eq!((sps.bit_depth_luma, sps.bit_depth_chroma), (8, 8));
+        assert_eq!(sps.profile_tier_level, vps.profile_tier_level);
+
+        assert!(Sps::parse(&main_profile_vps()).is_err());
+        assert!(Sps::parse(&[NAL_SPS << 1, 1, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sps_out_of_range() {
+        let sps = |width: u32, crop_bottom: u32, bit_depth: u32| {
+            let mut w = BitWriter::default();
+            w.bits(4, 0).bits(3, 0).bits(1, 1);
+            main_profile_tier_level(&mut w);
+            w.ue(0).ue(1).ue(width).ue(1088);
+            w.bits(1, 1).ue(0).ue(0).ue(0).ue(crop_bottom);
+            w.ue(bit_depth).ue(0);
+            Sps::parse(&nal(NAL_SPS, w.finish()))
+        };
+        assert_eq!(sps(16384, 4, 2).unwrap().bit_depth_luma, 10);
+        assert!(sps(65536, 4, 0).is_err());
+        assert!(sps(1920, 544, 0).is_err());
+        assert!(sps(1920, u32::MAX - 1, 0).is_err());
+        assert!(sps(1920, 4, 9).is_err());
+    }
+
+    #[test]
+    fn test_parse_hvcc() {
+        let (vps, sps) = (main_profile_vps(), main_profile_sps());
+        let pps = nal(NAL_PPS, vec![0xC1, 0x72, 0xB4, 0x62, 0x40]);
+        let config = HevcDecoderConfig::parse(&hvcc_record(&vps, &sps, &pps)).unwrap();
+        assert_eq!(config.nal_length_size, 4);
+        assert_eq!(config.chroma_format_idc, 1);
+        assert_eq!(config.bit_depth_luma, 8);
+        assert_eq!(config.codec_string("hvc1"), "hvc1.1.6.L120.B0");
+        assert_eq!(config.video_parameter_sets, vec![vps]);
+        assert_eq!(config.picture_parameter_sets, vec![pps]);
+        assert_eq!(config.sps.height, 1080);
+        assert_eq!(
+            &config.parameter_sets_annex_b()[..6],
+            &[0, 0, 0, 1, 0x40, 1]
+        );
+
+        let mut video = VideoInfo::default();
+        config.sps.fill_video_info(&mut video);
+        assert_eq!((video.width, video.height), (1920, 1080));
+        assert_eq!(video.bit_depth, Some(8));
+
+        let mut record = hvcc_record(&main_profile_vps(), &main_profile_sps(), &[0x44, 1]);
+        record.truncate(30);
+        assert!(HevcDecoderConfig::parse(&record).is_err());
+        assert!(HevcDecoderConfig::parse(&[0; 23]).is_err());
+    }
+}
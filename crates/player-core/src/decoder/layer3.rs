@@ -0,0 +1,870 @@
+//! Layer III granule decoding.
+//!
+//! Reads the side information and main data of a frame and runs the
+//! decoding pipeline of ISO/IEC 11172-3 section 2.4.3.4 and ISO/IEC
+//! 13818-3: scale factors, Huffman decoding, requantization, stereo
+//! processing, reordering, alias reduction, the IMDCT and the polyphase
+//! synthesis filterbank.
+
+use super::bits::BitReader;
+use super::mp3::{FrameHeader, MpegVersion};
+use super::mp3tables::{
+    BIG_VALUE_TABLES, COUNT1_TABLES, LONG_BANDS, MIXED_BANDS, PRETAB, SHORT_BANDS, SYNTHESIS_WINDOW,
+};
+use crate::error::{PlayerError, Result};
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// Number of frequency lines in a granule.
+const GRANULE_LINES: usize = 576;
+
+/// Block type of short windows.
+const SHORT_BLOCK: u8 = 2;
+
+/// Bit lengths of the two MPEG-1 scale factor groups, indexed by
+/// `scalefac_compress`.
+const SLEN: [(u8, u8); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (3, 0),
+    (1, 1),
+    (1, 2),
+    (1, 3),
+    (2, 1),
+    (2, 2),
+    (2, 3),
+    (3, 1),
+    (3, 2),
+    (3, 3),
+    (4, 2),
+    (4, 3),
+];
+
+/// Number of scale factors in each of the four MPEG-2 partitions, indexed
+/// by the `scalefac_compress` range (the last three for the intensity
+/// stereo channel) and by long, short or mixed blocks.
+const LSF_PARTITIONS: [[[u8; 4]; 3]; 6] = [
+    [[6, 5, 5, 5], [9, 9, 9, 9], [6, 9, 9, 9]],
+    [[6, 5, 7, 3], [9, 9, 12, 6], [6, 9, 12, 6]],
+    [[11, 10, 0, 0], [18, 18, 0, 0], [15, 18, 0, 0]],
+    [[7, 7, 7, 0], [12, 12, 12, 0], [6, 15, 12, 0]],
+    [[6, 6, 6, 3], [12, 9, 9, 6], [6, 12, 9, 6]],
+    [[8, 8, 5, 0], [15, 12, 9, 0], [6, 18, 9, 0]],
+];
+
+/// Alias reduction coefficients `c[i]`.
+const ALIAS_COEFFICIENTS: [f64; 8] = [
+    -0.6, -0.535, -0.33, -0.185, -0.095, -0.041, -0.0142, -0.0037,
+];
+
+/// Side information of one channel in one granule.
+#[derive(Debug, Clone, Copy, Default)]
+struct GranuleChannel {
+    part2_3_length: usize,
+    big_values: usize,
+    global_gain: i32,
+    scalefac_compress: u32,
+    block_type: u8,
+    mixed_block: bool,
+    table_select: [u8; 3],
+    subblock_gain: [u8; 3],
+    /// Number of bands in regions 0 and 1, minus one.
+    region_count: [usize; 2],
+    preflag: bool,
+    scalefac_scale: bool,
+    count1_table: u8,
+}
+
+/// Side information of a frame.
+#[derive(Debug, Clone, Default)]
+pub(super) struct SideInfo {
+    /// Offset of the main data start back into the bit reservoir, in bytes.
+    pub main_data_begin: usize,
+    /// Scale factor selection information per channel (MPEG-1 only).
+    scfsi: [[bool; 4]; 2],
+    granules: [[GranuleChannel; 2]; 2],
+}
+
+impl SideInfo {
+    /// Parses the side information following the frame header (and CRC).
+    pub(super) fn parse(header: &FrameHeader, data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        let channels = header.channels() as usize;
+        let mut side = SideInfo::default();
+        if header.version == MpegVersion::Mpeg1 {
+            side.main_data_begin = r.bits(9)? as usize;
+            r.skip(if channels == 1 { 5 } else { 3 })?;
+            for scfsi in &mut side.scfsi[..channels] {
+                for group in scfsi.iter_mut() {
+                    *group = r.flag()?;
+                }
+            }
+        } else {
+            side.main_data_begin = r.bits(8)? as usize;
+            r.skip(channels)?;
+        }
+
+        for granule in &mut side.granules[..header.granules()] {
+            for (ch, gc) in granule[..channels].iter_mut().enumerate() {
+                gc.part2_3_length = r.bits(12)? as usize;
+                gc.big_values = r.bits(9)? as usize;
+                if gc.big_values > GRANULE_LINES / 2 {
+                    return Err(PlayerError::decoder("Invalid MP3 big_values"));
+                }
+                gc.global_gain = r.bits(8)? as i32;
+                gc.scalefac_compress = r.bits(if header.version == MpegVersion::Mpeg1 {
+                    4
+                } else {
+                    9
+                })?;
+                if r.flag()? {
+                    gc.block_type = r.bits(2)? as u8;
+                    if gc.block_type == 0 {
+                        return Err(PlayerError::decoder("Invalid MP3 block type"));
+                    }
+                    gc.mixed_block = r.flag()?;
+                    for table in &mut gc.table_select[..2] {
+                        *table = r.bits(5)? as u8;
+                    }
+                    for gain in &mut gc.subblock_gain {
+                        *gain = r.bits(3)? as u8;
+                    }
+                    // Region 0 spans 36 lines of long bands or three short
+                    // bands; region 1 takes the rest.
+                    let short = gc.block_type == SHORT_BLOCK && !gc.mixed_block;
+                    gc.region_count = [if short { 8 } else { 7 }, GRANULE_LINES];
+                } else {
+                    for table in &mut gc.table_select {
+                        *table = r.bits(5)? as u8;
+                    }
+                    gc.region_count = [r.bits(4)? as usize, r.bits(3)? as usize];
+                }
+                gc.preflag = if header.version == MpegVersion::Mpeg1 {
+                    r.flag()?
+                } else {
+                    // MPEG-2 implies the pre-emphasis through the upper
+                    // scalefac_compress range, outside intensity stereo.
+                    gc.scalefac_compress >= 500 && !(ch == 1 && header.is_intensity_stereo())
+                };
+                gc.scalefac_scale = r.flag()?;
+                gc.count1_table = r.bits(1)? as u8;
+            }
+        }
+        Ok(side)
+    }
+
+    /// Returns the total length of the main data in bits.
+    pub(super) fn main_data_bits(&self, header: &FrameHeader) -> usize {
+        self.granules[..header.granules()]
+            .iter()
+            .flat_map(|granule| &granule[..header.channels() as usize])
+            .map(|gc| gc.part2_3_length)
+            .sum()
+    }
+}
+
+/// Scale factor band layout of a granule channel.
+struct BandLayout {
+    /// Band widths in bitstream order; short bands are repeated for the
+    /// three windows.
+    widths: &'static [u8],
+    /// Number of leading long bands.
+    long_bands: usize,
+}
+
+impl BandLayout {
+    fn new(header: &FrameHeader, gc: &GranuleChannel) -> Self {
+        let index = header.band_table_index();
+        if gc.block_type != SHORT_BLOCK {
+            return Self {
+                widths: &LONG_BANDS[index],
+                long_bands: LONG_BANDS[index].len(),
+            };
+        }
+        if !gc.mixed_block {
+            return Self {
+                widths: &SHORT_BANDS[index],
+                long_bands: 0,
+            };
+        }
+        let widths = MIXED_BANDS[index];
+        let mut lines = 0;
+        let long_bands = widths
+            .iter()
+            .take_while(|&&width| {
+                lines += width as usize;
+                lines <= 36
+            })
+            .count();
+        Self { widths, long_bands }
+    }
+
+    /// Returns whether the layout holds short bands.
+    fn is_short(&self) -> bool {
+        self.long_bands < self.widths.len()
+    }
+
+    /// Returns the line range `(start, end)` of each band.
+    fn boundaries(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.widths.iter().scan(0, |start, &width| {
+            let band = (*start, *start + width as usize);
+            *start = band.1;
+            Some(band)
+        })
+    }
+}
+
+/// Persistent filterbank state of one channel.
+#[derive(Debug, Clone)]
+pub(super) struct ChannelState {
+    /// Second halves of the previous IMDCT outputs, per subband.
+    overlap: [[f32; 18]; 32],
+    /// Polyphase synthesis buffer `V`.
+    synthesis: [f32; 1024],
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            overlap: [[0.0; 18]; 32],
+            synthesis: [0.0; 1024],
+        }
+    }
+}
+
+/// Precomputed transform tables.
+struct Tables {
+    /// `|x|^(4/3)` for every value a Huffman code with linbits can carry.
+    pow43: Vec<f32>,
+    /// Binary decoding trees of the big value tables.
+    big_values: Vec<HuffmanTree>,
+    /// Binary decoding trees of the count1 tables.
+    count1: [HuffmanTree; 2],
+    /// IMDCT windows for block types 0 to 3.
+    windows: [[f32; 36]; 4],
+    /// Cosine terms of the 36-point IMDCT.
+    imdct36: Vec<[f32; 18]>,
+    /// Cosine terms of the 12-point IMDCT.
+    imdct12: [[f32; 6]; 12],
+    /// Alias reduction butterflies `(cs, ca)`.
+    alias: [(f32, f32); 8],
+    /// Synthesis matrixing coefficients `N[i][k]`.
+    matrixing: Vec<[f32; 32]>,
+    /// Synthesis window `D[i]`.
+    window: [f32; 512],
+}
+
+/// Returns the lazily built transform tables.
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let sine = |n: usize, i: usize| ((PI / n as f64) * (i as f64 + 0.5)).sin() as f32;
+        let mut windows = [[0.0; 36]; 4];
+        for (i, w) in windows[0].iter_mut().enumerate() {
+            *w = sine(36, i);
+        }
+        for i in 0..18 {
+            windows[1][i] = sine(36, i);
+            windows[3][i + 18] = sine(36, i + 18);
+        }
+        for i in 0..6 {
+            windows[1][i + 18] = 1.0;
+            windows[1][i + 24] = sine(12, i + 6);
+            windows[3][i + 6] = sine(12, i);
+            windows[3][i + 12] = 1.0;
+        }
+        for (i, w) in windows[2][..12].iter_mut().enumerate() {
+            *w = sine(12, i);
+        }
+
+        let imdct = |n: usize, i: usize, k: usize| {
+            ((PI / (2 * n) as f64) * (2 * i + 1 + n / 2) as f64 * (2 * k + 1) as f64).cos() as f32
+        };
+        let imdct36 = (0..36)
+            .map(|i| std::array::from_fn(|k| imdct(36, i, k)))
+            .collect();
+        let imdct12 = std::array::from_fn(|i| std::array::from_fn(|k| imdct(12, i, k)));
+
+        let alias = ALIAS_COEFFICIENTS.map(|c| {
+            let root = (1.0 + c * c).sqrt();
+            ((1.0 / root) as f32, (c / root) as f32)
+        });
+
+        let matrixing = (0..64)
+            .map(|i| {
+                std::array::from_fn(|k| {
+                    ((16 + i) as f64 * (2 * k + 1) as f64 * PI / 64.0).cos() as f32
+                })
+            })
+            .collect();
+        let mut window = [0.0; 512];
+        for (i, d) in window.iter_mut().enumerate() {
+            let value = match i {
+                0..=256 => SYNTHESIS_WINDOW[i],
+                _ if i % 64 == 0 => SYNTHESIS_WINDOW[512 - i],
+                _ => -SYNTHESIS_WINDOW[512 - i],
+            };
+            *d = value as f32 / 65536.0;
+        }
+
+        Tables {
+            pow43: (0..8207)
+                .map(|x| (x as f64).powf(4.0 / 3.0) as f32)
+                .collect(),
+            big_values: BIG_VALUE_TABLES
+                .iter()
+                .map(|table| HuffmanTree::new(table.codes))
+                .collect(),
+            count1: COUNT1_TABLES.map(HuffmanTree::new),
+            windows,
+            imdct36,
+            imdct12,
+            alias,
+            matrixing,
+            window,
+        }
+    })
+}
+
+/// Binary decoding tree of a Huffman code table.
+struct HuffmanTree {
+    /// Child pairs; entries with the top bit set are leaves holding the
+    /// symbol index, others are node indices.
+    nodes: Vec<[u16; 2]>,
+}
+
+impl HuffmanTree {
+    const LEAF: u16 = 0x8000;
+
+    fn new(codes: &[(u8, u32)]) -> Self {
+        let mut nodes = vec![[0u16; 2]];
+        for (symbol, &(len, code)) in codes.iter().enumerate() {
+            let mut node = 0;
+            for bit in (0..len).rev() {
+                let branch = ((code >> bit) & 1) as usize;
+                if bit == 0 {
+                    nodes[node][branch] = Self::LEAF | symbol as u16;
+                } else {
+                    if nodes[node][branch] == 0 {
+                        nodes.push([0; 2]);
+                        nodes[node][branch] = (nodes.len() - 1) as u16;
+                    }
+                    node = nodes[node][branch] as usize;
+                }
+            }
+        }
+        Self { nodes }
+    }
+
+    /// Reads one code word and returns its symbol index.
+    fn decode(&self, r: &mut BitReader) -> Result<usize> {
+        let mut node = 0;
+        loop {
+            let next = self.nodes[node][r.bits(1)? as usize];
+            if next & Self::LEAF != 0 {
+                return Ok((next & !Self::LEAF) as usize);
+            }
+            if next == 0 {
+                return Err(PlayerError::decoder("Invalid MP3 Huffman code"));
+            }
+            node = next as usize;
+        }
+    }
+}
+
+/// Decoded and requantized lines of one channel in a granule.
+struct GranuleLines {
+    lines: [f32; GRANULE_LINES],
+    /// Scale factors in band order.
+    scalefac: [u8; 39],
+    /// Intensity stereo positions in band order; 255 marks an illegal
+    /// position.
+    is_pos: [u8; 39],
+}
+
+/// Decodes all granules of a frame, appending interleaved samples to `out`.
+///
+/// `main_data` starts at the first bit of the frame's main data.
+pub(super) fn decode_frame(
+    header: &FrameHeader,
+    side: &SideInfo,
+    main_data: &[u8],
+    states: &mut [ChannelState; 2],
+    out: &mut Vec<f32>,
+) -> Result<()> {
+    let channels = header.channels() as usize;
+    let mut start = 0;
+    // Scale factors of granule 0, shared with granule 1 through scfsi.
+    let mut previous = [[0u8; 39]; 2];
+
+    for (gr, granule) in side.granules[..header.granules()].iter().enumerate() {
+        let mut decoded: [GranuleLines; 2] = std::array::from_fn(|_| GranuleLines {
+            lines: [0.0; GRANULE_LINES],
+            scalefac: [0; 39],
+            is_pos: [0; 39],
+        });
+        for ch in 0..channels {
+            let gc = &granule[ch];
+            let mut r = BitReader::new(main_data);
+            r.skip(start)?;
+            start += gc.part2_3_length;
+            let layout = BandLayout::new(header, gc);
+            let channel = &mut decoded[ch];
+            // scfsi is void when either granule uses short blocks.
+            let short = side.granules[..2]
+                .iter()
+                .any(|granule| granule[ch].block_type == SHORT_BLOCK);
+            let scfsi = if gr == 1 && !short {
+                side.scfsi[ch]
+            } else {
+                [false; 4]
+            };
+            read_scale_factors(
+                header,
+                gc,
+                ch,
+                &layout,
+                scfsi,
+                &previous[ch],
+                &mut r,
+                channel,
+            )?;
+            previous[ch] = channel.scalefac;
+            read_huffman(gc, &layout, start, &mut r, &mut channel.lines)?;
+            requantize(header, gc, &layout, channel);
+        }
+
+        if channels == 2 && header.is_joint_stereo() {
+            let layout = BandLayout::new(header, &granule[0]);
+            let [left, right] = &mut decoded;
+            process_stereo(header, &granule[1], &layout, left, right);
+        }
+
+        for ch in 0..channels {
+            let gc = &granule[ch];
+            let layout = BandLayout::new(header, gc);
+            let lines = &mut decoded[ch].lines;
+            let long_subbands = if layout.is_short() {
+                reorder(&layout, lines);
+                if gc.mixed_block {
+                    2
+                } else {
+                    0
+                }
+            } else {
+                32
+            };
+            reduce_aliases(lines, long_subbands);
+            hybrid_synthesis(gc.block_type, long_subbands, lines, &mut states[ch].overlap);
+            polyphase_synthesis(lines, &mut states[ch].synthesis);
+        }
+
+        let start = out.len();
+        out.resize(start + GRANULE_LINES * channels, 0.0);
+        for (ch, channel) in decoded[..channels].iter().enumerate() {
+            for (i, sample) in channel.lines.iter().enumerate() {
+                out[start + i * channels + ch] = sample.clamp(-1.0, 1.0);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the scale factors of a granule channel (part 2 of the main data).
+#[allow(clippy::too_many_arguments)]
+fn read_scale_factors(
+    header: &FrameHeader,
+    gc: &GranuleChannel,
+    ch: usize,
+    layout: &BandLayout,
+    scfsi: [bool; 4],
+    previous: &[u8; 39],
+    r: &mut BitReader,
+    channel: &mut GranuleLines,
+) -> Result<()> {
+    let kind = match (layout.is_short(), gc.mixed_block) {
+        (false, _) => 0,
+        (true, false) => 1,
+        (true, true) => 2,
+    };
+    let intensity_channel = ch == 1 && header.is_intensity_stereo();
+    let (sizes, counts) = if header.version == MpegVersion::Mpeg1 {
+        let (slen1, slen2) = SLEN[gc.scalefac_compress as usize];
+        let counts = match kind {
+            0 => [6, 5, 5, 5],
+            1 => [9, 9, 6, 12],
+            _ => [8, 9, 6, 12],
+        };
+        ([slen1, slen1, slen2, slen2], counts)
+    } else {
+        lsf_scale_factor_sizes(gc.scalefac_compress, intensity_channel, kind)
+    };
+
+    let mut band = 0;
+    for (group, (&size, &count)) in sizes.iter().zip(&counts).enumerate() {
+        // In MPEG-2 intensity stereo the largest value marks an illegal
+        // position.
+        let illegal =
+            (header.version != MpegVersion::Mpeg1 && size > 0).then(|| (1u32 << size) - 1);
+        for (i, &copied) in previous.iter().enumerate().skip(band).take(count as usize) {
+            let value = if scfsi[group] {
+                copied as u32
+            } else {
+                r.bits(size as u32)?
+            };
+            channel.scalefac[i] = value as u8;
+            channel.is_pos[i] = if Some(value) == illegal {
+                255
+            } else {
+                value as u8
+            };
+        }
+        band += count as usize;
+    }
+    Ok(())
+}
+
+/// Returns the bit lengths and counts of the four MPEG-2 scale factor
+/// partitions (ISO/IEC 13818-3 section 2.4.3.2).
+fn lsf_scale_factor_sizes(
+    scalefac_compress: u32,
+    intensity_channel: bool,
+    kind: usize,
+) -> ([u8; 4], [u8; 4]) {
+    let (case, sizes) = if intensity_channel {
+        let sfc = scalefac_compress >> 1;
+        match sfc {
+            0..=179 => (3, [sfc / 36, sfc % 36 / 6, sfc % 6, 0]),
+            180..=243 => (
+                4,
+                [
+                    ((sfc - 180) % 64) >> 4,
+                    ((sfc - 180) % 16) >> 2,
+                    (sfc - 180) % 4,
+                    0,
+                ],
+            ),
+            _ => (5, [(sfc - 244) / 3, (sfc - 244) % 3, 0, 0]),
+        }
+    } else {
+        let sfc = scalefac_compress;
+        match sfc {
+            0..=399 => (
+                0,
+                [(sfc >> 4) / 5, (sfc >> 4) % 5, (sfc % 16) >> 2, sfc % 4],
+            ),
+            400..=499 => (
+                1,
+                [
+                    ((sfc - 400) >> 2) / 5,
+                    ((sfc - 400) >> 2) % 5,
+                    (sfc - 400) % 4,
+                    0,
+                ],
+            ),
+            _ => (2, [(sfc - 500) / 3, (sfc - 500) % 3, 0, 0]),
+        }
+    };
+    (sizes.map(|size| size as u8), LSF_PARTITIONS[case][kind])
+}
+
+/// Decodes the Huffman coded lines of a granule channel (part 3 of the
+/// main data), ending at bit `end`. Values are stored unscaled.
+fn read_huffman(
+    gc: &GranuleChannel,
+    layout: &BandLayout,
+    end: usize,
+    r: &mut BitReader,
+    lines: &mut [f32; GRANULE_LINES],
+) -> Result<()> {
+    let tables = tables();
+    let big_values = gc.big_values * 2;
+    let region_end = |count: usize| -> usize {
+        layout
+            .widths
+            .iter()
+            .take(count + 1)
+            .map(|&width| width as usize)
+            .sum::<usize>()
+            .min(big_values)
+    };
+    let region1 = region_end(gc.region_count[0]);
+    let region2 = region_end(gc.region_count[0] + gc.region_count[1] + 1);
+
+    let mut i = 0;
+    while i < big_values {
+        let region = match i {
+            _ if i < region1 => 0,
+            _ if i < region2 => 1,
+            _ => 2,
+        };
+        let table_select = gc.table_select[region] as usize;
+        let table = &BIG_VALUE_TABLES[table_select];
+        if table.size == 0 {
+            // Tables 0, 4 and 14 code all-zero regions.
+            i += 2;
+            continue;
+        }
+        let symbol = tables.big_values[table_select].decode(r)?;
+        for value in [symbol / table.size, symbol % table.size] {
+            let mut magnitude = value;
+            if magnitude == 15 && table.linbits > 0 {
+                magnitude += r.bits(table.linbits)? as usize;
+            }
+            lines[i] = if magnitude == 0 {
+                0.0
+            } else if r.flag()? {
+                -tables.pow43[magnitude]
+            } else {
+                tables.pow43[magnitude]
+            };
+            i += 1;
+        }
+    }
+
+    let count1 = &tables.count1[gc.count1_table as usize];
+    while i + 4 <= GRANULE_LINES && r.position() < end {
+        let symbol = count1.decode(r)?;
+        // A code word running past the end of part 3 is discarded.
+        if r.position() > end {
+            break;
+        }
+        for (j, line) in lines[i..i + 4].iter_mut().enumerate() {
+            if symbol & (8 >> j) != 0 {
+                *line = if r.flag()? { -1.0 } else { 1.0 };
+            }
+        }
+        i += 4;
+    }
+    Ok(())
+}
+
+/// Scales the decoded lines of a granule channel by the global gain,
+/// subblock gains and scale factors.
+fn requantize(
+    header: &FrameHeader,
+    gc: &GranuleChannel,
+    layout: &BandLayout,
+    channel: &mut GranuleLines,
+) {
+    // Exponents are in steps of 2^(1/4). Mid/side stereo folds the 1/sqrt(2)
+    // of its matrix into the gain.
+    let mut gain = gc.global_gain - 210;
+    if header.is_ms_stereo() {
+        gain -= 2;
+    }
+    let shift = 1 + gc.scalefac_scale as u32;
+    for (band, (start, end)) in layout.boundaries().enumerate() {
+        let mut scalefac = channel.scalefac[band] as i32;
+        let mut exponent = gain;
+        if band < layout.long_bands {
+            if gc.preflag && !layout.is_short() {
+                scalefac += PRETAB[band] as i32;
+            }
+        } else {
+            let window = (band - layout.long_bands) % 3;
+            exponent -= 8 * gc.subblock_gain[window] as i32;
+        }
+        exponent -= scalefac << shift;
+        let scale = 2f64.powf(exponent as f64 / 4.0) as f32;
+        for line in &mut channel.lines[start..end] {
+            *line *= scale;
+        }
+    }
+}
+
+/// Applies mid/side and intensity stereo to a granule.
+fn process_stereo(
+    header: &FrameHeader,
+    right_gc: &GranuleChannel,
+    layout: &BandLayout,
+    left: &mut GranuleLines,
+    right: &mut GranuleLines,
+) {
+    let ms = header.is_ms_stereo();
+    if !header.is_intensity_stereo() {
+        if ms {
+            mid_side(&mut left.lines, &mut right.lines);
+        }
+        return;
+    }
+
+    // Intensity stereo covers the bands above the last non-zero band of
+    // the right channel, per window for short blocks.
+    let windows = if layout.is_short() { 3 } else { 1 };
+    let bands: Vec<(usize, usize)> = layout.boundaries().collect();
+    let mut top = [None; 3];
+    for (band, &(start, end)) in bands.iter().enumerate() {
+        if right.lines[start..end].iter().any(|&line| line != 0.0) {
+            top[band % 3] = Some(band);
+        }
+    }
+    if layout.long_bands > 0 {
+        let max = top.iter().max().copied().flatten();
+        top = [max; 3];
+    }
+
+    // The last band has no scale factor and takes the previous position.
+    let mpeg1 = header.version == MpegVersion::Mpeg1;
+    for (window, window_top) in top.iter().enumerate().take(windows) {
+        let last = bands.len() - windows + window;
+        let previous = last - windows;
+        right.is_pos[last] = if window_top.is_some_and(|band| band >= previous) {
+            if mpeg1 {
+                3
+            } else {
+                0
+            }
+        } else {
+            right.is_pos[previous]
+        };
+    }
+
+    let max_pos = if mpeg1 { 7 } else { 64 };
+    let ms_gain = if ms { std::f32::consts::SQRT_2 } else { 1.0 };
+    for (band, &(start, end)) in bands.iter().enumerate() {
+        let pos = right.is_pos[band] as u32;
+        let above = top[band % 3].is_none_or(|top| band > top);
+        if above && pos < max_pos {
+            let (kl, kr) = if mpeg1 {
+                let ratio = (pos as f64 * PI / 12.0).tan();
+                ((ratio / (1.0 + ratio)) as f32, (1.0 / (1.0 + ratio)) as f32)
+            } else {
+                let steps = (pos + 1) >> 1 << (right_gc.scalefac_compress & 1);
+                let k = 2f64.powf(-(steps as f64) / 4.0) as f32;
+                if pos & 1 == 1 {
+                    (k, 1.0)
+                } else {
+                    (1.0, k)
+                }
+            };
+            for i in start..end {
+                let value = left.lines[i] * ms_gain;
+                left.lines[i] = value * kl;
+                right.lines[i] = value * kr;
+            }
+        } else if ms {
+            mid_side(&mut left.lines[start..end], &mut right.lines[start..end]);
+        }
+    }
+}
+
+/// Converts mid/side lines to left/right.
+fn mid_side(mid: &mut [f32], side: &mut [f32]) {
+    for (m, s) in mid.iter_mut().zip(side) {
+        (*m, *s) = (*m + *s, *m - *s);
+    }
+}
+
+/// Reorders the short bands of a granule from band-window-line order to
+/// line-window order within each band, as the IMDCT expects.
+fn reorder(layout: &BandLayout, lines: &mut [f32; GRANULE_LINES]) {
+    let start: usize = layout.widths[..layout.long_bands]
+        .iter()
+        .map(|&width| width as usize)
+        .sum();
+    let mut reordered = [0.0; GRANULE_LINES];
+    let mut src = start;
+    let mut dst = start;
+    for &width in layout.widths[layout.long_bands..].iter().step_by(3) {
+        let width = width as usize;
+        for i in 0..width {
+            for window in 0..3 {
+                reordered[dst] = lines[src + window * width + i];
+                dst += 1;
+            }
+        }
+        src += 3 * width;
+    }
+    lines[start..dst].copy_from_slice(&reordered[start..dst]);
+}
+
+/// Applies the alias reduction butterflies between the long subbands.
+fn reduce_aliases(lines: &mut [f32; GRANULE_LINES], long_subbands: usize) {
+    let alias = &tables().alias;
+    for sb in 1..long_subbands {
+        let boundary = sb * 18;
+        for (i, &(cs, ca)) in alias.iter().enumerate() {
+            let lower = lines[boundary - 1 - i];
+            let upper = lines[boundary + i];
+            lines[boundary - 1 - i] = lower * cs - upper * ca;
+            lines[boundary + i] = upper * cs + lower * ca;
+        }
+    }
+}
+
+/// Runs the IMDCT with windowing and overlap-add for each subband, then
+/// inverts the frequency of the odd subbands.
+fn hybrid_synthesis(
+    block_type: u8,
+    long_subbands: usize,
+    lines: &mut [f32; GRANULE_LINES],
+    overlap: &mut [[f32; 18]; 32],
+) {
+    let tables = tables();
+    for (sb, overlap) in overlap.iter_mut().enumerate() {
+        let input = &mut lines[sb * 18..sb * 18 + 18];
+        let mut output = [0.0f32; 36];
+        if sb < long_subbands {
+            // The long subbands of mixed blocks use the normal window.
+            let window = if block_type == SHORT_BLOCK {
+                &tables.windows[0]
+            } else {
+                &tables.windows[block_type as usize]
+            };
+            for (i, out) in output.iter_mut().enumerate() {
+                let sum: f32 = input
+                    .iter()
+                    .zip(&tables.imdct36[i])
+                    .map(|(x, c)| x * c)
+                    .sum();
+                *out = sum * window[i];
+            }
+        } else {
+            for window in 0..3 {
+                for i in 0..12 {
+                    let sum: f32 = (0..6)
+                        .map(|k| input[3 * k + window] * tables.imdct12[i][k])
+                        .sum();
+                    output[6 + 6 * window + i] += sum * tables.windows[2][i];
+                }
+            }
+        }
+        for i in 0..18 {
+            input[i] = output[i] + overlap[i];
+            overlap[i] = output[i + 18];
+        }
+        if sb % 2 == 1 {
+            for sample in input.iter_mut().skip(1).step_by(2) {
+                *sample = -*sample;
+            }
+        }
+    }
+}
+
+/// Runs the polyphase synthesis filterbank over the 18 time slots of a
+/// granule, replacing the subband samples with PCM samples in time order.
+fn polyphase_synthesis(lines: &mut [f32; GRANULE_LINES], v: &mut [f32; 1024]) {
+    let tables = tables();
+    let subbands = *lines;
+    for slot in 0..18 {
+        v.copy_within(0..960, 64);
+        for (i, coefficients) in tables.matrixing.iter().enumerate() {
+            v[i] = coefficients
+                .iter()
+                .enumerate()
+                .map(|(k, c)| subbands[k * 18 + slot] * c)
+                .sum();
+        }
+        for (j, sample) in lines[slot * 32..slot * 32 + 32].iter_mut().enumerate() {
+            let mut sum = 0.0;
+            for i in 0..8 {
+                sum += v[128 * i + j] * tables.window[64 * i + j];
+                sum += v[128 * i + 96 + j] * tables.window[64 * i + 32 + j];
+            }
+            *sample = sum;
+        }
+    }
+}
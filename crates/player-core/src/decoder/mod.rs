@@ -4,8 +4,39 @@
 //! streams into raw frames that can be rendered.
 
 use crate::error::{PlayerError, Result};
+use crate::time::{Timebase, Timestamp};
 use serde::{Deserialize, Serialize};
 
+pub mod aac;
+pub mod av1;
+mod bits;
+pub mod flac;
+pub mod h264;
+pub mod h265;
+mod layer3;
+pub mod mp3;
+mod mp3tables;
+pub mod nal;
+pub mod opus;
+pub mod pcm;
+pub mod vorbis;
+pub mod vp8;
+pub mod vp9;
+
+use aac::AudioSpecificConfig;
+use av1::Av1DecoderConfig;
+use flac::FlacStreamInfo;
+use h264::AvcDecoderConfig;
+use h265::HevcDecoderConfig;
+use mp3::{Mp3Decoder, XingHeader};
+use opus::OpusHead;
+use pcm::WaveFormat;
+use vorbis::VorbisHeaders;
+use vp9::VpcDecoderConfig;
+
+/// Largest picture width or height accepted from a bitstream header.
+pub const MAX_DIMENSION: u32 = 16384;
+
 /// Supported video codecs.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VideoCodec {
@@ -26,10 +57,34 @@ pub enum VideoCodec {
 pub enum AudioCodec {
     /// AAC.
     Aac,
+    /// FLAC.
+    Flac,
     /// MP3.
     Mp3,
     /// Opus.
     Opus,
+    /// Unsigned 8-bit PCM.
+    PcmU8,
+    /// Signed 16-bit little-endian PCM.
+    PcmS16le,
+    /// Signed 16-bit big-endian PCM.
+    PcmS16be,
+    /// Signed 24-bit little-endian PCM.
+    PcmS24le,
+    /// Signed 24-bit big-endian PCM.
+    PcmS24be,
+    /// Signed 32-bit little-endian PCM.
+    PcmS32le,
+    /// Signed 32-bit big-endian PCM.
+    PcmS32be,
+    /// 32-bit little-endian floating-point PCM.
+    PcmF32le,
+    /// 32-bit big-endian floating-point PCM.
+    PcmF32be,
+    /// G.711 µ-law.
+    PcmMulaw,
+    /// G.711 A-law.
+    PcmAlaw,
     /// Vorbis.
     Vorbis,
 }
@@ -41,8 +96,8 @@ pub struct VideoFrame {
     pub width: u32,
     /// Frame height in pixels.
     pub height: u32,
-    /// Presentation timestamp in milliseconds.
-    pub pts_ms: u64,
+    /// Presentation timestamp, in the time base of the source stream.
+    pub pts: Timestamp,
     /// Pixel format.
     pub format: PixelFormat,
     /// Raw pixel data.
@@ -56,8 +111,8 @@ pub struct AudioFrame {
     pub channels: u8,
     /// Sample rate in Hz.
     pub sample_rate: u32,
-    /// Presentation timestamp in milliseconds.
-    pub pts_ms: u64,
+    /// Presentation timestamp, in the time base of the source stream.
+    pub pts: Timestamp,
     /// Sample format.
     pub format: SampleFormat,
     /// Raw audio samples.
@@ -102,6 +157,19 @@ impl Default for DecoderConfig {
     }
 }
 
+/// Parsed codec configuration record of a video stream.
+#[derive(Debug, Clone)]
+enum CodecRecord {
+    /// H.264 `avcC`; packets use length-prefixed NAL units.
+    Avc(AvcDecoderConfig),
+    /// H.265 `hvcC`; packets use length-prefixed NAL units.
+    Hevc(HevcDecoderConfig),
+    /// AV1 `av1C`; packets are OBUs with size fields.
+    Av1(Av1DecoderConfig),
+    /// VP8 or VP9 `vpcC`; VP9 packets may be superframes.
+    Vpx(VpcDecoderConfig),
+}
+
 /// Video decoder for decoding compressed video frames.
 #[derive(Debug)]
 pub struct VideoDecoder {
@@ -111,6 +179,13 @@ pub struct VideoDecoder {
     config: DecoderConfig,
     /// Whether the decoder has been initialized.
     initialized: bool,
+    /// Configuration record parsed from the initialization data.
+    record: Option<CodecRecord>,
+    /// Frame size signalled by the last VP8 or VP9 key frame.
+    frame_size: Option<(u32, u32)>,
+    /// VP9 configuration derived from the last key frame, used when no
+    /// `vpcC` record was given.
+    vp9_frame_config: Option<VpcDecoderConfig>,
 }
 
 impl Default for VideoDecoder {
@@ -126,6 +201,9 @@ impl VideoDecoder {
             codec: None,
             config,
             initialized: false,
+            record: None,
+            frame_size: None,
+            vp9_frame_config: None,
         }
     }
 
@@ -133,13 +211,29 @@ impl VideoDecoder {
     ///
     /// # Arguments
     /// * `codec` - The video codec to decode.
-    /// * `extra_data` - Codec-specific initialization data (e.g., SPS/PPS for H.264).
+    /// * `extra_data` - Codec-specific initialization data (the `avcC`,
+    ///   `hvcC`, `av1C` or `vpcC` record). Without it, H.264 and H.265 packets are
+    ///   expected in Annex B framing with in-band parameter sets.
     ///
     /// # Errors
-    /// Returns an error if the codec is not supported or initialization fails.
+    /// Returns an error if the codec is not supported or the initialization
+    /// data is malformed.
     pub fn init(&mut self, codec: VideoCodec, extra_data: Option<&[u8]>) -> Result<()> {
-        // Stub implementation
-        let _ = extra_data;
+        self.record = match (codec, extra_data) {
+            (VideoCodec::H264, Some(data)) => {
+                Some(CodecRecord::Avc(AvcDecoderConfig::parse(data)?))
+            }
+            (VideoCodec::H265, Some(data)) => {
+                Some(CodecRecord::Hevc(HevcDecoderConfig::parse(data)?))
+            }
+            (VideoCodec::Av1, Some(data)) => Some(CodecRecord::Av1(Av1DecoderConfig::parse(data)?)),
+            (VideoCodec::Vp8 | VideoCodec::Vp9, Some(data)) => {
+                Some(CodecRecord::Vpx(VpcDecoderConfig::parse(data)?))
+            }
+            _ => None,
+        };
+        self.frame_size = None;
+        self.vp9_frame_config = None;
 
         self.codec = Some(codec);
         self.initialized = true;
@@ -151,14 +245,15 @@ impl VideoDecoder {
     ///
     /// # Arguments
     /// * `data` - Compressed video data.
-    /// * `pts_ms` - Presentation timestamp in milliseconds.
+    /// * `pts` - Presentation timestamp of the packet.
     ///
     /// # Returns
-    /// Decoded video frame, or `None` if more data is needed.
+    /// Decoded video frame, or `None` if more data is needed or the packet
+    /// only holds frames that are not shown.
     ///
     /// # Errors
     /// Returns an error if decoding fails.
-    pub fn decode(&mut self, data: &[u8], pts_ms: u64) -> Result<Option<VideoFrame>> {
+    pub fn decode(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<VideoFrame>> {
         if !self.initialized {
             return Err(PlayerError::decoder("Decoder not initialized"));
         }
@@ -167,16 +262,80 @@ impl VideoDecoder {
             return Err(PlayerError::decoder("Empty packet data"));
         }
 
+        // Reject packets whose framing does not match the record.
+        match &self.record {
+            Some(CodecRecord::Avc(avc)) => {
+                nal::split_avcc(data, avc.nal_length_size)?;
+            }
+            Some(CodecRecord::Hevc(hevc)) => {
+                nal::split_avcc(data, hevc.nal_length_size)?;
+            }
+            Some(CodecRecord::Av1(_)) => {
+                av1::split_obus(data)?;
+            }
+            Some(CodecRecord::Vpx(_)) | None => {}
+        }
+
+        let shown = match self.codec {
+            Some(VideoCodec::Vp8) => self.read_vp8_frame(data)?,
+            Some(VideoCodec::Vp9) => self.read_vp9_frames(data)?,
+            _ => true,
+        };
+        if !shown {
+            return Ok(None);
+        }
+
         // Stub implementation - returns a placeholder frame
+        let (width, height) = match &self.record {
+            Some(CodecRecord::Avc(avc)) => (avc.sps.width, avc.sps.height),
+            Some(CodecRecord::Hevc(hevc)) => (hevc.sps.width, hevc.sps.height),
+            Some(CodecRecord::Av1(Av1DecoderConfig {
+                sequence_header: Some(header),
+                ..
+            })) => (header.max_width, header.max_height),
+            _ => self.frame_size.unwrap_or((1920, 1080)),
+        };
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(PlayerError::decoder("Frame size too large"));
+        }
+        let size = width as u64 * height as u64 * 3 / 2; // YUV420p size
         Ok(Some(VideoFrame {
-            width: 1920,
-            height: 1080,
-            pts_ms,
+            width,
+            height,
+            pts,
             format: PixelFormat::Yuv420p,
-            data: vec![0; 1920 * 1080 * 3 / 2], // YUV420p size
+            data: vec![0; size as usize],
         }))
     }
 
+    /// Reads a VP8 frame header, tracking the frame size of key frames.
+    /// Returns whether the frame is shown.
+    fn read_vp8_frame(&mut self, data: &[u8]) -> Result<bool> {
+        let header = vp8::FrameHeader::parse(data)?;
+        if let Some(size) = header.frame_size {
+            self.frame_size = Some(size);
+        }
+        Ok(header.show_frame)
+    }
+
+    /// Reads the frame headers of a VP9 packet, splitting superframes and
+    /// tracking the frame size of key and intra-only frames. Returns
+    /// whether any frame in the packet is shown.
+    fn read_vp9_frames(&mut self, data: &[u8]) -> Result<bool> {
+        let mut shown = false;
+        for frame in vp9::split_superframe(data)? {
+            let header = vp9::FrameHeader::parse(frame)?;
+            if let Some(size) = header.render_size.or(header.frame_size) {
+                self.frame_size = Some(size);
+            }
+            if header.kind == vp9::FrameKind::Key {
+                self.vp9_frame_config = VpcDecoderConfig::from_frame_header(&header);
+            }
+            shown |= header.show_frame;
+        }
+        Ok(shown)
+    }
+
     /// Flushes any buffered frames from the decoder.
     ///
     /// # Returns
@@ -195,6 +354,60 @@ impl VideoDecoder {
         self.codec
     }
 
+    /// Returns the RFC 6381 codec string for WebCodecs, if known from the
+    /// initialization data. H.265 strings use the `hvc1` sample entry type.
+    /// VP8 is always `vp8`; VP9 without a record is described from the
+    /// last key frame.
+    pub fn codec_string(&self) -> Option<String> {
+        if self.codec == Some(VideoCodec::Vp8) {
+            return Some("vp8".to_string());
+        }
+        Some(match self.record.as_ref() {
+            Some(CodecRecord::Avc(avc)) => avc.codec_string(),
+            Some(CodecRecord::Hevc(hevc)) => hevc.codec_string("hvc1"),
+            Some(CodecRecord::Av1(av1)) => av1.codec_string(),
+            Some(CodecRecord::Vpx(vpx)) => vpx.codec_string("vp09"),
+            None => self.vp9_frame_config.as_ref()?.codec_string("vp09"),
+        })
+    }
+
+    /// Returns the parsed H.264 configuration record, if one was given.
+    pub fn avc_config(&self) -> Option<&AvcDecoderConfig> {
+        match &self.record {
+            Some(CodecRecord::Avc(avc)) => Some(avc),
+            _ => None,
+        }
+    }
+
+    /// Returns the parsed H.265 configuration record, if one was given.
+    pub fn hevc_config(&self) -> Option<&HevcDecoderConfig> {
+        match &self.record {
+            Some(CodecRecord::Hevc(hevc)) => Some(hevc),
+            _ => None,
+        }
+    }
+
+    /// Returns the parsed AV1 configuration record, if one was given.
+    pub fn av1_config(&self) -> Option<&Av1DecoderConfig> {
+        match &self.record {
+            Some(CodecRecord::Av1(av1)) => Some(av1),
+            _ => None,
+        }
+    }
+
+    /// Returns the parsed VP8 or VP9 configuration record, if one was given.
+    pub fn vpc_config(&self) -> Option<&VpcDecoderConfig> {
+        match &self.record {
+            Some(CodecRecord::Vpx(vpx)) => Some(vpx),
+            _ => None,
+        }
+    }
+
+    /// Returns the frame size signalled by the last VP8 or VP9 key frame.
+    pub fn frame_size(&self) -> Option<(u32, u32)> {
+        self.frame_size
+    }
+
     /// Returns whether the decoder is initialized.
     pub fn is_initialized(&self) -> bool {
         self.initialized
@@ -206,6 +419,26 @@ impl VideoDecoder {
     }
 }
 
+/// Parsed codec configuration of an audio stream.
+#[derive(Debug, Clone)]
+enum AudioRecord {
+    /// AAC AudioSpecificConfig, from the initialization data or the last
+    /// ADTS header.
+    Aac(AudioSpecificConfig),
+    /// FLAC STREAMINFO; gives the sample rate and size when frame headers
+    /// refer to it.
+    Flac(FlacStreamInfo),
+    /// MP3 Xing/Info tag frame; gives the stream length for trimming the
+    /// encoder padding.
+    Mp3(XingHeader),
+    /// Opus identification header; gives the channel count.
+    Opus(OpusHead),
+    /// PCM `WAVEFORMATEX`; gives the sample rate and channel count.
+    Pcm(WaveFormat),
+    /// Vorbis headers; give the block sizes packet durations depend on.
+    Vorbis(VorbisHeaders),
+}
+
 /// Audio decoder for decoding compressed audio frames.
 #[derive(Debug)]
 pub struct AudioDecoder {
@@ -215,6 +448,15 @@ pub struct AudioDecoder {
     config: DecoderConfig,
     /// Whether the decoder has been initialized.
     initialized: bool,
+    /// Configuration parsed from the initialization data or the stream.
+    record: Option<AudioRecord>,
+    /// MP3 decoding state.
+    mp3: Option<Box<Mp3Decoder>>,
+    /// Block size of the last Vorbis packet, whose second half overlaps
+    /// the next one.
+    vorbis_block: Option<u16>,
+    /// Presentation time at which the stream ends.
+    end: Option<Timestamp>,
 }
 
 impl Default for AudioDecoder {
@@ -230,12 +472,54 @@ impl AudioDecoder {
             codec: None,
             config,
             initialized: false,
+            record: None,
+            mp3: None,
+            vorbis_block: None,
+            end: None,
         }
     }
 
-    /// Initializes the decoder for a specific codec.
+    /// Initializes the decoder for a specific codec, clearing the stream
+    /// end.
+    ///
+    /// # Arguments
+    /// * `codec` - The audio codec to decode.
+    /// * `extra_data` - Codec-specific initialization data (the AAC
+    ///   AudioSpecificConfig, the FLAC metadata blocks starting with
+    ///   STREAMINFO, the Xing/Info frame of an MP3 stream, the `OpusHead`,
+    ///   the three Vorbis headers in Xiph lacing, or the `WAVEFORMATEX` of
+    ///   a PCM stream). Without it, AAC packets are expected in ADTS
+    ///   framing and Opus is taken as stereo.
+    ///
+    /// # Errors
+    /// Returns an error if the initialization data is malformed, or
+    /// missing for PCM or Vorbis.
     pub fn init(&mut self, codec: AudioCodec, extra_data: Option<&[u8]>) -> Result<()> {
-        let _ = extra_data;
+        self.record = match (codec, extra_data) {
+            (AudioCodec::Aac, Some(data)) => {
+                Some(AudioRecord::Aac(AudioSpecificConfig::parse(data)?))
+            }
+            (AudioCodec::Flac, Some(data)) => Some(AudioRecord::Flac(FlacStreamInfo::parse(data)?)),
+            (AudioCodec::Mp3, Some(data)) => Some(AudioRecord::Mp3(
+                XingHeader::parse(data)
+                    .ok_or_else(|| PlayerError::decoder("Invalid MP3 Xing/Info frame"))?,
+            )),
+            (AudioCodec::Opus, Some(data)) => Some(AudioRecord::Opus(OpusHead::parse(data)?)),
+            (AudioCodec::Vorbis, data) => {
+                let data =
+                    data.ok_or_else(|| PlayerError::decoder("Vorbis requires its headers"))?;
+                Some(AudioRecord::Vorbis(VorbisHeaders::parse(data)?))
+            }
+            (codec, data) if pcm::sample_size(codec).is_some() => {
+                let data =
+                    data.ok_or_else(|| PlayerError::decoder("PCM requires a WAVEFORMATEX"))?;
+                Some(AudioRecord::Pcm(WaveFormat::parse(data)?))
+            }
+            _ => None,
+        };
+        self.mp3 = (codec == AudioCodec::Mp3).then(Box::default);
+        self.vorbis_block = None;
+        self.end = None;
 
         self.codec = Some(codec);
         self.initialized = true;
@@ -244,7 +528,7 @@ impl AudioDecoder {
     }
 
     /// Decodes a compressed audio packet.
-    pub fn decode(&mut self, data: &[u8], pts_ms: u64) -> Result<Option<AudioFrame>> {
+    pub fn decode(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<AudioFrame>> {
         if !self.initialized {
             return Err(PlayerError::decoder("Decoder not initialized"));
         }
@@ -253,13 +537,257 @@ impl AudioDecoder {
             return Err(PlayerError::decoder("Empty packet data"));
         }
 
-        // Stub implementation
+        match self.codec {
+            Some(AudioCodec::Aac) => return self.decode_aac(data, pts),
+            Some(AudioCodec::Flac) => return self.decode_flac(data, pts),
+            Some(AudioCodec::Mp3) => return self.decode_mp3(data, pts),
+            Some(AudioCodec::Opus) => return self.decode_opus(data, pts),
+            Some(AudioCodec::Vorbis) => return self.decode_vorbis(data, pts),
+            Some(codec) if pcm::sample_size(codec).is_some() => {
+                return self.decode_pcm(codec, data, pts)
+            }
+            _ => {}
+        }
+
+        Err(PlayerError::decoder("Unsupported codec"))
+    }
+
+    /// Sets the presentation time at which the stream ends, from
+    /// `StreamInfo::end_pts`. Opus and Vorbis samples decoded past it are
+    /// encoder padding and are dropped.
+    pub fn set_end(&mut self, end: Option<Timestamp>) {
+        self.end = end;
+    }
+
+    /// Decodes an AAC packet holding either one raw access unit or a run
+    /// of ADTS frames. ADTS headers replace the configuration.
+    fn decode_aac(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<AudioFrame>> {
+        let mut frames = 1;
+        if aac::is_adts(data) {
+            let adts = aac::split_adts(data)?;
+            frames = adts
+                .iter()
+                .map(|(header, _)| header.raw_blocks as u32)
+                .sum();
+            if let Some((header, _)) = adts.last() {
+                let config = header.audio_specific_config();
+                // Keep SBR/PS signalled out of band for the same core.
+                let same_core = matches!(&self.record, Some(AudioRecord::Aac(current))
+                    if current.sampling_index == config.sampling_index
+                        && current.channel_config == config.channel_config);
+                if !same_core {
+                    self.record = Some(AudioRecord::Aac(config));
+                }
+            }
+        }
+
+        // Stub implementation - returns silence in the stream's format
+        let (channels, sample_rate, samples) = match &self.record {
+            Some(AudioRecord::Aac(config)) => (
+                config.output_channels(),
+                config.output_sample_rate(),
+                config.output_frame_length() * frames,
+            ),
+            _ => (2, 48000, 1024 * frames),
+        };
+        let channels =
+            u8::try_from(channels).map_err(|_| PlayerError::decoder("Too many AAC channels"))?;
+        Ok(Some(AudioFrame {
+            channels,
+            sample_rate,
+            pts,
+            format: SampleFormat::F32,
+            data: vec![0; samples as usize * channels as usize * 4],
+        }))
+    }
+
+    /// Decodes a packet of one or more MP3 frames into 32-bit float
+    /// samples, trimming the encoder delay and padding of gapless streams.
+    fn decode_mp3(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<AudioFrame>> {
+        let decoder = self.mp3.get_or_insert_with(Box::default);
+        let mut samples = Vec::new();
+        let mut first = None;
+        // Frames decoded before the first output, while the bit reservoir
+        // refills after a seek.
+        let mut skipped = 0;
+        let mut pos = 0;
+        while pos < data.len() {
+            let header = decoder.decode_frame(&data[pos..], &mut samples)?;
+            pos += header.frame_len;
+            if samples.is_empty() {
+                skipped += header.samples_per_frame() as i64;
+            }
+            first.get_or_insert(header);
+        }
+        let header = match first {
+            Some(header) if !samples.is_empty() => header,
+            _ => return Ok(None),
+        };
+
+        let channels = header.channels();
+        let timebase = Timebase::from_rate(header.sample_rate);
+        let origin = pts.rescale(timebase).ticks;
+        let end = match &self.record {
+            Some(AudioRecord::Mp3(xing)) => xing
+                .sample_count(header.samples_per_frame())
+                .map(|count| count as i64),
+            _ => None,
+        };
+        let start = trim_samples(&mut samples, channels as usize, origin + skipped, end);
+        if samples.is_empty() {
+            return Ok(None);
+        }
+        let pts = if start == origin {
+            pts
+        } else {
+            Timestamp::new(start, timebase).rescale(pts.timebase)
+        };
         Ok(Some(AudioFrame {
-            channels: 2,
-            sample_rate: 48000,
-            pts_ms,
+            channels,
+            sample_rate: header.sample_rate,
+            pts,
             format: SampleFormat::F32,
-            data: vec![0; 4096],
+            data: samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+        }))
+    }
+
+    /// Decodes an Opus packet. The pre-skip is trimmed as the samples the
+    /// demuxer places before zero.
+    fn decode_opus(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<AudioFrame>> {
+        let samples = opus::packet_samples(data)
+            .ok_or_else(|| PlayerError::decoder("Truncated Opus packet"))?;
+        let channels = match &self.record {
+            Some(AudioRecord::Opus(head)) => head.channels,
+            _ => 2,
+        };
+
+        // Stub implementation - returns silence of the packet's duration
+        let samples = vec![0.0; samples as usize * channels as usize];
+        Ok(self.trimmed_frame(samples, channels, opus::SAMPLE_RATE, pts))
+    }
+
+    /// Decodes a Vorbis packet. The first packet after initialization or a
+    /// flush has no previous block to overlap and outputs nothing.
+    fn decode_vorbis(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<AudioFrame>> {
+        let headers = match &self.record {
+            Some(AudioRecord::Vorbis(headers)) => headers,
+            _ => return Err(PlayerError::decoder("Vorbis requires its headers")),
+        };
+        let blocksize = headers
+            .packet_blocksize(data)
+            .ok_or_else(|| PlayerError::decoder("Invalid Vorbis audio packet"))?;
+        let id = headers.identification;
+        let previous = match self.vorbis_block.replace(blocksize) {
+            Some(previous) => previous,
+            None => return Ok(None),
+        };
+
+        // Stub implementation - returns silence of the packet's duration
+        let samples = vorbis::overlap_samples(previous, blocksize) as usize;
+        let samples = vec![0.0; samples * id.channels as usize];
+        Ok(self.trimmed_frame(samples, id.channels, id.sample_rate, pts))
+    }
+
+    /// Builds a 32-bit float frame from samples presented from `pts`,
+    /// dropping those before zero or past the stream end.
+    fn trimmed_frame(
+        &self,
+        mut samples: Vec<f32>,
+        channels: u8,
+        sample_rate: u32,
+        pts: Timestamp,
+    ) -> Option<AudioFrame> {
+        let timebase = Timebase::from_rate(sample_rate);
+        let origin = pts.rescale(timebase).ticks;
+        let end = self.end.map(|end| end.rescale(timebase).ticks);
+        let start = trim_samples(&mut samples, channels as usize, origin, end);
+        if samples.is_empty() {
+            return None;
+        }
+        let pts = if start == origin {
+            pts
+        } else {
+            Timestamp::new(start, timebase).rescale(pts.timebase)
+        };
+        Some(AudioFrame {
+            channels,
+            sample_rate,
+            pts,
+            format: SampleFormat::F32,
+            data: samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+        })
+    }
+
+    /// Decodes a packet of one or more FLAC frames. Samples of up to 16
+    /// bits are output as 16-bit integers, deeper ones as 32-bit floats.
+    fn decode_flac(&mut self, data: &[u8], pts: Timestamp) -> Result<Option<AudioFrame>> {
+        let info = match &self.record {
+            Some(AudioRecord::Flac(info)) => Some(info),
+            _ => None,
+        };
+        let mut samples = Vec::new();
+        let mut first = None;
+        let mut pos = 0;
+        while pos < data.len() {
+            let frame = flac::decode_frame(&data[pos..], info, &mut samples)?;
+            pos += frame.frame_len;
+            first.get_or_insert(frame);
+        }
+        let frame = match first {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+
+        let bits = frame.bits_per_sample as u32;
+        let (format, data) = if bits <= 16 {
+            let data = samples
+                .iter()
+                .flat_map(|&s| ((s << (16 - bits)) as i16).to_le_bytes())
+                .collect();
+            (SampleFormat::S16, data)
+        } else {
+            let scale = 1.0 / (1u64 << (bits - 1)) as f32;
+            let data = samples
+                .iter()
+                .flat_map(|&s| (s as f32 * scale).to_le_bytes())
+                .collect();
+            (SampleFormat::F32, data)
+        };
+        Ok(Some(AudioFrame {
+            channels: frame.header.channels(),
+            sample_rate: frame.sample_rate,
+            pts,
+            format,
+            data,
+        }))
+    }
+
+    /// Converts a packet of PCM sample frames; a trailing partial frame is
+    /// dropped.
+    fn decode_pcm(
+        &mut self,
+        codec: AudioCodec,
+        data: &[u8],
+        pts: Timestamp,
+    ) -> Result<Option<AudioFrame>> {
+        let format = match &self.record {
+            Some(AudioRecord::Pcm(format)) => format,
+            _ => return Err(PlayerError::decoder("PCM requires a WAVEFORMATEX")),
+        };
+        let frame_len = pcm::sample_size(codec).unwrap_or(1) * format.channels as usize;
+        let len = data.len() / frame_len * frame_len;
+        if len == 0 {
+            return Ok(None);
+        }
+        let channels = u8::try_from(format.channels)
+            .map_err(|_| PlayerError::decoder("Too many PCM channels"))?;
+        let (sample_format, data) = pcm::convert(codec, &data[..len]);
+        Ok(Some(AudioFrame {
+            channels,
+            sample_rate: format.sample_rate,
+            pts,
+            format: sample_format,
+            data,
         }))
     }
 
@@ -269,6 +797,13 @@ impl AudioDecoder {
             return Err(PlayerError::decoder("Decoder not initialized"));
         }
 
+        // Frames after a seek must not refer back to the old reservoir or
+        // overlap the old block.
+        if let Some(mp3) = self.mp3.as_mut() {
+            mp3.reset();
+        }
+        self.vorbis_block = None;
+
         Ok(Vec::new())
     }
 
@@ -277,6 +812,74 @@ impl AudioDecoder {
         self.codec
     }
 
+    /// Returns the RFC 6381 codec string for WebCodecs, if known from the
+    /// initialization data or the stream.
+    pub fn codec_string(&self) -> Option<String> {
+        match self.codec {
+            Some(AudioCodec::Flac) => return Some("flac".to_string()),
+            Some(AudioCodec::Mp3) => return Some("mp3".to_string()),
+            Some(codec) if pcm::sample_size(codec).is_some() => {
+                return pcm::codec_string(codec).map(str::to_string)
+            }
+            _ => {}
+        }
+        match self.record.as_ref()? {
+            AudioRecord::Aac(aac) => Some(aac.codec_string()),
+            AudioRecord::Opus(_) => Some("opus".to_string()),
+            AudioRecord::Vorbis(_) => Some("vorbis".to_string()),
+            AudioRecord::Flac(_) | AudioRecord::Mp3(_) | AudioRecord::Pcm(_) => None,
+        }
+    }
+
+    /// Returns the AAC configuration, if one was given or read from an
+    /// ADTS header.
+    pub fn aac_config(&self) -> Option<&AudioSpecificConfig> {
+        match &self.record {
+            Some(AudioRecord::Aac(aac)) => Some(aac),
+            _ => None,
+        }
+    }
+
+    /// Returns the FLAC STREAMINFO, if one was given.
+    pub fn flac_stream_info(&self) -> Option<&FlacStreamInfo> {
+        match &self.record {
+            Some(AudioRecord::Flac(info)) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// Returns the Opus identification header, if one was given.
+    pub fn opus_head(&self) -> Option<&OpusHead> {
+        match &self.record {
+            Some(AudioRecord::Opus(head)) => Some(head),
+            _ => None,
+        }
+    }
+
+    /// Returns the Vorbis headers.
+    pub fn vorbis_headers(&self) -> Option<&VorbisHeaders> {
+        match &self.record {
+            Some(AudioRecord::Vorbis(headers)) => Some(headers),
+            _ => None,
+        }
+    }
+
+    /// Returns the PCM `WAVEFORMATEX`, if one was given.
+    pub fn wave_format(&self) -> Option<&WaveFormat> {
+        match &self.record {
+            Some(AudioRecord::Pcm(format)) => Some(format),
+            _ => None,
+        }
+    }
+
+    /// Returns the MP3 Xing/Info tag, if one was given.
+    pub fn xing_header(&self) -> Option<&XingHeader> {
+        match &self.record {
+            Some(AudioRecord::Mp3(xing)) => Some(xing),
+            _ => None,
+        }
+    }
+
     /// Returns whether the decoder is initialized.
     pub fn is_initialized(&self) -> bool {
         self.initialized
@@ -288,6 +891,20 @@ impl AudioDecoder {
     }
 }
 
+/// Drops the interleaved `samples` presented outside `0..end`, given the
+/// position of the first one in samples per channel. Returns the position
+/// of the first sample kept.
+fn trim_samples(samples: &mut Vec<f32>, channels: usize, start: i64, end: Option<i64>) -> i64 {
+    let frames = (samples.len() / channels) as i64;
+    if let Some(end) = end {
+        let keep = (end - start).clamp(0, frames);
+        samples.truncate(keep as usize * channels);
+    }
+    let skip = (-start).clamp(0, frames);
+    samples.drain(..(skip as usize * channels).min(samples.len()));
+    start + skip
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -311,7 +928,7 @@ mod tests {
     #[test]
     fn test_video_decoder_decode_without_init() {
         let mut decoder = VideoDecoder::default();
-        let result = decoder.decode(&[1, 2, 3], 0);
+        let result = decoder.decode(&[1, 2, 3], Timestamp::ZERO);
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -323,7 +940,7 @@ mod tests {
     fn test_video_decoder_decode_empty_data() {
         let mut decoder = VideoDecoder::default();
         decoder.init(VideoCodec::H264, None).unwrap();
-        let result = decoder.decode(&[], 0);
+        let result = decoder.decode(&[], Timestamp::ZERO);
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Empty packet"));
     }
@@ -332,14 +949,141 @@ mod tests {
     fn test_video_decoder_decode_success() {
         let mut decoder = VideoDecoder::default();
         decoder.init(VideoCodec::H264, None).unwrap();
-        let result = decoder.decode(&[0, 0, 0, 1, 0x67], 1000);
+        let result = decoder.decode(&[0, 0, 0, 1, 0x67], Timestamp::from_ms(1000));
         assert!(result.is_ok());
         let frame = result.unwrap().unwrap();
-        assert_eq!(frame.pts_ms, 1000);
+        assert_eq!(frame.pts, Timestamp::from_ms(1000));
         assert_eq!(frame.width, 1920);
         assert_eq!(frame.height, 1080);
     }
 
+    #[test]
+    fn test_video_decoder_rejects_oversized_frames() {
+        let mut decoder = VideoDecoder::default();
+        decoder.init(VideoCodec::H264, None).unwrap();
+        decoder.frame_size = Some((65536, 65536));
+        let result = decoder.decode(&[0, 0, 0, 1, 0x67], Timestamp::ZERO);
+        assert!(result.unwrap_err().to_string().contains("too large"));
+    }
+
+    #[test]
+    fn test_video_decoder_init_avcc() {
+        use h264::tests::{avcc_record, high_profile_sps};
+
+        let mut decoder = VideoDecoder::default();
+        let record = avcc_record(&high_profile_sps(), &[0x68, 0xEE, 0x3C, 0x80]);
+        decoder.init(VideoCodec::H264, Some(&record)).unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("avc1.640028"));
+        assert_eq!(decoder.avc_config().unwrap().nal_length_size, 4);
+
+        let frame = decoder
+            .decode(&[0, 0, 0, 2, 0x65, 0x88], Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.width, frame.height), (1920, 1080));
+        assert!(decoder
+            .decode(&[0, 0, 0, 9, 0x65], Timestamp::ZERO)
+            .is_err());
+
+        let mut decoder = VideoDecoder::default();
+        assert!(decoder.init(VideoCodec::H264, Some(&[1, 100])).is_err());
+        assert!(!decoder.is_initialized());
+    }
+
+    #[test]
+    fn test_video_decoder_init_hvcc() {
+        use h265::tests::{hvcc_record, main_profile_sps, main_profile_vps};
+
+        let mut decoder = VideoDecoder::default();
+        let record = hvcc_record(&main_profile_vps(), &main_profile_sps(), &[0x44, 1, 0xC1]);
+        decoder.init(VideoCodec::H265, Some(&record)).unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("hvc1.1.6.L120.B0"));
+        assert!(decoder.avc_config().is_none());
+
+        let frame = decoder
+            .decode(&[0, 0, 0, 3, 0x26, 1, 0xAF], Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.width, frame.height), (1920, 1080));
+        assert!(decoder
+            .decode(&[0, 0, 0, 9, 0x26], Timestamp::ZERO)
+            .is_err());
+
+        // Re-initializing for another codec drops the record.
+        decoder.init(VideoCodec::Vp9, None).unwrap();
+        assert!(decoder.hevc_config().is_none());
+        assert!(decoder.codec_string().is_none());
+    }
+
+    #[test]
+    fn test_video_decoder_init_av1c() {
+        let mut decoder = VideoDecoder::default();
+        decoder
+            .init(VideoCodec::Av1, Some(&av1::tests::av1c_record()))
+            .unwrap();
+        assert_eq!(
+            decoder.codec_string().as_deref(),
+            Some("av01.0.13M.10.0.110.09.16.09.0")
+        );
+        assert!(decoder.av1_config().unwrap().sequence_header.is_some());
+
+        // A temporal delimiter followed by a frame OBU.
+        let frame = decoder
+            .decode(&[0x12, 0, 0x32, 1, 0x10], Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.width, frame.height), (3840, 2160));
+        assert!(decoder.decode(&[0x32, 9, 0x10], Timestamp::ZERO).is_err());
+        assert!(decoder.init(VideoCodec::Av1, Some(&[0x81])).is_err());
+    }
+
+    #[test]
+    fn test_video_decoder_vp9_superframe() {
+        use vp9::tests::{hidden_inter_frame, key_frame, superframe};
+
+        let mut decoder = VideoDecoder::default();
+        decoder.init(VideoCodec::Vp9, None).unwrap();
+        assert!(decoder.codec_string().is_none());
+
+        let frame = decoder
+            .decode(&key_frame(1280, 720), Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.width, frame.height), (1280, 720));
+        assert_eq!(
+            decoder.codec_string().as_deref(),
+            Some("vp09.00.31.08.01.02.02.01.00")
+        );
+
+        // A hidden frame alone produces no output; packed with a shown
+        // frame it does.
+        let hidden = hidden_inter_frame();
+        assert!(decoder.decode(&hidden, Timestamp::ZERO).unwrap().is_none());
+        let packet = superframe(&[&hidden, &key_frame(640, 360)]);
+        let frame = decoder.decode(&packet, Timestamp::ZERO).unwrap().unwrap();
+        assert_eq!((frame.width, frame.height), (640, 360));
+        assert!(decoder.decode(&[0x00, 0x00], Timestamp::ZERO).is_err());
+
+        let record = [1, 0, 0, 0, 2, 41, 0xA2, 9, 16, 9, 0, 0];
+        decoder.init(VideoCodec::Vp9, Some(&record)).unwrap();
+        assert_eq!(decoder.vpc_config().unwrap().bit_depth, 10);
+        assert!(decoder.frame_size().is_none());
+        assert!(decoder.init(VideoCodec::Vp9, Some(&[1, 0])).is_err());
+    }
+
+    #[test]
+    fn test_video_decoder_vp8_key_frame() {
+        let mut decoder = VideoDecoder::default();
+        decoder.init(VideoCodec::Vp8, None).unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("vp8"));
+        let frame = decoder
+            .decode(&vp8::tests::key_frame(176, 144), Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.width, frame.height), (176, 144));
+        assert_eq!(decoder.frame_size(), Some((176, 144)));
+    }
+
     #[test]
     fn test_audio_decoder_new() {
         let decoder = AudioDecoder::default();
@@ -360,14 +1104,245 @@ mod tests {
     fn test_audio_decoder_decode_success() {
         let mut decoder = AudioDecoder::default();
         decoder.init(AudioCodec::Opus, None).unwrap();
-        let result = decoder.decode(&[1, 2, 3, 4], 500);
+        let result = decoder.decode(&[1, 2, 3, 4], Timestamp::from_ms(500));
         assert!(result.is_ok());
         let frame = result.unwrap().unwrap();
-        assert_eq!(frame.pts_ms, 500);
+        assert_eq!(frame.pts, Timestamp::from_ms(500));
         assert_eq!(frame.channels, 2);
         assert_eq!(frame.sample_rate, 48000);
     }
 
+    #[test]
+    fn test_audio_decoder_aac_config() {
+        use aac::tests::LC_STEREO_44100;
+
+        let mut decoder = AudioDecoder::default();
+        decoder
+            .init(AudioCodec::Aac, Some(&[0xEB, 0x09, 0x88, 0x00]))
+            .unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("mp4a.40.29"));
+        let frame = decoder
+            .decode(&[0x21, 0x10], Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (2, 48000));
+        assert_eq!(frame.data.len(), 2048 * 2 * 4);
+
+        // Two ADTS frames in one packet replace the configuration.
+        let config = AudioSpecificConfig::parse(&LC_STEREO_44100).unwrap();
+        let mut packet = config.to_adts(&[0x21, 0x10]).unwrap();
+        packet.extend(config.to_adts(&[0x21]).unwrap());
+        let frame = decoder.decode(&packet, Timestamp::ZERO).unwrap().unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (2, 44100));
+        assert_eq!(frame.data.len(), 2 * 1024 * 2 * 4);
+        assert_eq!(decoder.aac_config(), Some(&config));
+        assert!(decoder.decode(&packet[..12], Timestamp::ZERO).is_err());
+
+        // A channel count that does not fit the frame is rejected.
+        let mut config = config;
+        config.channels = 300;
+        decoder.record = Some(AudioRecord::Aac(config));
+        assert!(decoder.decode(&[0x21], Timestamp::ZERO).is_err());
+
+        assert!(decoder.init(AudioCodec::Aac, Some(&[0x16])).is_err());
+        decoder.init(AudioCodec::Opus, None).unwrap();
+        assert!(decoder.codec_string().is_none());
+    }
+
+    #[test]
+    fn test_audio_decoder_mp3_gapless() {
+        use mp3::tests::{info_frame, silent_frame};
+
+        let mut decoder = AudioDecoder::default();
+        decoder
+            .init(AudioCodec::Mp3, Some(&info_frame(4, 576, 1000)))
+            .unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("mp3"));
+        assert_eq!(decoder.xing_header().unwrap().frames, Some(4));
+
+        // Timestamps as assigned by the demuxer: the first frame starts
+        // 576 + 529 samples before zero and 3032 samples remain.
+        let timebase = Timebase::from_rate(44100);
+        let decode = |decoder: &mut AudioDecoder, ticks| {
+            decoder
+                .decode(&silent_frame(), Timestamp::new(ticks, timebase))
+                .unwrap()
+        };
+        let frame = decode(&mut decoder, -1105).unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (2, 44100));
+        assert_eq!(frame.format, SampleFormat::F32);
+        assert_eq!(frame.pts, Timestamp::new(0, timebase));
+        assert_eq!(frame.data.len(), 47 * 2 * 4);
+        let frame = decode(&mut decoder, 47).unwrap();
+        assert_eq!(frame.data.len(), 1152 * 2 * 4);
+        decode(&mut decoder, 1199).unwrap();
+        let frame = decode(&mut decoder, 2351).unwrap();
+        assert_eq!(frame.data.len(), 681 * 2 * 4);
+        assert!(decode(&mut decoder, 3503).is_none());
+
+        // Several frames may share a packet.
+        decoder.init(AudioCodec::Mp3, None).unwrap();
+        let packet = [silent_frame(), silent_frame()].concat();
+        let frame = decoder.decode(&packet, Timestamp::ZERO).unwrap().unwrap();
+        assert_eq!(frame.data.len(), 2 * 1152 * 2 * 4);
+        assert!(decoder.xing_header().is_none());
+
+        assert!(decoder
+            .init(AudioCodec::Mp3, Some(&silent_frame()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_audio_decoder_flac() {
+        use flac::tests::{constant_frame, streaminfo};
+
+        let mut decoder = AudioDecoder::default();
+        decoder.init(AudioCodec::Flac, None).unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("flac"));
+        let packet = [constant_frame(0, 16, -2), constant_frame(1, 16, 3)].concat();
+        let frame = decoder
+            .decode(&packet, Timestamp::from_ms(20))
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (1, 44100));
+        assert_eq!(frame.format, SampleFormat::S16);
+        assert_eq!(frame.pts, Timestamp::from_ms(20));
+        assert_eq!(&frame.data[..2], &(-2i16).to_le_bytes());
+        assert_eq!(&frame.data[32..34], &3i16.to_le_bytes());
+        assert_eq!(frame.data.len(), 32 * 2);
+
+        // 24-bit samples whose size is only given by STREAMINFO decode to
+        // floats.
+        let mut record = b"fLaC\x80\x00\x00\x22".to_vec();
+        record.extend(streaminfo(16, 44100, 1, 24, 16));
+        decoder.init(AudioCodec::Flac, Some(&record)).unwrap();
+        assert_eq!(decoder.flac_stream_info().unwrap().bits_per_sample, 24);
+        let packet = flac::tests::frame(0, 16, 0, 0, |w| {
+            w.bits(8, 0).bits(24, 0xC00000);
+        });
+        let frame = decoder.decode(&packet, Timestamp::ZERO).unwrap().unwrap();
+        assert_eq!(frame.format, SampleFormat::F32);
+        assert_eq!(&frame.data[..4], &(-0.5f32).to_le_bytes());
+        assert!(decoder.decode(&packet[..10], Timestamp::ZERO).is_err());
+
+        assert!(decoder.init(AudioCodec::Flac, Some(b"fLaC")).is_err());
+    }
+
+    #[test]
+    fn test_audio_decoder_pcm() {
+        let mut decoder = AudioDecoder::default();
+        assert!(decoder.init(AudioCodec::PcmS24le, None).is_err());
+
+        let format = WaveFormat::new(AudioCodec::PcmS24le, 96000, 2);
+        decoder
+            .init(AudioCodec::PcmS24le, Some(&format.to_bytes()))
+            .unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("pcm-s24"));
+        assert_eq!(decoder.wave_format(), Some(&format));
+        // Two stereo frames and a stray byte.
+        let packet = [0, 0, 0x40, 0, 0, 0xC0, 0, 0, 0x80, 0xFF, 0xFF, 0x7F, 0];
+        let frame = decoder
+            .decode(&packet, Timestamp::from_ms(10))
+            .unwrap()
+            .unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (2, 96000));
+        assert_eq!(frame.format, SampleFormat::F32);
+        assert_eq!(frame.pts, Timestamp::from_ms(10));
+        assert_eq!(frame.data.len(), 4 * 4);
+        assert_eq!(&frame.data[..4], &0.5f32.to_le_bytes());
+        assert_eq!(&frame.data[4..8], &(-0.5f32).to_le_bytes());
+        assert!(decoder
+            .decode(&packet[..5], Timestamp::ZERO)
+            .unwrap()
+            .is_none());
+
+        let format = WaveFormat::new(AudioCodec::PcmS16be, 44100, 1);
+        decoder
+            .init(AudioCodec::PcmS16be, Some(&format.to_bytes()))
+            .unwrap();
+        assert!(decoder.codec_string().is_none());
+        let frame = decoder
+            .decode(&[0x01, 0x02], Timestamp::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.format, SampleFormat::S16);
+        assert_eq!(frame.data, vec![0x02, 0x01]);
+
+        // More channels than a frame can describe.
+        let format = WaveFormat::new(AudioCodec::PcmU8, 8000, 300);
+        decoder
+            .init(AudioCodec::PcmU8, Some(&format.to_bytes()))
+            .unwrap();
+        assert!(decoder.decode(&[0x80; 300], Timestamp::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_audio_decoder_opus_gapless() {
+        let mut decoder = AudioDecoder::default();
+        decoder
+            .init(AudioCodec::Opus, Some(&opus::tests::opus_head(1, 312)))
+            .unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("opus"));
+        assert_eq!(decoder.opus_head().unwrap().pre_skip, 312);
+        decoder.set_end(Some(Timestamp::new(1500, Timebase::from_rate(48000))));
+
+        // Timestamps as assigned by the demuxer: the pre-skip is before
+        // zero, and 1500 samples follow it.
+        let timebase = Timebase::from_rate(48000);
+        let decode = |decoder: &mut AudioDecoder, ticks| {
+            decoder
+                .decode(&[0xF8], Timestamp::new(ticks, timebase))
+                .unwrap()
+        };
+        let frame = decode(&mut decoder, -312).unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (1, 48000));
+        assert_eq!(frame.pts, Timestamp::new(0, timebase));
+        assert_eq!(frame.data.len(), 648 * 4);
+        let frame = decode(&mut decoder, 648).unwrap();
+        assert_eq!(frame.pts, Timestamp::new(648, timebase));
+        assert_eq!(frame.data.len(), 852 * 4);
+        assert!(decode(&mut decoder, 1608).is_none());
+
+        // Initialization clears the end.
+        decoder.init(AudioCodec::Opus, None).unwrap();
+        let frame = decode(&mut decoder, 1608).unwrap();
+        assert_eq!(frame.data.len(), 960 * 2 * 4);
+        assert!(decoder.decode(&[0x03], Timestamp::ZERO).is_err());
+
+        assert!(decoder.init(AudioCodec::Opus, Some(b"OpusHead")).is_err());
+    }
+
+    #[test]
+    fn test_audio_decoder_vorbis() {
+        let mut decoder = AudioDecoder::default();
+        assert!(decoder.init(AudioCodec::Vorbis, None).is_err());
+
+        // Short blocks of 256 and long blocks of 2048 samples, at 44.1 kHz.
+        decoder
+            .init(AudioCodec::Vorbis, Some(&vorbis::tests::headers()))
+            .unwrap();
+        assert_eq!(decoder.codec_string().as_deref(), Some("vorbis"));
+        assert_eq!(decoder.vorbis_headers().unwrap().mode_blockflags.len(), 2);
+        let timebase = Timebase::from_rate(44100);
+        decoder.set_end(Some(Timestamp::new(1000, timebase)));
+        let decode = |decoder: &mut AudioDecoder, packet: u8, ticks| {
+            decoder
+                .decode(&[packet], Timestamp::new(ticks, timebase))
+                .unwrap()
+        };
+        assert!(decode(&mut decoder, 0b00, 0).is_none());
+        let frame = decode(&mut decoder, 0b10, 0).unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (2, 44100));
+        assert_eq!(frame.data.len(), 576 * 2 * 4);
+        let frame = decode(&mut decoder, 0b10, 576).unwrap();
+        assert_eq!(frame.data.len(), 424 * 2 * 4);
+
+        // A flush forgets the previous block.
+        decoder.flush().unwrap();
+        assert!(decode(&mut decoder, 0b10, 0).is_none());
+        assert!(decoder.decode(&[0x01], Timestamp::ZERO).is_err());
+    }
+
     #[test]
     fn test_decoder_config_default() {
         let config = DecoderConfig::default();
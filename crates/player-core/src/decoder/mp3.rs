@@ -0,0 +1,574 @@
+//! MPEG audio Layer III (MP3) parsing and decoding.
+//!
+//! Parses frame headers (ISO/IEC 11172-3, ISO/IEC 13818-3 and the MPEG-2.5
+//! extension), ID3v2 tags, and the Xing/Info and LAME tags that carry the
+//! frame count and the encoder delay and padding of gapless streams.
+//! [`Mp3Decoder`] decodes Layer III frames to interleaved 32-bit float
+//! samples.
+
+use super::layer3::{self, ChannelState, SideInfo};
+use crate::error::{PlayerError, Result};
+
+/// Length of a frame header in bytes.
+pub const HEADER_LEN: usize = 4;
+
+/// Delay of the Layer III decoder filterbanks in samples. Gapless players
+/// skip it in addition to the encoder delay of a LAME tag.
+pub const DECODER_DELAY: u32 = 529;
+
+/// Largest main data back pointer (9 bits in MPEG-1).
+const MAX_RESERVOIR: usize = 511;
+
+/// Bitrates in kbit/s, indexed by MPEG-1 or not, layer and bitrate index.
+#[rustfmt::skip]
+const BITRATES: [[[u16; 15]; 3]; 2] = [
+    [
+        [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+        [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+        [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320],
+    ],
+    [
+        [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ],
+];
+
+/// MPEG-1 sample rates; MPEG-2 halves and MPEG-2.5 quarters them.
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+/// MPEG audio version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpegVersion {
+    /// MPEG-1 (32 to 48 kHz).
+    Mpeg1,
+    /// MPEG-2 low sampling frequencies (16 to 24 kHz).
+    Mpeg2,
+    /// Unofficial MPEG-2.5 extension (8 to 12 kHz).
+    Mpeg25,
+}
+
+/// Channel mode of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Independent stereo.
+    Stereo,
+    /// Mid/side and/or intensity stereo, as signalled by the mode extension.
+    JointStereo,
+    /// Two independent mono channels.
+    DualChannel,
+    /// Single channel.
+    Mono,
+}
+
+/// Decoded fields of an MPEG audio frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// MPEG version.
+    pub version: MpegVersion,
+    /// Layer (1 to 3).
+    pub layer: u8,
+    /// Whether a CRC follows the header.
+    pub crc: bool,
+    /// Bitrate in bits per second.
+    pub bitrate: u32,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Sample rate index (0 to 2).
+    pub sampling_index: u8,
+    /// Whether the frame carries a padding slot.
+    pub padding: bool,
+    /// Channel mode.
+    pub channel_mode: ChannelMode,
+    /// Joint stereo mode extension; for Layer III bit 0 enables intensity
+    /// and bit 1 mid/side stereo.
+    pub mode_extension: u8,
+    /// Length of the whole frame in bytes, header included.
+    pub frame_len: usize,
+}
+
+impl FrameHeader {
+    /// Parses a frame header.
+    ///
+    /// # Errors
+    /// Returns an error if the data is truncated, lacks the frame sync,
+    /// uses reserved values or signals a free-format bitrate.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let h = data
+            .get(..HEADER_LEN)
+            .ok_or_else(|| PlayerError::decoder("MPEG audio header truncated"))?;
+        if h[0] != 0xFF || h[1] & 0xE0 != 0xE0 {
+            return Err(PlayerError::decoder("Missing MPEG audio frame sync"));
+        }
+        let version = match (h[1] >> 3) & 0x03 {
+            0 => MpegVersion::Mpeg25,
+            2 => MpegVersion::Mpeg2,
+            3 => MpegVersion::Mpeg1,
+            _ => return Err(PlayerError::decoder("Reserved MPEG audio version")),
+        };
+        let layer = match (h[1] >> 1) & 0x03 {
+            0 => return Err(PlayerError::decoder("Reserved MPEG audio layer")),
+            bits => 4 - bits,
+        };
+        let bitrate_index = (h[2] >> 4) as usize;
+        if bitrate_index == 0 {
+            return Err(PlayerError::decoder("Free-format MP3 is not supported"));
+        }
+        let kbps = BITRATES[(version != MpegVersion::Mpeg1) as usize][layer as usize - 1]
+            .get(bitrate_index)
+            .ok_or_else(|| PlayerError::decoder("Invalid MPEG audio bitrate"))?;
+        let sampling_index = (h[2] >> 2) & 0x03;
+        let base_rate = *SAMPLE_RATES
+            .get(sampling_index as usize)
+            .ok_or_else(|| PlayerError::decoder("Invalid MPEG audio sample rate"))?;
+        let sample_rate = match version {
+            MpegVersion::Mpeg1 => base_rate,
+            MpegVersion::Mpeg2 => base_rate / 2,
+            MpegVersion::Mpeg25 => base_rate / 4,
+        };
+        let padding = (h[2] >> 1) & 1 == 1;
+        let channel_mode = match h[3] >> 6 {
+            0 => ChannelMode::Stereo,
+            1 => ChannelMode::JointStereo,
+            2 => ChannelMode::DualChannel,
+            _ => ChannelMode::Mono,
+        };
+
+        let bitrate = *kbps as u32 * 1000;
+        let frame_len = if layer == 1 {
+            (12 * bitrate / sample_rate + padding as u32) * 4
+        } else {
+            let slots = if layer == 3 && version != MpegVersion::Mpeg1 {
+                72
+            } else {
+                144
+            };
+            slots * bitrate / sample_rate + padding as u32
+        };
+        Ok(Self {
+            version,
+            layer,
+            crc: h[1] & 1 == 0,
+            bitrate,
+            sample_rate,
+            sampling_index,
+            padding,
+            channel_mode,
+            mode_extension: (h[3] >> 4) & 0x03,
+            frame_len: frame_len as usize,
+        })
+    }
+
+    /// Returns the number of channels.
+    pub fn channels(&self) -> u8 {
+        if self.channel_mode == ChannelMode::Mono {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns the number of samples per channel in the frame.
+    pub fn samples_per_frame(&self) -> u32 {
+        match (self.layer, self.version) {
+            (1, _) => 384,
+            (3, MpegVersion::Mpeg2 | MpegVersion::Mpeg25) => 576,
+            _ => 1152,
+        }
+    }
+
+    /// Returns whether `other` can belong to the same stream: the version,
+    /// layer and sample rate match.
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.layer == other.layer
+            && self.sample_rate == other.sample_rate
+    }
+
+    /// Returns the number of Layer III granules in the frame.
+    pub(super) fn granules(&self) -> usize {
+        if self.version == MpegVersion::Mpeg1 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Returns the length of the Layer III side information in bytes.
+    pub(super) fn side_info_len(&self) -> usize {
+        match (self.version == MpegVersion::Mpeg1, self.channels()) {
+            (true, 1) => 17,
+            (true, _) => 32,
+            (false, 1) => 9,
+            (false, _) => 17,
+        }
+    }
+
+    /// Returns the row of the scale factor band tables for the sample rate.
+    pub(super) fn band_table_index(&self) -> usize {
+        let version = match self.version {
+            MpegVersion::Mpeg1 => 0,
+            MpegVersion::Mpeg2 => 1,
+            MpegVersion::Mpeg25 => 2,
+        };
+        version * 3 + self.sampling_index as usize
+    }
+
+    /// Returns whether the frame uses joint stereo.
+    pub(super) fn is_joint_stereo(&self) -> bool {
+        self.channel_mode == ChannelMode::JointStereo
+    }
+
+    /// Returns whether the frame uses mid/side stereo.
+    pub(super) fn is_ms_stereo(&self) -> bool {
+        self.is_joint_stereo() && self.mode_extension & 0x02 != 0
+    }
+
+    /// Returns whether the frame uses intensity stereo.
+    pub(super) fn is_intensity_stereo(&self) -> bool {
+        self.is_joint_stereo() && self.mode_extension & 0x01 != 0
+    }
+}
+
+/// Returns the length of the ID3v2 tag at the start of `data`, including
+/// its header and footer, or `None` if `data` does not start with a
+/// complete tag header.
+pub fn id3v2_len(data: &[u8]) -> Option<usize> {
+    let header = data.get(..10)?;
+    if &header[..3] != b"ID3" || header[3] == 0xFF || header[4] == 0xFF {
+        return None;
+    }
+    let size = header[6..10].iter().try_fold(0usize, |size, &byte| {
+        (byte < 0x80).then_some(size << 7 | byte as usize)
+    })?;
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Some(10 + size + footer)
+}
+
+/// Xing/Info tag of the first frame of a VBR or gapless stream, with the
+/// LAME extension when present.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XingHeader {
+    /// Number of audio frames in the stream, excluding the tag frame.
+    pub frames: Option<u32>,
+    /// Number of bytes in the stream.
+    pub bytes: Option<u32>,
+    /// Seek table: for each percent of the duration, the byte position
+    /// as a fraction of 256 of the stream length.
+    pub toc: Option<Vec<u8>>,
+    /// Samples of encoder priming at the start, if a LAME tag gives it.
+    pub encoder_delay: Option<u32>,
+    /// Samples of padding at the end, if a LAME tag gives it.
+    pub encoder_padding: Option<u32>,
+}
+
+impl XingHeader {
+    /// Parses the Xing/Info tag of `frame`, a complete Layer III frame.
+    /// Returns `None` if the frame does not carry one.
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let header = FrameHeader::parse(frame).ok()?;
+        if header.layer != 3 {
+            return None;
+        }
+        let side_start = HEADER_LEN + if header.crc { 2 } else { 0 };
+        let data = frame.get(side_start + header.side_info_len()..)?;
+        if !(data.starts_with(b"Xing") || data.starts_with(b"Info")) {
+            return None;
+        }
+        let be32 = |pos: usize| -> Option<u32> {
+            Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+        };
+        let flags = be32(4)?;
+        let mut pos = 8;
+        let mut field = |flag: u32, len: usize| -> Option<Option<usize>> {
+            if flags & flag == 0 {
+                return Some(None);
+            }
+            data.get(pos..pos + len)?;
+            pos += len;
+            Some(Some(pos - len))
+        };
+        let frames = field(0x01, 4)?;
+        let bytes = field(0x02, 4)?;
+        let toc = field(0x04, 100)?;
+        field(0x08, 4)?;
+
+        let mut xing = Self {
+            frames: frames.and_then(be32),
+            bytes: bytes.and_then(be32),
+            toc: toc.map(|start| data[start..start + 100].to_vec()),
+            encoder_delay: None,
+            encoder_padding: None,
+        };
+        // The LAME tag follows with a 9-byte encoder version string; FFmpeg
+        // writes the same layout.
+        if let Some(lame) = data.get(pos..pos + 24) {
+            if [b"LAME", b"Lavf", b"Lavc"]
+                .iter()
+                .any(|id| lame.starts_with(*id))
+            {
+                xing.encoder_delay = Some((lame[21] as u32) << 4 | (lame[22] as u32) >> 4);
+                xing.encoder_padding = Some((lame[22] as u32 & 0x0F) << 8 | lame[23] as u32);
+            }
+        }
+        Some(xing)
+    }
+
+    /// Returns the number of decoded samples per channel that precede the
+    /// start of the stream: the encoder delay plus [`DECODER_DELAY`], or
+    /// zero without a LAME tag.
+    pub fn start_padding(&self) -> u32 {
+        self.encoder_delay.map_or(0, |delay| delay + DECODER_DELAY)
+    }
+
+    /// Returns the number of samples per channel after removing the
+    /// encoder delay and padding, if the frame count is known.
+    pub fn sample_count(&self, samples_per_frame: u32) -> Option<u64> {
+        let total = self.frames? as u64 * samples_per_frame as u64;
+        let trimmed = self.encoder_delay.unwrap_or(0) + self.encoder_padding.unwrap_or(0);
+        Some(total.saturating_sub(trimmed as u64))
+    }
+}
+
+/// Layer III decoder keeping the bit reservoir and filterbank state across
+/// frames.
+#[derive(Debug, Clone, Default)]
+pub struct Mp3Decoder {
+    /// Main data of previous frames that later frames may refer back to.
+    reservoir: Vec<u8>,
+    /// Filterbank state per channel.
+    channels: [ChannelState; 2],
+}
+
+impl Mp3Decoder {
+    /// Creates a decoder with empty state.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clears the bit reservoir and filterbank state, as after a seek.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Decodes one Layer III frame, appending its interleaved samples to
+    /// `out`, and returns the frame header.
+    ///
+    /// Nothing is appended when the frame refers back to main data the
+    /// reservoir does not hold, as for the first frames after a seek.
+    ///
+    /// # Errors
+    /// Returns an error if the frame is truncated, is not Layer III or its
+    /// main data is malformed.
+    pub fn decode_frame(&mut self, frame: &[u8], out: &mut Vec<f32>) -> Result<FrameHeader> {
+        let header = FrameHeader::parse(frame)?;
+        if header.layer != 3 {
+            return Err(PlayerError::decoder(format!(
+                "Unsupported MPEG audio layer {}",
+                header.layer
+            )));
+        }
+        let side_start = HEADER_LEN + if header.crc { 2 } else { 0 };
+        let main_start = side_start + header.side_info_len();
+        if frame.len() < header.frame_len || header.frame_len < main_start {
+            return Err(PlayerError::decoder("MP3 frame truncated"));
+        }
+        let side = SideInfo::parse(&header, &frame[side_start..main_start])?;
+        let main = &frame[main_start..header.frame_len];
+
+        let available = self.reservoir.len();
+        let data = (side.main_data_begin <= available).then(|| {
+            let mut data = self.reservoir[available - side.main_data_begin..].to_vec();
+            data.extend_from_slice(main);
+            data
+        });
+        self.reservoir.extend_from_slice(main);
+        let excess = self.reservoir.len().saturating_sub(MAX_RESERVOIR);
+        self.reservoir.drain(..excess);
+
+        let Some(data) = data else {
+            return Ok(header);
+        };
+        if side.main_data_bits(&header) > data.len() * 8 {
+            return Err(PlayerError::decoder("MP3 main data exceeds the frame"));
+        }
+        layer3::decode_frame(&header, &side, &data, &mut self.channels, out)?;
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Header of an MPEG-1 Layer III frame: 128 kbit/s, 44.1 kHz, joint
+    /// stereo with mid/side coding, 417 bytes.
+    const HEADER_128K_JOINT: [u8; 4] = [0xFF, 0xFB, 0x90, 0x64];
+
+    /// Builds a silent frame with [`HEADER_128K_JOINT`].
+    pub(crate) fn silent_frame() -> Vec<u8> {
+        let mut frame = vec![0; 417];
+        frame[..4].copy_from_slice(&HEADER_128K_JOINT);
+        frame
+    }
+
+    /// Builds an Info tag frame for `frames` audio frames with a LAME tag
+    /// giving the encoder delay and padding.
+    pub(crate) fn info_frame(frames: u32, delay: u32, padding: u32) -> Vec<u8> {
+        let mut frame = silent_frame();
+        let tag = HEADER_LEN + 32;
+        frame[tag..tag + 4].copy_from_slice(b"Info");
+        frame[tag + 4..tag + 8].copy_from_slice(&0x01u32.to_be_bytes());
+        frame[tag + 8..tag + 12].copy_from_slice(&frames.to_be_bytes());
+        let lame = tag + 12;
+        frame[lame..lame + 9].copy_from_slice(b"LAME3.100");
+        frame[lame + 21] = (delay >> 4) as u8;
+        frame[lame + 22] = ((delay & 0x0F) << 4 | padding >> 8) as u8;
+        frame[lame + 23] = padding as u8;
+        frame
+    }
+
+    /// Packs `(value, bits)` fields MSB first.
+    fn pack(fields: &[(u32, u32)], len: usize) -> Vec<u8> {
+        let mut out = vec![0u8; len];
+        let mut pos = 0;
+        for &(value, bits) in fields {
+            for i in (0..bits).rev() {
+                if value >> i & 1 != 0 {
+                    out[pos / 8] |= 0x80 >> (pos % 8);
+                }
+                pos += 1;
+            }
+        }
+        out
+    }
+
+    /// Builds a frame whose first granule holds one spectral line in the
+    /// mid channel, coded as the pair (1, 0) with Huffman table 1.
+    fn tone_frame() -> Vec<u8> {
+        let granule = |part2_3: u32, big_values: u32, gain: u32, table: u32| {
+            [
+                (part2_3, 12),
+                (big_values, 9),
+                (gain, 8),
+                (0, 4), // scalefac_compress
+                (0, 1), // window switching
+                (table, 5),
+                (0, 10), // table_select of regions 1 and 2
+                (0, 7),  // region counts
+                (0, 3),  // preflag, scalefac_scale, count1 table
+            ]
+        };
+        let mut fields = vec![(0, 9), (0, 3), (0, 8)];
+        fields.extend(granule(3, 1, 200, 1));
+        for _ in 0..3 {
+            fields.extend(granule(0, 0, 0, 0));
+        }
+        let mut frame = HEADER_128K_JOINT.to_vec();
+        frame.extend(pack(&fields, 32));
+        // Code "01" and a positive sign.
+        frame.push(0x40);
+        frame.resize(417, 0);
+        frame
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header = FrameHeader::parse(&HEADER_128K_JOINT).unwrap();
+        assert_eq!(header.version, MpegVersion::Mpeg1);
+        assert_eq!((header.layer, header.crc), (3, false));
+        assert_eq!((header.bitrate, header.sample_rate), (128_000, 44100));
+        assert_eq!(header.channel_mode, ChannelMode::JointStereo);
+        assert!(header.is_ms_stereo() && !header.is_intensity_stereo());
+        assert_eq!((header.frame_len, header.samples_per_frame()), (417, 1152));
+
+        // MPEG-2, 64 kbit/s, 22.05 kHz, padded, mono.
+        let header = FrameHeader::parse(&[0xFF, 0xF3, 0x82, 0xC0]).unwrap();
+        assert_eq!(header.version, MpegVersion::Mpeg2);
+        assert_eq!((header.sample_rate, header.channels()), (22050, 1));
+        assert_eq!((header.frame_len, header.samples_per_frame()), (209, 576));
+        assert_eq!(header.side_info_len(), 9);
+
+        assert!(FrameHeader::parse(&[0xFF, 0xFB, 0x00, 0x64]).is_err());
+        assert!(FrameHeader::parse(&[0xFF, 0xF9, 0x90, 0x64]).is_err());
+        assert!(FrameHeader::parse(&[0xFF, 0x7B, 0x90, 0x64]).is_err());
+        assert!(FrameHeader::parse(&[0xFF, 0xFB]).is_err());
+    }
+
+    #[test]
+    fn test_id3v2_len() {
+        assert_eq!(
+            id3v2_len(b"ID3\x04\x00\x00\x00\x00\x02\x01"),
+            Some(10 + 257)
+        );
+        // A footer adds ten bytes.
+        assert_eq!(id3v2_len(b"ID3\x04\x00\x10\x00\x00\x00\x05"), Some(25));
+        assert_eq!(id3v2_len(b"ID3\x04\x00\x00\x00\x80\x00\x00"), None);
+        assert_eq!(id3v2_len(b"ID3\x04"), None);
+        assert_eq!(id3v2_len(&HEADER_128K_JOINT), None);
+    }
+
+    #[test]
+    fn test_parse_xing_header() {
+        let xing = XingHeader::parse(&info_frame(100, 576, 1234)).unwrap();
+        assert_eq!(xing.frames, Some(100));
+        assert!(xing.bytes.is_none() && xing.toc.is_none());
+        assert_eq!(xing.encoder_delay, Some(576));
+        assert_eq!(xing.encoder_padding, Some(1234));
+        assert_eq!(xing.start_padding(), 576 + 529);
+        assert_eq!(xing.sample_count(1152), Some(100 * 1152 - 576 - 1234));
+
+        // Without a LAME tag there is nothing to trim.
+        let mut frame = info_frame(100, 576, 1234);
+        frame[48..52].copy_from_slice(b"none");
+        let xing = XingHeader::parse(&frame).unwrap();
+        assert_eq!(xing.encoder_delay, None);
+        assert_eq!(xing.start_padding(), 0);
+        assert_eq!(xing.sample_count(1152), Some(115_200));
+
+        assert!(XingHeader::parse(&silent_frame()).is_none());
+    }
+
+    #[test]
+    fn test_decode_silence() {
+        let mut decoder = Mp3Decoder::new();
+        let mut out = Vec::new();
+        let header = decoder.decode_frame(&silent_frame(), &mut out).unwrap();
+        assert_eq!(header.channels(), 2);
+        assert_eq!(out.len(), 1152 * 2);
+        assert!(out.iter().all(|&s| s == 0.0));
+
+        assert!(decoder
+            .decode_frame(&silent_frame()[..200], &mut out)
+            .is_err());
+    }
+
+    #[test]
+    fn test_decode_mid_channel() {
+        let mut decoder = Mp3Decoder::new();
+        let mut out = Vec::new();
+        decoder.decode_frame(&tone_frame(), &mut out).unwrap();
+        decoder.decode_frame(&silent_frame(), &mut out).unwrap();
+        assert_eq!(out.len(), 2 * 1152 * 2);
+
+        // With a silent side channel both outputs are equal.
+        let (left, right): (Vec<f32>, Vec<f32>) =
+            out.chunks(2).map(|pair| (pair[0], pair[1])).unzip();
+        assert_eq!(left, right);
+        let peak = left.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak > 1e-4 && peak < 1.0, "peak {peak}");
+    }
+
+    #[test]
+    fn test_reservoir_underflow() {
+        // The first frame after a seek may point back into a previous frame.
+        let mut frame = silent_frame();
+        frame[4] = 0x80;
+        let mut decoder = Mp3Decoder::new();
+        let mut out = Vec::new();
+        decoder.decode_frame(&frame, &mut out).unwrap();
+        assert!(out.is_empty());
+        decoder.decode_frame(&frame, &mut out).unwrap();
+        assert_eq!(out.len(), 1152 * 2);
+    }
+}
@@ -0,0 +1,537 @@
+//! Constant tables for Layer III decoding (ISO/IEC 11172-3 annex B and
+//! ISO/IEC 13818-3).
+
+/// A Huffman code table for big values: codes for the pairs `(x, y)` with
+/// `x, y < size`, in row-major order.
+#[derive(Debug)]
+pub(super) struct BigValueTable {
+    /// Number of values per coordinate.
+    pub size: usize,
+    /// Number of linbits extending the value 15.
+    pub linbits: u32,
+    /// `(length, code)` pairs.
+    pub codes: &'static [(u8, u32)],
+}
+
+#[rustfmt::skip]
+const TABLE_1: [(u8, u32); 4] = [
+    (1, 0x1), (3, 0x1), (2, 0x1), (3, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_2: [(u8, u32); 9] = [
+    (1, 0x1), (3, 0x2), (6, 0x1), (3, 0x3), (3, 0x1), (5, 0x1), (5, 0x3), (5, 0x2),
+    (6, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_3: [(u8, u32); 9] = [
+    (2, 0x3), (2, 0x2), (6, 0x1), (3, 0x1), (2, 0x1), (5, 0x1), (5, 0x3), (5, 0x2),
+    (6, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_5: [(u8, u32); 16] = [
+    (1, 0x1), (3, 0x2), (6, 0x6), (7, 0x5), (3, 0x3), (3, 0x1), (6, 0x4), (7, 0x4),
+    (6, 0x7), (6, 0x5), (7, 0x7), (8, 0x1), (7, 0x6), (6, 0x1), (7, 0x1), (8, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_6: [(u8, u32); 16] = [
+    (3, 0x7), (3, 0x3), (5, 0x5), (7, 0x1), (3, 0x6), (2, 0x2), (4, 0x3), (5, 0x2),
+    (4, 0x5), (4, 0x4), (5, 0x4), (6, 0x1), (6, 0x3), (5, 0x3), (6, 0x2), (7, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_7: [(u8, u32); 36] = [
+    (1, 0x1), (3, 0x2), (6, 0xa), (8, 0x13), (8, 0x10), (9, 0xa), (3, 0x3), (4, 0x3),
+    (6, 0x7), (7, 0xa), (7, 0x5), (8, 0x3), (6, 0xb), (5, 0x4), (7, 0xd), (8, 0x11),
+    (8, 0x8), (9, 0x4), (7, 0xc), (7, 0xb), (8, 0x12), (9, 0xf), (9, 0xb), (9, 0x2),
+    (7, 0x7), (7, 0x6), (8, 0x9), (9, 0xe), (9, 0x3), (10, 0x1), (8, 0x6), (8, 0x4),
+    (9, 0x5), (10, 0x3), (10, 0x2), (10, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_8: [(u8, u32); 36] = [
+    (2, 0x3), (3, 0x4), (6, 0x6), (8, 0x12), (8, 0xc), (9, 0x5), (3, 0x5), (2, 0x1),
+    (4, 0x2), (8, 0x10), (8, 0x9), (8, 0x3), (6, 0x7), (4, 0x3), (6, 0x5), (8, 0xe),
+    (8, 0x7), (9, 0x3), (8, 0x13), (8, 0x11), (8, 0xf), (9, 0xd), (9, 0xa), (10, 0x4),
+    (8, 0xd), (7, 0x5), (8, 0x8), (9, 0xb), (10, 0x5), (10, 0x1), (9, 0xc), (8, 0x4),
+    (9, 0x4), (9, 0x1), (11, 0x1), (11, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_9: [(u8, u32); 36] = [
+    (3, 0x7), (3, 0x5), (5, 0x9), (6, 0xe), (8, 0xf), (9, 0x7), (3, 0x6), (3, 0x4),
+    (4, 0x5), (5, 0x5), (6, 0x6), (8, 0x7), (4, 0x7), (4, 0x6), (5, 0x8), (6, 0x8),
+    (7, 0x8), (8, 0x5), (6, 0xf), (5, 0x6), (6, 0x9), (7, 0xa), (7, 0x5), (8, 0x1),
+    (7, 0xb), (6, 0x7), (7, 0x9), (7, 0x6), (8, 0x4), (9, 0x1), (8, 0xe), (7, 0x4),
+    (8, 0x6), (8, 0x2), (9, 0x6), (9, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_10: [(u8, u32); 64] = [
+    (1, 0x1), (3, 0x2), (6, 0xa), (8, 0x17), (9, 0x23), (9, 0x1e), (9, 0xc), (10, 0x11),
+    (3, 0x3), (4, 0x3), (6, 0x8), (7, 0xc), (8, 0x12), (9, 0x15), (8, 0xc), (8, 0x7),
+    (6, 0xb), (6, 0x9), (7, 0xf), (8, 0x15), (9, 0x20), (10, 0x28), (9, 0x13), (9, 0x6),
+    (7, 0xe), (7, 0xd), (8, 0x16), (9, 0x22), (10, 0x2e), (10, 0x17), (9, 0x12), (10, 0x7),
+    (8, 0x14), (8, 0x13), (9, 0x21), (10, 0x2f), (10, 0x1b), (10, 0x16), (10, 0x9), (10, 0x3),
+    (9, 0x1f), (9, 0x16), (10, 0x29), (10, 0x1a), (11, 0x15), (11, 0x14), (10, 0x5), (11, 0x3),
+    (8, 0xe), (8, 0xd), (9, 0xa), (10, 0xb), (10, 0x10), (10, 0x6), (11, 0x5), (11, 0x1),
+    (9, 0x9), (8, 0x8), (9, 0x7), (10, 0x8), (10, 0x4), (11, 0x4), (11, 0x2), (11, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_11: [(u8, u32); 64] = [
+    (2, 0x3), (3, 0x4), (5, 0xa), (7, 0x18), (8, 0x22), (9, 0x21), (8, 0x15), (9, 0xf),
+    (3, 0x5), (3, 0x3), (4, 0x4), (6, 0xa), (8, 0x20), (8, 0x11), (7, 0xb), (8, 0xa),
+    (5, 0xb), (5, 0x7), (6, 0xd), (7, 0x12), (8, 0x1e), (9, 0x1f), (8, 0x14), (8, 0x5),
+    (7, 0x19), (6, 0xb), (7, 0x13), (9, 0x3b), (8, 0x1b), (10, 0x12), (8, 0xc), (9, 0x5),
+    (8, 0x23), (8, 0x21), (8, 0x1f), (9, 0x3a), (9, 0x1e), (10, 0x10), (9, 0x7), (10, 0x5),
+    (8, 0x1c), (8, 0x1a), (9, 0x20), (10, 0x13), (10, 0x11), (11, 0xf), (10, 0x8), (11, 0xe),
+    (8, 0xe), (7, 0xc), (7, 0x9), (8, 0xd), (9, 0xe), (10, 0x9), (10, 0x4), (10, 0x1),
+    (8, 0xb), (7, 0x4), (8, 0x6), (9, 0x6), (10, 0x6), (10, 0x3), (10, 0x2), (10, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_12: [(u8, u32); 64] = [
+    (4, 0x9), (3, 0x6), (5, 0x10), (7, 0x21), (8, 0x29), (9, 0x27), (9, 0x26), (9, 0x1a),
+    (3, 0x7), (3, 0x5), (4, 0x6), (5, 0x9), (7, 0x17), (7, 0x10), (8, 0x1a), (8, 0xb),
+    (5, 0x11), (4, 0x7), (5, 0xb), (6, 0xe), (7, 0x15), (8, 0x1e), (7, 0xa), (8, 0x7),
+    (6, 0x11), (5, 0xa), (6, 0xf), (6, 0xc), (7, 0x12), (8, 0x1c), (8, 0xe), (8, 0x5),
+    (7, 0x20), (6, 0xd), (7, 0x16), (7, 0x13), (8, 0x12), (8, 0x10), (8, 0x9), (9, 0x5),
+    (8, 0x28), (7, 0x11), (8, 0x1f), (8, 0x1d), (8, 0x11), (9, 0xd), (8, 0x4), (9, 0x2),
+    (8, 0x1b), (7, 0xc), (7, 0xb), (8, 0xf), (8, 0xa), (9, 0x7), (9, 0x4), (10, 0x1),
+    (9, 0x1b), (8, 0xc), (8, 0x8), (9, 0xc), (9, 0x6), (9, 0x3), (9, 0x1), (10, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_13: [(u8, u32); 256] = [
+    (1, 0x1), (4, 0x5), (6, 0xe), (7, 0x15), (8, 0x22), (9, 0x33), (9, 0x2e), (10, 0x47),
+    (9, 0x2a), (10, 0x34), (11, 0x44), (11, 0x34), (12, 0x43), (12, 0x2c), (13, 0x2b), (13, 0x13),
+    (3, 0x3), (4, 0x4), (6, 0xc), (7, 0x13), (8, 0x1f), (8, 0x1a), (9, 0x2c), (9, 0x21),
+    (9, 0x1f), (9, 0x18), (10, 0x20), (10, 0x18), (11, 0x1f), (12, 0x23), (12, 0x16), (12, 0xe),
+    (6, 0xf), (6, 0xd), (7, 0x17), (8, 0x24), (9, 0x3b), (9, 0x31), (10, 0x4d), (10, 0x41),
+    (9, 0x1d), (10, 0x28), (10, 0x1e), (11, 0x28), (11, 0x1b), (12, 0x21), (13, 0x2a), (13, 0x10),
+    (7, 0x16), (7, 0x14), (8, 0x25), (9, 0x3d), (9, 0x38), (10, 0x4f), (10, 0x49), (10, 0x40),
+    (10, 0x2b), (11, 0x4c), (11, 0x38), (11, 0x25), (11, 0x1a), (12, 0x1f), (13, 0x19), (13, 0xe),
+    (8, 0x23), (7, 0x10), (9, 0x3c), (9, 0x39), (10, 0x61), (10, 0x4b), (11, 0x72), (11, 0x5b),
+    (10, 0x36), (11, 0x49), (11, 0x37), (12, 0x29), (12, 0x30), (13, 0x35), (13, 0x17), (14, 0x18),
+    (9, 0x3a), (8, 0x1b), (9, 0x32), (10, 0x60), (10, 0x4c), (10, 0x46), (11, 0x5d), (11, 0x54),
+    (11, 0x4d), (11, 0x3a), (12, 0x4f), (11, 0x1d), (13, 0x4a), (13, 0x31), (14, 0x29), (14, 0x11),
+    (9, 0x2f), (9, 0x2d), (10, 0x4e), (10, 0x4a), (11, 0x73), (11, 0x5e), (11, 0x5a), (11, 0x4f),
+    (11, 0x45), (12, 0x53), (12, 0x47), (12, 0x32), (13, 0x3b), (13, 0x26), (14, 0x24), (14, 0xf),
+    (10, 0x48), (9, 0x22), (10, 0x38), (11, 0x5f), (11, 0x5c), (11, 0x55), (12, 0x5b), (12, 0x5a),
+    (12, 0x56), (12, 0x49), (13, 0x4d), (13, 0x41), (13, 0x33), (14, 0x2c), (16, 0x2b), (16, 0x2a),
+    (9, 0x2b), (8, 0x14), (9, 0x1e), (10, 0x2c), (10, 0x37), (11, 0x4e), (11, 0x48), (12, 0x57),
+    (12, 0x4e), (12, 0x3d), (12, 0x2e), (13, 0x36), (13, 0x25), (14, 0x1e), (15, 0x14), (15, 0x10),
+    (10, 0x35), (9, 0x19), (10, 0x29), (10, 0x25), (11, 0x2c), (11, 0x3b), (11, 0x36), (13, 0x51),
+    (12, 0x42), (13, 0x4c), (13, 0x39), (14, 0x36), (14, 0x25), (14, 0x12), (16, 0x27), (15, 0xb),
+    (10, 0x23), (10, 0x21), (10, 0x1f), (11, 0x39), (11, 0x2a), (12, 0x52), (12, 0x48), (13, 0x50),
+    (12, 0x2f), (13, 0x3a), (14, 0x37), (13, 0x15), (14, 0x16), (15, 0x1a), (16, 0x26), (17, 0x16),
+    (11, 0x35), (10, 0x19), (10, 0x17), (11, 0x26), (12, 0x46), (12, 0x3c), (12, 0x33), (12, 0x24),
+    (13, 0x37), (13, 0x1a), (13, 0x22), (14, 0x17), (15, 0x1b), (15, 0xe), (15, 0x9), (16, 0x7),
+    (11, 0x22), (11, 0x20), (11, 0x1c), (12, 0x27), (12, 0x31), (13, 0x4b), (12, 0x1e), (13, 0x34),
+    (14, 0x30), (14, 0x28), (15, 0x34), (15, 0x1c), (15, 0x12), (16, 0x11), (16, 0x9), (16, 0x5),
+    (12, 0x2d), (11, 0x15), (12, 0x22), (13, 0x40), (13, 0x38), (13, 0x32), (14, 0x31), (14, 0x2d),
+    (14, 0x1f), (14, 0x13), (14, 0xc), (15, 0xf), (16, 0xa), (15, 0x7), (16, 0x6), (16, 0x3),
+    (13, 0x30), (12, 0x17), (12, 0x14), (13, 0x27), (13, 0x24), (13, 0x23), (15, 0x35), (14, 0x15),
+    (14, 0x10), (17, 0x17), (15, 0xd), (15, 0xa), (15, 0x6), (17, 0x1), (16, 0x4), (16, 0x2),
+    (12, 0x10), (12, 0xf), (13, 0x11), (14, 0x1b), (14, 0x19), (14, 0x14), (15, 0x1d), (14, 0xb),
+    (15, 0x11), (15, 0xc), (16, 0x10), (16, 0x8), (19, 0x1), (18, 0x1), (19, 0x0), (16, 0x1),
+];
+
+#[rustfmt::skip]
+const TABLE_15: [(u8, u32); 256] = [
+    (3, 0x7), (4, 0xc), (5, 0x12), (7, 0x35), (7, 0x2f), (8, 0x4c), (9, 0x7c), (9, 0x6c),
+    (9, 0x59), (10, 0x7b), (10, 0x6c), (11, 0x77), (11, 0x6b), (11, 0x51), (12, 0x7a), (13, 0x3f),
+    (4, 0xd), (3, 0x5), (5, 0x10), (6, 0x1b), (7, 0x2e), (7, 0x24), (8, 0x3d), (8, 0x33),
+    (8, 0x2a), (9, 0x46), (9, 0x34), (10, 0x53), (10, 0x41), (10, 0x29), (11, 0x3b), (11, 0x24),
+    (5, 0x13), (5, 0x11), (5, 0xf), (6, 0x18), (7, 0x29), (7, 0x22), (8, 0x3b), (8, 0x30),
+    (8, 0x28), (9, 0x40), (9, 0x32), (10, 0x4e), (10, 0x3e), (11, 0x50), (11, 0x38), (11, 0x21),
+    (6, 0x1d), (6, 0x1c), (6, 0x19), (7, 0x2b), (7, 0x27), (8, 0x3f), (8, 0x37), (9, 0x5d),
+    (9, 0x4c), (9, 0x3b), (10, 0x5d), (10, 0x48), (10, 0x36), (11, 0x4b), (11, 0x32), (11, 0x1d),
+    (7, 0x34), (6, 0x16), (7, 0x2a), (7, 0x28), (8, 0x43), (8, 0x39), (9, 0x5f), (9, 0x4f),
+    (9, 0x48), (9, 0x39), (10, 0x59), (10, 0x45), (10, 0x31), (11, 0x42), (11, 0x2e), (11, 0x1b),
+    (8, 0x4d), (7, 0x25), (7, 0x23), (8, 0x42), (8, 0x3a), (8, 0x34), (9, 0x5b), (9, 0x4a),
+    (9, 0x3e), (9, 0x30), (10, 0x4f), (10, 0x3f), (11, 0x5a), (11, 0x3e), (11, 0x28), (12, 0x26),
+    (9, 0x7d), (7, 0x20), (8, 0x3c), (8, 0x38), (8, 0x32), (9, 0x5c), (9, 0x4e), (9, 0x41),
+    (9, 0x37), (10, 0x57), (10, 0x47), (10, 0x33), (11, 0x49), (11, 0x33), (12, 0x46), (12, 0x1e),
+    (9, 0x6d), (8, 0x35), (8, 0x31), (9, 0x5e), (9, 0x58), (9, 0x4b), (9, 0x42), (10, 0x7a),
+    (10, 0x5b), (10, 0x49), (10, 0x38), (10, 0x2a), (11, 0x40), (11, 0x2c), (11, 0x15), (12, 0x19),
+    (9, 0x5a), (8, 0x2b), (8, 0x29), (9, 0x4d), (9, 0x49), (9, 0x3f), (9, 0x38), (10, 0x5c),
+    (10, 0x4d), (10, 0x42), (10, 0x2f), (11, 0x43), (11, 0x30), (12, 0x35), (12, 0x24), (12, 0x14),
+    (9, 0x47), (8, 0x22), (9, 0x43), (9, 0x3c), (9, 0x3a), (9, 0x31), (10, 0x58), (10, 0x4c),
+    (10, 0x43), (11, 0x6a), (11, 0x47), (11, 0x36), (11, 0x26), (12, 0x27), (12, 0x17), (12, 0xf),
+    (10, 0x6d), (9, 0x35), (9, 0x33), (9, 0x2f), (10, 0x5a), (10, 0x52), (10, 0x3a), (10, 0x39),
+    (10, 0x30), (11, 0x48), (11, 0x39), (11, 0x29), (11, 0x17), (12, 0x1b), (13, 0x3e), (12, 0x9),
+    (10, 0x56), (9, 0x2a), (9, 0x28), (9, 0x25), (10, 0x46), (10, 0x40), (10, 0x34), (10, 0x2b),
+    (11, 0x46), (11, 0x37), (11, 0x2a), (11, 0x19), (12, 0x1d), (12, 0x12), (12, 0xb), (13, 0xb),
+    (11, 0x76), (10, 0x44), (9, 0x1e), (10, 0x37), (10, 0x32), (10, 0x2e), (11, 0x4a), (11, 0x41),
+    (11, 0x31), (11, 0x27), (11, 0x18), (11, 0x10), (12, 0x16), (12, 0xd), (13, 0xe), (13, 0x7),
+    (11, 0x5b), (10, 0x2c), (10, 0x27), (10, 0x26), (10, 0x22), (11, 0x3f), (11, 0x34), (11, 0x2d),
+    (11, 0x1f), (12, 0x34), (12, 0x1c), (12, 0x13), (12, 0xe), (12, 0x8), (13, 0x9), (13, 0x3),
+    (12, 0x7b), (11, 0x3c), (11, 0x3a), (11, 0x35), (11, 0x2f), (11, 0x2b), (11, 0x20), (11, 0x16),
+    (12, 0x25), (12, 0x18), (12, 0x11), (12, 0xc), (13, 0xf), (13, 0xa), (12, 0x2), (13, 0x1),
+    (12, 0x47), (11, 0x25), (11, 0x22), (11, 0x1e), (11, 0x1c), (11, 0x14), (11, 0x11), (12, 0x1a),
+    (12, 0x15), (12, 0x10), (12, 0xa), (12, 0x6), (13, 0x8), (13, 0x6), (13, 0x2), (13, 0x0),
+];
+
+#[rustfmt::skip]
+const TABLE_16: [(u8, u32); 256] = [
+    (1, 0x1), (4, 0x5), (6, 0xe), (8, 0x2c), (9, 0x4a), (9, 0x3f), (10, 0x6e), (10, 0x5d),
+    (11, 0xac), (11, 0x95), (11, 0x8a), (12, 0xf2), (12, 0xe1), (12, 0xc3), (13, 0x178), (9, 0x11),
+    (3, 0x3), (4, 0x4), (6, 0xc), (7, 0x14), (8, 0x23), (9, 0x3e), (9, 0x35), (9, 0x2f),
+    (10, 0x53), (10, 0x4b), (10, 0x44), (11, 0x77), (12, 0xc9), (11, 0x6b), (12, 0xcf), (8, 0x9),
+    (6, 0xf), (6, 0xd), (7, 0x17), (8, 0x26), (9, 0x43), (9, 0x3a), (10, 0x67), (10, 0x5a),
+    (11, 0xa1), (10, 0x48), (11, 0x7f), (11, 0x75), (11, 0x6e), (12, 0xd1), (12, 0xce), (9, 0x10),
+    (8, 0x2d), (7, 0x15), (8, 0x27), (9, 0x45), (9, 0x40), (10, 0x72), (10, 0x63), (10, 0x57),
+    (11, 0x9e), (11, 0x8c), (12, 0xfc), (12, 0xd4), (12, 0xc7), (13, 0x183), (13, 0x16d), (10, 0x1a),
+    (9, 0x4b), (8, 0x24), (9, 0x44), (9, 0x41), (10, 0x73), (10, 0x65), (11, 0xb3), (11, 0xa4),
+    (11, 0x9b), (12, 0x108), (12, 0xf6), (12, 0xe2), (13, 0x18b), (13, 0x17e), (13, 0x16a), (9, 0x9),
+    (9, 0x42), (8, 0x1e), (9, 0x3b), (9, 0x38), (10, 0x66), (11, 0xb9), (11, 0xad), (12, 0x109),
+    (11, 0x8e), (12, 0xfd), (12, 0xe8), (13, 0x190), (13, 0x184), (13, 0x17a), (14, 0x1bd), (10, 0x10),
+    (10, 0x6f), (9, 0x36), (9, 0x34), (10, 0x64), (11, 0xb8), (11, 0xb2), (11, 0xa0), (11, 0x85),
+    (12, 0x101), (12, 0xf4), (12, 0xe4), (12, 0xd9), (13, 0x181), (13, 0x16e), (14, 0x2cb), (10, 0xa),
+    (10, 0x62), (9, 0x30), (10, 0x5b), (10, 0x58), (11, 0xa5), (11, 0x9d), (11, 0x94), (12, 0x105),
+    (12, 0xf8), (13, 0x197), (13, 0x18d), (13, 0x174), (13, 0x17c), (15, 0x379), (15, 0x374), (10, 0x8),
+    (10, 0x55), (10, 0x54), (10, 0x51), (11, 0x9f), (11, 0x9c), (11, 0x8f), (12, 0x104), (12, 0xf9),
+    (13, 0x1ab), (13, 0x191), (13, 0x188), (13, 0x17f), (14, 0x2d7), (14, 0x2c9), (14, 0x2c4), (10, 0x7),
+    (11, 0x9a), (10, 0x4c), (10, 0x49), (11, 0x8d), (11, 0x83), (12, 0x100), (12, 0xf5), (13, 0x1aa),
+    (13, 0x196), (13, 0x18a), (13, 0x180), (14, 0x2df), (13, 0x167), (14, 0x2c6), (13, 0x160), (11, 0xb),
+    (11, 0x8b), (11, 0x81), (10, 0x43), (11, 0x7d), (12, 0xf7), (12, 0xe9), (12, 0xe5), (12, 0xdb),
+    (13, 0x189), (14, 0x2e7), (14, 0x2e1), (14, 0x2d0), (15, 0x375), (15, 0x372), (14, 0x1b7), (10, 0x4),
+    (12, 0xf3), (11, 0x78), (11, 0x76), (11, 0x73), (12, 0xe3), (12, 0xdf), (13, 0x18c), (14, 0x2ea),
+    (14, 0x2e6), (14, 0x2e0), (14, 0x2d1), (14, 0x2c8), (14, 0x2c2), (13, 0xdf), (14, 0x1b4), (11, 0x6),
+    (12, 0xca), (12, 0xe0), (12, 0xde), (12, 0xda), (12, 0xd8), (13, 0x185), (13, 0x182), (13, 0x17d),
+    (13, 0x16c), (15, 0x378), (14, 0x1bb), (14, 0x2c3), (14, 0x1b8), (14, 0x1b5), (16, 0x6c0), (11, 0x4),
+    (14, 0x2eb), (12, 0xd3), (12, 0xd2), (12, 0xd0), (13, 0x172), (13, 0x17b), (14, 0x2de), (14, 0x2d3),
+    (14, 0x2ca), (16, 0x6c7), (15, 0x373), (15, 0x36d), (15, 0x36c), (17, 0xd83), (15, 0x361), (11, 0x2),
+    (13, 0x179), (13, 0x171), (11, 0x66), (12, 0xbb), (14, 0x2d6), (14, 0x2d2), (13, 0x166), (14, 0x2c7),
+    (14, 0x2c5), (15, 0x362), (16, 0x6c6), (15, 0x367), (17, 0xd82), (15, 0x366), (14, 0x1b2), (11, 0x0),
+    (9, 0xc), (8, 0xa), (8, 0x7), (9, 0xb), (9, 0xa), (10, 0x11), (10, 0xb), (10, 0x9),
+    (11, 0xd), (11, 0xc), (11, 0xa), (11, 0x7), (11, 0x5), (11, 0x3), (11, 0x1), (8, 0x3),
+];
+
+#[rustfmt::skip]
+const TABLE_24: [(u8, u32); 256] = [
+    (4, 0xf), (4, 0xd), (6, 0x2e), (7, 0x50), (8, 0x92), (9, 0x106), (9, 0xf8), (10, 0x1b2),
+    (10, 0x1aa), (11, 0x29d), (11, 0x28d), (11, 0x289), (11, 0x26d), (11, 0x205), (12, 0x408), (9, 0x58),
+    (4, 0xe), (4, 0xc), (5, 0x15), (6, 0x26), (7, 0x47), (8, 0x82), (8, 0x7a), (9, 0xd8),
+    (9, 0xd1), (9, 0xc6), (10, 0x147), (10, 0x159), (10, 0x13f), (10, 0x129), (10, 0x117), (8, 0x2a),
+    (6, 0x2f), (5, 0x16), (6, 0x29), (7, 0x4a), (7, 0x44), (8, 0x80), (8, 0x78), (9, 0xdd),
+    (9, 0xcf), (9, 0xc2), (9, 0xb6), (10, 0x154), (10, 0x13b), (10, 0x127), (11, 0x21d), (7, 0x12),
+    (7, 0x51), (6, 0x27), (7, 0x4b), (7, 0x46), (8, 0x86), (8, 0x7d), (8, 0x74), (9, 0xdc),
+    (9, 0xcc), (9, 0xbe), (9, 0xb2), (10, 0x145), (10, 0x137), (10, 0x125), (10, 0x10f), (7, 0x10),
+    (8, 0x93), (7, 0x48), (7, 0x45), (8, 0x87), (8, 0x7f), (8, 0x76), (8, 0x70), (9, 0xd2),
+    (9, 0xc8), (9, 0xbc), (10, 0x160), (10, 0x143), (10, 0x132), (10, 0x11d), (11, 0x21c), (7, 0xe),
+    (9, 0x107), (7, 0x42), (8, 0x81), (8, 0x7e), (8, 0x77), (8, 0x72), (9, 0xd6), (9, 0xca),
+    (9, 0xc0), (9, 0xb4), (10, 0x155), (10, 0x13d), (10, 0x12d), (10, 0x119), (10, 0x106), (7, 0xc),
+    (9, 0xf9), (8, 0x7b), (8, 0x79), (8, 0x75), (8, 0x71), (9, 0xd7), (9, 0xce), (9, 0xc3),
+    (9, 0xb9), (10, 0x15b), (10, 0x14a), (10, 0x134), (10, 0x123), (10, 0x110), (11, 0x208), (7, 0xa),
+    (10, 0x1b3), (8, 0x73), (8, 0x6f), (8, 0x6d), (9, 0xd3), (9, 0xcb), (9, 0xc4), (9, 0xbb),
+    (10, 0x161), (10, 0x14c), (10, 0x139), (10, 0x12a), (10, 0x11b), (11, 0x213), (11, 0x17d), (8, 0x11),
+    (10, 0x1ab), (9, 0xd4), (9, 0xd0), (9, 0xcd), (9, 0xc9), (9, 0xc1), (9, 0xba), (9, 0xb1),
+    (9, 0xa9), (10, 0x140), (10, 0x12f), (10, 0x11e), (10, 0x10c), (11, 0x202), (11, 0x179), (8, 0x10),
+    (10, 0x14f), (9, 0xc7), (9, 0xc5), (9, 0xbf), (9, 0xbd), (9, 0xb5), (9, 0xae), (10, 0x14d),
+    (10, 0x141), (10, 0x131), (10, 0x121), (10, 0x113), (11, 0x209), (11, 0x17b), (11, 0x173), (8, 0xb),
+    (11, 0x29c), (9, 0xb8), (9, 0xb7), (9, 0xb3), (9, 0xaf), (10, 0x158), (10, 0x14b), (10, 0x13a),
+    (10, 0x130), (10, 0x122), (10, 0x115), (11, 0x212), (11, 0x17f), (11, 0x175), (11, 0x16e), (8, 0xa),
+    (11, 0x28c), (10, 0x15a), (9, 0xab), (9, 0xa8), (9, 0xa4), (10, 0x13e), (10, 0x135), (10, 0x12b),
+    (10, 0x11f), (10, 0x114), (10, 0x107), (11, 0x201), (11, 0x177), (11, 0x170), (11, 0x16a), (8, 0x6),
+    (11, 0x288), (10, 0x142), (10, 0x13c), (10, 0x138), (10, 0x133), (10, 0x12e), (10, 0x124), (10, 0x11c),
+    (10, 0x10d), (10, 0x105), (11, 0x200), (11, 0x178), (11, 0x172), (11, 0x16c), (11, 0x167), (8, 0x4),
+    (11, 0x26c), (10, 0x12c), (10, 0x128), (10, 0x126), (10, 0x120), (10, 0x11a), (10, 0x111), (10, 0x10a),
+    (11, 0x203), (11, 0x17c), (11, 0x176), (11, 0x171), (11, 0x16d), (11, 0x169), (11, 0x165), (8, 0x2),
+    (12, 0x409), (10, 0x118), (10, 0x116), (10, 0x112), (10, 0x10b), (10, 0x108), (10, 0x103), (11, 0x17e),
+    (11, 0x17a), (11, 0x174), (11, 0x16f), (11, 0x16b), (11, 0x168), (11, 0x166), (11, 0x164), (8, 0x0),
+    (8, 0x2b), (7, 0x14), (7, 0x13), (7, 0x11), (7, 0xf), (7, 0xd), (7, 0xb), (7, 0x9),
+    (7, 0x7), (7, 0x6), (7, 0x4), (8, 0x7), (8, 0x5), (8, 0x3), (8, 0x1), (4, 0x3),
+];
+
+#[rustfmt::skip]
+const COUNT1_TABLE_A: [(u8, u32); 16] = [
+    (1, 0x1), (4, 0x5), (4, 0x4), (5, 0x5), (4, 0x6), (6, 0x5), (5, 0x4), (6, 0x4),
+    (4, 0x7), (5, 0x3), (5, 0x6), (6, 0x0), (5, 0x7), (6, 0x2), (6, 0x3), (6, 0x1),
+];
+
+#[rustfmt::skip]
+const COUNT1_TABLE_B: [(u8, u32); 16] = [
+    (4, 0xf), (4, 0xe), (4, 0xd), (4, 0xc), (4, 0xb), (4, 0xa), (4, 0x9), (4, 0x8),
+    (4, 0x7), (4, 0x6), (4, 0x5), (4, 0x4), (4, 0x3), (4, 0x2), (4, 0x1), (4, 0x0),
+];
+
+/// Big value tables indexed by `table_select`. Tables 4 and 14 are unused.
+pub(super) const BIG_VALUE_TABLES: [BigValueTable; 32] = [
+    BigValueTable {
+        size: 0,
+        linbits: 0,
+        codes: &[],
+    },
+    BigValueTable {
+        size: 2,
+        linbits: 0,
+        codes: &TABLE_1,
+    },
+    BigValueTable {
+        size: 3,
+        linbits: 0,
+        codes: &TABLE_2,
+    },
+    BigValueTable {
+        size: 3,
+        linbits: 0,
+        codes: &TABLE_3,
+    },
+    BigValueTable {
+        size: 0,
+        linbits: 0,
+        codes: &[],
+    },
+    BigValueTable {
+        size: 4,
+        linbits: 0,
+        codes: &TABLE_5,
+    },
+    BigValueTable {
+        size: 4,
+        linbits: 0,
+        codes: &TABLE_6,
+    },
+    BigValueTable {
+        size: 6,
+        linbits: 0,
+        codes: &TABLE_7,
+    },
+    BigValueTable {
+        size: 6,
+        linbits: 0,
+        codes: &TABLE_8,
+    },
+    BigValueTable {
+        size: 6,
+        linbits: 0,
+        codes: &TABLE_9,
+    },
+    BigValueTable {
+        size: 8,
+        linbits: 0,
+        codes: &TABLE_10,
+    },
+    BigValueTable {
+        size: 8,
+        linbits: 0,
+        codes: &TABLE_11,
+    },
+    BigValueTable {
+        size: 8,
+        linbits: 0,
+        codes: &TABLE_12,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 0,
+        codes: &TABLE_13,
+    },
+    BigValueTable {
+        size: 0,
+        linbits: 0,
+        codes: &[],
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 0,
+        codes: &TABLE_15,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 1,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 2,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 3,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 4,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 6,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 8,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 10,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 13,
+        codes: &TABLE_16,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 4,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 5,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 6,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 7,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 8,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 9,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 11,
+        codes: &TABLE_24,
+    },
+    BigValueTable {
+        size: 16,
+        linbits: 13,
+        codes: &TABLE_24,
+    },
+];
+
+/// Count1 tables A and B for quadruples `(v, w, x, y)`, indexed by
+/// `8v + 4w + 2x + y`.
+pub(super) const COUNT1_TABLES: [&[(u8, u32)]; 2] = [&COUNT1_TABLE_A, &COUNT1_TABLE_B];
+
+/// First half of the synthesis window `D[i]` (table B.3) in units of
+/// 2^-16. The second half mirrors it: `D[512 - i]` is `D[i]` for multiples
+/// of 64 and `-D[i]` otherwise.
+#[rustfmt::skip]
+pub(super) const SYNTHESIS_WINDOW: [i32; 257] = [
+    0, -1, -1, -1, -1, -1, -1, -2, -2, -2, -2, -3,
+    -3, -4, -4, -5, -5, -6, -7, -7, -8, -9, -10, -11,
+    -13, -14, -16, -17, -19, -21, -24, -26, -29, -31, -35, -38,
+    -41, -45, -49, -53, -58, -63, -68, -73, -79, -85, -91, -97,
+    -104, -111, -117, -125, -132, -139, -147, -154, -161, -169, -176, -183,
+    -190, -196, -202, -208, 213, 218, 222, 225, 227, 228, 228, 227,
+    224, 221, 215, 208, 200, 189, 177, 163, 146, 127, 106, 83,
+    57, 29, -2, -36, -72, -111, -153, -197, -244, -294, -347, -401,
+    -459, -519, -581, -645, -711, -779, -848, -919, -991, -1064, -1137, -1210,
+    -1283, -1356, -1428, -1498, -1567, -1634, -1698, -1759, -1817, -1870, -1919, -1962,
+    -2001, -2032, -2057, -2075, -2085, -2087, -2080, -2063, 2037, 2000, 1952, 1893,
+    1822, 1739, 1644, 1535, 1414, 1280, 1131, 970, 794, 605, 402, 185,
+    -45, -288, -545, -814, -1095, -1388, -1692, -2006, -2330, -2663, -3004, -3351,
+    -3705, -4063, -4425, -4788, -5153, -5517, -5879, -6237, -6589, -6935, -7271, -7597,
+    -7910, -8209, -8491, -8755, -8998, -9219, -9416, -9585, -9727, -9838, -9916, -9959,
+    -9966, -9935, -9863, -9750, -9592, -9389, -9139, -8840, -8492, -8092, -7640, -7134,
+    6574, 5959, 5288, 4561, 3776, 2935, 2037, 1082, 70, -998, -2122, -3300,
+    -4533, -5818, -7154, -8540, -9975, -11455, -12980, -14548, -16155, -17799, -19478, -21189,
+    -22929, -24694, -26482, -28289, -30112, -31947, -33791, -35640, -37489, -39336, -41176, -43006,
+    -44821, -46617, -48390, -50137, -51853, -53534, -55178, -56778, -58333, -59838, -61289, -62684,
+    -64019, -65290, -66494, -67629, -68692, -69679, -70590, -71420, -72169, -72835, -73415, -73908,
+    -74313, -74630, -74856, -74992, 75038,
+];
+/// Widths of the 22 scale factor bands of long blocks, indexed by sample
+/// rate: 44.1, 48 and 32 kHz (MPEG-1), then their halves (MPEG-2) and
+/// quarters (MPEG-2.5).
+#[rustfmt::skip]
+pub(super) const LONG_BANDS: [[u8; 22]; 9] = [
+    // 44.1 kHz
+    [4, 4, 4, 4, 4, 4, 6, 6, 8, 8, 10, 12, 16, 20, 24, 28, 34, 42, 50, 54, 76, 158],
+    // 48 kHz
+    [4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 10, 12, 16, 18, 22, 28, 34, 40, 46, 54, 54, 192],
+    // 32 kHz
+    [4, 4, 4, 4, 4, 4, 6, 6, 8, 10, 12, 16, 20, 24, 30, 38, 46, 56, 68, 84, 102, 26],
+    // 22.05 kHz
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    // 24 kHz
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 18, 22, 26, 32, 38, 46, 54, 62, 70, 76, 36],
+    // 16 kHz
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    // 11.025 kHz
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    // 12 kHz
+    [6, 6, 6, 6, 6, 6, 8, 10, 12, 14, 16, 20, 24, 28, 32, 38, 46, 52, 60, 68, 58, 54],
+    // 8 kHz
+    [12, 12, 12, 12, 12, 12, 16, 20, 24, 28, 32, 40, 48, 56, 64, 76, 90, 2, 2, 2, 2, 2],
+];
+
+/// Widths of the 13 scale factor bands of short blocks, each repeated for
+/// the three windows.
+#[rustfmt::skip]
+pub(super) const SHORT_BANDS: [[u8; 39]; 9] = [
+    // 44.1 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 22, 22, 22, 30, 30, 30, 56, 56, 56],
+    // 48 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 6, 6, 6, 10, 10, 10, 12, 12, 12, 14, 14, 14, 16, 16, 16, 20, 20, 20, 26, 26, 26, 66, 66, 66],
+    // 32 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 26, 26, 26, 34, 34, 34, 42, 42, 42, 12, 12, 12],
+    // 22.05 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 14, 14, 14, 18, 18, 18, 26, 26, 26, 32, 32, 32, 42, 42, 42, 18, 18, 18],
+    // 24 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 32, 32, 32, 44, 44, 44, 12, 12, 12],
+    // 16 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    // 11.025 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    // 12 kHz
+    [4, 4, 4, 4, 4, 4, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    // 8 kHz
+    [8, 8, 8, 8, 8, 8, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 24, 24, 24, 28, 28, 28, 36, 36, 36, 2, 2, 2, 2, 2, 2, 2, 2, 2, 26, 26, 26],
+];
+
+/// Band widths of mixed blocks: long bands covering the first 36 lines,
+/// then short bands as in [`SHORT_BANDS`].
+#[rustfmt::skip]
+pub(super) const MIXED_BANDS: [&[u8]; 9] = [
+    // 44.1 kHz
+    &[4, 4, 4, 4, 4, 4, 6, 6, 4, 4, 4, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 22, 22, 22, 30, 30, 30, 56, 56, 56],
+    // 48 kHz
+    &[4, 4, 4, 4, 4, 4, 6, 6, 4, 4, 4, 6, 6, 6, 6, 6, 6, 10, 10, 10, 12, 12, 12, 14, 14, 14, 16, 16, 16, 20, 20, 20, 26, 26, 26, 66, 66, 66],
+    // 32 kHz
+    &[4, 4, 4, 4, 4, 4, 6, 6, 4, 4, 4, 6, 6, 6, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 26, 26, 26, 34, 34, 34, 42, 42, 42, 12, 12, 12],
+    // 22.05 kHz
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 14, 14, 14, 18, 18, 18, 26, 26, 26, 32, 32, 32, 42, 42, 42, 18, 18, 18],
+    // 24 kHz
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 32, 32, 32, 44, 44, 44, 12, 12, 12],
+    // 16 kHz
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    // 11.025 kHz
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    // 12 kHz
+    &[6, 6, 6, 6, 6, 6, 6, 6, 6, 8, 8, 8, 10, 10, 10, 12, 12, 12, 14, 14, 14, 18, 18, 18, 24, 24, 24, 30, 30, 30, 40, 40, 40, 18, 18, 18],
+    // 8 kHz
+    &[12, 12, 12, 4, 4, 4, 8, 8, 8, 12, 12, 12, 16, 16, 16, 20, 20, 20, 24, 24, 24, 28, 28, 28, 36, 36, 36, 2, 2, 2, 2, 2, 2, 2, 2, 2, 26, 26, 26],
+];
+
+/// Pre-emphasis added to the scale factors of long bands when `preflag`
+/// is set.
+pub(super) const PRETAB: [u8; 22] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 3, 2, 0,
+];
@@ -0,0 +1,170 @@
+//! NAL unit framing shared by H.264 and H.265.
+//!
+//! MP4 and Matroska store NAL units with a big-endian length prefix
+//! ("AVCC" framing, 1 to 4 bytes as declared by the decoder configuration
+//! record), while MPEG-TS and raw elementary streams separate them with
+//! `00 00 01` start codes ("Annex B" framing).
+
+use crate::error::{PlayerError, Result};
+
+/// Four-byte Annex B start code.
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// Splits length-prefixed NAL units.
+///
+/// # Errors
+/// Returns an error if `nal_length_size` is not 1, 2 or 4, or a length
+/// prefix runs past the end of the data.
+pub fn split_avcc(data: &[u8], nal_length_size: u8) -> Result<Vec<&[u8]>> {
+    let size = match nal_length_size {
+        1 | 2 | 4 => nal_length_size as usize,
+        _ => return Err(PlayerError::decoder("Invalid NAL length size")),
+    };
+    let mut units = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let len = data
+            .get(pos..pos + size)
+            .ok_or_else(|| PlayerError::decoder("Truncated NAL length"))?
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | b as usize);
+        pos += size;
+        let unit = pos
+            .checked_add(len)
+            .and_then(|end| data.get(pos..end))
+            .ok_or_else(|| PlayerError::decoder("NAL unit exceeds packet"))?;
+        units.push(unit);
+        pos += len;
+    }
+    Ok(units)
+}
+
+/// Splits an Annex B byte stream at its start codes.
+///
+/// Leading zero bytes and empty units are dropped.
+pub fn split_annex_b(data: &[u8]) -> Vec<&[u8]> {
+    let mut units = Vec::new();
+    let mut start = None;
+    let mut i = 0;
+    while i + 2 < data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            if let Some(s) = start {
+                units.push(trim_trailing_zeros(&data[s..i]));
+            }
+            i += 3;
+            start = Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    if let Some(s) = start {
+        units.push(&data[s..]);
+    }
+    units.retain(|unit| !unit.is_empty());
+    units
+}
+
+/// Drops the zero bytes that precede a four-byte start code.
+fn trim_trailing_zeros(unit: &[u8]) -> &[u8] {
+    let end = unit.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    &unit[..end]
+}
+
+/// Converts length-prefixed NAL units to an Annex B byte stream.
+///
+/// # Errors
+/// Returns an error if the length prefixes are malformed.
+pub fn avcc_to_annex_b(data: &[u8], nal_length_size: u8) -> Result<Vec<u8>> {
+    let units = split_avcc(data, nal_length_size)?;
+    Ok(join_annex_b(&units))
+}
+
+/// Prefixes each NAL unit with a four-byte start code.
+pub fn join_annex_b(units: &[&[u8]]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(units.iter().map(|u| u.len() + 4).sum());
+    for unit in units {
+        out.extend_from_slice(&START_CODE);
+        out.extend_from_slice(unit);
+    }
+    out
+}
+
+/// Converts an Annex B byte stream to length-prefixed NAL units.
+///
+/// # Errors
+/// Returns an error if `nal_length_size` is not 1, 2 or 4, or a NAL unit
+/// is too long for it.
+pub fn annex_b_to_avcc(data: &[u8], nal_length_size: u8) -> Result<Vec<u8>> {
+    if !matches!(nal_length_size, 1 | 2 | 4) {
+        return Err(PlayerError::decoder("Invalid NAL length size"));
+    }
+    let mut out = Vec::with_capacity(data.len());
+    for unit in split_annex_b(data) {
+        if nal_length_size < 4 && unit.len() >= 1 << (8 * nal_length_size) {
+            return Err(PlayerError::decoder("NAL unit too long for length size"));
+        }
+        let len = (unit.len() as u32).to_be_bytes();
+        out.extend_from_slice(&len[4 - nal_length_size as usize..]);
+        out.extend_from_slice(unit);
+    }
+    Ok(out)
+}
+
+/// Removes emulation prevention bytes (`00 00 03`) from a NAL unit payload.
+pub(crate) fn unescape_rbsp(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &b in data {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        out.push(b);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_avcc_annex_b_round_trip() {
+        let avcc = [0, 0, 0, 2, 0x67, 0x42, 0, 0, 0, 1, 0x68];
+        let annex_b = avcc_to_annex_b(&avcc, 4).unwrap();
+        assert_eq!(annex_b, vec![0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68]);
+        assert_eq!(annex_b_to_avcc(&annex_b, 4).unwrap(), avcc.to_vec());
+        assert_eq!(
+            annex_b_to_avcc(&annex_b, 2).unwrap(),
+            vec![0, 2, 0x67, 0x42, 0, 1, 0x68]
+        );
+
+        assert!(avcc_to_annex_b(&[0, 0, 0, 9, 0x65], 4).is_err());
+        assert!(split_avcc(&[0xFF, 0xFF, 0xFF, 0xFF, 0x65], 4).is_err());
+        assert!(avcc_to_annex_b(&avcc, 3).is_err());
+        assert!(annex_b_to_avcc(&[0, 0, 1, 0x65, 0, 0], 4).is_ok());
+
+        let mut long = vec![0, 0, 1];
+        long.extend([0x65; 256]);
+        assert!(annex_b_to_avcc(&long, 1).is_err());
+    }
+
+    #[test]
+    fn test_split_annex_b_start_codes() {
+        let data = [
+            0, 0, 0, 1, 0x09, 0xF0, 0, 0, 1, 0x65, 0x88, 0, 0, 0, 1, 0x41,
+        ];
+        let units = split_annex_b(&data);
+        assert_eq!(units, vec![&[0x09, 0xF0][..], &[0x65, 0x88], &[0x41]]);
+        assert!(split_annex_b(&[0x65, 0x88]).is_empty());
+    }
+
+    #[test]
+    fn test_unescape_rbsp() {
+        assert_eq!(
+            unescape_rbsp(&[0x64, 0, 0, 3, 1, 0, 0, 3, 0, 3]),
+            vec![0x64, 0, 0, 1, 0, 0, 0, 3]
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Opus header parsing.
+//!
+//! Parses the `OpusHead` identification header (RFC 7845), which Ogg and
+//! Matroska carry as is and MP4 stores as a big-endian `dOps` box, and
+//! reads packet durations from the TOC byte (RFC 6716).
+
+use crate::demuxer::AudioInfo;
+use crate::error::{PlayerError, Result};
+
+/// Magic signature starting the identification header.
+pub const OPUS_HEAD_MAGIC: [u8; 8] = *b"OpusHead";
+
+/// Opus always decodes at 48 kHz, whatever the input rate was.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// Length of the identification header without a channel mapping table.
+const HEAD_LEN: usize = 19;
+
+/// The `OpusHead` identification header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpusHead {
+    /// Encapsulation version; only the major version 0 is understood.
+    pub version: u8,
+    /// Number of output channels.
+    pub channels: u8,
+    /// Number of 48 kHz samples to discard from the decoder output at the
+    /// start of the stream.
+    pub pre_skip: u16,
+    /// Sample rate of the encoder input, informational only.
+    pub input_sample_rate: u32,
+    /// Gain to apply to the output, in Q7.8 dB.
+    pub output_gain: i16,
+    /// Channel mapping family: 0 for mono or stereo, 1 for the Vorbis
+    /// channel order, 255 for an undefined order.
+    pub mapping_family: u8,
+    /// Number of Opus streams in each packet.
+    pub stream_count: u8,
+    /// Number of those streams that code two channels.
+    pub coupled_count: u8,
+    /// Decoded stream channel for each output channel; 255 is silence.
+    pub channel_mapping: Vec<u8>,
+}
+
+impl OpusHead {
+    /// Parses an `OpusHead` packet.
+    ///
+    /// # Errors
+    /// Returns an error if the header is truncated, has an unknown major
+    /// version, or its channel mapping is inconsistent.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let h = data
+            .strip_prefix(&OPUS_HEAD_MAGIC[..])
+            .filter(|h| h.len() >= HEAD_LEN - OPUS_HEAD_MAGIC.len())
+            .ok_or_else(|| PlayerError::decoder("Invalid OpusHead"))?;
+        let version = h[0];
+        if version >> 4 != 0 {
+            return Err(PlayerError::decoder("Unsupported OpusHead version"));
+        }
+        Self::parse_fields(
+            version,
+            h[1],
+            u16::from_le_bytes([h[2], h[3]]),
+            u32::from_le_bytes([h[4], h[5], h[6], h[7]]),
+            i16::from_le_bytes([h[8], h[9]]),
+            h[10],
+            &h[11..],
+        )
+    }
+
+    /// Parses the payload of an MP4 `dOps` box, which holds the same
+    /// fields as `OpusHead` in big-endian order without the signature.
+    ///
+    /// # Errors
+    /// Returns an error if the box is truncated, has an unknown version, or
+    /// its channel mapping is inconsistent.
+    pub fn parse_dops(payload: &[u8]) -> Result<Self> {
+        let p = payload
+            .get(..HEAD_LEN - OPUS_HEAD_MAGIC.len())
+            .ok_or_else(|| PlayerError::decoder("Invalid dOps box"))?;
+        if p[0] != 0 {
+            return Err(PlayerError::decoder("Unsupported dOps version"));
+        }
+        Self::parse_fields(
+            1,
+            p[1],
+            u16::from_be_bytes([p[2], p[3]]),
+            u32::from_be_bytes([p[4], p[5], p[6], p[7]]),
+            i16::from_be_bytes([p[8], p[9]]),
+            p[10],
+            &payload[11..],
+        )
+    }
+
+    /// Checks the channel mapping, which `table` starts with for families
+    /// other than 0.
+    fn parse_fields(
+        version: u8,
+        channels: u8,
+        pre_skip: u16,
+        input_sample_rate: u32,
+        output_gain: i16,
+        mapping_family: u8,
+        table: &[u8],
+    ) -> Result<Self> {
+        let (stream_count, coupled_count, channel_mapping) = if mapping_family == 0 {
+            if !(1..=2).contains(&channels) {
+                return Err(PlayerError::decoder(
+                    "Opus mapping family 0 allows one or two channels",
+                ));
+            }
+            (1, channels - 1, (0..channels).collect())
+        } else {
+            let t = table
+                .get(..2 + channels as usize)
+                .ok_or_else(|| PlayerError::decoder("Opus channel mapping table truncated"))?;
+            (t[0], t[1], t[2..].to_vec())
+        };
+        let decoded = stream_count as u32 + coupled_count as u32;
+        if channels == 0
+            || stream_count == 0
+            || coupled_count > stream_count
+            || decoded > 255
+            || (mapping_family == 1 && channels > 8)
+            || channel_mapping
+                .iter()
+                .any(|&c| c != 255 && c as u32 >= decoded)
+        {
+            return Err(PlayerError::decoder("Invalid Opus channel mapping"));
+        }
+        Ok(Self {
+            version,
+            channels,
+            pre_skip,
+            input_sample_rate,
+            output_gain,
+            mapping_family,
+            stream_count,
+            coupled_count,
+            channel_mapping,
+        })
+    }
+
+    /// Serializes the header as an `OpusHead` packet.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = OPUS_HEAD_MAGIC.to_vec();
+        out.push(self.version);
+        out.push(self.channels);
+        out.extend_from_slice(&self.pre_skip.to_le_bytes());
+        out.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        out.extend_from_slice(&self.output_gain.to_le_bytes());
+        out.push(self.mapping_family);
+        if self.mapping_family != 0 {
+            out.push(self.stream_count);
+            out.push(self.coupled_count);
+            out.extend_from_slice(&self.channel_mapping);
+        }
+        out
+    }
+
+    /// Returns the output gain as a linear amplitude factor.
+    pub fn gain(&self) -> f32 {
+        10f32.powf(self.output_gain as f32 / (20.0 * 256.0))
+    }
+
+    /// Fills in the output sample rate and channel count.
+    pub fn fill_audio_info(&self, audio: &mut AudioInfo) {
+        *audio = AudioInfo::new(SAMPLE_RATE, self.channels as u16);
+    }
+}
+
+/// Returns the number of 48 kHz samples in an Opus packet from its TOC
+/// byte, or `None` if the packet is empty or truncated.
+pub fn packet_samples(packet: &[u8]) -> Option<u32> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+    let frame_size = match config {
+        0..=11 => [480, 960, 1920, 2880][(config & 3) as usize],
+        12..=15 => [480, 960][(config & 1) as usize],
+        _ => [120, 240, 480, 960][(config & 3) as usize],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1)? & 0x3F) as u32,
+    };
+    Some(frame_size * frames)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds an `OpusHead` with mapping family 0.
+    pub(crate) fn opus_head(channels: u8, pre_skip: u16) -> Vec<u8> {
+        let mut h = OPUS_HEAD_MAGIC.to_vec();
+        h.extend_from_slice(&[1, channels]);
+        h.extend_from_slice(&pre_skip.to_le_bytes());
+        h.extend_from_slice(&44100u32.to_le_bytes());
+        h.extend_from_slice(&[0, 0, 0]);
+        h
+    }
+
+    #[test]
+    fn test_parse_opus_head() {
+        let head = OpusHead::parse(&opus_head(2, 312)).unwrap();
+        assert_eq!((head.channels, head.pre_skip), (2, 312));
+        assert_eq!(head.input_sample_rate, 44100);
+        assert_eq!((head.stream_count, head.coupled_count), (1, 1));
+        assert_eq!(head.channel_mapping, vec![0, 1]);
+        assert_eq!(head.gain(), 1.0);
+        assert_eq!(head.to_bytes(), opus_head(2, 312));
+
+        // 5.1 in Vorbis order: four streams, two of them coupled, and
+        // -6 dB of output gain.
+        let mut data = opus_head(6, 3840);
+        data[16..18].copy_from_slice(&(-6i16 * 256).to_le_bytes());
+        data[18] = 1;
+        data.extend_from_slice(&[4, 2, 0, 4, 1, 2, 3, 5]);
+        let head = OpusHead::parse(&data).unwrap();
+        assert_eq!(head.mapping_family, 1);
+        assert_eq!((head.stream_count, head.coupled_count), (4, 2));
+        assert_eq!(head.channel_mapping, vec![0, 4, 1, 2, 3, 5]);
+        assert!((head.gain() - 0.501).abs() < 0.001);
+        assert_eq!(head.to_bytes(), data);
+
+        // A mapping to the seventh decoded channel of six.
+        data[26] = 6;
+        assert!(OpusHead::parse(&data).is_err());
+        assert!(OpusHead::parse(&data[..22]).is_err());
+        assert!(OpusHead::parse(&opus_head(3, 0)).is_err());
+        let mut data = opus_head(2, 0);
+        data[8] = 0x10;
+        assert!(OpusHead::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_parse_dops() {
+        let mut dops = vec![0, 1];
+        dops.extend_from_slice(&312u16.to_be_bytes());
+        dops.extend_from_slice(&48000u32.to_be_bytes());
+        dops.extend_from_slice(&[0xFF, 0x00, 0]);
+        let head = OpusHead::parse_dops(&dops).unwrap();
+        assert_eq!((head.channels, head.pre_skip), (1, 312));
+        assert_eq!(head.output_gain, -256);
+        assert_eq!(OpusHead::parse(&head.to_bytes()).unwrap(), head);
+        assert!(OpusHead::parse_dops(&dops[..10]).is_err());
+    }
+
+    #[test]
+    fn test_packet_samples() {
+        assert_eq!(packet_samples(&[0xF8]), Some(960));
+        assert_eq!(packet_samples(&[0x08 | 0x01]), Some(1920));
+        assert_eq!(packet_samples(&[0x03 << 3 | 0x03, 3]), Some(8640));
+        assert_eq!(packet_samples(&[]), None);
+    }
+}
@@ -0,0 +1,440 @@
+//! Uncompressed PCM and G.711 audio.
+//!
+//! Parses the `WAVEFORMATEX` and `WAVEFORMATEXTENSIBLE` records that
+//! describe PCM streams in WAVE and AVI files, and that are synthesized as
+//! the initialization data of PCM tracks in other containers, and converts
+//! the supported sample encodings to 16-bit integer or 32-bit float samples.
+
+use super::{AudioCodec, SampleFormat};
+use crate::demuxer::{channel_mask_layout, AudioInfo};
+use crate::error::{PlayerError, Result};
+
+/// `wFormatTag` of integer PCM.
+pub const WAVE_FORMAT_PCM: u16 = 0x0001;
+/// `wFormatTag` of IEEE floating-point PCM.
+pub const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+/// `wFormatTag` of G.711 A-law.
+pub const WAVE_FORMAT_ALAW: u16 = 0x0006;
+/// `wFormatTag` of G.711 µ-law.
+pub const WAVE_FORMAT_MULAW: u16 = 0x0007;
+/// `wFormatTag` of `WAVEFORMATEXTENSIBLE`, whose sub-format GUID gives the
+/// actual format.
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Length of a `WAVEFORMATEX` without extension.
+const WAVEFORMATEX_LEN: usize = 18;
+/// Length of a `WAVEFORMATEXTENSIBLE`.
+const WAVEFORMATEXTENSIBLE_LEN: usize = 40;
+/// Bytes 2 to 15 shared by the `KSDATAFORMAT_SUBTYPE` GUIDs of the WAVE
+/// format tags, which fill bytes 0 and 1.
+const SUBTYPE_GUID_SUFFIX: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// Codec names of the PCM codecs, as used for demuxed streams. The
+/// little-endian ones are also the WebCodecs codec strings.
+const CODEC_NAMES: [(AudioCodec, &str); 11] = [
+    (AudioCodec::PcmU8, "pcm-u8"),
+    (AudioCodec::PcmS16le, "pcm-s16"),
+    (AudioCodec::PcmS16be, "pcm-s16be"),
+    (AudioCodec::PcmS24le, "pcm-s24"),
+    (AudioCodec::PcmS24be, "pcm-s24be"),
+    (AudioCodec::PcmS32le, "pcm-s32"),
+    (AudioCodec::PcmS32be, "pcm-s32be"),
+    (AudioCodec::PcmF32le, "pcm-f32"),
+    (AudioCodec::PcmF32be, "pcm-f32be"),
+    (AudioCodec::PcmMulaw, "ulaw"),
+    (AudioCodec::PcmAlaw, "alaw"),
+];
+
+/// Returns the stream codec name of a PCM codec.
+pub fn codec_name(codec: AudioCodec) -> Option<&'static str> {
+    CODEC_NAMES
+        .iter()
+        .find(|(c, _)| *c == codec)
+        .map(|(_, name)| *name)
+}
+
+/// Returns the PCM codec of a stream codec name.
+pub fn codec_for_name(name: &str) -> Option<AudioCodec> {
+    CODEC_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(codec, _)| *codec)
+}
+
+/// Returns the WebCodecs codec string of a PCM codec; WebCodecs has no
+/// big-endian formats.
+pub fn codec_string(codec: AudioCodec) -> Option<&'static str> {
+    codec_name(codec).filter(|name| !name.ends_with("be"))
+}
+
+/// Returns the PCM codec of integer samples of `bits` bits, which are
+/// unsigned at 8 bits and signed otherwise.
+pub fn integer_codec(bits: u16, big_endian: bool) -> Option<AudioCodec> {
+    Some(match (bits, big_endian) {
+        (8, _) => AudioCodec::PcmU8,
+        (16, false) => AudioCodec::PcmS16le,
+        (16, true) => AudioCodec::PcmS16be,
+        (24, false) => AudioCodec::PcmS24le,
+        (24, true) => AudioCodec::PcmS24be,
+        (32, false) => AudioCodec::PcmS32le,
+        (32, true) => AudioCodec::PcmS32be,
+        _ => return None,
+    })
+}
+
+/// Returns the PCM codec of floating-point samples of `bits` bits.
+pub fn float_codec(bits: u16, big_endian: bool) -> Option<AudioCodec> {
+    match (bits, big_endian) {
+        (32, false) => Some(AudioCodec::PcmF32le),
+        (32, true) => Some(AudioCodec::PcmF32be),
+        _ => None,
+    }
+}
+
+/// Returns the size of one sample of a PCM codec in bytes, or `None` for
+/// compressed codecs.
+pub fn sample_size(codec: AudioCodec) -> Option<usize> {
+    Some(match codec {
+        AudioCodec::PcmU8 | AudioCodec::PcmMulaw | AudioCodec::PcmAlaw => 1,
+        AudioCodec::PcmS16le | AudioCodec::PcmS16be => 2,
+        AudioCodec::PcmS24le | AudioCodec::PcmS24be => 3,
+        AudioCodec::PcmS32le
+        | AudioCodec::PcmS32be
+        | AudioCodec::PcmF32le
+        | AudioCodec::PcmF32be => 4,
+        _ => return None,
+    })
+}
+
+/// A `WAVEFORMATEX` record, optionally extended to `WAVEFORMATEXTENSIBLE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveFormat {
+    /// Format tag; for `WAVEFORMATEXTENSIBLE`, the tag of the sub-format.
+    pub format_tag: u16,
+    /// Number of channels.
+    pub channels: u16,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Average data rate in bytes per second.
+    pub byte_rate: u32,
+    /// Size of one sample frame (or compressed block) in bytes.
+    pub block_align: u16,
+    /// Container size of one sample in bits.
+    pub bits_per_sample: u16,
+    /// Significant bits per sample, when extensible.
+    pub valid_bits_per_sample: Option<u16>,
+    /// WAVE speaker position mask, when extensible.
+    pub channel_mask: Option<u32>,
+}
+
+impl WaveFormat {
+    /// Describes a PCM stream with the default channel order.
+    pub fn new(codec: AudioCodec, sample_rate: u32, channels: u16) -> Self {
+        let size = sample_size(codec).unwrap_or(0) as u16;
+        let block_align = size.saturating_mul(channels);
+        Self {
+            format_tag: match codec {
+                AudioCodec::PcmF32le | AudioCodec::PcmF32be => WAVE_FORMAT_IEEE_FLOAT,
+                AudioCodec::PcmAlaw => WAVE_FORMAT_ALAW,
+                AudioCodec::PcmMulaw => WAVE_FORMAT_MULAW,
+                _ => WAVE_FORMAT_PCM,
+            },
+            channels,
+            sample_rate,
+            byte_rate: sample_rate.saturating_mul(block_align as u32),
+            block_align,
+            bits_per_sample: size * 8,
+            valid_bits_per_sample: None,
+            channel_mask: None,
+        }
+    }
+
+    /// Parses a `WAVEFORMATEX` or `WAVEFORMATEXTENSIBLE` record; the
+    /// `cbSize` field of a plain `WAVEFORMATEX` may be missing.
+    ///
+    /// # Errors
+    /// Returns an error if the record is truncated or has no channels.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 16 {
+            return Err(PlayerError::decoder("Truncated WAVEFORMATEX"));
+        }
+        let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+        let u32_at =
+            |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        let mut format = Self {
+            format_tag: u16_at(0),
+            channels: u16_at(2),
+            sample_rate: u32_at(4),
+            byte_rate: u32_at(8),
+            block_align: u16_at(12),
+            bits_per_sample: u16_at(14),
+            valid_bits_per_sample: None,
+            channel_mask: None,
+        };
+        if format.channels == 0 {
+            return Err(PlayerError::decoder("WAVEFORMATEX has no channels"));
+        }
+        if format.format_tag == WAVE_FORMAT_EXTENSIBLE {
+            if data.len() < WAVEFORMATEXTENSIBLE_LEN {
+                return Err(PlayerError::decoder("Truncated WAVEFORMATEXTENSIBLE"));
+            }
+            format.valid_bits_per_sample = Some(u16_at(18)).filter(|&bits| bits > 0);
+            format.channel_mask = Some(u32_at(20));
+            // Sub-formats outside the WAVE tag space keep the extensible tag.
+            if data[26..40] == SUBTYPE_GUID_SUFFIX {
+                format.format_tag = u16_at(24);
+            }
+        }
+        Ok(format)
+    }
+
+    /// Serializes the record, as `WAVEFORMATEXTENSIBLE` if it has a
+    /// channel mask or valid bits.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let extensible = self.channel_mask.is_some() || self.valid_bits_per_sample.is_some();
+        let mut data = Vec::with_capacity(WAVEFORMATEXTENSIBLE_LEN);
+        let tag = if extensible {
+            WAVE_FORMAT_EXTENSIBLE
+        } else {
+            self.format_tag
+        };
+        data.extend(tag.to_le_bytes());
+        data.extend(self.channels.to_le_bytes());
+        data.extend(self.sample_rate.to_le_bytes());
+        data.extend(self.byte_rate.to_le_bytes());
+        data.extend(self.block_align.to_le_bytes());
+        data.extend(self.bits_per_sample.to_le_bytes());
+        if extensible {
+            data.extend(((WAVEFORMATEXTENSIBLE_LEN - WAVEFORMATEX_LEN) as u16).to_le_bytes());
+            let valid_bits = self.valid_bits_per_sample.unwrap_or(self.bits_per_sample);
+            data.extend(valid_bits.to_le_bytes());
+            data.extend(self.channel_mask.unwrap_or(0).to_le_bytes());
+            data.extend(self.format_tag.to_le_bytes());
+            data.extend(SUBTYPE_GUID_SUFFIX);
+        } else {
+            data.extend(0u16.to_le_bytes());
+        }
+        data
+    }
+
+    /// Returns the PCM codec of the record, or `None` for compressed
+    /// formats and unsupported sample sizes. A missing sample size is
+    /// derived from the block alignment.
+    pub fn codec(&self) -> Option<AudioCodec> {
+        let bits = match self.bits_per_sample {
+            0 => self
+                .block_align
+                .checked_div(self.channels)
+                .and_then(|size| size.checked_mul(8))?,
+            bits => bits,
+        };
+        match self.format_tag {
+            WAVE_FORMAT_PCM => integer_codec(bits, false),
+            WAVE_FORMAT_IEEE_FLOAT => float_codec(bits, false),
+            WAVE_FORMAT_ALAW if bits == 8 => Some(AudioCodec::PcmAlaw),
+            WAVE_FORMAT_MULAW if bits == 8 => Some(AudioCodec::PcmMulaw),
+            _ => None,
+        }
+    }
+
+    /// Fills in the sample rate, channels and bit depth, and the layout of
+    /// the speaker mask.
+    pub fn fill_audio_info(&self, audio: &mut AudioInfo) {
+        *audio = AudioInfo::new(self.sample_rate, self.channels);
+        let bits = self.valid_bits_per_sample.unwrap_or(self.bits_per_sample);
+        audio.bit_depth = Some(bits as u8).filter(|&bits| bits > 0);
+        if let Some(layout) = self.channel_mask.and_then(channel_mask_layout) {
+            audio.channel_layout = Some(layout.to_string());
+        }
+    }
+}
+
+/// Converts whole sample frames of a PCM codec to interleaved
+/// little-endian samples: 16-bit integers for 8- and 16-bit and G.711
+/// input, 32-bit floats for deeper input.
+///
+/// # Panics
+/// Panics if `codec` is not a PCM codec.
+pub fn convert(codec: AudioCodec, data: &[u8]) -> (SampleFormat, Vec<u8>) {
+    const S24_SCALE: f32 = 1.0 / (1 << 23) as f32;
+    const S32_SCALE: f32 = 1.0 / (1u32 << 31) as f32;
+    let size = sample_size(codec).expect("not a PCM codec");
+    let samples = data.chunks_exact(size);
+    match codec {
+        AudioCodec::PcmU8 => to_s16(samples.map(|s| ((s[0] as i16) - 128) << 8)),
+        AudioCodec::PcmS16le => to_s16(samples.map(|s| i16::from_le_bytes([s[0], s[1]]))),
+        AudioCodec::PcmS16be => to_s16(samples.map(|s| i16::from_be_bytes([s[0], s[1]]))),
+        AudioCodec::PcmMulaw => to_s16(samples.map(|s| mulaw_to_linear(s[0]))),
+        AudioCodec::PcmAlaw => to_s16(samples.map(|s| alaw_to_linear(s[0]))),
+        // 24-bit samples are placed in the top bytes to extend the sign.
+        AudioCodec::PcmS24le => to_f32(
+            samples.map(|s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 * S24_SCALE),
+        ),
+        AudioCodec::PcmS24be => to_f32(
+            samples.map(|s| (i32::from_be_bytes([s[0], s[1], s[2], 0]) >> 8) as f32 * S24_SCALE),
+        ),
+        AudioCodec::PcmS32le => {
+            to_f32(samples.map(|s| i32::from_le_bytes(s.try_into().unwrap()) as f32 * S32_SCALE))
+        }
+        AudioCodec::PcmS32be => {
+            to_f32(samples.map(|s| i32::from_be_bytes(s.try_into().unwrap()) as f32 * S32_SCALE))
+        }
+        AudioCodec::PcmF32le => to_f32(samples.map(|s| f32::from_le_bytes(s.try_into().unwrap()))),
+        AudioCodec::PcmF32be => to_f32(samples.map(|s| f32::from_be_bytes(s.try_into().unwrap()))),
+        _ => unreachable!(),
+    }
+}
+
+/// Collects 16-bit samples as little-endian bytes.
+fn to_s16(samples: impl Iterator<Item = i16>) -> (SampleFormat, Vec<u8>) {
+    (
+        SampleFormat::S16,
+        samples.flat_map(i16::to_le_bytes).collect(),
+    )
+}
+
+/// Collects 32-bit float samples as little-endian bytes.
+fn to_f32(samples: impl Iterator<Item = f32>) -> (SampleFormat, Vec<u8>) {
+    (
+        SampleFormat::F32,
+        samples.flat_map(f32::to_le_bytes).collect(),
+    )
+}
+
+/// Expands a G.711 µ-law code (ITU-T G.711, table 2) to a 16-bit sample.
+fn mulaw_to_linear(code: u8) -> i16 {
+    let code = !code;
+    let magnitude = ((((code & 0x0F) as i32) << 3) + 0x84) << ((code & 0x70) >> 4);
+    (if code & 0x80 != 0 {
+        0x84 - magnitude
+    } else {
+        magnitude - 0x84
+    }) as i16
+}
+
+/// Expands a G.711 A-law code (ITU-T G.711, table 1) to a 16-bit sample.
+fn alaw_to_linear(code: u8) -> i16 {
+    let code = code ^ 0x55;
+    let segment = (code & 0x70) >> 4;
+    let mantissa = ((code & 0x0F) as i32) << 4;
+    let magnitude = match segment {
+        0 => mantissa + 8,
+        _ => (mantissa + 0x108) << (segment - 1),
+    };
+    (if code & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s16(data: &[u8]) -> Vec<i16> {
+        data.chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]))
+            .collect()
+    }
+
+    fn f32s(data: &[u8]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .map(|s| f32::from_le_bytes(s.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_wave_format_parse() {
+        // 24-bit 5.1 in 32-bit containers, WAVEFORMATEXTENSIBLE.
+        let mut format = WaveFormat::new(AudioCodec::PcmS32le, 48000, 6);
+        format.valid_bits_per_sample = Some(24);
+        format.channel_mask = Some(0x60F);
+        let data = format.to_bytes();
+        assert_eq!(data.len(), 40);
+        assert_eq!(&data[..2], &[0xFE, 0xFF]);
+        let parsed = WaveFormat::parse(&data).unwrap();
+        assert_eq!(parsed, format);
+        assert_eq!(parsed.codec(), Some(AudioCodec::PcmS32le));
+        assert_eq!(parsed.block_align, 24);
+        assert_eq!(parsed.byte_rate, 48000 * 24);
+
+        let mut audio = AudioInfo::default();
+        parsed.fill_audio_info(&mut audio);
+        assert_eq!((audio.sample_rate, audio.channels), (48000, 6));
+        assert_eq!(audio.bit_depth, Some(24));
+        assert_eq!(audio.channel_layout.as_deref(), Some("5.1"));
+
+        // Plain WAVEFORMATEX without cbSize; float and G.711 tags.
+        let float = WaveFormat::new(AudioCodec::PcmF32le, 44100, 2).to_bytes();
+        assert_eq!(float.len(), 18);
+        let parsed = WaveFormat::parse(&float[..16]).unwrap();
+        assert_eq!(parsed.codec(), Some(AudioCodec::PcmF32le));
+        let alaw = WaveFormat::new(AudioCodec::PcmAlaw, 8000, 1);
+        assert_eq!(alaw.format_tag, WAVE_FORMAT_ALAW);
+        assert_eq!(alaw.codec(), Some(AudioCodec::PcmAlaw));
+
+        // Unknown sample size derived from the block alignment.
+        let mut format = WaveFormat::new(AudioCodec::PcmS16le, 8000, 2);
+        format.bits_per_sample = 0;
+        assert_eq!(format.codec(), Some(AudioCodec::PcmS16le));
+        format.format_tag = 0x0055;
+        assert_eq!(format.codec(), None);
+        // A block of 8 KB per channel overflows the bit count.
+        format.format_tag = WAVE_FORMAT_PCM;
+        format.block_align = 0x4000;
+        format.channels = 1;
+        assert_eq!(format.codec(), None);
+
+        assert!(WaveFormat::parse(&data[..20]).is_err());
+        assert!(
+            WaveFormat::parse(&[1, 0, 0, 0, 0x40, 0x1F, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_codec_names() {
+        assert_eq!(codec_name(AudioCodec::PcmS24le), Some("pcm-s24"));
+        assert_eq!(codec_for_name("pcm-s16be"), Some(AudioCodec::PcmS16be));
+        assert_eq!(codec_string(AudioCodec::PcmMulaw), Some("ulaw"));
+        assert_eq!(codec_string(AudioCodec::PcmF32be), None);
+        assert_eq!(codec_name(AudioCodec::Flac), None);
+        assert_eq!(codec_for_name("fLaC"), None);
+        assert_eq!(integer_codec(24, true), Some(AudioCodec::PcmS24be));
+        assert_eq!(float_codec(64, false), None);
+    }
+
+    #[test]
+    fn test_convert_integer() {
+        let (format, data) = convert(AudioCodec::PcmU8, &[0x00, 0x80, 0xFF]);
+        assert_eq!(format, SampleFormat::S16);
+        assert_eq!(s16(&data), vec![-32768, 0, 32512]);
+
+        let (_, data) = convert(AudioCodec::PcmS16be, &[0x12, 0x34, 0xFF, 0xFE]);
+        assert_eq!(s16(&data), vec![0x1234, -2]);
+
+        let (format, data) = convert(AudioCodec::PcmS24le, &[0, 0, 0x80, 0, 0, 0x40]);
+        assert_eq!(format, SampleFormat::F32);
+        assert_eq!(f32s(&data), vec![-1.0, 0.5]);
+        let (_, data) = convert(AudioCodec::PcmS24be, &[0xC0, 0, 0]);
+        assert_eq!(f32s(&data), vec![-0.5]);
+
+        let (_, data) = convert(AudioCodec::PcmS32be, &[0x40, 0, 0, 0, 0x80, 0, 0, 0]);
+        assert_eq!(f32s(&data), vec![0.5, -1.0]);
+
+        let (_, data) = convert(AudioCodec::PcmF32be, &0.25f32.to_be_bytes());
+        assert_eq!(f32s(&data), vec![0.25]);
+    }
+
+    #[test]
+    fn test_convert_g711() {
+        let (format, data) = convert(AudioCodec::PcmMulaw, &[0xFF, 0x7F, 0x00, 0x80]);
+        assert_eq!(format, SampleFormat::S16);
+        assert_eq!(s16(&data), vec![0, 0, -32124, 32124]);
+
+        let (_, data) = convert(AudioCodec::PcmAlaw, &[0xD5, 0x55, 0xAA, 0x2A]);
+        assert_eq!(s16(&data), vec![8, -8, 32256, -32256]);
+    }
+}
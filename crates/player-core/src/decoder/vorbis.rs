@@ -0,0 +1,428 @@
+//! Vorbis header parsing.
+//!
+//! Parses the identification and comment headers, and reads the block flag
+//! of each mode from the end of the setup header, which is enough to derive
+//! packet durations without decoding the codebooks and floors before it.
+//! The three headers are stored in Xiph lacing as in Matroska
+//! `CodecPrivate`.
+
+use crate::demuxer::AudioInfo;
+use crate::error::{PlayerError, Result};
+
+/// Packet type of the identification header.
+const TYPE_IDENTIFICATION: u8 = 1;
+/// Packet type of the comment header.
+const TYPE_COMMENT: u8 = 3;
+/// Packet type of the setup header.
+const TYPE_SETUP: u8 = 5;
+/// Codec signature following the packet type of every header.
+const SIGNATURE: &[u8; 6] = b"vorbis";
+/// Length of the identification header.
+const IDENTIFICATION_LEN: usize = 30;
+/// Bits of a setup header mode: block flag, window and transform types and
+/// mapping number.
+const MODE_BITS: usize = 41;
+/// Most modes a setup header can define.
+const MAX_MODES: usize = 64;
+
+/// Stream properties from the identification header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VorbisIdentification {
+    /// Number of channels.
+    pub channels: u8,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Maximum bitrate in bits per second, if set.
+    pub bitrate_maximum: Option<u32>,
+    /// Nominal bitrate in bits per second, if set.
+    pub bitrate_nominal: Option<u32>,
+    /// Minimum bitrate in bits per second, if set.
+    pub bitrate_minimum: Option<u32>,
+    /// Short block size in samples.
+    pub blocksize_0: u16,
+    /// Long block size in samples.
+    pub blocksize_1: u16,
+}
+
+impl VorbisIdentification {
+    /// Parses an identification header packet.
+    ///
+    /// # Errors
+    /// Returns an error if the packet is truncated, has a nonzero version
+    /// or invalid block sizes, or lacks the framing bit.
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        let p = header_body(packet, TYPE_IDENTIFICATION)
+            .filter(|_| packet.len() >= IDENTIFICATION_LEN)
+            .ok_or_else(|| PlayerError::decoder("Invalid Vorbis identification header"))?;
+        let le_u32 = |at: usize| u32::from_le_bytes([p[at], p[at + 1], p[at + 2], p[at + 3]]);
+        let bitrate = |at: usize| Some(le_u32(at)).filter(|&b| b as i32 > 0);
+        let (exp_0, exp_1) = (p[21] & 0x0F, p[21] >> 4);
+        if le_u32(0) != 0
+            || p[4] == 0
+            || le_u32(5) == 0
+            || !(6..=13).contains(&exp_0)
+            || !(exp_0..=13).contains(&exp_1)
+            || p[22] & 0x01 == 0
+        {
+            return Err(PlayerError::decoder("Invalid Vorbis identification header"));
+        }
+        Ok(Self {
+            channels: p[4],
+            sample_rate: le_u32(5),
+            bitrate_maximum: bitrate(9),
+            bitrate_nominal: bitrate(13),
+            bitrate_minimum: bitrate(17),
+            blocksize_0: 1 << exp_0,
+            blocksize_1: 1 << exp_1,
+        })
+    }
+}
+
+/// Vendor string and user comments from the comment header.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VorbisComment {
+    /// Encoder vendor string.
+    pub vendor: String,
+    /// User comments, each a `FIELD=value` pair.
+    pub comments: Vec<String>,
+}
+
+impl VorbisComment {
+    /// Parses a comment header packet. Text that is not UTF-8 is replaced
+    /// rather than rejected.
+    ///
+    /// # Errors
+    /// Returns an error if the packet is not a comment header or a length
+    /// runs past its end.
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        let truncated = || PlayerError::decoder("Vorbis comment header truncated");
+        let mut p = header_body(packet, TYPE_COMMENT)
+            .ok_or_else(|| PlayerError::decoder("Invalid Vorbis comment header"))?;
+        let string = |p: &mut &[u8]| -> Result<String> {
+            let len = p
+                .get(..4)
+                .map(|l| u32::from_le_bytes(l.try_into().unwrap()) as usize)
+                .ok_or_else(truncated)?;
+            let end = 4usize.checked_add(len).ok_or_else(truncated)?;
+            let text = p.get(4..end).ok_or_else(truncated)?;
+            *p = &p[end..];
+            Ok(String::from_utf8_lossy(text).into_owned())
+        };
+        let vendor = string(&mut p)?;
+        let count = p
+            .get(..4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .ok_or_else(truncated)?;
+        p = &p[4..];
+        let comments = (0..count).map(|_| string(&mut p)).collect::<Result<_>>()?;
+        Ok(Self { vendor, comments })
+    }
+}
+
+/// The three Vorbis headers, with only the mode block flags kept from the
+/// setup header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VorbisHeaders {
+    /// Identification header.
+    pub identification: VorbisIdentification,
+    /// Comment header.
+    pub comment: VorbisComment,
+    /// Whether each mode of the setup header uses the long block size.
+    pub mode_blockflags: Vec<bool>,
+}
+
+impl VorbisHeaders {
+    /// Parses the three headers stored in Xiph lacing, as in Matroska
+    /// `CodecPrivate`.
+    ///
+    /// # Errors
+    /// Returns an error if the lacing is malformed or a header is invalid.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let [identification, comment, setup] = split_codec_private(data)
+            .ok_or_else(|| PlayerError::decoder("Invalid Vorbis codec private data"))?;
+        Self::from_packets(identification, comment, setup)
+    }
+
+    /// Parses the three header packets.
+    ///
+    /// # Errors
+    /// Returns an error if a header is invalid.
+    pub fn from_packets(identification: &[u8], comment: &[u8], setup: &[u8]) -> Result<Self> {
+        Ok(Self {
+            identification: VorbisIdentification::parse(identification)?,
+            comment: VorbisComment::parse(comment)?,
+            mode_blockflags: parse_mode_blockflags(setup)?,
+        })
+    }
+
+    /// Returns the block size of an audio packet from the mode number after
+    /// its packet type bit, or `None` if it is not an audio packet or names
+    /// an undefined mode.
+    pub fn packet_blocksize(&self, packet: &[u8]) -> Option<u16> {
+        let &first = packet.first()?;
+        if first & 0x01 != 0 {
+            return None;
+        }
+        let mode_bits = usize::BITS - self.mode_blockflags.len().saturating_sub(1).leading_zeros();
+        let mode = read_bits(packet, 1, mode_bits as usize)? as usize;
+        let long = *self.mode_blockflags.get(mode)?;
+        Some(if long {
+            self.identification.blocksize_1
+        } else {
+            self.identification.blocksize_0
+        })
+    }
+
+    /// Fills in the sample rate and channel count; Vorbis orders up to
+    /// eight channels like WAVE.
+    pub fn fill_audio_info(&self, audio: &mut AudioInfo) {
+        let id = &self.identification;
+        *audio = AudioInfo::new(id.sample_rate, id.channels as u16);
+    }
+}
+
+/// Returns the samples output by decoding a packet of `blocksize` after one
+/// of `previous`: the overlap of their windows.
+pub fn overlap_samples(previous: u16, blocksize: u16) -> u32 {
+    (previous as u32 + blocksize as u32) / 4
+}
+
+/// Stores the three headers in Xiph lacing: the packet count minus one,
+/// the laced sizes of all packets but the last, then the packets.
+pub fn codec_private(headers: [&[u8]; 3]) -> Vec<u8> {
+    let mut out = vec![2];
+    for header in &headers[..2] {
+        out.extend(std::iter::repeat_n(255u8, header.len() / 255));
+        out.push((header.len() % 255) as u8);
+    }
+    for header in headers {
+        out.extend_from_slice(header);
+    }
+    out
+}
+
+/// Splits Xiph-laced `CodecPrivate` into the three headers.
+fn split_codec_private(data: &[u8]) -> Option<[&[u8]; 3]> {
+    if *data.first()? != 2 {
+        return None;
+    }
+    let mut pos = 1;
+    let mut sizes = [0usize; 2];
+    for size in &mut sizes {
+        loop {
+            let lace = *data.get(pos)?;
+            pos += 1;
+            *size += lace as usize;
+            if lace < 255 {
+                break;
+            }
+        }
+    }
+    let (identification, rest) = data.get(pos..)?.split_at_checked(sizes[0])?;
+    let (comment, setup) = rest.split_at_checked(sizes[1])?;
+    Some([identification, comment, setup])
+}
+
+/// Returns the payload of a header packet after its type and signature.
+fn header_body(packet: &[u8], kind: u8) -> Option<&[u8]> {
+    match packet.split_first()? {
+        (&first, rest) if first == kind => rest.strip_prefix(&SIGNATURE[..]),
+        _ => None,
+    }
+}
+
+/// Reads `n` bits of the LSB-first bitstream starting at bit `pos`.
+fn read_bits(data: &[u8], pos: usize, n: usize) -> Option<u32> {
+    (0..n).try_fold(0u32, |value, i| {
+        let bit = (data.get((pos + i) / 8)? >> ((pos + i) % 8)) & 1;
+        Some(value | (bit as u32) << i)
+    })
+}
+
+/// Reads the mode block flags from the end of a setup header.
+///
+/// The modes are the last fields before the framing bit, each 41 bits with
+/// zero window and transform types, preceded by their count. Searching
+/// backwards for the longest run of modes whose count matches avoids
+/// parsing the variable-length fields before them.
+fn parse_mode_blockflags(packet: &[u8]) -> Result<Vec<bool>> {
+    let invalid = || PlayerError::decoder("Invalid Vorbis setup header");
+    let body = header_body(packet, TYPE_SETUP).ok_or_else(invalid)?;
+    let start = (packet.len() - body.len()) * 8;
+    let last = packet.iter().rposition(|&b| b != 0).ok_or_else(invalid)?;
+    let framing = last * 8 + 7 - packet[last].leading_zeros() as usize;
+
+    let mode_start = |i: usize| framing.checked_sub(MODE_BITS * i);
+    let mut count = None;
+    for modes in 1..=MAX_MODES {
+        let valid = mode_start(modes)
+            .filter(|&pos| pos >= start)
+            .and_then(|pos| {
+                let window = read_bits(packet, pos + 1, 16)?;
+                let transform = read_bits(packet, pos + 17, 16)?;
+                let mapping = read_bits(packet, pos + 33, 8)?;
+                Some(window == 0 && transform == 0 && mapping < MAX_MODES as u32)
+            })
+            .unwrap_or(false);
+        if !valid {
+            break;
+        }
+        let field = mode_start(modes)
+            .and_then(|pos| pos.checked_sub(6))
+            .filter(|&pos| pos >= start)
+            .and_then(|pos| read_bits(packet, pos, 6));
+        if field == Some(modes as u32 - 1) {
+            count = Some(modes);
+        }
+    }
+    let count = count.ok_or_else(invalid)?;
+    Ok((0..count)
+        .filter_map(|i| read_bits(packet, mode_start(count - i)?, 1))
+        .map(|flag| flag == 1)
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Writes LSB-first bit fields.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bits: usize,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, n: usize, value: u32) -> &mut Self {
+            for i in 0..n {
+                if self.bits.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                let bit = (value >> i) as u8 & 1;
+                *self.bytes.last_mut().unwrap() |= bit << (self.bits % 8);
+                self.bits += 1;
+            }
+            self
+        }
+    }
+
+    pub(crate) fn identification(channels: u8, rate: u32) -> Vec<u8> {
+        let mut h = b"\x01vorbis".to_vec();
+        h.extend_from_slice(&[0; 4]);
+        h.push(channels);
+        h.extend_from_slice(&rate.to_le_bytes());
+        h.extend_from_slice(&0u32.to_le_bytes());
+        h.extend_from_slice(&128_000u32.to_le_bytes());
+        h.extend_from_slice(&0u32.to_le_bytes());
+        // 256 and 2048 sample blocks.
+        h.extend_from_slice(&[0xB8, 0x01]);
+        h
+    }
+
+    pub(crate) fn comment(vendor: &str, comments: &[&str]) -> Vec<u8> {
+        let mut h = b"\x03vorbis".to_vec();
+        h.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        h.extend_from_slice(vendor.as_bytes());
+        h.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+        for c in comments {
+            h.extend_from_slice(&(c.len() as u32).to_le_bytes());
+            h.extend_from_slice(c.as_bytes());
+        }
+        h.push(1);
+        h
+    }
+
+    /// Builds a setup header whose modes follow a stand-in for the
+    /// codebooks, floors, residues and mappings.
+    pub(crate) fn setup(blockflags: &[bool]) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        for byte in b"\x05vorbis" {
+            w.bits(8, *byte as u32);
+        }
+        w.bits(13, 0x1ABC);
+        w.bits(6, blockflags.len() as u32 - 1);
+        for (i, &long) in blockflags.iter().enumerate() {
+            w.bits(1, long as u32)
+                .bits(16, 0)
+                .bits(16, 0)
+                .bits(8, i as u32);
+        }
+        w.bits(1, 1);
+        w.bytes
+    }
+
+    pub(crate) fn headers() -> Vec<u8> {
+        codec_private([
+            &identification(2, 44100),
+            &comment("test", &["TITLE=Loop"]),
+            &setup(&[false, true]),
+        ])
+    }
+
+    #[test]
+    fn test_parse_identification() {
+        let id = VorbisIdentification::parse(&identification(2, 44100)).unwrap();
+        assert_eq!((id.channels, id.sample_rate), (2, 44100));
+        assert_eq!(id.bitrate_nominal, Some(128_000));
+        assert_eq!((id.bitrate_maximum, id.bitrate_minimum), (None, None));
+        assert_eq!((id.blocksize_0, id.blocksize_1), (256, 2048));
+
+        let mut data = identification(2, 44100);
+        data[28] = 0x8B;
+        assert!(VorbisIdentification::parse(&data).is_err());
+        assert!(VorbisIdentification::parse(&data[..29]).is_err());
+        assert!(VorbisIdentification::parse(&comment("", &[])).is_err());
+    }
+
+    #[test]
+    fn test_parse_comment() {
+        let c = VorbisComment::parse(&comment("Xiph.Org libVorbis", &["A=1", "B=2"])).unwrap();
+        assert_eq!(c.vendor, "Xiph.Org libVorbis");
+        assert_eq!(c.comments, vec!["A=1", "B=2"]);
+        let data = comment("x", &["LONG=comment"]);
+        assert!(VorbisComment::parse(&data[..data.len() - 4]).is_err());
+        let mut data = comment("x", &[]);
+        data[7..11].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(VorbisComment::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = VorbisHeaders::parse(&headers()).unwrap();
+        assert_eq!(headers.identification.sample_rate, 44100);
+        assert_eq!(headers.comment.comments, vec!["TITLE=Loop"]);
+        assert_eq!(headers.mode_blockflags, vec![false, true]);
+        // The mode number follows the packet type bit.
+        assert_eq!(headers.packet_blocksize(&[0b00]), Some(256));
+        assert_eq!(headers.packet_blocksize(&[0b10]), Some(2048));
+        assert_eq!(headers.packet_blocksize(&[0b01]), None);
+        assert_eq!(overlap_samples(256, 2048), 576);
+
+        let modes: Vec<bool> = (0..5).map(|i| i % 2 == 1).collect();
+        assert_eq!(parse_mode_blockflags(&setup(&modes)).unwrap(), modes);
+        let headers = VorbisHeaders::from_packets(
+            &identification(1, 8000),
+            &comment("", &[]),
+            &setup(&modes),
+        )
+        .unwrap();
+        assert_eq!(headers.packet_blocksize(&[0b0110]), Some(2048));
+        assert_eq!(headers.packet_blocksize(&[0b1100]), None);
+
+        // A comment header longer than 255 bytes takes two lacing values.
+        let text = format!("C={}", "x".repeat(278));
+        let data = codec_private([
+            &identification(2, 48000),
+            &comment("", &[&text]),
+            &setup(&[false]),
+        ]);
+        assert_eq!(&data[..4], &[2, 30, 255, 45]);
+        let headers = VorbisHeaders::parse(&data).unwrap();
+        assert_eq!(headers.comment.comments, vec![text]);
+        assert_eq!(headers.packet_blocksize(&[0xFE]), Some(256));
+
+        assert!(VorbisHeaders::parse(&data[..40]).is_err());
+        assert!(VorbisHeaders::parse(&setup(&[true])).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! VP8 frame header parsing.
+//!
+//! Reads the frame tag every VP8 frame starts with and the start code and
+//! dimensions that follow it in key frames (RFC 6386, section 9.1).
+
+use crate::error::{PlayerError, Result};
+
+/// Start code following the frame tag of a key frame.
+const START_CODE: [u8; 3] = [0x9D, 0x01, 0x2A];
+
+/// Decoded fields of a VP8 frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Whether the frame is a key frame.
+    pub key_frame: bool,
+    /// Bitstream version (0 to 3).
+    pub version: u8,
+    /// Whether the frame is shown after decoding.
+    pub show_frame: bool,
+    /// Size of the first partition in bytes.
+    pub first_partition_size: u32,
+    /// Frame size in pixels, signalled by key frames.
+    pub frame_size: Option<(u32, u32)>,
+    /// Horizontal and vertical upscaling mode (0 none, 1 5/4, 2 5/3, 3 2).
+    pub scale: (u8, u8),
+}
+
+impl FrameHeader {
+    /// Parses the frame tag and, for key frames, the frame dimensions.
+    ///
+    /// # Errors
+    /// Returns an error if the header is truncated or a key frame lacks the
+    /// start code.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let tag = match data {
+            [a, b, c, ..] => u32::from_le_bytes([*a, *b, *c, 0]),
+            _ => return Err(PlayerError::decoder("VP8 frame tag truncated")),
+        };
+        let mut header = Self {
+            key_frame: tag & 1 == 0,
+            version: ((tag >> 1) & 0x07) as u8,
+            show_frame: (tag >> 4) & 1 == 1,
+            first_partition_size: tag >> 5,
+            frame_size: None,
+            scale: (0, 0),
+        };
+        if !header.key_frame {
+            return Ok(header);
+        }
+
+        let key = data
+            .get(3..10)
+            .ok_or_else(|| PlayerError::decoder("VP8 key frame header truncated"))?;
+        if key[..3] != START_CODE {
+            return Err(PlayerError::decoder("Invalid VP8 start code"));
+        }
+        let width = u16::from_le_bytes([key[3], key[4]]);
+        let height = u16::from_le_bytes([key[5], key[6]]);
+        header.frame_size = Some(((width & 0x3FFF) as u32, (height & 0x3FFF) as u32));
+        header.scale = ((width >> 14) as u8, (height >> 14) as u8);
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a shown key frame header with a 16-byte first partition.
+    pub(crate) fn key_frame(width: u16, height: u16) -> Vec<u8> {
+        let tag = (16u32 << 5) | (1 << 4);
+        let mut frame = tag.to_le_bytes()[..3].to_vec();
+        frame.extend(START_CODE);
+        frame.extend(width.to_le_bytes());
+        frame.extend(height.to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_parse_key_frame() {
+        let header = FrameHeader::parse(&key_frame(640, 480)).unwrap();
+        assert!(header.key_frame && header.show_frame);
+        assert_eq!(header.first_partition_size, 16);
+        assert_eq!(header.frame_size, Some((640, 480)));
+        assert_eq!(header.scale, (0, 0));
+
+        let scaled = FrameHeader::parse(&key_frame(0x4000 | 176, 144)).unwrap();
+        assert_eq!(scaled.frame_size, Some((176, 144)));
+        assert_eq!(scaled.scale, (1, 0));
+
+        let mut bad = key_frame(640, 480);
+        bad[3] = 0;
+        assert!(FrameHeader::parse(&bad).is_err());
+        assert!(FrameHeader::parse(&bad[..5]).is_err());
+    }
+
+    #[test]
+    fn test_parse_inter_frame() {
+        let header = FrameHeader::parse(&[0x31, 0x02, 0x00]).unwrap();
+        assert!(!header.key_frame);
+        assert!(header.show_frame);
+        assert_eq!(header.first_partition_size, 0x11);
+        assert!(header.frame_size.is_none());
+        assert!(FrameHeader::parse(&[0x31]).is_err());
+    }
+}
@@ -0,0 +1,608 @@
+//! VP9 bitstream parsing.
+//!
+//! Reads the `vpcC` configuration record (VP Codec ISOBMFF binding), the
+//! WebM `CodecPrivate` feature list, the uncompressed frame header and the
+//! superframe index that packs hidden frames with the frame shown after
+//! them. The record also describes VP8 streams stored in MP4.
+
+use super::bits::BitReader;
+use super::MAX_DIMENSION;
+use crate::demuxer::{ColorInfo, VideoInfo};
+use crate::error::{PlayerError, Result};
+
+/// `frame_sync_code` at the start of key and intra-only frames.
+const SYNC_CODE: u32 = 0x49_83_42;
+
+/// `color_space` value signalling RGB (`CS_RGB`).
+const CS_RGB: u8 = 7;
+
+/// `chroma_subsampling` values of the `vpcC` record.
+const CHROMA_420_COLOCATED: u8 = 1;
+const CHROMA_422: u8 = 2;
+const CHROMA_444: u8 = 3;
+
+/// WebM `CodecPrivate` feature IDs.
+const FEATURE_PROFILE: u8 = 1;
+const FEATURE_LEVEL: u8 = 2;
+const FEATURE_BIT_DEPTH: u8 = 3;
+const FEATURE_CHROMA_SUBSAMPLING: u8 = 4;
+
+/// Colour properties from the frame header `color_config()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorConfig {
+    /// Bits per sample (8, 10 or 12).
+    pub bit_depth: u8,
+    /// `color_space` (0 unknown, 1 BT.601, 2 BT.709, 3 SMPTE 170,
+    /// 4 SMPTE 240, 5 BT.2020, 7 RGB).
+    pub color_space: u8,
+    /// Whether samples use the full range.
+    pub full_range: bool,
+    /// Whether chroma is horizontally subsampled.
+    pub subsampling_x: bool,
+    /// Whether chroma is vertically subsampled.
+    pub subsampling_y: bool,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            bit_depth: 8,
+            color_space: 1,
+            full_range: false,
+            subsampling_x: true,
+            subsampling_y: true,
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Reads a `color_config()` structure.
+    fn read(r: &mut BitReader<'_>, profile: u8) -> Result<Self> {
+        let mut color = Self {
+            bit_depth: match profile {
+                2 | 3 if r.flag()? => 12,
+                2 | 3 => 10,
+                _ => 8,
+            },
+            color_space: r.bits(3)? as u8,
+            ..Self::default()
+        };
+        if color.color_space == CS_RGB {
+            color.full_range = true;
+            if profile & 1 == 1 {
+                color.subsampling_x = false;
+                color.subsampling_y = false;
+                r.skip(1)?; // reserved_zero
+            }
+        } else {
+            color.full_range = r.flag()?;
+            if profile & 1 == 1 {
+                color.subsampling_x = r.flag()?;
+                color.subsampling_y = r.flag()?;
+                r.skip(1)?; // reserved_zero
+            }
+        }
+        Ok(color)
+    }
+
+    /// Returns the H.273 matrix coefficients for the colour space.
+    pub fn matrix_coefficients(&self) -> Option<u8> {
+        Some(match self.color_space {
+            1 | 3 => 6,
+            2 => 1,
+            4 => 7,
+            5 => 9,
+            CS_RGB => 0,
+            _ => return None,
+        })
+    }
+
+    /// Returns the `vpcC` `chroma_subsampling` value.
+    fn chroma_subsampling(&self) -> u8 {
+        match (self.subsampling_x, self.subsampling_y) {
+            (true, true) => CHROMA_420_COLOCATED,
+            (true, false) => CHROMA_422,
+            _ => CHROMA_444,
+        }
+    }
+}
+
+/// Type of a VP9 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// A key frame, which resets all references.
+    Key,
+    /// An intra-only frame that does not reset references.
+    IntraOnly,
+    /// An inter frame.
+    Inter,
+    /// A repeat of an already decoded frame (`show_existing_frame`).
+    ShowExisting,
+}
+
+/// Decoded fields of a VP9 uncompressed frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// `profile` (0 to 3).
+    pub profile: u8,
+    /// Type of the frame.
+    pub kind: FrameKind,
+    /// Whether the frame is shown after decoding. Always true for
+    /// `show_existing_frame`.
+    pub show_frame: bool,
+    /// Colour properties, signalled by key frames and intra-only frames of
+    /// profiles above 0.
+    pub color: Option<ColorConfig>,
+    /// Frame size in pixels, signalled by key and intra-only frames.
+    pub frame_size: Option<(u32, u32)>,
+    /// Size the frame is meant to be displayed at, if it differs from
+    /// `frame_size`.
+    pub render_size: Option<(u32, u32)>,
+}
+
+impl FrameHeader {
+    /// Parses the start of an uncompressed frame header.
+    ///
+    /// Inter frames take their size from a reference, so only key and
+    /// intra-only frames report `frame_size`.
+    ///
+    /// # Errors
+    /// Returns an error if the frame marker or sync code is wrong, the
+    /// header is truncated, or a size exceeds [`MAX_DIMENSION`].
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        if r.bits(2)? != 2 {
+            return Err(PlayerError::decoder("Invalid VP9 frame marker"));
+        }
+        let low = r.bits(1)?;
+        let profile = ((r.bits(1)? << 1) | low) as u8;
+        if profile == 3 {
+            r.skip(1)?; // reserved_zero
+        }
+        let mut header = Self {
+            profile,
+            kind: FrameKind::ShowExisting,
+            show_frame: true,
+            color: None,
+            frame_size: None,
+            render_size: None,
+        };
+        if r.flag()? {
+            return Ok(header);
+        }
+
+        let key_frame = !r.flag()?;
+        header.show_frame = r.flag()?;
+        let error_resilient = r.flag()?;
+        header.kind = if key_frame {
+            FrameKind::Key
+        } else if !header.show_frame && r.flag()? {
+            FrameKind::IntraOnly
+        } else {
+            FrameKind::Inter
+        };
+        match header.kind {
+            FrameKind::Key => {
+                read_sync_code(&mut r)?;
+                header.color = Some(ColorConfig::read(&mut r, profile)?);
+            }
+            FrameKind::IntraOnly => {
+                if !error_resilient {
+                    r.skip(2)?; // reset_frame_context
+                }
+                read_sync_code(&mut r)?;
+                header.color = Some(if profile > 0 {
+                    ColorConfig::read(&mut r, profile)?
+                } else {
+                    ColorConfig::default()
+                });
+                r.skip(8)?; // refresh_frame_flags
+            }
+            _ => return Ok(header),
+        }
+        header.frame_size = Some(read_size(&mut r)?);
+        if r.flag()? {
+            header.render_size = Some(read_size(&mut r)?);
+        }
+        Ok(header)
+    }
+}
+
+/// Reads a `*_width_minus_1`/`*_height_minus_1` pair.
+fn read_size(r: &mut BitReader<'_>) -> Result<(u32, u32)> {
+    let (width, height) = (r.bits(16)? + 1, r.bits(16)? + 1);
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(PlayerError::decoder("VP9 frame size too large"));
+    }
+    Ok((width, height))
+}
+
+/// Checks the `frame_sync_code`.
+fn read_sync_code(r: &mut BitReader<'_>) -> Result<()> {
+    if r.bits(24)? != SYNC_CODE {
+        return Err(PlayerError::decoder("Invalid VP9 sync code"));
+    }
+    Ok(())
+}
+
+/// Splits a packet into the frames of its superframe. A packet without a
+/// superframe index is returned as a single frame.
+///
+/// # Errors
+/// Returns an error if the frame sizes in the index exceed the packet.
+pub fn split_superframe(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let marker = match data.last() {
+        Some(&marker) if marker & 0xE0 == 0xC0 => marker,
+        _ => return Ok(vec![data]),
+    };
+    let frames = (marker & 0x07) as usize + 1;
+    let size_bytes = ((marker >> 3) & 0x03) as usize + 1;
+    let index_len = 2 + size_bytes * frames;
+    if data.len() < index_len || data[data.len() - index_len] != marker {
+        return Ok(vec![data]);
+    }
+
+    let index = &data[data.len() - index_len + 1..data.len() - 1];
+    let mut payload = &data[..data.len() - index_len];
+    let mut out = Vec::with_capacity(frames);
+    for size in index.chunks_exact(size_bytes) {
+        let size = size
+            .iter()
+            .rev()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        if size > payload.len() {
+            return Err(PlayerError::decoder("VP9 superframe exceeds packet"));
+        }
+        let (frame, rest) = payload.split_at(size);
+        // A zero size marks an unused slot.
+        if size > 0 {
+            out.push(frame);
+        }
+        payload = rest;
+    }
+    Ok(out)
+}
+
+/// Returns the lowest VP9 level (as `10 * level`) whose maximum picture
+/// size fits the given dimensions.
+pub fn level_for_picture_size(width: u32, height: u32) -> u8 {
+    const LEVELS: [(u64, u8); 10] = [
+        (36_864, 10),
+        (73_728, 11),
+        (122_880, 20),
+        (245_760, 21),
+        (552_960, 30),
+        (983_040, 31),
+        (2_228_224, 40),
+        (8_912_896, 50),
+        (35_651_584, 60),
+        (u64::MAX, 62),
+    ];
+    let samples = width as u64 * height as u64;
+    LEVELS
+        .iter()
+        .find(|(max, _)| samples <= *max)
+        .map_or(62, |&(_, level)| level)
+}
+
+/// VP codec configuration record (`vpcC`), shared by VP8 and VP9.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VpcDecoderConfig {
+    /// Codec profile.
+    pub profile: u8,
+    /// Level as `10 * level`, or 0 if unknown.
+    pub level: u8,
+    /// Bits per sample.
+    pub bit_depth: u8,
+    /// `chroma_subsampling` (0 and 1 for 4:2:0, 2 for 4:2:2, 3 for 4:4:4).
+    pub chroma_subsampling: u8,
+    /// Whether samples use the full range.
+    pub full_range: bool,
+    /// Colour primaries (H.273).
+    pub primaries: u8,
+    /// Transfer characteristics (H.273).
+    pub transfer: u8,
+    /// Matrix coefficients (H.273).
+    pub matrix: u8,
+}
+
+impl Default for VpcDecoderConfig {
+    fn default() -> Self {
+        Self {
+            profile: 0,
+            level: 0,
+            bit_depth: 8,
+            chroma_subsampling: CHROMA_420_COLOCATED,
+            full_range: false,
+            primaries: 1,
+            transfer: 1,
+            matrix: 1,
+        }
+    }
+}
+
+impl VpcDecoderConfig {
+    /// Parses the payload of a `vpcC` box, including its version and flags.
+    ///
+    /// # Errors
+    /// Returns an error if the record is truncated or has an unknown version.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(data);
+        let version = r.bits(8)?;
+        r.skip(24)?; // flags
+        if version != 1 {
+            return Err(PlayerError::decoder("Unsupported vpcC version"));
+        }
+        Ok(Self {
+            profile: r.bits(8)? as u8,
+            level: r.bits(8)? as u8,
+            bit_depth: r.bits(4)? as u8,
+            chroma_subsampling: r.bits(3)? as u8,
+            full_range: r.flag()?,
+            primaries: r.bits(8)? as u8,
+            transfer: r.bits(8)? as u8,
+            matrix: r.bits(8)? as u8,
+        })
+    }
+
+    /// Builds a record from the WebM VP9 `CodecPrivate` feature list,
+    /// taking colour properties from the track's `Colour` element.
+    ///
+    /// # Errors
+    /// Returns an error if a feature runs past the end of the data.
+    pub fn from_codec_private(data: &[u8], color: Option<&ColorInfo>) -> Result<Self> {
+        let mut config = Self::default();
+        let mut rest = data;
+        while let [id, len, tail @ ..] = rest {
+            let len = *len as usize;
+            let value = tail
+                .get(..len)
+                .ok_or_else(|| PlayerError::decoder("VP9 CodecPrivate feature truncated"))?;
+            if len == 1 {
+                match *id {
+                    FEATURE_PROFILE => config.profile = value[0],
+                    FEATURE_LEVEL => config.level = value[0],
+                    FEATURE_BIT_DEPTH => config.bit_depth = value[0],
+                    FEATURE_CHROMA_SUBSAMPLING => config.chroma_subsampling = value[0],
+                    _ => {}
+                }
+            }
+            rest = &tail[len..];
+        }
+        if let Some(color) = color {
+            config.primaries = color.primaries.unwrap_or(config.primaries);
+            config.transfer = color.transfer.unwrap_or(config.transfer);
+            config.matrix = color.matrix.unwrap_or(config.matrix);
+            config.full_range = color.full_range.unwrap_or(config.full_range);
+        }
+        Ok(config)
+    }
+
+    /// Builds a record from a key frame header. The level is derived from
+    /// the frame size, and only the matrix is known among colour properties.
+    pub fn from_frame_header(header: &FrameHeader) -> Option<Self> {
+        let color = header.color?;
+        let (width, height) = header.frame_size?;
+        Some(Self {
+            profile: header.profile,
+            level: level_for_picture_size(width, height),
+            bit_depth: color.bit_depth,
+            chroma_subsampling: color.chroma_subsampling(),
+            full_range: color.full_range,
+            matrix: color.matrix_coefficients().unwrap_or(2),
+            primaries: 2,
+            transfer: 2,
+        })
+    }
+
+    /// Serializes the record as the payload of a version 1 `vpcC` box,
+    /// without codec initialization data.
+    pub fn to_vpcc(&self) -> Vec<u8> {
+        vec![
+            1,
+            0,
+            0,
+            0,
+            self.profile,
+            self.level,
+            (self.bit_depth << 4) | (self.chroma_subsampling << 1) | self.full_range as u8,
+            self.primaries,
+            self.transfer,
+            self.matrix,
+            0,
+            0,
+        ]
+    }
+
+    /// Returns the codec string for a `vp08` or `vp09` sample entry, e.g.
+    /// `vp09.00.41.08` or, when colour properties differ from the
+    /// defaults, `vp09.02.10.10.01.09.16.09.00`.
+    pub fn codec_string(&self, fourcc: &str) -> String {
+        let mut codec = format!(
+            "{}.{:02}.{:02}.{:02}",
+            fourcc, self.profile, self.level, self.bit_depth
+        );
+        let defaults = Self {
+            profile: self.profile,
+            level: self.level,
+            bit_depth: self.bit_depth,
+            ..Self::default()
+        };
+        if *self != defaults {
+            codec.push_str(&format!(
+                ".{:02}.{:02}.{:02}.{:02}.{:02}",
+                self.chroma_subsampling,
+                self.primaries,
+                self.transfer,
+                self.matrix,
+                self.full_range as u8
+            ));
+        }
+        codec
+    }
+
+    /// Fills picture properties the container left unset.
+    pub fn fill_video_info(&self, video: &mut VideoInfo) {
+        video.bit_depth = video.bit_depth.or(Some(self.bit_depth));
+        // 2 means unspecified for every colour code point.
+        let known = |value: u8| (value != 2).then_some(value);
+        video.color = video.color.or(Some(ColorInfo {
+            primaries: known(self.primaries),
+            transfer: known(self.transfer),
+            matrix: known(self.matrix),
+            full_range: Some(self.full_range),
+        }));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::decoder::h264::tests::BitWriter;
+
+    /// Builds a profile 0 key frame header for 8-bit BT.709 video.
+    pub(crate) fn key_frame(width: u32, height: u32) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(2, 2).bits(2, 0).bits(1, 0); // marker, profile 0, not existing
+        w.bits(1, 0).bits(1, 1).bits(1, 0); // key frame, shown, not resilient
+        w.bits(24, SYNC_CODE);
+        w.bits(3, 2).bits(1, 0); // BT.709, limited range
+        w.bits(16, width - 1).bits(16, height - 1).bits(1, 0);
+        let mut frame = w.finish();
+        frame.extend([0; 4]);
+        frame
+    }
+
+    /// Builds a hidden inter frame header.
+    pub(crate) fn hidden_inter_frame() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.bits(2, 2).bits(2, 0).bits(1, 0);
+        w.bits(1, 1).bits(1, 0).bits(1, 0).bits(1, 0); // inter, hidden, not intra-only
+        let mut frame = w.finish();
+        frame.extend([0; 4]);
+        frame
+    }
+
+    /// Packs frames into a superframe with 2-byte sizes.
+    pub(crate) fn superframe(frames: &[&[u8]]) -> Vec<u8> {
+        let marker = 0xC0 | (1 << 3) | (frames.len() as u8 - 1);
+        let mut data: Vec<u8> = frames.concat();
+        data.push(marker);
+        for frame in frames {
+            data.extend((frame.len() as u16).to_le_bytes());
+        }
+        data.push(marker);
+        data
+    }
+
+    #[test]
+    fn test_parse_key_frame_header() {
+        let header = FrameHeader::parse(&key_frame(1280, 720)).unwrap();
+        assert_eq!(header.kind, FrameKind::Key);
+        assert!(header.show_frame);
+        assert_eq!(header.frame_size, Some((1280, 720)));
+        let color = header.color.unwrap();
+        assert_eq!(color.matrix_coefficients(), Some(1));
+        assert!(color.subsampling_x && color.subsampling_y);
+
+        let config = VpcDecoderConfig::from_frame_header(&header).unwrap();
+        assert_eq!(config.level, 31);
+        assert_eq!(config.codec_string("vp09"), "vp09.00.31.08.01.02.02.01.00");
+
+        // Profile 2, 10-bit 4:2:0 BT.2020 with a render size.
+        let mut w = BitWriter::default();
+        w.bits(2, 2).bits(1, 0).bits(1, 1).bits(1, 0);
+        w.bits(1, 0).bits(1, 1).bits(1, 0);
+        w.bits(24, SYNC_CODE);
+        w.bits(1, 0).bits(3, 5).bits(1, 0);
+        w.bits(16, 3839).bits(16, 2159);
+        w.bits(1, 1).bits(16, 1919).bits(16, 1079);
+        let header = FrameHeader::parse(&w.finish()).unwrap();
+        assert_eq!(header.profile, 2);
+        assert_eq!(header.color.unwrap().bit_depth, 10);
+        assert_eq!(header.render_size, Some((1920, 1080)));
+
+        assert!(FrameHeader::parse(&key_frame(16384, 16384)).is_ok());
+        assert!(FrameHeader::parse(&key_frame(65536, 720)).is_err());
+        assert!(FrameHeader::parse(&[0x00]).is_err());
+        assert!(FrameHeader::parse(&[0x80, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_parse_other_frame_headers() {
+        let header = FrameHeader::parse(&hidden_inter_frame()).unwrap();
+        assert_eq!(header.kind, FrameKind::Inter);
+        assert!(!header.show_frame);
+        assert!(header.frame_size.is_none());
+
+        // show_existing_frame of slot 3.
+        let header = FrameHeader::parse(&[0b1000_1011]).unwrap();
+        assert_eq!(header.kind, FrameKind::ShowExisting);
+        assert!(header.show_frame);
+
+        // Profile 0 intra-only frame uses the default colour config.
+        let mut w = BitWriter::default();
+        w.bits(2, 2).bits(2, 0).bits(1, 0);
+        w.bits(1, 1).bits(1, 0).bits(1, 0).bits(1, 1).bits(2, 0);
+        w.bits(24, SYNC_CODE).bits(8, 0xFF);
+        w.bits(16, 639).bits(16, 359).bits(1, 0);
+        let header = FrameHeader::parse(&w.finish()).unwrap();
+        assert_eq!(header.kind, FrameKind::IntraOnly);
+        assert_eq!(header.frame_size, Some((640, 360)));
+        assert_eq!(header.color, Some(ColorConfig::default()));
+    }
+
+    #[test]
+    fn test_split_superframe() {
+        let hidden = hidden_inter_frame();
+        let shown = key_frame(320, 240);
+        let data = superframe(&[&hidden, &shown]);
+        let frames = split_superframe(&data).unwrap();
+        assert_eq!(frames, vec![&hidden[..], &shown[..]]);
+
+        // Without a matching index the packet is a single frame.
+        assert_eq!(split_superframe(&shown).unwrap().len(), 1);
+        let mut bad = data.clone();
+        let len = bad.len();
+        bad[len - 3] = 0xFF;
+        assert!(split_superframe(&bad).is_err());
+    }
+
+    #[test]
+    fn test_parse_vpcc() {
+        let record = [1, 0, 0, 0, 2, 41, 0xA2, 9, 16, 9, 0, 0];
+        let config = VpcDecoderConfig::parse(&record).unwrap();
+        assert_eq!(
+            (config.profile, config.level, config.bit_depth),
+            (2, 41, 10)
+        );
+        assert_eq!(config.chroma_subsampling, 1);
+        assert_eq!(config.codec_string("vp09"), "vp09.02.41.10.01.09.16.09.00");
+        assert_eq!(config.to_vpcc(), record);
+
+        let mut video = VideoInfo::default();
+        config.fill_video_info(&mut video);
+        assert_eq!(video.color.unwrap().transfer, Some(16));
+        assert_eq!(video.bit_depth, Some(10));
+
+        let config = VpcDecoderConfig::parse(&[1, 0, 0, 0, 0, 30, 0x82, 1, 1, 1, 0, 0]).unwrap();
+        assert_eq!(config.codec_string("vp08"), "vp08.00.30.08");
+
+        assert!(VpcDecoderConfig::parse(&[0, 0, 0, 0, 0, 30, 0x82, 1, 1, 1]).is_err());
+        assert!(VpcDecoderConfig::parse(&[1, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_vp9_codec_private() {
+        let private = [1, 1, 0, 2, 1, 40, 3, 1, 8, 4, 1, 1, 9, 2, 0, 0];
+        let color = ColorInfo {
+            full_range: Some(false),
+            ..ColorInfo::default()
+        };
+        let config = VpcDecoderConfig::from_codec_private(&private, Some(&color)).unwrap();
+        assert_eq!(config.codec_string("vp09"), "vp09.00.40.08");
+
+        assert!(VpcDecoderConfig::from_codec_private(&[1, 2, 0], None).is_err());
+        assert_eq!(level_for_picture_size(3840, 2160), 50);
+    }
+}
@@ -0,0 +1,637 @@
+//! AVI (RIFF) container parsing.
+//!
+//! Reads stream headers from `hdrl`/`strl` and locates chunk data through,
+//! in order of preference, OpenDML `indx` super indexes (needed for files
+//! past the 1 GB `RIFF` limit), the legacy `idx1` index, or a scan of the
+//! `movi` lists.
+
+use super::riff::{chunks, le_u32, le_u64, read_chunk_at, read_chunks, Chunk, ChunkHeader};
+use super::source::SourceReader;
+use super::wav::audio_codec;
+use super::{
+    to_ms, AudioInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType, VideoInfo,
+};
+use crate::decoder::pcm::WaveFormat;
+use crate::error::{PlayerError, Result};
+use std::collections::HashSet;
+
+/// `idx1` flag marking a keyframe.
+const AVIIF_KEYFRAME: u32 = 0x10;
+/// OpenDML index entry size bit marking a non-keyframe.
+const ODML_NON_KEYFRAME: u32 = 0x8000_0000;
+/// `bIndexType` of an index of indexes.
+const AVI_INDEX_OF_INDEXES: u8 = 0x00;
+/// `bIndexType` of an index of chunks.
+const AVI_INDEX_OF_CHUNKS: u8 = 0x01;
+
+/// Timing parameters of one `strl`.
+#[derive(Debug, Clone)]
+struct StreamHeader {
+    /// Index into the exposed streams, `None` for unsupported stream types.
+    stream_index: Option<u32>,
+    stream_type: StreamType,
+    scale: u32,
+    start: u32,
+    /// Bytes per sample for constant-rate audio, zero when every chunk is one frame.
+    sample_size: u32,
+    /// OpenDML super index payload, if present.
+    indx: Option<Vec<u8>>,
+}
+
+/// A chunk reference from an index or a `movi` scan.
+#[derive(Debug, Clone, Copy)]
+struct ChunkRef {
+    stream: usize,
+    offset: u64,
+    size: u32,
+    is_keyframe: bool,
+}
+
+/// Returns the AVI stream number of a `movi` chunk id such as `01wb`.
+fn stream_number(id: &[u8]) -> Option<usize> {
+    let tens = (id[0] as char).to_digit(10)?;
+    let units = (id[1] as char).to_digit(10)?;
+    // Palette changes share the numbering but carry no media.
+    if &id[2..4] == b"pc" {
+        return None;
+    }
+    Some((tens * 10 + units) as usize)
+}
+
+/// Maps a video FourCC to a codec name.
+fn video_codec(fourcc: &[u8]) -> String {
+    let upper: Vec<u8> = fourcc.to_ascii_uppercase();
+    let name = match &upper[..] {
+        b"H264" | b"X264" | b"AVC1" | b"DAVC" => "avc1",
+        b"HEVC" | b"H265" | b"HVC1" | b"HEV1" => "hvc1",
+        b"XVID" | b"DIVX" | b"DX50" | b"FMP4" | b"MP4V" => "mp4v",
+        b"MJPG" => "mjpg",
+        b"VP80" => "vp08",
+        b"VP90" => "vp09",
+        b"AV01" => "av01",
+        _ => return String::from_utf8_lossy(fourcc).trim_end().to_string(),
+    };
+    name.to_string()
+}
+
+/// AVI parser.
+///
+/// Only `hdrl` and the indexes are read in full; `movi` lists are located by
+/// their headers. Each call re-reads the top-level structure, so a call after
+/// the source has grown picks up chunks that were previously missing.
+#[derive(Debug, Default)]
+pub(crate) struct AviParser {
+    /// Audio and video streams, in header order.
+    streams: Vec<StreamInfo>,
+    /// Stream and offset of every chunk already handed over, which later
+    /// calls skip. A newly arrived index can list chunks before them.
+    handed_over: HashSet<(u32, u64)>,
+    /// Chunks of all streams not yet handed over.
+    samples: Vec<SampleEntry>,
+}
+
+impl AviParser {
+    /// Creates a parser.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ContainerParser for AviParser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        let mut riffs = read_chunks(reader, 0, reader.len())?
+            .into_iter()
+            .filter(|c| c.id == *b"RIFF");
+        let avi = riffs
+            .next()
+            .filter(|c| c.list_type == Some(*b"AVI "))
+            .ok_or_else(|| PlayerError::invalid_format("Missing AVI RIFF header"))?;
+        let children = avi.children(reader)?;
+        let hdrl = match children.iter().find(|c| c.is_list(b"hdrl")) {
+            Some(hdrl) if reader.is_complete() || hdrl.end() <= reader.len() => {
+                hdrl.load(reader)?
+            }
+            // The header list has not fully arrived yet.
+            _ if !reader.is_complete() => return Ok(()),
+            _ => return Err(PlayerError::invalid_format("Missing AVI hdrl list")),
+        };
+        let hdrl = chunks(&hdrl, 0)
+            .next()
+            .ok_or_else(|| PlayerError::invalid_format("Missing AVI hdrl list"))?;
+
+        self.streams.clear();
+        self.samples.clear();
+        let mut headers = Vec::new();
+        for strl in hdrl.children().filter(|c| c.is_list(b"strl")) {
+            let header = parse_strl(&strl, &mut self.streams)?;
+            headers.push(header);
+        }
+
+        // Every RIFF (the first `AVI ` and any OpenDML `AVIX` extensions) has a movi list.
+        let mut movis: Vec<ChunkHeader> = children
+            .iter()
+            .filter(|c| c.is_list(b"movi"))
+            .copied()
+            .collect();
+        for riff in riffs.filter(|c| c.list_type == Some(*b"AVIX")) {
+            movis.extend(
+                riff.children(reader)?
+                    .into_iter()
+                    .filter(|c| c.is_list(b"movi")),
+            );
+        }
+
+        let refs = if headers.iter().any(|h| h.indx.is_some()) {
+            odml_index(reader, &headers)?
+        } else if let Some(idx1) = children
+            .iter()
+            .find(|c| c.id == *b"idx1" && c.end() <= reader.len())
+        {
+            let idx1 = reader
+                .bytes(idx1.payload_offset, idx1.size as usize)?
+                .to_vec();
+            let movi_base = movis.first().map_or(0, |m| m.payload_offset);
+            legacy_index(reader, &idx1, movi_base)?
+        } else {
+            Vec::new()
+        };
+        let refs = if refs.is_empty() {
+            scan_movi(reader, &movis)?
+        } else {
+            refs
+        };
+
+        // Assign timestamps by counting frames (or bytes, for constant-rate
+        // audio) per stream in index order.
+        let mut counters = vec![0u64; headers.len()];
+        for chunk in refs {
+            let header = match headers.get(chunk.stream) {
+                Some(header) => header,
+                None => continue,
+            };
+            let ticks = (header.start as u64 + counters[chunk.stream]) * header.scale as u64;
+            counters[chunk.stream] += chunk
+                .size
+                .checked_div(header.sample_size)
+                .map_or(1, |samples| samples as u64);
+            let stream_index = match header.stream_index {
+                Some(index) if chunk.size > 0 => index,
+                // Empty video chunks are dropped frames: they only advance the clock.
+                _ => continue,
+            };
+            if self.handed_over.contains(&(stream_index, chunk.offset)) {
+                continue;
+            }
+            self.samples.push(SampleEntry {
+                stream_index,
+                offset: chunk.offset,
+                size: chunk.size,
+                continuation: None,
+                dts: ticks as i64,
+                pts: ticks as i64,
+                is_keyframe: chunk.is_keyframe || header.stream_type != StreamType::Video,
+            });
+        }
+
+        for (header, count) in headers.iter().zip(&counters) {
+            if let Some(index) = header.stream_index {
+                let stream = &mut self.streams[index as usize];
+                let end = (header.start as u64 + count) * header.scale as u64;
+                stream.duration_ms = Some(to_ms(end, stream.timescale));
+            }
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        self.handed_over
+            .extend(self.samples.iter().map(|s| (s.stream_index, s.offset)));
+        Container {
+            duration_ms: self.streams.iter().filter_map(|s| s.duration_ms).max(),
+            streams: self.streams.clone(),
+            samples: std::mem::take(&mut self.samples),
+            ..Container::default()
+        }
+    }
+}
+
+/// Parses a `strl` list, registering audio and video streams in `streams`.
+fn parse_strl(strl: &Chunk<'_>, streams: &mut Vec<StreamInfo>) -> Result<StreamHeader> {
+    let strh = strl
+        .child(b"strh")
+        .filter(|c| c.payload.len() >= 48)
+        .ok_or_else(|| PlayerError::invalid_format("Invalid AVI stream header"))?;
+    let strh = strh.payload;
+    let strf = strl.child(b"strf").map(|c| c.payload).unwrap_or(&[]);
+
+    let scale = le_u32(&strh[20..]).max(1);
+    let rate = le_u32(&strh[24..]);
+    let described = match &strh[0..4] {
+        b"vids" => {
+            let fourcc = strf.get(16..20).unwrap_or(&strh[4..8]);
+            Some((StreamType::Video, video_codec(fourcc)))
+        }
+        b"auds" if strf.len() >= 2 => Some((StreamType::Audio, audio_codec(strf))),
+        _ => None,
+    };
+
+    let stream_type = described
+        .as_ref()
+        .map_or(StreamType::Video, |(kind, _)| *kind);
+    let stream_index = described.map(|(stream_type, codec)| {
+        let index = streams.len() as u32;
+        let mut info = StreamInfo::new(index, stream_type, codec, rate);
+        info.title = strl
+            .child(b"strn")
+            .map(|strn| {
+                let end = strn.payload.iter().position(|&b| b == 0);
+                String::from_utf8_lossy(&strn.payload[..end.unwrap_or(strn.payload.len())])
+                    .into_owned()
+            })
+            .filter(|title| !title.is_empty());
+        match stream_type {
+            // BITMAPINFOHEADER; a negative height marks a top-down bitmap.
+            StreamType::Video if strf.len() >= 16 => {
+                let width = le_u32(&strf[4..]) as i32;
+                let height = le_u32(&strf[8..]) as i32;
+                info.video = Some(VideoInfo {
+                    width: width.unsigned_abs(),
+                    height: height.unsigned_abs(),
+                    display_aspect_ratio: (height != 0)
+                        .then(|| width.unsigned_abs() as f64 / height.unsigned_abs() as f64),
+                    frame_rate: (rate > 0).then(|| rate as f64 / scale as f64),
+                    ..VideoInfo::default()
+                });
+            }
+            // WAVEFORMATEX, optionally extended with a speaker mask.
+            StreamType::Audio => {
+                if let Ok(format) = WaveFormat::parse(strf) {
+                    format.fill_audio_info(info.audio.insert(AudioInfo::default()));
+                    info.bitrate = (format.byte_rate > 0).then_some(format.byte_rate as u64 * 8);
+                    // PCM decoders are initialized from the format record.
+                    if format.codec().is_some() {
+                        info.extra_data = Some(strf.to_vec());
+                    }
+                }
+            }
+            _ => {}
+        }
+        streams.push(info);
+        index
+    });
+
+    Ok(StreamHeader {
+        stream_index,
+        stream_type,
+        scale,
+        start: le_u32(&strh[28..]),
+        sample_size: if stream_type == StreamType::Audio {
+            le_u32(&strh[44..])
+        } else {
+            0
+        },
+        indx: strl.child(b"indx").map(|c| c.payload.to_vec()),
+    })
+}
+
+/// Collects chunk references from the OpenDML super indexes of every stream.
+fn odml_index(reader: &mut SourceReader<'_>, headers: &[StreamHeader]) -> Result<Vec<ChunkRef>> {
+    let mut refs = Vec::new();
+    for (stream, header) in headers.iter().enumerate() {
+        let indx = match &header.indx {
+            Some(indx) if indx.len() >= 24 => indx,
+            _ => continue,
+        };
+        let entries_in_use = le_u32(&indx[4..]) as usize;
+        match indx[3] {
+            AVI_INDEX_OF_INDEXES => {
+                for entry in indx[24..].chunks_exact(16).take(entries_in_use) {
+                    if let Some(ix) = read_chunk_at(reader, le_u64(entry))? {
+                        let payload = reader.bytes(ix.payload_offset, ix.size as usize)?;
+                        standard_index(payload, stream, &mut refs);
+                    }
+                }
+            }
+            AVI_INDEX_OF_CHUNKS => standard_index(indx, stream, &mut refs),
+            _ => {}
+        }
+    }
+    // Interleave the per-stream indexes back into file order; the sort is
+    // stable so each stream keeps its index order.
+    refs.sort_by_key(|r| r.offset);
+    Ok(refs)
+}
+
+/// Appends the entries of an OpenDML standard index (`ix##`) chunk.
+fn standard_index(payload: &[u8], stream: usize, refs: &mut Vec<ChunkRef>) {
+    if payload.len() < 24 || payload[3] != AVI_INDEX_OF_CHUNKS {
+        return;
+    }
+    let entries_in_use = le_u32(&payload[4..]) as usize;
+    let base = le_u64(&payload[12..]);
+    for entry in payload[24..].chunks_exact(8).take(entries_in_use) {
+        let size = le_u32(&entry[4..]);
+        refs.push(ChunkRef {
+            stream,
+            offset: base + le_u32(entry) as u64,
+            size: size & !ODML_NON_KEYFRAME,
+            is_keyframe: size & ODML_NON_KEYFRAME == 0,
+        });
+    }
+}
+
+/// Collects chunk references from a legacy `idx1` index.
+///
+/// Offsets are relative to the `movi` form type in most files, but some
+/// writers store absolute file offsets; the first entry decides which.
+fn legacy_index(
+    reader: &mut SourceReader<'_>,
+    idx1: &[u8],
+    movi_base: u64,
+) -> Result<Vec<ChunkRef>> {
+    let entries: Vec<&[u8]> = idx1
+        .chunks_exact(16)
+        .filter(|e| &e[0..4] != b"rec ")
+        .collect();
+    let base = match entries.first() {
+        Some(first) => {
+            let relative = movi_base + le_u32(&first[8..]) as u64;
+            if reader.exact(relative, 4)? == Some(&first[0..4]) {
+                movi_base
+            } else {
+                0
+            }
+        }
+        None => return Ok(Vec::new()),
+    };
+    Ok(entries
+        .into_iter()
+        .filter_map(|e| {
+            Some(ChunkRef {
+                stream: stream_number(&e[0..4])?,
+                offset: base + le_u32(&e[8..]) as u64 + 8,
+                size: le_u32(&e[12..]),
+                is_keyframe: le_u32(&e[4..]) & AVIIF_KEYFRAME != 0,
+            })
+        })
+        .collect())
+}
+
+/// Collects chunk references by walking the `movi` lists of an unindexed file.
+///
+/// Without an index there is no keyframe information, so every chunk is
+/// treated as a random access point.
+fn scan_movi(reader: &mut SourceReader<'_>, movis: &[ChunkHeader]) -> Result<Vec<ChunkRef>> {
+    fn walk(
+        reader: &mut SourceReader<'_>,
+        list: &ChunkHeader,
+        refs: &mut Vec<ChunkRef>,
+    ) -> Result<()> {
+        for chunk in list.children(reader)? {
+            // A chunk cut off at the end of the data has not fully arrived.
+            if chunk.end() > reader.len() {
+                break;
+            }
+            if chunk.is_list(b"rec ") {
+                walk(reader, &chunk, refs)?;
+            } else if let Some(stream) = stream_number(&chunk.id) {
+                refs.push(ChunkRef {
+                    stream,
+                    offset: chunk.payload_offset,
+                    size: chunk.size as u32,
+                    is_keyframe: true,
+                });
+            }
+        }
+        Ok(())
+    }
+    let mut refs = Vec::new();
+    for movi in movis {
+        walk(reader, movi, &mut refs)?;
+    }
+    Ok(refs)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::riff::tests::{chunk, list};
+    use super::*;
+    use crate::decoder::AudioCodec;
+    use crate::demuxer::{ByteSource, MemorySource};
+
+    /// Parses a complete in-memory AVI file.
+    fn parse(data: &[u8]) -> Result<Container> {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = AviParser::new();
+        parser.parse(&mut SourceReader::new(&mut source))?;
+        Ok(parser.container())
+    }
+
+    fn strh(kind: &[u8; 4], handler: &[u8; 4], scale: u32, rate: u32, sample_size: u32) -> Vec<u8> {
+        let mut h = kind.to_vec();
+        h.extend_from_slice(handler);
+        h.extend_from_slice(&[0; 12]);
+        h.extend_from_slice(&scale.to_le_bytes());
+        h.extend_from_slice(&rate.to_le_bytes());
+        h.extend_from_slice(&[0; 16]);
+        h.extend_from_slice(&sample_size.to_le_bytes());
+        h.extend_from_slice(&[0; 8]);
+        chunk(b"strh", &h)
+    }
+
+    fn video_strl(extra: &[u8]) -> Vec<u8> {
+        let mut strf = vec![0; 16];
+        strf.extend_from_slice(b"H264");
+        strf.extend_from_slice(&[0; 20]);
+        let mut body = strh(b"vids", b"h264", 1, 25, 0);
+        body.extend(chunk(b"strf", &strf));
+        body.extend_from_slice(extra);
+        list(b"LIST", b"strl", &body)
+    }
+
+    fn audio_strl(extra: &[u8]) -> Vec<u8> {
+        // 16-bit stereo PCM at 8 kHz: four bytes per sample.
+        let strf = WaveFormat::new(AudioCodec::PcmS16le, 8000, 2).to_bytes();
+        let mut body = strh(b"auds", b"\0\0\0\0", 1, 8000, 4);
+        body.extend(chunk(b"strf", &strf));
+        body.extend_from_slice(extra);
+        list(b"LIST", b"strl", &body)
+    }
+
+    /// Chunks written into `movi`: video keyframe, audio, video delta, audio.
+    fn movi_chunks() -> Vec<u8> {
+        let mut movi = chunk(b"00dc", &[0xA0; 6]);
+        movi.extend(chunk(b"01wb", &[0xB0; 8]));
+        movi.extend(chunk(b"00dc", &[0xA1; 3]));
+        movi.extend(chunk(b"01wb", &[0xB1; 8]));
+        movi
+    }
+
+    /// Builds an AVI with an `idx1` index (offsets relative to `movi`).
+    pub(crate) fn avi_file() -> Vec<u8> {
+        let mut hdrl = chunk(b"avih", &[0; 56]);
+        hdrl.extend(video_strl(&[]));
+        hdrl.extend(audio_strl(&[]));
+        let mut body = list(b"LIST", b"hdrl", &hdrl);
+        body.extend(list(b"LIST", b"movi", &movi_chunks()));
+        let mut idx1 = Vec::new();
+        for (id, flags, offset, size) in [
+            (b"00dc", AVIIF_KEYFRAME, 4u32, 6u32),
+            (b"01wb", AVIIF_KEYFRAME, 18, 8),
+            (b"00dc", 0, 34, 3),
+            (b"01wb", AVIIF_KEYFRAME, 46, 8),
+        ] {
+            idx1.extend_from_slice(id);
+            idx1.extend_from_slice(&flags.to_le_bytes());
+            idx1.extend_from_slice(&offset.to_le_bytes());
+            idx1.extend_from_slice(&size.to_le_bytes());
+        }
+        body.extend(chunk(b"idx1", &idx1));
+        list(b"RIFF", b"AVI ", &body)
+    }
+
+    fn summary(file: &Container) -> Vec<(u32, i64, u32, bool)> {
+        file.samples
+            .iter()
+            .map(|s| (s.stream_index, s.pts, s.size, s.is_keyframe))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_idx1() {
+        let data = avi_file();
+        let file = parse(&data).unwrap();
+
+        assert_eq!(file.streams.len(), 2);
+        assert_eq!(file.streams[0].codec, "avc1");
+        assert_eq!(file.streams[0].timescale, 25);
+        assert_eq!(file.streams[1].codec, "pcm-s16");
+        assert_eq!(file.streams[1].stream_type, StreamType::Audio);
+        assert_eq!(
+            file.streams[0].video.as_ref().unwrap().frame_rate,
+            Some(25.0)
+        );
+        let audio = file.streams[1].audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (8000, 2));
+        assert_eq!(audio.channel_layout.as_deref(), Some("stereo"));
+        assert_eq!(audio.bit_depth, Some(16));
+        assert_eq!(file.streams[1].bitrate, Some(256_000));
+        assert!(file.streams[1].extra_data.is_some());
+        assert_eq!(
+            summary(&file),
+            vec![
+                (0, 0, 6, true),
+                (1, 0, 8, true),
+                (0, 1, 3, false),
+                (1, 2, 8, true),
+            ]
+        );
+        assert_eq!(&data[file.samples[2].offset as usize..][..3], &[0xA1; 3]);
+        assert_eq!(file.streams[0].duration_ms, Some(80));
+    }
+
+    #[test]
+    fn test_parse_without_index() {
+        let mut hdrl = video_strl(&[]);
+        hdrl.extend(audio_strl(&[]));
+        let mut movi = chunk(b"00dc", &[0xA0; 6]);
+        movi.extend(list(b"LIST", b"rec ", &chunk(b"01wb", &[0xB0; 8])));
+        movi.extend(chunk(b"00pc", &[0; 4]));
+        movi.extend(chunk(b"00dc", &[]));
+        movi.extend(chunk(b"00dc", &[0xA2; 2]));
+        let mut body = list(b"LIST", b"hdrl", &hdrl);
+        body.extend(list(b"LIST", b"movi", &movi));
+        let file = parse(&list(b"RIFF", b"AVI ", &body)).unwrap();
+
+        // The empty chunk is a dropped frame that still advances the clock.
+        assert_eq!(
+            summary(&file),
+            vec![(0, 0, 6, true), (1, 0, 8, true), (0, 2, 2, true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_opendml_index() {
+        // Both streams are indexed through `indx` super indexes pointing at
+        // `ix00`/`ix01` chunks; the legacy index is absent.
+        let movi = movi_chunks();
+        let ix = |stream: &[u8; 4], entries: &[(u32, u32)]| {
+            let mut p = vec![2, 0, 0, AVI_INDEX_OF_CHUNKS];
+            p.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            p.extend_from_slice(stream);
+            p.extend_from_slice(&[0; 12]);
+            for &(offset, size) in entries {
+                p.extend_from_slice(&offset.to_le_bytes());
+                p.extend_from_slice(&size.to_le_bytes());
+            }
+            p
+        };
+        let super_index = |offset: u64| {
+            let mut p = vec![4, 0, 0, AVI_INDEX_OF_INDEXES];
+            p.extend_from_slice(&1u32.to_le_bytes());
+            p.extend_from_slice(&[0; 16]);
+            p.extend_from_slice(&offset.to_le_bytes());
+            p.extend_from_slice(&[0; 8]);
+            chunk(b"indx", &p)
+        };
+
+        // Lay out the header first with placeholder offsets to learn its size.
+        let build = |ix00_at: u64, ix01_at: u64, movi_data: u64| {
+            let mut hdrl = video_strl(&super_index(ix00_at));
+            hdrl.extend(audio_strl(&super_index(ix01_at)));
+            let mut body = list(b"LIST", b"hdrl", &hdrl);
+            let mut movi_body = movi.clone();
+            let mut v = ix(b"00dc", &[(8, 6), (8 + 14 + 16, 3 | ODML_NON_KEYFRAME)]);
+            let mut a = ix(b"01wb", &[(8 + 14, 8), (8 + 14 + 16 + 12, 8)]);
+            for p in [&mut v, &mut a] {
+                p[12..20].copy_from_slice(&movi_data.to_le_bytes());
+            }
+            movi_body.extend(chunk(b"ix00", &v));
+            movi_body.extend(chunk(b"ix01", &a));
+            body.extend(list(b"LIST", b"movi", &movi_body));
+            (
+                list(b"RIFF", b"AVI ", &body),
+                12 + hdrl.len() as u64 + 12 + 12,
+            )
+        };
+        let (_, movi_data) = build(0, 0, 0);
+        let ix00_at = movi_data + movi.len() as u64;
+        let ix01_at = ix00_at + 8 + 24 + 16;
+        let (data, _) = build(ix00_at, ix01_at, movi_data);
+
+        let file = parse(&data).unwrap();
+        assert_eq!(
+            summary(&file),
+            vec![
+                (0, 0, 6, true),
+                (1, 0, 8, true),
+                (0, 1, 3, false),
+                (1, 2, 8, true),
+            ]
+        );
+        assert_eq!(&data[file.samples[3].offset as usize..][..8], &[0xB1; 8]);
+
+        // Only the video index has arrived; the audio chunks it precedes in
+        // the file follow with their index.
+        let mut source = MemorySource::new(data[..ix01_at as usize].to_vec());
+        let mut parser = AviParser::new();
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        assert_eq!(
+            summary(&parser.container()),
+            vec![(0, 0, 6, true), (0, 1, 3, false)]
+        );
+        source.append(&data[ix01_at as usize..]).unwrap();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        assert_eq!(
+            summary(&parser.container()),
+            vec![(1, 0, 8, true), (1, 2, 8, true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_non_avi() {
+        assert!(parse(&list(b"RIFF", b"WAVE", &[])).is_err());
+    }
+}
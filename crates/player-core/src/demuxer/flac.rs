@@ -0,0 +1,424 @@
+//! Native FLAC stream parsing.
+//!
+//! Reads the metadata blocks that follow the `fLaC` marker, then splits the
+//! frames, which carry no length field: a frame ends where the next frame
+//! header of the stream starts, once that header passes its CRC-8 and
+//! continues the numbering, and the CRC-16 of the bytes before it checks
+//! out.
+
+use super::source::SourceReader;
+use super::{AudioInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType};
+use crate::decoder::flac::{
+    self, FlacStreamInfo, FrameHeader, MetadataBlockHeader, BLOCK_STREAMINFO, MAX_HEADER_LEN,
+    METADATA_HEADER_LEN, STREAM_MARKER,
+};
+use crate::decoder::mp3;
+use crate::error::{PlayerError, Result};
+
+/// Bytes scanned per read when resynchronizing on a frame header.
+const RESYNC_WINDOW: usize = 64 * 1024;
+/// Bytes first searched for the end of a frame when STREAMINFO does not
+/// give the largest frame size.
+const FRAME_WINDOW: usize = 64 * 1024;
+/// Longest frame searched for: 65535 verbatim samples of eight 32-bit
+/// channels fit in 2 MiB.
+const MAX_FRAME_LEN: usize = 4 << 20;
+/// Length of an ID3v2 tag header.
+const ID3V2_HEADER_LEN: usize = 10;
+
+/// Returns whether `data` starts with the FLAC stream marker, possibly
+/// after an ID3v2 tag.
+pub(crate) fn probe(data: &[u8]) -> bool {
+    let start = mp3::id3v2_len(data).unwrap_or(0);
+    data.get(start..)
+        .is_some_and(|rest| rest.starts_with(&STREAM_MARKER))
+}
+
+/// Outcome of the search for the end of a frame.
+enum FrameEnd {
+    /// The frame has this length.
+    Found(usize),
+    /// The end lies beyond the data received so far.
+    Pending,
+    /// No valid end was found; the frame is damaged.
+    Damaged,
+}
+
+/// Incremental FLAC parser.
+#[derive(Debug, Default)]
+pub(crate) struct FlacParser {
+    /// Offset of the next metadata block or frame.
+    pos: u64,
+    /// Whether the last metadata block has been read.
+    frames_started: bool,
+    /// Parsed STREAMINFO.
+    info: Option<FlacStreamInfo>,
+    /// Raw STREAMINFO payload, kept for the initialization data.
+    streaminfo: Vec<u8>,
+    /// The single audio stream, once the metadata has been read.
+    stream: Option<StreamInfo>,
+    /// Header of the last frame; the next one must continue its numbering.
+    last: Option<FrameHeader>,
+    /// Number of the sample after the last frame.
+    end_sample: u64,
+    /// Frames not yet handed over.
+    samples: Vec<SampleEntry>,
+}
+
+impl FlacParser {
+    /// Creates a parser positioned at the start of the file.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the stream marker and metadata blocks; returns whether the
+    /// last block has been read.
+    fn read_metadata(&mut self, reader: &mut SourceReader<'_>) -> Result<bool> {
+        if self.pos == 0 {
+            let head = reader.bytes(0, ID3V2_HEADER_LEN)?;
+            let start = mp3::id3v2_len(head).unwrap_or(0) as u64;
+            match reader.exact(start, STREAM_MARKER.len())? {
+                Some(marker) if marker == STREAM_MARKER => self.pos = start + 4,
+                Some(_) => return Err(PlayerError::invalid_format("Missing FLAC stream marker")),
+                None => return Ok(false),
+            }
+        }
+        loop {
+            let header = match reader.exact(self.pos, METADATA_HEADER_LEN)? {
+                Some(head) => MetadataBlockHeader::parse(head)
+                    .ok_or_else(|| PlayerError::invalid_format("Invalid FLAC metadata block"))?,
+                None => return Ok(false),
+            };
+            let payload_offset = self.pos + METADATA_HEADER_LEN as u64;
+            if self.info.is_none() && header.kind != BLOCK_STREAMINFO {
+                return Err(PlayerError::invalid_format(
+                    "FLAC stream does not start with STREAMINFO",
+                ));
+            }
+            if self.info.is_none() {
+                let payload = match reader.exact(payload_offset, header.len)? {
+                    Some(payload) => payload,
+                    None => return Ok(false),
+                };
+                self.info = Some(
+                    FlacStreamInfo::parse_streaminfo(payload)
+                        .map_err(|_| PlayerError::invalid_format("Invalid FLAC STREAMINFO"))?,
+                );
+                self.streaminfo = payload.to_vec();
+            }
+            self.pos = payload_offset + header.len as u64;
+            if header.last {
+                break;
+            }
+        }
+
+        self.frames_started = true;
+        if let Some(info) = &self.info {
+            let mut stream = StreamInfo::new(0, StreamType::Audio, "fLaC", info.sample_rate);
+            info.fill_audio_info(stream.audio.insert(AudioInfo::default()));
+            let mut extra_data = STREAM_MARKER.to_vec();
+            extra_data.push(0x80 | BLOCK_STREAMINFO);
+            extra_data.extend(&(self.streaminfo.len() as u32).to_be_bytes()[1..]);
+            extra_data.extend(&self.streaminfo);
+            stream.extra_data = Some(extra_data);
+            self.stream = Some(stream);
+        }
+        Ok(true)
+    }
+
+    /// Parses a frame header belonging to this stream.
+    fn frame_header(&self, data: &[u8]) -> Option<FrameHeader> {
+        let info = self.info.as_ref()?;
+        FrameHeader::parse(data).ok().filter(|h| {
+            h.channels() == info.channels
+                && h.sample_rate.is_none_or(|rate| rate == info.sample_rate)
+                && h.bits_per_sample
+                    .is_none_or(|bits| bits == info.bits_per_sample)
+                && self
+                    .last
+                    .is_none_or(|last| last.variable_block_size == h.variable_block_size)
+        })
+    }
+
+    /// Finds the end of the frame at `pos`, whose header is `header`.
+    fn frame_end(
+        &self,
+        reader: &mut SourceReader<'_>,
+        header: &FrameHeader,
+        complete: bool,
+    ) -> Result<FrameEnd> {
+        let info = match &self.info {
+            Some(info) => info,
+            None => return Ok(FrameEnd::Damaged),
+        };
+        let mut want = match info.max_frame_size as usize {
+            0 => FRAME_WINDOW,
+            max => max + MAX_HEADER_LEN,
+        };
+        loop {
+            let window = reader.bytes(self.pos, want)?;
+            let mut crc = 0;
+            let mut checked = 0;
+            for i in header.header_len..window.len().saturating_sub(1) {
+                if window[i] != 0xFF || window[i + 1] & 0xFE != 0xF8 {
+                    continue;
+                }
+                crc = flac::crc16(crc, &window[checked..i]);
+                checked = i;
+                let continues = || {
+                    self.frame_header(&window[i..])
+                        .is_some_and(|next| next.number == header.next_number())
+                };
+                if crc == 0 && continues() {
+                    return Ok(FrameEnd::Found(i));
+                }
+            }
+            let at_end = window.len() < want;
+            if at_end && !complete {
+                return Ok(FrameEnd::Pending);
+            }
+            if at_end || want >= MAX_FRAME_LEN {
+                // The last frame, or one followed by a damaged header:
+                // decoding it gives its length.
+                let mut samples = Vec::new();
+                return Ok(match flac::decode_frame(window, Some(info), &mut samples) {
+                    Ok(frame) => FrameEnd::Found(frame.frame_len),
+                    Err(_) => FrameEnd::Damaged,
+                });
+            }
+            want = (want * 2).min(MAX_FRAME_LEN);
+        }
+    }
+
+    /// Advances `pos` to the next frame header; returns `false` if there is
+    /// none in the available data.
+    fn resync(&mut self, reader: &mut SourceReader<'_>) -> Result<bool> {
+        loop {
+            let window = reader.bytes(self.pos + 1, RESYNC_WINDOW)?;
+            if window.len() < MAX_HEADER_LEN {
+                return Ok(false);
+            }
+            let found = (0..=window.len() - MAX_HEADER_LEN)
+                .find(|&i| self.frame_header(&window[i..]).is_some());
+            match found {
+                Some(skip) => {
+                    self.pos += 1 + skip as u64;
+                    return Ok(true);
+                }
+                None => self.pos += (window.len() - (MAX_HEADER_LEN - 1)) as u64,
+            }
+        }
+    }
+}
+
+impl ContainerParser for FlacParser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        if !self.frames_started && !self.read_metadata(reader)? {
+            return Ok(());
+        }
+        let fixed_block_size = match &self.info {
+            Some(info) if info.is_fixed_block_size() => Some(info.max_block_size as u32),
+            _ => None,
+        };
+        let complete = reader.is_complete();
+        loop {
+            let head = reader.bytes(self.pos, MAX_HEADER_LEN)?;
+            if head.is_empty() {
+                break;
+            }
+            let header = match self.frame_header(head) {
+                Some(header) => header,
+                // The header may be cut off by the end of the data so far.
+                None if head.len() < MAX_HEADER_LEN && !complete => break,
+                None => {
+                    if self.resync(reader)? {
+                        continue;
+                    }
+                    break;
+                }
+            };
+            let len = match self.frame_end(reader, &header, complete)? {
+                FrameEnd::Found(len) => len,
+                FrameEnd::Pending => break,
+                FrameEnd::Damaged => {
+                    if self.resync(reader)? {
+                        continue;
+                    }
+                    break;
+                }
+            };
+
+            let first = header.first_sample(fixed_block_size.unwrap_or(header.block_size));
+            let pts = first as i64;
+            self.samples.push(SampleEntry {
+                stream_index: 0,
+                offset: self.pos,
+                size: len as u32,
+                continuation: None,
+                dts: pts,
+                pts,
+                is_keyframe: true,
+            });
+            self.end_sample = self.end_sample.max(first + header.block_size as u64);
+            self.last = Some(header);
+            self.pos += len as u64;
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        let mut streams: Vec<StreamInfo> = self.stream.iter().cloned().collect();
+        if let (Some(stream), Some(info)) = (streams.first_mut(), &self.info) {
+            let samples = info.total_samples.unwrap_or(self.end_sample);
+            stream.duration_ms = Some(super::to_ms(samples, info.sample_rate));
+        }
+        Container {
+            duration_ms: streams.first().and_then(|s| s.duration_ms),
+            streams,
+            samples: std::mem::take(&mut self.samples),
+            ..Container::default()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::decoder::flac::tests::{constant_frame, streaminfo};
+    use crate::demuxer::mp3::tests::id3v2_tag;
+    use crate::demuxer::{ByteSource, MemorySource};
+
+    /// Parses a complete in-memory FLAC file.
+    fn parse(data: &[u8]) -> Container {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = FlacParser::new();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        parser.container()
+    }
+
+    /// Builds the metadata of a 16-bit mono 44.1 kHz stream of 4410-sample
+    /// blocks: STREAMINFO, a SEEKTABLE with one point at the third frame,
+    /// and padding, of which only STREAMINFO is read.
+    fn metadata(total_samples: u64) -> Vec<u8> {
+        let mut data = STREAM_MARKER.to_vec();
+        data.extend([BLOCK_STREAMINFO, 0, 0, 34]);
+        data.extend(streaminfo(4410, 44100, 1, 16, total_samples));
+        data.extend([3, 0, 0, 18]);
+        data.extend(8820u64.to_be_bytes());
+        data.extend((2 * constant_frame(0, 4410, 0).len() as u64).to_be_bytes());
+        data.extend(4410u16.to_be_bytes());
+        data.extend([0x81, 0, 0, 8]);
+        data.extend([0; 8]);
+        data
+    }
+
+    /// Builds a file of four frames of 4410 samples after an ID3v2 tag.
+    pub(crate) fn flac_file() -> Vec<u8> {
+        let mut data = id3v2_tag(20);
+        data.extend(metadata(4 * 4410));
+        for number in 0..4 {
+            data.extend(constant_frame(number, 4410, number as i16 * 100));
+        }
+        data
+    }
+
+    #[test]
+    fn test_probe() {
+        assert!(probe(&flac_file()));
+        assert!(probe(&flac_file()[30..]));
+        assert!(!probe(&id3v2_tag(20)));
+        assert!(!probe(b"OggS\0\x02\0\0\0\0\0\0"));
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let data = flac_file();
+        let file = parse(&data);
+
+        assert_eq!(file.streams.len(), 1);
+        let stream = &file.streams[0];
+        assert_eq!(stream.codec, "fLaC");
+        assert_eq!(stream.timescale, 44100);
+        let audio = stream.audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (44100, 1));
+        assert_eq!(audio.bit_depth, Some(16));
+        // STREAMINFO alone, flagged as the last block.
+        let extra_data = stream.extra_data.as_deref().unwrap();
+        assert_eq!(extra_data.len(), 42);
+        assert_eq!(&extra_data[..4], &data[30..34]);
+        assert_eq!(extra_data[4], 0x80 | BLOCK_STREAMINFO);
+        assert_eq!(&extra_data[5..], &data[35..72]);
+        FlacStreamInfo::parse(extra_data).unwrap();
+        assert_eq!(file.duration_ms, Some(400));
+
+        let frame_len = constant_frame(0, 4410, 0).len() as u64;
+        let samples: Vec<(u64, i64, u32)> = file
+            .samples
+            .iter()
+            .map(|s| (s.offset, s.pts, s.size))
+            .collect();
+        let first = 30 + metadata(0).len() as u64;
+        let expected: Vec<(u64, i64, u32)> = (0..4)
+            .map(|i| (first + i * frame_len, i as i64 * 4410, frame_len as u32))
+            .collect();
+        assert_eq!(samples, expected);
+        assert!(file.samples.iter().all(|s| s.is_keyframe));
+    }
+
+    #[test]
+    fn test_damaged_frames_and_trailing_tag() {
+        let mut data = metadata(0);
+        let first = data.len();
+        data.extend(constant_frame(0, 4410, 1));
+        // A damaged frame, then a false sync.
+        let mut damaged = constant_frame(1, 4410, 2);
+        damaged[9] ^= 0x40;
+        data.extend(damaged);
+        data.extend([0xFF, 0xF8, 0x12]);
+        data.extend(constant_frame(2, 4410, 3));
+        data.extend(constant_frame(3, 4410, 4));
+        data.extend(b"TAG");
+        data.resize(data.len() + 125, 0);
+        let file = parse(&data);
+
+        let frame_len = constant_frame(0, 4410, 0).len();
+        let offsets: Vec<u64> = file.samples.iter().map(|s| s.offset).collect();
+        let expected = [0, 2 * frame_len + 3, 3 * frame_len + 3];
+        assert_eq!(offsets, expected.map(|o| (first + o) as u64));
+        assert_eq!(file.samples[1].pts, 8820);
+        // Without a sample count, the duration runs to the last frame.
+        assert_eq!(file.duration_ms, Some(400));
+    }
+
+    #[test]
+    fn test_parse_growing_source() {
+        let data = flac_file();
+        let mut source = MemorySource::new(data[..60].to_vec());
+        let mut parser = FlacParser::new();
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        assert!(parser.container().streams.is_empty());
+
+        // The metadata and the first frame, which only ends once the next
+        // header arrives.
+        let split = 30 + metadata(0).len() + constant_frame(0, 4410, 0).len() + 4;
+        source.append(&data[60..split]).unwrap();
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        assert_eq!(parser.container().streams.len(), 1);
+        assert!(parser.container().samples.is_empty());
+
+        source.append(&data[split..]).unwrap();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        assert_eq!(parser.container().samples.len(), 4);
+    }
+
+    #[test]
+    fn test_missing_streaminfo() {
+        let mut source = MemorySource::new(b"fLaC\x81\0\0\x08\0\0\0\0\0\0\0\0".to_vec());
+        let mut parser = FlacParser::new();
+        assert!(parser.parse(&mut SourceReader::new(&mut source)).is_err());
+    }
+}
@@ -0,0 +1,469 @@
+//! Fragmented MP4 (`moof`/`mdat`) parsing.
+//!
+//! Expands `trun` boxes into samples using the defaults from `trex` and
+//! `tfhd`, with decode times anchored by `tfdt`. `sidx` durations are used
+//! when the movie header does not carry one.
+
+use std::collections::HashMap;
+
+use super::mp4::{EditList, Mp4Box, Mp4Movie};
+use super::reader::ByteReader;
+use super::{to_ms, SampleEntry};
+use crate::error::{PlayerError, Result};
+
+/// `tfhd` flag: explicit base data offset.
+const TFHD_BASE_DATA_OFFSET: u32 = 0x00_0001;
+/// `tfhd` flag: explicit sample description index.
+const TFHD_SAMPLE_DESCRIPTION_INDEX: u32 = 0x00_0002;
+/// `tfhd` flag: explicit default sample duration.
+const TFHD_DEFAULT_DURATION: u32 = 0x00_0008;
+/// `tfhd` flag: explicit default sample size.
+const TFHD_DEFAULT_SIZE: u32 = 0x00_0010;
+/// `tfhd` flag: explicit default sample flags.
+const TFHD_DEFAULT_FLAGS: u32 = 0x00_0020;
+/// `tfhd` flag: base data offset is the start of the `moof`.
+const TFHD_DEFAULT_BASE_IS_MOOF: u32 = 0x02_0000;
+
+/// `trun` flag: data offset present.
+const TRUN_DATA_OFFSET: u32 = 0x00_0001;
+/// `trun` flag: first sample flags present.
+const TRUN_FIRST_SAMPLE_FLAGS: u32 = 0x00_0004;
+/// `trun` flag: per-sample duration present.
+const TRUN_SAMPLE_DURATION: u32 = 0x00_0100;
+/// `trun` flag: per-sample size present.
+const TRUN_SAMPLE_SIZE: u32 = 0x00_0200;
+/// `trun` flag: per-sample flags present.
+const TRUN_SAMPLE_FLAGS: u32 = 0x00_0400;
+/// `trun` flag: per-sample composition time offset present.
+const TRUN_SAMPLE_CTO: u32 = 0x00_0800;
+
+/// `sample_is_non_sync_sample` bit of the sample flags.
+const SAMPLE_IS_NON_SYNC: u32 = 0x0001_0000;
+
+/// Per-track sample defaults from `trex`, possibly overridden by `tfhd`.
+#[derive(Debug, Clone, Copy, Default)]
+struct SampleDefaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+/// Fragment state of one track.
+#[derive(Debug, Clone)]
+struct TrackFragments {
+    /// Index of the track in `Mp4Movie::tracks`.
+    track_index: usize,
+    defaults: SampleDefaults,
+    /// Decode time following the last parsed sample.
+    next_dts: i64,
+    /// Edit list of the track from `moov`.
+    edits: EditList,
+}
+
+/// Incrementally parses movie fragments into the tracks of a movie.
+#[derive(Debug)]
+pub(crate) struct FragmentParser {
+    /// Fragment state keyed by track ID.
+    tracks: HashMap<u32, TrackFragments>,
+    /// Fragment duration from `mehd`, in movie timescale.
+    fragment_duration: Option<u64>,
+    /// Total duration of the first `sidx`, in milliseconds.
+    sidx_duration_ms: Option<u64>,
+}
+
+impl FragmentParser {
+    /// Creates a parser from the `mvex` box of a movie.
+    pub(crate) fn new(mvex: &Mp4Box<'_>, movie: &Mp4Movie) -> Result<Self> {
+        let mut tracks = HashMap::new();
+        let mut fragment_duration = None;
+
+        for b in mvex.children() {
+            let mut r = ByteReader::new(b.payload);
+            match &b.kind {
+                b"trex" => {
+                    r.skip(4)?;
+                    let track_id = r.u32()?;
+                    r.skip(4)?; // default_sample_description_index
+                    let defaults = SampleDefaults {
+                        duration: r.u32()?,
+                        size: r.u32()?,
+                        flags: r.u32()?,
+                    };
+                    if let Some(track_index) =
+                        movie.tracks.iter().position(|t| t.track_id == track_id)
+                    {
+                        let track = &movie.tracks[track_index];
+                        tracks.insert(
+                            track_id,
+                            TrackFragments {
+                                track_index,
+                                defaults,
+                                next_dts: track.end_dts,
+                                edits: track.edits.clone(),
+                            },
+                        );
+                    }
+                }
+                b"mehd" => {
+                    let version = r.u8()?;
+                    r.skip(3)?;
+                    fragment_duration = Some(if version == 1 {
+                        r.u64()?
+                    } else {
+                        r.u32()? as u64
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            tracks,
+            fragment_duration,
+            sidx_duration_ms: None,
+        })
+    }
+
+    /// Parses a `moof` box, appending its samples to the movie tracks.
+    ///
+    /// Sample data must end by `data_end`, the end of the `mdat` following
+    /// the fragment; track fragments that run past it are dropped.
+    pub(crate) fn parse_moof(
+        &mut self,
+        moof: &Mp4Box<'_>,
+        data_end: u64,
+        movie: &mut Mp4Movie,
+    ) -> Result<()> {
+        // Without an explicit base, the first traf is relative to the moof and
+        // later ones continue where the previous traf's data ended.
+        let mut previous_end = moof.offset;
+        for traf in moof.children().filter(|b| &b.kind == b"traf") {
+            previous_end = self.parse_traf(&traf, moof.offset, previous_end, data_end, movie)?;
+        }
+        Ok(())
+    }
+
+    /// Parses a `traf` box and returns the end offset of its sample data.
+    fn parse_traf(
+        &mut self,
+        traf: &Mp4Box<'_>,
+        moof_offset: u64,
+        previous_end: u64,
+        data_limit: u64,
+        movie: &mut Mp4Movie,
+    ) -> Result<u64> {
+        let tfhd = traf
+            .child(b"tfhd")
+            .ok_or_else(|| PlayerError::demuxer("Track fragment is missing tfhd box"))?;
+        let mut r = ByteReader::new(tfhd.payload);
+        let flags = r.u32()? & 0x00FF_FFFF;
+        let track_id = r.u32()?;
+
+        let state = match self.tracks.get_mut(&track_id) {
+            Some(state) => state,
+            // Fragments of tracks we don't expose are skipped.
+            None => return Ok(previous_end),
+        };
+
+        let mut base_offset = if flags & TFHD_DEFAULT_BASE_IS_MOOF != 0 {
+            moof_offset
+        } else {
+            previous_end
+        };
+        if flags & TFHD_BASE_DATA_OFFSET != 0 {
+            base_offset = r.u64()?;
+        }
+        if flags & TFHD_SAMPLE_DESCRIPTION_INDEX != 0 {
+            r.skip(4)?;
+        }
+        let mut defaults = state.defaults;
+        if flags & TFHD_DEFAULT_DURATION != 0 {
+            defaults.duration = r.u32()?;
+        }
+        if flags & TFHD_DEFAULT_SIZE != 0 {
+            defaults.size = r.u32()?;
+        }
+        if flags & TFHD_DEFAULT_FLAGS != 0 {
+            defaults.flags = r.u32()?;
+        }
+
+        if let Some(tfdt) = traf.child(b"tfdt") {
+            let mut r = ByteReader::new(tfdt.payload);
+            let version = r.u8()?;
+            r.skip(3)?;
+            let base = if version == 1 {
+                r.u64()?
+            } else {
+                r.u32()? as u64
+            };
+            state.next_dts = base as i64;
+        }
+
+        let stream_index = state.track_index as u32;
+        let mut samples = Vec::new();
+        let mut next_dts = state.next_dts;
+        let mut data_end = base_offset;
+
+        for trun in traf.children().filter(|b| &b.kind == b"trun") {
+            let mut r = ByteReader::new(trun.payload);
+            let version = r.u8()?;
+            let flags = r.u24()?;
+            let count = r.u32()?;
+            let offset = if flags & TRUN_DATA_OFFSET != 0 {
+                base_offset.checked_add_signed(r.u32()? as i32 as i64)
+            } else {
+                Some(data_end)
+            };
+            // Every sample takes at least one byte of the mdat.
+            let mut offset = match offset {
+                Some(offset) if count as u64 <= data_limit.saturating_sub(offset) => offset,
+                _ => return Ok(previous_end),
+            };
+            let first_flags = if flags & TRUN_FIRST_SAMPLE_FLAGS != 0 {
+                Some(r.u32()?)
+            } else {
+                None
+            };
+
+            for i in 0..count {
+                let duration = if flags & TRUN_SAMPLE_DURATION != 0 {
+                    r.u32()?
+                } else {
+                    defaults.duration
+                };
+                let size = if flags & TRUN_SAMPLE_SIZE != 0 {
+                    r.u32()?
+                } else {
+                    defaults.size
+                };
+                let sample_flags = if flags & TRUN_SAMPLE_FLAGS != 0 {
+                    r.u32()?
+                } else if i == 0 {
+                    first_flags.unwrap_or(defaults.flags)
+                } else {
+                    defaults.flags
+                };
+                let cto = if flags & TRUN_SAMPLE_CTO != 0 {
+                    let raw = r.u32()?;
+                    if version == 0 {
+                        raw as i64
+                    } else {
+                        raw as i32 as i64
+                    }
+                } else {
+                    0
+                };
+
+                let mut sample = SampleEntry {
+                    stream_index,
+                    offset,
+                    size,
+                    dts: next_dts,
+                    pts: next_dts + cto,
+                    is_keyframe: sample_flags & SAMPLE_IS_NON_SYNC == 0,
+                    continuation: None,
+                };
+                state.edits.apply(&mut sample);
+                samples.push(sample);
+                offset = match offset.checked_add(size as u64) {
+                    Some(end) if end <= data_limit => end,
+                    _ => return Ok(previous_end),
+                };
+                next_dts += duration as i64;
+            }
+            data_end = offset;
+        }
+
+        state.next_dts = next_dts;
+        movie.tracks[state.track_index].samples.extend(samples);
+        Ok(data_end)
+    }
+
+    /// Parses a `sidx` box, recording the total indexed duration.
+    pub(crate) fn parse_sidx(&mut self, sidx: &Mp4Box<'_>) -> Result<()> {
+        if self.sidx_duration_ms.is_some() {
+            return Ok(());
+        }
+        let mut r = ByteReader::new(sidx.payload);
+        let version = r.u8()?;
+        r.skip(3)?;
+        r.skip(4)?; // reference_ID
+        let timescale = r.u32()?;
+        r.skip(if version == 0 { 8 } else { 16 })?;
+        r.skip(2)?;
+        let count = r.u16()?;
+
+        let mut total = 0u64;
+        for _ in 0..count {
+            r.skip(4)?; // reference_type + referenced_size
+            total += r.u32()? as u64;
+            r.skip(4)?; // SAP fields
+        }
+        self.sidx_duration_ms = Some(to_ms(total, timescale));
+        Ok(())
+    }
+
+    /// Fills in durations that the movie header leaves unspecified.
+    pub(crate) fn finish(&self, movie: &mut Mp4Movie) {
+        for state in self.tracks.values() {
+            let track = &mut movie.tracks[state.track_index];
+            if track.duration_ms.unwrap_or(0) == 0 && state.next_dts > 0 {
+                track.duration_ms = Some(to_ms(state.next_dts as u64, track.timescale));
+            }
+        }
+
+        let fragment_ms = self.fragment_duration.map(|d| to_ms(d, movie.timescale));
+        if movie.duration_ms.unwrap_or(0) == 0 {
+            movie.duration_ms = fragment_ms
+                .or(self.sidx_duration_ms)
+                .or_else(|| movie.tracks.iter().filter_map(|t| t.duration_ms).max());
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::demuxer::mp4::tests::{
+        ftyp, full_box, mp4_box, mvhd, parse, stco, stsc, stsz, stts, TrakSpec,
+    };
+
+    pub(crate) fn trex(track_id: u32, duration: u32, size: u32, flags: u32) -> Vec<u8> {
+        let mut p = track_id.to_be_bytes().to_vec();
+        p.extend_from_slice(&1u32.to_be_bytes());
+        p.extend_from_slice(&duration.to_be_bytes());
+        p.extend_from_slice(&size.to_be_bytes());
+        p.extend_from_slice(&flags.to_be_bytes());
+        full_box(b"trex", 0, &p)
+    }
+
+    /// Builds a `moof` with one `traf` (default-base-is-moof) whose single
+    /// `trun` lists `(duration, size, flags)` per sample.
+    pub(crate) fn moof(
+        track_id: u32,
+        base_dts: u64,
+        data_offset: u32,
+        samples: &[(u32, u32, u32)],
+    ) -> Vec<u8> {
+        let mut tfhd = vec![0, 0x02, 0, 0];
+        tfhd.extend_from_slice(&track_id.to_be_bytes());
+        let tfhd = mp4_box(b"tfhd", &tfhd);
+        let tfdt = full_box(b"tfdt", 1, &base_dts.to_be_bytes());
+
+        let mut trun = vec![0, 0, 0x07, 0x01];
+        trun.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+        trun.extend_from_slice(&data_offset.to_be_bytes());
+        for &(duration, size, flags) in samples {
+            trun.extend_from_slice(&duration.to_be_bytes());
+            trun.extend_from_slice(&size.to_be_bytes());
+            trun.extend_from_slice(&flags.to_be_bytes());
+        }
+        let trun = mp4_box(b"trun", &trun);
+
+        let mut traf = tfhd;
+        traf.extend(tfdt);
+        traf.extend(trun);
+        let mut moof = full_box(b"mfhd", 0, &1u32.to_be_bytes());
+        moof.extend(mp4_box(b"traf", &traf));
+        mp4_box(b"moof", &moof)
+    }
+
+    /// Builds a fragmented file with a single video track and two fragments.
+    pub(crate) fn fragmented_mp4() -> Vec<u8> {
+        let mut empty_stbl = stsz(&[]);
+        empty_stbl.extend(stco(&[]));
+        empty_stbl.extend(stsc(&[]));
+        empty_stbl.extend(stts(&[]));
+        let mut moov = mvhd(1000, 0);
+        moov.extend(
+            TrakSpec {
+                timescale: 1000,
+                stbl: empty_stbl,
+                ..TrakSpec::video(b"avc1")
+            }
+            .build(),
+        );
+        moov.extend(mp4_box(b"mvex", &trex(1, 40, 0, SAMPLE_IS_NON_SYNC)));
+
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+        for (fragment, base_dts) in [(0u8, 0u64), (1, 80)] {
+            let samples = [(40, 3, 0), (40, 2, SAMPLE_IS_NON_SYNC)];
+            let moof_len = moof(1, base_dts, 0, &samples).len() as u32;
+            data.extend(moof(1, base_dts, moof_len + 8, &samples));
+            let fill = 0x10 * (fragment + 1);
+            data.extend(mp4_box(b"mdat", &[fill, fill, fill, fill + 1, fill + 1]));
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_fragments() {
+        let movie = parse(&fragmented_mp4()).unwrap();
+        let track = &movie.tracks[0];
+        let summary: Vec<(i64, u32, bool)> = track
+            .samples
+            .iter()
+            .map(|s| (s.dts, s.size, s.is_keyframe))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(0, 3, true), (40, 2, false), (80, 3, true), (120, 2, false)]
+        );
+        assert_eq!(track.duration_ms, Some(160));
+        assert_eq!(movie.duration_ms, Some(160));
+    }
+
+    #[test]
+    fn test_fragment_past_mdat_dropped() {
+        // A trun declaring far more default-sized samples than its mdat holds.
+        let mut tfhd = vec![0, 0x02, 0, 0];
+        tfhd.extend_from_slice(&1u32.to_be_bytes());
+        let mut trun = vec![0, 0, 0, 0x01];
+        trun.extend_from_slice(&u32::MAX.to_be_bytes());
+        trun.extend_from_slice(&0u32.to_be_bytes());
+        let mut traf = mp4_box(b"tfhd", &tfhd);
+        traf.extend(mp4_box(b"trun", &trun));
+        let mut data = fragmented_mp4();
+        data.extend(mp4_box(b"moof", &mp4_box(b"traf", &traf)));
+        data.extend(mp4_box(b"mdat", &[0; 4]));
+
+        // A base data offset that overflows once the samples are added.
+        let mut tfhd = vec![0, 0, 0, 0x01];
+        tfhd.extend_from_slice(&1u32.to_be_bytes());
+        tfhd.extend_from_slice(&(u64::MAX - 2).to_be_bytes());
+        let mut trun = vec![0, 0, 0x02, 0x01];
+        trun.extend_from_slice(&2u32.to_be_bytes());
+        trun.extend_from_slice(&0u32.to_be_bytes());
+        trun.extend_from_slice(&[0, 0, 0, 2, 0, 0, 0, 2]);
+        let mut traf = mp4_box(b"tfhd", &tfhd);
+        traf.extend(mp4_box(b"trun", &trun));
+        data.extend(mp4_box(b"moof", &mp4_box(b"traf", &traf)));
+
+        let movie = parse(&data).unwrap();
+        assert_eq!(movie.tracks[0].samples.len(), 4);
+        assert_eq!(movie.duration_ms, Some(160));
+    }
+
+    #[test]
+    fn test_sidx_duration() {
+        let mut p = 1u32.to_be_bytes().to_vec();
+        p.extend_from_slice(&90000u32.to_be_bytes());
+        p.extend_from_slice(&[0; 8]);
+        p.extend_from_slice(&[0, 0, 0, 2]);
+        for _ in 0..2 {
+            p.extend_from_slice(&100u32.to_be_bytes());
+            p.extend_from_slice(&180000u32.to_be_bytes());
+            p.extend_from_slice(&0x9000_0000u32.to_be_bytes());
+        }
+        let sidx = full_box(b"sidx", 0, &p);
+        let b = crate::demuxer::mp4::boxes(&sidx, 0).next().unwrap();
+
+        let movie = Mp4Movie::default();
+        let mvex = mp4_box(b"mvex", &[]);
+        let mvex = crate::demuxer::mp4::boxes(&mvex, 0).next().unwrap();
+        let mut parser = FragmentParser::new(&mvex, &movie).unwrap();
+        parser.parse_sidx(&b).unwrap();
+        assert_eq!(parser.sidx_duration_ms, Some(4000));
+    }
+}
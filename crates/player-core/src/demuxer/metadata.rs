@@ -0,0 +1,164 @@
+//! Container-level metadata: descriptive tags, embedded files and chapters.
+
+use serde::{Deserialize, Serialize};
+
+/// Descriptive tags and attachments of a container.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    /// Title of the work.
+    pub title: Option<String>,
+    /// Performer or author.
+    pub artist: Option<String>,
+    /// Album or collection the work belongs to.
+    pub album: Option<String>,
+    /// Release or recording date, as written in the file.
+    pub date: Option<String>,
+    /// Application or library that wrote the file.
+    pub encoder: Option<String>,
+    /// Free-form comment.
+    pub comment: Option<String>,
+    /// Embedded files such as cover art and fonts.
+    pub attachments: Vec<Attachment>,
+}
+
+impl Metadata {
+    /// Returns the first attachment marked as cover art.
+    pub fn cover(&self) -> Option<&Attachment> {
+        self.attachments.iter().find(|a| a.is_cover)
+    }
+}
+
+/// File embedded in the container.
+///
+/// Only the location of the data is recorded; the bytes are read from the
+/// source on request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    /// File name, if stored.
+    pub name: Option<String>,
+    /// MIME type of the data.
+    pub mime_type: String,
+    /// Description, if stored.
+    pub description: Option<String>,
+    /// Whether this is cover art for the file.
+    pub is_cover: bool,
+    /// Size of the data in bytes.
+    pub size: u64,
+    /// Absolute file offset of the data.
+    #[serde(skip)]
+    pub(crate) offset: u64,
+}
+
+/// Chapter of the media timeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chapter {
+    /// Start time in milliseconds.
+    pub start_ms: u64,
+    /// End time in milliseconds, if known.
+    pub end_ms: Option<u64>,
+    /// Display title, the first of `titles` when several languages are stored.
+    pub title: Option<String>,
+    /// Titles in every stored language.
+    pub titles: Vec<ChapterTitle>,
+    /// Nesting depth, 0 for top-level chapters.
+    pub depth: u32,
+}
+
+/// Chapter title in one language.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterTitle {
+    /// Title text.
+    pub title: String,
+    /// Language code (ISO 639-2 or BCP 47), if stored.
+    pub language: Option<String>,
+}
+
+/// Fills in missing chapter end times from the following chapter's start,
+/// ending the last chapter at `duration_ms`.
+pub(crate) fn close_chapters(chapters: &mut [Chapter], duration_ms: Option<u64>) {
+    for i in 0..chapters.len() {
+        if chapters[i].end_ms.is_none() {
+            let depth = chapters[i].depth;
+            chapters[i].end_ms = chapters[i + 1..]
+                .iter()
+                .find(|c| c.depth <= depth)
+                .map(|c| c.start_ms)
+                .or(duration_ms);
+        }
+    }
+}
+
+/// Returns the MIME type of an image from its magic bytes.
+pub(crate) fn image_mime_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG") {
+        Some("image/png")
+    } else if data.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else if data.starts_with(b"BM") {
+        Some("image/bmp")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_mime_type() {
+        assert_eq!(
+            image_mime_type(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            Some("image/jpeg")
+        );
+        assert_eq!(image_mime_type(b"\x89PNG\r\n\x1a\n"), Some("image/png"));
+        assert_eq!(image_mime_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(image_mime_type(b"text"), None);
+    }
+
+    #[test]
+    fn test_metadata_serialization_omits_offset() {
+        let metadata = Metadata {
+            title: Some("Title".to_string()),
+            attachments: vec![Attachment {
+                name: None,
+                mime_type: "image/png".to_string(),
+                description: None,
+                is_cover: true,
+                size: 4,
+                offset: 100,
+            }],
+            ..Metadata::default()
+        };
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert!(json.contains("\"mimeType\":\"image/png\""));
+        assert!(json.contains("\"isCover\":true"));
+        assert!(!json.contains("offset"));
+        assert_eq!(metadata.cover().unwrap().size, 4);
+    }
+
+    #[test]
+    fn test_close_chapters() {
+        let chapter = |start_ms, depth| Chapter {
+            start_ms,
+            depth,
+            ..Chapter::default()
+        };
+        let mut chapters = vec![chapter(0, 0), chapter(1000, 1), chapter(2000, 1)];
+        chapters.push(Chapter {
+            end_ms: Some(4000),
+            ..chapter(3000, 0)
+        });
+        close_chapters(&mut chapters, Some(5000));
+        let ends: Vec<_> = chapters.iter().map(|c| c.end_ms).collect();
+        assert_eq!(ends, [Some(3000), Some(2000), Some(3000), Some(4000)]);
+    }
+}
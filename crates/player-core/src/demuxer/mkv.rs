@@ -0,0 +1,1613 @@
+//! Matroska / WebM (EBML) parsing.
+//!
+//! Reads the EBML header to distinguish `webm` from `matroska`, then walks the
+//! Segment (`Info`, `Tracks`, `Cluster`, `Cues`, `Tags`, `Chapters`,
+//! `Attachments`) and
+//! indexes every frame of every `SimpleBlock`/`BlockGroup`, splitting Xiph,
+//! EBML and fixed-size lacing.
+
+use super::metadata::{
+    close_chapters, image_mime_type, Attachment, Chapter, ChapterTitle, Metadata,
+};
+use super::source::SourceReader;
+use super::{
+    AudioInfo, ColorInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType,
+    VideoInfo,
+};
+use crate::decoder::opus::{self, OpusHead};
+use crate::decoder::pcm;
+use crate::decoder::vp9::VpcDecoderConfig;
+use crate::error::{PlayerError, Result};
+
+/// EBML element IDs used by the parser.
+pub(crate) mod ids {
+    pub const EBML: u32 = 0x1A45_DFA3;
+    pub const DOC_TYPE: u32 = 0x4282;
+    pub const SEGMENT: u32 = 0x1853_8067;
+    pub const SEEK_HEAD: u32 = 0x114D_9B74;
+    pub const INFO: u32 = 0x1549_A966;
+    pub const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+    pub const DURATION: u32 = 0x4489;
+    pub const TITLE: u32 = 0x7BA9;
+    pub const WRITING_APP: u32 = 0x5741;
+    pub const TRACKS: u32 = 0x1654_AE6B;
+    pub const TRACK_ENTRY: u32 = 0xAE;
+    pub const TRACK_NUMBER: u32 = 0xD7;
+    pub const TRACK_TYPE: u32 = 0x83;
+    pub const CODEC_ID: u32 = 0x86;
+    pub const CODEC_PRIVATE: u32 = 0x63A2;
+    pub const CODEC_DELAY: u32 = 0x56AA;
+    pub const DEFAULT_DURATION: u32 = 0x23_E383;
+    pub const NAME: u32 = 0x536E;
+    pub const LANGUAGE: u32 = 0x22_B59C;
+    pub const LANGUAGE_BCP47: u32 = 0x22_B59D;
+    pub const FLAG_DEFAULT: u32 = 0x88;
+    pub const FLAG_FORCED: u32 = 0x55AA;
+    pub const VIDEO: u32 = 0xE0;
+    pub const PIXEL_WIDTH: u32 = 0xB0;
+    pub const PIXEL_HEIGHT: u32 = 0xBA;
+    pub const DISPLAY_WIDTH: u32 = 0x54B0;
+    pub const DISPLAY_HEIGHT: u32 = 0x54BA;
+    pub const COLOUR: u32 = 0x55B0;
+    pub const MATRIX_COEFFICIENTS: u32 = 0x55B1;
+    pub const BITS_PER_CHANNEL: u32 = 0x55B2;
+    pub const RANGE: u32 = 0x55B9;
+    pub const TRANSFER_CHARACTERISTICS: u32 = 0x55BA;
+    pub const PRIMARIES: u32 = 0x55BB;
+    pub const AUDIO: u32 = 0xE1;
+    pub const SAMPLING_FREQUENCY: u32 = 0xB5;
+    pub const OUTPUT_SAMPLING_FREQUENCY: u32 = 0x78B5;
+    pub const CHANNELS: u32 = 0x9F;
+    pub const BIT_DEPTH: u32 = 0x6264;
+    pub const CLUSTER: u32 = 0x1F43_B675;
+    pub const TIMESTAMP: u32 = 0xE7;
+    pub const SIMPLE_BLOCK: u32 = 0xA3;
+    pub const BLOCK_GROUP: u32 = 0xA0;
+    pub const BLOCK: u32 = 0xA1;
+    pub const REFERENCE_BLOCK: u32 = 0xFB;
+    pub const DISCARD_PADDING: u32 = 0x75A2;
+    pub const CUES: u32 = 0x1C53_BB6B;
+    pub const CUE_POINT: u32 = 0xBB;
+    pub const CUE_TIME: u32 = 0xB3;
+    pub const CUE_TRACK_POSITIONS: u32 = 0xB7;
+    pub const CUE_TRACK: u32 = 0xF7;
+    pub const CUE_CLUSTER_POSITION: u32 = 0xF1;
+    pub const TAGS: u32 = 0x1254_C367;
+    pub const TAG: u32 = 0x7373;
+    pub const TARGETS: u32 = 0x63C0;
+    pub const TARGET_TYPE_VALUE: u32 = 0x68CA;
+    pub const TAG_TRACK_UID: u32 = 0x63C5;
+    pub const TAG_EDITION_UID: u32 = 0x63C9;
+    pub const TAG_CHAPTER_UID: u32 = 0x63C4;
+    pub const TAG_ATTACHMENT_UID: u32 = 0x63C6;
+    pub const SIMPLE_TAG: u32 = 0x67C8;
+    pub const TAG_NAME: u32 = 0x45A3;
+    pub const TAG_STRING: u32 = 0x4487;
+    pub const CHAPTERS: u32 = 0x1043_A770;
+    pub const EDITION_ENTRY: u32 = 0x45B9;
+    pub const EDITION_FLAG_HIDDEN: u32 = 0x45BD;
+    pub const EDITION_FLAG_DEFAULT: u32 = 0x45DB;
+    pub const CHAPTER_ATOM: u32 = 0xB6;
+    pub const CHAPTER_TIME_START: u32 = 0x91;
+    pub const CHAPTER_TIME_END: u32 = 0x92;
+    pub const CHAPTER_FLAG_HIDDEN: u32 = 0x98;
+    pub const CHAPTER_FLAG_ENABLED: u32 = 0x4598;
+    pub const CHAPTER_DISPLAY: u32 = 0x80;
+    pub const CHAP_STRING: u32 = 0x85;
+    pub const CHAP_LANGUAGE: u32 = 0x437C;
+    pub const CHAP_LANGUAGE_BCP47: u32 = 0x437D;
+    pub const ATTACHMENTS: u32 = 0x1941_A469;
+    pub const ATTACHED_FILE: u32 = 0x61A7;
+    pub const FILE_DESCRIPTION: u32 = 0x467E;
+    pub const FILE_NAME: u32 = 0x466E;
+    pub const FILE_MIME_TYPE: u32 = 0x4660;
+    pub const FILE_DATA: u32 = 0x465C;
+}
+
+/// Default `TimestampScale` (1ms per tick).
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+/// `TargetTypeValue` of tags describing a single track or song.
+const TARGET_TRACK: u64 = 30;
+/// `TargetTypeValue` of tags describing an album, movie or episode.
+const TARGET_ALBUM: u64 = 50;
+
+/// Header of an EBML element.
+#[derive(Debug, Clone, Copy)]
+struct ElementHeader {
+    id: u32,
+    /// Payload size, or `None` for unknown-sized elements.
+    size: Option<u64>,
+    /// Length of the ID and size fields.
+    header_len: usize,
+}
+
+/// A single EBML element with its payload.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Element<'a> {
+    /// Element ID (including the length marker bits).
+    pub id: u32,
+    /// Element payload.
+    pub payload: &'a [u8],
+    /// Absolute file offset of the first payload byte.
+    pub payload_offset: u64,
+}
+
+/// Reads a variable-length integer, returning `(value, length)`.
+///
+/// With `keep_marker` the length marker bit is retained, as used by element IDs.
+fn read_vint(data: &[u8], keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    if first == 0 {
+        return None;
+    }
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || data.len() < len {
+        return None;
+    }
+    let mut value = if keep_marker {
+        first as u64
+    } else {
+        (first as u64) & (0xFF >> len)
+    };
+    for &b in &data[1..len] {
+        value = (value << 8) | b as u64;
+    }
+    Some((value, len))
+}
+
+/// Reads an element header at the start of `data`.
+fn read_header(data: &[u8]) -> Option<ElementHeader> {
+    let (id, id_len) = read_vint(data, true)?;
+    if id_len > 4 {
+        return None;
+    }
+    let (size, size_len) = read_vint(&data[id_len..], false)?;
+    let unknown = size == (1u64 << (7 * size_len)) - 1;
+    Some(ElementHeader {
+        id: id as u32,
+        size: (!unknown).then_some(size),
+        header_len: id_len + size_len,
+    })
+}
+
+/// Iterator over sibling elements in a payload.
+///
+/// Unknown-sized elements extend to the end of the parent. Iteration stops at
+/// the first truncated or malformed header.
+#[derive(Debug, Clone)]
+pub(crate) struct ElementIter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    base_offset: u64,
+}
+
+impl<'a> Iterator for ElementIter<'a> {
+    type Item = Element<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.data[self.pos..];
+        let header = read_header(rest)?;
+        let available = (rest.len() - header.header_len) as u64;
+        let size = header.size.unwrap_or(available);
+        if size > available {
+            return None;
+        }
+        let start = header.header_len;
+        let end = start + size as usize;
+        let element = Element {
+            id: header.id,
+            payload: &rest[start..end],
+            payload_offset: self.base_offset + (self.pos + start) as u64,
+        };
+        self.pos += end;
+        Some(element)
+    }
+}
+
+/// Iterates over the elements contained in `data`, which starts at `base_offset`.
+pub(crate) fn elements(data: &[u8], base_offset: u64) -> ElementIter<'_> {
+    ElementIter {
+        data,
+        pos: 0,
+        base_offset,
+    }
+}
+
+impl<'a> Element<'a> {
+    /// Iterates over the child elements of this master element.
+    pub(crate) fn children(&self) -> ElementIter<'a> {
+        elements(self.payload, self.payload_offset)
+    }
+
+    /// Returns the first child element with the given ID.
+    pub(crate) fn child(&self, id: u32) -> Option<Element<'a>> {
+        self.children().find(|e| e.id == id)
+    }
+
+    /// Interprets the payload as an unsigned integer.
+    pub(crate) fn uint(&self) -> u64 {
+        self.payload
+            .iter()
+            .take(8)
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64)
+    }
+
+    /// Interprets the payload as a two's complement signed integer.
+    pub(crate) fn int(&self) -> i64 {
+        let len = self.payload.len().min(8);
+        if len == 0 {
+            return 0;
+        }
+        let shift = 64 - 8 * len as u32;
+        ((self.uint() << shift) as i64) >> shift
+    }
+
+    /// Interprets the payload as a float (4 or 8 bytes).
+    pub(crate) fn float(&self) -> Option<f64> {
+        match self.payload.len() {
+            4 => Some(f32::from_be_bytes(self.payload.try_into().ok()?) as f64),
+            8 => Some(f64::from_be_bytes(self.payload.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// Interprets the payload as a string, trimming trailing NUL padding.
+    pub(crate) fn string(&self) -> String {
+        String::from_utf8_lossy(self.payload)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
+
+/// Returns the EBML `DocType` (e.g. `webm`, `matroska`) if the header is readable.
+pub(crate) fn doc_type(data: &[u8]) -> Option<String> {
+    let header = elements(data, 0).next().filter(|e| e.id == ids::EBML)?;
+    header.child(ids::DOC_TYPE).map(|e| e.string())
+}
+
+/// Parsed per-track information.
+#[derive(Debug, Clone)]
+pub(crate) struct MkvTrack {
+    /// Matroska track number referenced by blocks.
+    pub number: u64,
+    /// Stream type derived from `TrackType`.
+    pub stream_type: StreamType,
+    /// Codec name, normalized to the MP4 sample entry code where one exists.
+    pub codec: String,
+    /// Language, if not undetermined.
+    pub language: Option<String>,
+    /// Track name.
+    pub title: Option<String>,
+    /// `FlagDefault`, set unless the track says otherwise.
+    pub is_default: bool,
+    /// `FlagForced`.
+    pub is_forced: bool,
+    /// Picture properties from `Video`.
+    pub video: Option<VideoInfo>,
+    /// Sound properties from `Audio`.
+    pub audio: Option<AudioInfo>,
+    /// `CodecPrivate` initialization data.
+    pub codec_private: Option<Vec<u8>>,
+    /// End of the last block less its `DiscardPadding`, in track units.
+    pub end_pts: Option<i64>,
+    /// Frame duration in nanoseconds, used to time laced frames.
+    default_duration_ns: Option<u64>,
+    /// `CodecDelay` in nanoseconds, subtracted from every block timestamp.
+    codec_delay_ns: u64,
+}
+
+/// Parsed Matroska file.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MkvFile {
+    /// Segment duration in milliseconds, if known.
+    pub duration_ms: Option<u64>,
+    /// Timestamp units per second for all tracks.
+    pub timescale: u32,
+    /// Tracks with a supported type.
+    pub tracks: Vec<MkvTrack>,
+    /// Frames of all tracks parsed since the previous call, in file order,
+    /// stream index matching `tracks`.
+    pub samples: Vec<SampleEntry>,
+    /// Segment tags and attachments.
+    pub metadata: Metadata,
+    /// Chapters of the default edition, in start order.
+    pub chapters: Vec<Chapter>,
+}
+
+/// Maps a Matroska `CodecID` to the codec name used for MP4 tracks, or
+/// for PCM, the PCM codec name of the sample size.
+fn normalize_codec(codec_id: &str, bit_depth: Option<u8>) -> String {
+    let bits = bit_depth.unwrap_or(0) as u16;
+    let pcm = match codec_id {
+        "A_PCM/INT/LIT" => pcm::integer_codec(bits, false),
+        "A_PCM/INT/BIG" => pcm::integer_codec(bits, true),
+        "A_PCM/FLOAT/IEEE" => pcm::float_codec(bits, false),
+        _ => None,
+    };
+    if let Some(name) = pcm.and_then(pcm::codec_name) {
+        return name.to_string();
+    }
+    let name = match codec_id {
+        "V_MPEG4/ISO/AVC" => "avc1",
+        "V_MPEGH/ISO/HEVC" => "hvc1",
+        "V_AV1" => "av01",
+        "V_VP8" => "vp08",
+        "V_VP9" => "vp09",
+        "A_OPUS" => "Opus",
+        "A_VORBIS" => "vorbis",
+        "A_FLAC" => "fLaC",
+        "A_MPEG/L3" => "mp3",
+        "A_AC3" => "ac-3",
+        "A_EAC3" => "ec-3",
+        id if id.starts_with("A_AAC") => "mp4a",
+        id => id,
+    };
+    name.to_string()
+}
+
+/// Frame location collected while walking clusters, before timestamps are resolved.
+#[derive(Debug, Clone, Copy)]
+struct RawFrame {
+    track_number: u64,
+    /// Block timestamp in `TimestampScale` units.
+    ticks: i64,
+    /// Position of the frame within its lace.
+    lace_index: u32,
+    /// Duration of the frame less the block's `DiscardPadding`, in
+    /// nanoseconds, when the padding is given and the duration known.
+    kept_ns: Option<i64>,
+    offset: u64,
+    size: u32,
+    is_keyframe: bool,
+    cluster_offset: u64,
+}
+
+/// Cluster whose children are being walked.
+#[derive(Debug, Clone, Copy)]
+struct ClusterState {
+    /// Absolute offset of the Cluster element, as referenced by `Cues`.
+    offset: u64,
+    /// End of the cluster payload, `None` for unknown-sized clusters.
+    end: Option<u64>,
+    /// Cluster timestamp in `TimestampScale` units.
+    ticks: i64,
+}
+
+/// Incremental Matroska/WebM parser.
+///
+/// Walks the Segment children one element at a time and Clusters one block
+/// at a time, so only the element being parsed is held in memory.
+#[derive(Debug)]
+pub(crate) struct MkvParser {
+    /// Offset of the next element header.
+    pos: u64,
+    /// Segment payload start and end, once the Segment header has been read.
+    segment: Option<(u64, Option<u64>)>,
+    cluster: Option<ClusterState>,
+    /// End of the `Attachments` payload while its files are being read.
+    attachments_end: Option<u64>,
+    /// Set when nothing more can be parsed (unknown-sized non-cluster element).
+    done: bool,
+    timestamp_scale: u64,
+    duration_ticks: Option<f64>,
+    tracks: Vec<MkvTrack>,
+    /// Frames not yet handed over.
+    frames: Vec<RawFrame>,
+    /// `(time, track, cluster offset)` from `Cues`.
+    cues: Vec<(u64, u64, u64)>,
+    /// `Title` and `WritingApp` from `Info`.
+    info_title: Option<String>,
+    writing_app: Option<String>,
+    /// Segment-wide `(TargetTypeValue, TagName, TagString)` tags.
+    tags: Vec<(u64, String, String)>,
+    attachments: Vec<Attachment>,
+    chapters: Vec<Chapter>,
+}
+
+/// Returns whether an element ID is a top-level Segment child.
+fn is_segment_child(id: u32) -> bool {
+    matches!(
+        id,
+        ids::SEEK_HEAD
+            | ids::INFO
+            | ids::TRACKS
+            | ids::CLUSTER
+            | ids::CUES
+            | ids::TAGS
+            | ids::CHAPTERS
+            | ids::ATTACHMENTS
+    )
+}
+
+impl ContainerParser for MkvParser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        while !self.done {
+            if let Some(end) = self.cluster.and_then(|c| c.end) {
+                if self.pos >= end {
+                    self.cluster = None;
+                }
+            }
+            if let Some(end) = self.attachments_end {
+                if self.pos >= end {
+                    self.attachments_end = None;
+                }
+            }
+            if let Some((_, Some(end))) = self.segment {
+                if self.pos >= end {
+                    break;
+                }
+            }
+
+            let header = match read_header(reader.bytes(self.pos, 12)?) {
+                Some(header) => header,
+                None => break,
+            };
+            let start = self.pos + header.header_len as u64;
+
+            let (segment_offset, _) = match self.segment {
+                Some(segment) => segment,
+                None => {
+                    // Skip the EBML header and anything else preceding the Segment.
+                    if header.id == ids::SEGMENT {
+                        self.segment = Some((start, header.size.map(|s| start + s)));
+                        self.pos = start;
+                    } else {
+                        match header.size {
+                            Some(size) => self.pos = start + size,
+                            None => self.done = true,
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(cluster) = self.cluster {
+                // Unknown-sized clusters end at the next top-level element.
+                if cluster.end.is_none() && is_segment_child(header.id) {
+                    self.cluster = None;
+                    continue;
+                }
+            } else if header.id == ids::CLUSTER {
+                self.cluster = Some(ClusterState {
+                    offset: self.pos,
+                    end: header.size.map(|s| start + s),
+                    ticks: 0,
+                });
+                self.pos = start;
+                continue;
+            } else if header.id == ids::ATTACHMENTS && self.attachments_end.is_none() {
+                // Read attached files one at a time rather than the whole list.
+                if let Some(size) = header.size {
+                    self.attachments_end = Some(start + size);
+                    self.pos = start;
+                    continue;
+                }
+            }
+
+            let size = match header.size {
+                Some(size) => size,
+                // Unknown-sized non-cluster element: nothing more to read.
+                None => {
+                    self.done = true;
+                    break;
+                }
+            };
+            let wanted = if self.cluster.is_some() {
+                matches!(
+                    header.id,
+                    ids::TIMESTAMP | ids::SIMPLE_BLOCK | ids::BLOCK_GROUP
+                )
+            } else if self.attachments_end.is_some() {
+                header.id == ids::ATTACHED_FILE
+            } else {
+                matches!(
+                    header.id,
+                    ids::INFO | ids::TRACKS | ids::CUES | ids::TAGS | ids::CHAPTERS
+                )
+            };
+            if wanted {
+                let payload = match reader.exact(start, size as usize)? {
+                    Some(payload) => payload,
+                    None => break,
+                };
+                let element = Element {
+                    id: header.id,
+                    payload,
+                    payload_offset: start,
+                };
+                match self.cluster {
+                    Some(cluster) => self.parse_cluster_child(&element, cluster)?,
+                    None => match header.id {
+                        ids::INFO => self.parse_info(&element),
+                        ids::TRACKS => self.parse_tracks(&element),
+                        ids::TAGS => self.parse_tags(&element),
+                        ids::CHAPTERS => self.parse_chapters(&element),
+                        ids::ATTACHED_FILE => self.parse_attached_file(&element),
+                        _ => self.parse_cues(&element, segment_offset),
+                    },
+                }
+            }
+            self.pos = start + size;
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        let file = self.file();
+        Container {
+            streams: file
+                .tracks
+                .into_iter()
+                .enumerate()
+                .map(|(index, track)| StreamInfo {
+                    duration_ms: file.duration_ms,
+                    language: track.language,
+                    title: track.title,
+                    is_default: track.is_default,
+                    is_forced: track.is_forced,
+                    video: track.video,
+                    audio: track.audio,
+                    extra_data: track.codec_private,
+                    end_pts: track.end_pts,
+                    ..StreamInfo::new(index as u32, track.stream_type, track.codec, file.timescale)
+                })
+                .collect(),
+            duration_ms: file.duration_ms,
+            samples: file.samples,
+            metadata: file.metadata,
+            chapters: file.chapters,
+        }
+    }
+}
+
+impl MkvParser {
+    /// Creates a parser positioned at the start of the file.
+    pub(crate) fn new() -> Self {
+        Self {
+            pos: 0,
+            segment: None,
+            cluster: None,
+            attachments_end: None,
+            done: false,
+            timestamp_scale: DEFAULT_TIMESTAMP_SCALE,
+            duration_ticks: None,
+            tracks: Vec::new(),
+            frames: Vec::new(),
+            cues: Vec::new(),
+            info_title: None,
+            writing_app: None,
+            tags: Vec::new(),
+            attachments: Vec::new(),
+            chapters: Vec::new(),
+        }
+    }
+
+    fn parse_info(&mut self, info: &Element<'_>) {
+        for e in info.children() {
+            match e.id {
+                ids::TIMESTAMP_SCALE if e.uint() > 0 => self.timestamp_scale = e.uint(),
+                ids::DURATION => self.duration_ticks = e.float(),
+                ids::TITLE => self.info_title = Some(e.string()),
+                ids::WRITING_APP => self.writing_app = Some(e.string()),
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_tracks(&mut self, tracks: &Element<'_>) {
+        for entry in tracks.children().filter(|e| e.id == ids::TRACK_ENTRY) {
+            let mut number = None;
+            let mut stream_type = None;
+            let mut codec_id = String::new();
+            let mut default_duration_ns = None;
+            let mut language = None;
+            let mut language_bcp47 = None;
+            let mut title = None;
+            let mut is_default = true;
+            let mut is_forced = false;
+            let mut video = None;
+            let mut audio = None;
+            let mut codec_private = None;
+            let mut codec_delay_ns = None;
+            for e in entry.children() {
+                match e.id {
+                    ids::TRACK_NUMBER => number = Some(e.uint()),
+                    ids::TRACK_TYPE => {
+                        stream_type = match e.uint() {
+                            1 => Some(StreamType::Video),
+                            2 => Some(StreamType::Audio),
+                            0x11 => Some(StreamType::Subtitle),
+                            _ => None,
+                        }
+                    }
+                    ids::CODEC_ID => codec_id = e.string(),
+                    ids::CODEC_PRIVATE => codec_private = Some(e.payload.to_vec()),
+                    ids::CODEC_DELAY => codec_delay_ns = Some(e.uint()),
+                    ids::DEFAULT_DURATION => default_duration_ns = Some(e.uint()),
+                    ids::NAME => title = Some(e.string()),
+                    ids::LANGUAGE => language = Some(e.string()),
+                    ids::LANGUAGE_BCP47 => language_bcp47 = Some(e.string()),
+                    ids::FLAG_DEFAULT => is_default = e.uint() != 0,
+                    ids::FLAG_FORCED => is_forced = e.uint() != 0,
+                    ids::VIDEO => video = Some(video_info(&e)),
+                    ids::AUDIO => audio = Some(audio_info(&e)),
+                    _ => {}
+                }
+            }
+            if let (Some(video), Some(frame_ns)) = (video.as_mut(), default_duration_ns) {
+                if frame_ns > 0 {
+                    video.frame_rate = Some(1e9 / frame_ns as f64);
+                }
+            }
+            // VP9 `CodecPrivate` is a feature list; store it as a `vpcC`
+            // record like MP4 so decoders see one format.
+            if codec_id == "V_VP9" {
+                let color = video.as_ref().and_then(|v| v.color.as_ref());
+                codec_private = codec_private
+                    .and_then(|p| VpcDecoderConfig::from_codec_private(&p, color).ok())
+                    .map(|config| config.to_vpcc());
+            }
+            // Files from before `CodecDelay` only give the Opus pre-skip.
+            if codec_id == "A_OPUS" && codec_delay_ns.is_none() {
+                codec_delay_ns = codec_private
+                    .as_deref()
+                    .and_then(|p| OpusHead::parse(p).ok())
+                    .map(|head| head.pre_skip as u64 * 1_000_000_000 / opus::SAMPLE_RATE as u64);
+            }
+            // `Language` defaults to English; the BCP 47 tag supersedes it.
+            let language = language_bcp47
+                .or(language)
+                .unwrap_or_else(|| "eng".to_string());
+            if let (Some(number), Some(stream_type)) = (number, stream_type) {
+                self.tracks.push(MkvTrack {
+                    number,
+                    stream_type,
+                    codec: normalize_codec(&codec_id, audio.as_ref().and_then(|a| a.bit_depth)),
+                    language: (language != "und").then_some(language),
+                    title,
+                    is_default,
+                    is_forced,
+                    video,
+                    audio,
+                    codec_private,
+                    end_pts: None,
+                    default_duration_ns,
+                    codec_delay_ns: codec_delay_ns.unwrap_or(0),
+                });
+            }
+        }
+    }
+
+    fn parse_cues(&mut self, cues: &Element<'_>, segment_offset: u64) {
+        for point in cues.children().filter(|e| e.id == ids::CUE_POINT) {
+            let time = point.child(ids::CUE_TIME).map(|e| e.uint());
+            for positions in point
+                .children()
+                .filter(|e| e.id == ids::CUE_TRACK_POSITIONS)
+            {
+                let track = positions.child(ids::CUE_TRACK).map(|e| e.uint());
+                let cluster = positions.child(ids::CUE_CLUSTER_POSITION).map(|e| e.uint());
+                if let (Some(time), Some(track), Some(cluster)) = (time, track, cluster) {
+                    self.cues.push((time, track, segment_offset + cluster));
+                }
+            }
+        }
+        self.cues.sort_unstable();
+    }
+
+    /// Collects the segment-wide tags of a `Tags` element.
+    ///
+    /// Tags targeting a track, edition, chapter or attachment are skipped.
+    fn parse_tags(&mut self, tags: &Element<'_>) {
+        for tag in tags.children().filter(|e| e.id == ids::TAG) {
+            let mut level = TARGET_ALBUM;
+            if let Some(targets) = tag.child(ids::TARGETS) {
+                let scoped = targets.children().any(|e| {
+                    matches!(
+                        e.id,
+                        ids::TAG_TRACK_UID
+                            | ids::TAG_EDITION_UID
+                            | ids::TAG_CHAPTER_UID
+                            | ids::TAG_ATTACHMENT_UID
+                    ) && e.uint() != 0
+                });
+                if scoped {
+                    continue;
+                }
+                if let Some(value) = targets.child(ids::TARGET_TYPE_VALUE) {
+                    level = value.uint();
+                }
+            }
+            for simple in tag.children().filter(|e| e.id == ids::SIMPLE_TAG) {
+                let name = simple.child(ids::TAG_NAME).map(|e| e.string());
+                let value = simple.child(ids::TAG_STRING).map(|e| e.string());
+                if let (Some(name), Some(value)) = (name, value) {
+                    self.tags.push((level, name.to_ascii_uppercase(), value));
+                }
+            }
+        }
+    }
+
+    /// Collects the chapters of the default (or first visible) edition.
+    fn parse_chapters(&mut self, chapters: &Element<'_>) {
+        let editions: Vec<_> = chapters
+            .children()
+            .filter(|e| e.id == ids::EDITION_ENTRY)
+            .filter(|e| {
+                e.child(ids::EDITION_FLAG_HIDDEN)
+                    .is_none_or(|f| f.uint() == 0)
+            })
+            .collect();
+        let edition = editions
+            .iter()
+            .find(|e| {
+                e.child(ids::EDITION_FLAG_DEFAULT)
+                    .is_some_and(|f| f.uint() != 0)
+            })
+            .or(editions.first());
+        if let Some(edition) = edition {
+            self.chapters.clear();
+            collect_chapter_atoms(edition, 0, &mut self.chapters);
+        }
+    }
+
+    /// Records an `AttachedFile`; its data is left in the source.
+    fn parse_attached_file(&mut self, file: &Element<'_>) {
+        let mut name = None;
+        let mut mime_type = None;
+        let mut description = None;
+        let mut data = None;
+        for e in file.children() {
+            match e.id {
+                ids::FILE_NAME => name = Some(e.string()),
+                ids::FILE_MIME_TYPE => mime_type = Some(e.string()),
+                ids::FILE_DESCRIPTION => description = Some(e.string()),
+                ids::FILE_DATA => data = Some(e),
+                _ => {}
+            }
+        }
+        let data = match data {
+            Some(data) => data,
+            None => return,
+        };
+        let mime_type = mime_type
+            .or_else(|| image_mime_type(data.payload).map(str::to_string))
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        // Matroska names cover art `cover.*`, `small_cover.*`, `cover_land.*`, ...
+        let is_cover = mime_type.starts_with("image/")
+            && name
+                .as_deref()
+                .is_some_and(|n| n.to_ascii_lowercase().contains("cover"));
+        self.attachments.push(Attachment {
+            name,
+            mime_type,
+            description,
+            is_cover,
+            size: data.payload.len() as u64,
+            offset: data.payload_offset,
+        });
+    }
+
+    /// Resolves the collected tags into container metadata.
+    fn metadata(&self) -> Metadata {
+        let tag = |names: &[&str], level: Option<u64>| {
+            names.iter().find_map(|name| {
+                self.tags
+                    .iter()
+                    .find(|(l, n, _)| n == name && level.is_none_or(|level| *l == level))
+                    .map(|(_, _, value)| value.clone())
+            })
+        };
+        // With both track- and album-level titles (music), the latter is the album.
+        let track_title = tag(&["TITLE"], Some(TARGET_TRACK));
+        let album_title = tag(&["TITLE"], Some(TARGET_ALBUM));
+        let (title, album) = match track_title {
+            Some(title) => (Some(title), album_title),
+            None => (
+                tag(&["TITLE"], None).or_else(|| self.info_title.clone()),
+                None,
+            ),
+        };
+        Metadata {
+            title,
+            artist: tag(&["ARTIST", "LEAD_PERFORMER", "DIRECTOR"], None),
+            album,
+            date: tag(&["DATE_RELEASED", "DATE_RECORDED", "DATE_ENCODED"], None),
+            encoder: tag(&["ENCODER"], None).or_else(|| self.writing_app.clone()),
+            comment: tag(&["COMMENT", "DESCRIPTION", "SUMMARY"], None),
+            attachments: self.attachments.clone(),
+        }
+    }
+
+    /// Handles a `Timestamp`, `SimpleBlock` or `BlockGroup` inside a cluster.
+    fn parse_cluster_child(&mut self, element: &Element<'_>, cluster: ClusterState) -> Result<()> {
+        match element.id {
+            ids::TIMESTAMP => {
+                if let Some(current) = self.cluster.as_mut() {
+                    current.ticks = element.uint() as i64;
+                }
+            }
+            ids::SIMPLE_BLOCK => {
+                let keyframe = |flags: u8| flags & 0x80 != 0;
+                self.parse_block(element, cluster, None, keyframe)?;
+            }
+            ids::BLOCK_GROUP => {
+                let referenced = element.child(ids::REFERENCE_BLOCK).is_some();
+                let padding = element.child(ids::DISCARD_PADDING).map(|e| e.int());
+                if let Some(block) = element.child(ids::BLOCK) {
+                    self.parse_block(&block, cluster, padding, |_| !referenced)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Splits a `SimpleBlock`/`Block` into frames and records them, with
+    /// the `DiscardPadding` of a `BlockGroup` applied to the last frame.
+    fn parse_block(
+        &mut self,
+        block: &Element<'_>,
+        cluster: ClusterState,
+        discard_padding_ns: Option<i64>,
+        is_keyframe: impl Fn(u8) -> bool,
+    ) -> Result<()> {
+        let data = block.payload;
+        let (track_number, number_len) = read_vint(data, false)
+            .ok_or_else(|| PlayerError::demuxer("Invalid block track number"))?;
+        if data.len() < number_len + 3 {
+            return Err(PlayerError::demuxer("Truncated block header"));
+        }
+        let relative = i16::from_be_bytes([data[number_len], data[number_len + 1]]) as i64;
+        let flags = data[number_len + 2];
+        let header_len = number_len + 3;
+
+        let sizes = lace_sizes(&data[header_len..], flags)?;
+        let is_opus = self
+            .tracks
+            .iter()
+            .any(|t| t.number == track_number && t.codec == "Opus");
+        let mut pos = header_len + sizes.header_len;
+        for (lace_index, &size) in sizes.frames.iter().enumerate() {
+            let frame = data.get(pos..pos + size as usize).unwrap_or_default();
+            let kept_ns = discard_padding_ns
+                .filter(|_| is_opus && lace_index + 1 == sizes.frames.len())
+                .and_then(|padding| {
+                    let samples = opus::packet_samples(frame)? as i64;
+                    Some(samples * 1_000_000_000 / opus::SAMPLE_RATE as i64 - padding)
+                });
+            self.frames.push(RawFrame {
+                track_number,
+                ticks: cluster.ticks + relative,
+                lace_index: lace_index as u32,
+                kept_ns,
+                offset: block.payload_offset + pos as u64,
+                size,
+                is_keyframe: is_keyframe(flags),
+                cluster_offset: cluster.offset,
+            });
+            pos += size as usize;
+        }
+        Ok(())
+    }
+
+    /// Returns the track timescale and a converter from block ticks to it.
+    fn timescale(&self) -> (u32, u64) {
+        if 1_000_000_000 % self.timestamp_scale == 0 {
+            ((1_000_000_000 / self.timestamp_scale) as u32, 1)
+        } else {
+            // Fall back to microseconds when the scale doesn't divide a second.
+            (1_000_000, self.timestamp_scale)
+        }
+    }
+
+    /// Resolves the timestamps and keyframes of the frames parsed since the
+    /// previous call into the parsed file.
+    fn file(&mut self) -> MkvFile {
+        let (timescale, multiplier) = self.timescale();
+        let to_track_units = |ticks: i64| -> i64 {
+            if multiplier == 1 {
+                ticks
+            } else {
+                ticks.saturating_mul(multiplier as i64) / 1000
+            }
+        };
+        let ns_to_track_units =
+            |ns: u64| -> i64 { (ns as u128 * timescale as u128 / 1_000_000_000) as i64 };
+
+        // Cue points mark keyframes for blocks that don't flag them (BlockGroups).
+        let frames = std::mem::take(&mut self.frames);
+        let mut samples = Vec::with_capacity(frames.len());
+        for frame in &frames {
+            let stream_index = match self
+                .tracks
+                .iter()
+                .position(|t| t.number == frame.track_number)
+            {
+                Some(index) => index,
+                None => continue,
+            };
+            let track = &self.tracks[stream_index];
+            let lace_offset = track
+                .default_duration_ns
+                .map_or(0, |d| ns_to_track_units(d) * frame.lace_index as i64);
+            let delay = ns_to_track_units(track.codec_delay_ns);
+            let pts = to_track_units(frame.ticks) + lace_offset - delay;
+            if let Some(kept_ns) = frame.kept_ns {
+                let end = pts + kept_ns.signum() * ns_to_track_units(kept_ns.unsigned_abs());
+                let end_pts = &mut self.tracks[stream_index].end_pts;
+                *end_pts = Some(end_pts.map_or(end, |e| e.max(end)));
+            }
+            let is_cue = frame.lace_index == 0
+                && frame.ticks >= 0
+                && self
+                    .cues
+                    .binary_search(&(frame.ticks as u64, frame.track_number, frame.cluster_offset))
+                    .is_ok();
+            samples.push(SampleEntry {
+                stream_index: stream_index as u32,
+                offset: frame.offset,
+                size: frame.size,
+                dts: pts,
+                pts,
+                is_keyframe: frame.is_keyframe || is_cue,
+                continuation: None,
+            });
+        }
+
+        let duration_ms = self
+            .duration_ticks
+            .map(|d| (d * self.timestamp_scale as f64 / 1_000_000.0) as u64);
+        let mut chapters = self.chapters.clone();
+        close_chapters(&mut chapters, duration_ms);
+
+        MkvFile {
+            duration_ms,
+            timescale,
+            tracks: self.tracks.clone(),
+            samples,
+            metadata: self.metadata(),
+            chapters,
+        }
+    }
+}
+
+/// Reads the picture properties of a `Video` element.
+fn video_info(video: &Element<'_>) -> VideoInfo {
+    let mut info = VideoInfo::default();
+    let (mut display_width, mut display_height) = (None, None);
+    for e in video.children() {
+        match e.id {
+            ids::PIXEL_WIDTH => info.width = e.uint() as u32,
+            ids::PIXEL_HEIGHT => info.height = e.uint() as u32,
+            ids::DISPLAY_WIDTH => display_width = Some(e.uint()),
+            ids::DISPLAY_HEIGHT => display_height = Some(e.uint()),
+            ids::COLOUR => {
+                let mut color = ColorInfo::default();
+                for c in e.children() {
+                    match c.id {
+                        ids::PRIMARIES => color.primaries = Some(c.uint() as u8),
+                        ids::TRANSFER_CHARACTERISTICS => color.transfer = Some(c.uint() as u8),
+                        ids::MATRIX_COEFFICIENTS => color.matrix = Some(c.uint() as u8),
+                        ids::RANGE => color.full_range = (c.uint() != 0).then(|| c.uint() == 2),
+                        ids::BITS_PER_CHANNEL if c.uint() > 0 => {
+                            info.bit_depth = Some(c.uint() as u8)
+                        }
+                        _ => {}
+                    }
+                }
+                info.color = Some(color);
+            }
+            _ => {}
+        }
+    }
+    // The display size defaults to the pixel size; whatever its unit, it
+    // gives the aspect ratio.
+    let width = display_width.unwrap_or(info.width as u64);
+    let height = display_height.unwrap_or(info.height as u64);
+    if width > 0 && height > 0 {
+        info.display_aspect_ratio = Some(width as f64 / height as f64);
+    }
+    info
+}
+
+/// Reads the sound properties of an `Audio` element.
+fn audio_info(audio: &Element<'_>) -> AudioInfo {
+    let mut rate = 8000.0;
+    let mut output_rate = None;
+    let mut channels = 1;
+    let mut bit_depth = None;
+    for e in audio.children() {
+        match e.id {
+            ids::SAMPLING_FREQUENCY => rate = e.float().unwrap_or(rate),
+            ids::OUTPUT_SAMPLING_FREQUENCY => output_rate = e.float(),
+            ids::CHANNELS => channels = e.uint() as u16,
+            ids::BIT_DEPTH => bit_depth = Some(e.uint() as u8),
+            _ => {}
+        }
+    }
+    // SBR streams store the doubled rate as the output frequency.
+    let mut info = AudioInfo::new(output_rate.unwrap_or(rate) as u32, channels);
+    info.bit_depth = bit_depth;
+    info
+}
+
+/// Appends the visible `ChapterAtom`s of an edition or chapter, depth first.
+fn collect_chapter_atoms(parent: &Element<'_>, depth: u32, chapters: &mut Vec<Chapter>) {
+    for atom in parent.children().filter(|e| e.id == ids::CHAPTER_ATOM) {
+        let hidden = atom
+            .child(ids::CHAPTER_FLAG_HIDDEN)
+            .is_some_and(|f| f.uint() != 0);
+        let enabled = atom
+            .child(ids::CHAPTER_FLAG_ENABLED)
+            .is_none_or(|f| f.uint() != 0);
+        if hidden || !enabled {
+            continue;
+        }
+        let mut titles = Vec::new();
+        for display in atom.children().filter(|e| e.id == ids::CHAPTER_DISPLAY) {
+            let title = match display.child(ids::CHAP_STRING) {
+                Some(e) => e.string(),
+                None => continue,
+            };
+            // The BCP 47 tag supersedes the legacy ISO 639-2 code.
+            let language = display
+                .child(ids::CHAP_LANGUAGE_BCP47)
+                .or_else(|| display.child(ids::CHAP_LANGUAGE))
+                .map(|e| e.string());
+            titles.push(ChapterTitle { title, language });
+        }
+        // Chapter times are in nanoseconds, independent of `TimestampScale`.
+        chapters.push(Chapter {
+            start_ms: atom
+                .child(ids::CHAPTER_TIME_START)
+                .map_or(0, |e| e.uint() / 1_000_000),
+            end_ms: atom
+                .child(ids::CHAPTER_TIME_END)
+                .map(|e| e.uint() / 1_000_000),
+            title: titles.first().map(|t| t.title.clone()),
+            titles,
+            depth,
+        });
+        collect_chapter_atoms(&atom, depth + 1, chapters);
+    }
+}
+
+/// Frame sizes of a (possibly laced) block.
+#[derive(Debug)]
+struct LaceSizes {
+    /// Length of the lace header preceding the frame data.
+    header_len: usize,
+    /// Size of each frame.
+    frames: Vec<u32>,
+}
+
+/// Decodes the lacing header of a block body.
+fn lace_sizes(body: &[u8], flags: u8) -> Result<LaceSizes> {
+    let lacing = (flags >> 1) & 0x03;
+    if lacing == 0 {
+        return Ok(LaceSizes {
+            header_len: 0,
+            frames: vec![body.len() as u32],
+        });
+    }
+
+    let truncated = || PlayerError::demuxer("Truncated lace header");
+    let count = *body.first().ok_or_else(truncated)? as usize + 1;
+    let mut pos = 1;
+    let mut frames = Vec::with_capacity(count);
+
+    match lacing {
+        // Xiph lacing: sizes as runs of 255-valued bytes.
+        0b01 => {
+            for _ in 0..count - 1 {
+                let mut size = 0u32;
+                loop {
+                    let b = *body.get(pos).ok_or_else(truncated)?;
+                    pos += 1;
+                    size += b as u32;
+                    if b != 255 {
+                        break;
+                    }
+                }
+                frames.push(size);
+            }
+        }
+        // EBML lacing: first size as a VINT, the rest as signed differences.
+        0b11 => {
+            let (first, len) = read_vint(&body[pos..], false).ok_or_else(truncated)?;
+            pos += len;
+            frames.push(first as u32);
+            let mut previous = first as i64;
+            for _ in 1..count - 1 {
+                let (raw, len) = read_vint(&body[pos..], false).ok_or_else(truncated)?;
+                pos += len;
+                let bias = (1i64 << (7 * len - 1)) - 1;
+                let size = previous + (raw as i64 - bias);
+                if size < 0 {
+                    return Err(PlayerError::demuxer("Invalid EBML lace size"));
+                }
+                frames.push(size as u32);
+                previous = size;
+            }
+        }
+        // Fixed-size lacing: equal division of the remaining data.
+        _ => {
+            let remaining = body.len() - pos;
+            if !remaining.is_multiple_of(count) {
+                return Err(PlayerError::demuxer("Invalid fixed-size lacing"));
+            }
+            return Ok(LaceSizes {
+                header_len: pos,
+                frames: vec![(remaining / count) as u32; count],
+            });
+        }
+    }
+
+    let used: u64 = frames.iter().map(|&s| s as u64).sum();
+    let remaining = (body.len() - pos) as u64;
+    if used > remaining {
+        return Err(PlayerError::demuxer("Lace sizes exceed block size"));
+    }
+    frames.push((remaining - used) as u32);
+
+    Ok(LaceSizes {
+        header_len: pos,
+        frames,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::demuxer::MemorySource;
+
+    /// Runs the parser over a complete in-memory file.
+    fn parse(data: &[u8]) -> Result<MkvFile> {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = MkvParser::new();
+        parser.parse(&mut SourceReader::new(&mut source))?;
+        Ok(parser.file())
+    }
+
+    /// Encodes an element with the given ID and payload.
+    pub(crate) fn element(id: u32, payload: &[u8]) -> Vec<u8> {
+        let id_bytes = id.to_be_bytes();
+        let skip = id_bytes.iter().take_while(|&&b| b == 0).count();
+        let mut out = id_bytes[skip..].to_vec();
+        // Always use an 8-byte size for simplicity.
+        out.push(0x01);
+        out.extend_from_slice(&(payload.len() as u64).to_be_bytes()[1..]);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Encodes an unsigned integer element.
+    pub(crate) fn uint_element(id: u32, value: u64) -> Vec<u8> {
+        element(id, &value.to_be_bytes())
+    }
+
+    pub(crate) fn ebml_header(doc_type: &str) -> Vec<u8> {
+        element(ids::EBML, &element(ids::DOC_TYPE, doc_type.as_bytes()))
+    }
+
+    pub(crate) fn track_entry(number: u64, track_type: u64, codec_id: &str) -> Vec<u8> {
+        let mut p = uint_element(ids::TRACK_NUMBER, number);
+        p.extend(uint_element(ids::TRACK_TYPE, track_type));
+        p.extend(element(ids::CODEC_ID, codec_id.as_bytes()));
+        element(ids::TRACK_ENTRY, &p)
+    }
+
+    pub(crate) fn simple_block(track: u8, relative: i16, flags: u8, body: &[u8]) -> Vec<u8> {
+        let mut p = vec![0x80 | track];
+        p.extend_from_slice(&relative.to_be_bytes());
+        p.push(flags);
+        p.extend_from_slice(body);
+        element(ids::SIMPLE_BLOCK, &p)
+    }
+
+    #[test]
+    fn test_read_vint() {
+        assert_eq!(read_vint(&[0x81], false), Some((1, 1)));
+        assert_eq!(read_vint(&[0x40, 0x02], false), Some((2, 2)));
+        assert_eq!(
+            read_vint(&[0x1A, 0x45, 0xDF, 0xA3], true),
+            Some((0x1A45DFA3, 4))
+        );
+        assert_eq!(read_vint(&[0x00], false), None);
+        assert_eq!(read_vint(&[0x40], false), None);
+    }
+
+    #[test]
+    fn test_unknown_size_header() {
+        let header = read_header(&[0xA3, 0xFF]).unwrap();
+        assert_eq!(header.id, 0xA3);
+        assert!(header.size.is_none());
+    }
+
+    #[test]
+    fn test_doc_type() {
+        assert_eq!(doc_type(&ebml_header("webm")).as_deref(), Some("webm"));
+        assert_eq!(
+            doc_type(&ebml_header("matroska")).as_deref(),
+            Some("matroska")
+        );
+        assert_eq!(doc_type(&[0x1A, 0x45, 0xDF, 0xA3, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_xiph_lacing() {
+        // 3 frames: 300 bytes, 2 bytes, remainder (4 bytes).
+        let mut body = vec![2, 255, 45, 2];
+        body.extend(vec![0; 306]);
+        let sizes = lace_sizes(&body, 0b0000_0010).unwrap();
+        assert_eq!(sizes.header_len, 4);
+        assert_eq!(sizes.frames, vec![300, 2, 4]);
+    }
+
+    #[test]
+    fn test_ebml_lacing() {
+        // 3 frames: 10, 8 (diff -2), remainder 5.
+        let diff = (-2i64 + 63) as u8 | 0x80;
+        let mut body = vec![2, 0x8A, diff];
+        body.extend(vec![0; 23]);
+        let sizes = lace_sizes(&body, 0b0000_0110).unwrap();
+        assert_eq!(sizes.header_len, 3);
+        assert_eq!(sizes.frames, vec![10, 8, 5]);
+    }
+
+    #[test]
+    fn test_fixed_lacing() {
+        let mut body = vec![3];
+        body.extend(vec![0; 12]);
+        let sizes = lace_sizes(&body, 0b0000_0100).unwrap();
+        assert_eq!(sizes.frames, vec![3, 3, 3, 3]);
+        assert!(lace_sizes(&[3, 0, 0, 0], 0b0000_0100).is_err());
+    }
+
+    #[test]
+    fn test_parse_segment() {
+        let mut info = uint_element(ids::TIMESTAMP_SCALE, 1_000_000);
+        info.extend(element(ids::DURATION, &2500.0f64.to_be_bytes()));
+        let mut tracks = track_entry(1, 1, "V_VP9");
+        tracks.extend(track_entry(2, 2, "A_OPUS"));
+        tracks.extend(track_entry(3, 0x12, "B_BUTTONS"));
+
+        let mut cluster = uint_element(ids::TIMESTAMP, 1000);
+        cluster.extend(simple_block(1, 0, 0x80, &[1, 2, 3]));
+        cluster.extend(simple_block(2, 5, 0x80, &[4, 5]));
+        // Fixed lacing, two audio frames.
+        cluster.extend(simple_block(2, 25, 0x84, &[1, 6, 6, 7, 7]));
+        let mut group = element(ids::BLOCK, &{
+            let mut p = vec![0x81, 0, 40, 0];
+            p.extend_from_slice(&[8, 8]);
+            p
+        });
+        group.extend(uint_element(ids::REFERENCE_BLOCK, 1));
+        cluster.extend(element(ids::BLOCK_GROUP, &group));
+
+        let mut segment = element(ids::INFO, &info);
+        segment.extend(element(ids::TRACKS, &tracks));
+        segment.extend(element(ids::CLUSTER, &cluster));
+
+        let mut data = ebml_header("webm");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let file = parse(&data).unwrap();
+        assert_eq!(file.duration_ms, Some(2500));
+        assert_eq!(file.timescale, 1000);
+        assert_eq!(file.tracks.len(), 2);
+        assert_eq!(file.tracks[0].codec, "vp09");
+        assert_eq!(file.tracks[1].codec, "Opus");
+
+        let summary: Vec<(u32, i64, u32, bool)> = file
+            .samples
+            .iter()
+            .map(|s| (s.stream_index, s.pts, s.size, s.is_keyframe))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 1000, 3, true),
+                (1, 1005, 2, true),
+                (1, 1025, 2, true),
+                (1, 1025, 2, true),
+                (0, 1040, 2, false),
+            ]
+        );
+        let first = &file.samples[0];
+        let start = first.offset as usize;
+        assert_eq!(&data[start..start + 3], &[1, 2, 3]);
+        let laced = &file.samples[3];
+        assert_eq!(
+            &data[laced.offset as usize..laced.offset as usize + 2],
+            &[7, 7]
+        );
+    }
+
+    #[test]
+    fn test_unknown_size_cluster() {
+        let tracks = track_entry(1, 2, "A_AAC/MPEG4/LC");
+        let mut cluster1 = vec![0x1F, 0x43, 0xB6, 0x75, 0xFF];
+        cluster1.extend(uint_element(ids::TIMESTAMP, 0));
+        cluster1.extend(simple_block(1, 0, 0x80, &[1]));
+        let mut cluster2 = uint_element(ids::TIMESTAMP, 100);
+        cluster2.extend(simple_block(1, 0, 0x80, &[2]));
+
+        let mut segment = element(ids::TRACKS, &tracks);
+        segment.extend(cluster1);
+        segment.extend(element(ids::CLUSTER, &cluster2));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let file = parse(&data).unwrap();
+        let pts: Vec<i64> = file.samples.iter().map(|s| s.pts).collect();
+        assert_eq!(pts, vec![0, 100]);
+        assert_eq!(file.tracks[0].codec, "mp4a");
+    }
+
+    #[test]
+    fn test_cues_mark_keyframes() {
+        let tracks = track_entry(1, 1, "V_MPEG4/ISO/AVC");
+        let mut group = element(ids::BLOCK, &[0x81, 0, 0, 0, 9]);
+        group.extend(uint_element(ids::REFERENCE_BLOCK, 1));
+        let mut cluster = uint_element(ids::TIMESTAMP, 0);
+        cluster.extend(element(ids::BLOCK_GROUP, &group));
+
+        let tracks_el = element(ids::TRACKS, &tracks);
+        let mut positions = uint_element(ids::CUE_TRACK, 1);
+        positions.extend(uint_element(
+            ids::CUE_CLUSTER_POSITION,
+            tracks_el.len() as u64,
+        ));
+        let mut point = uint_element(ids::CUE_TIME, 0);
+        point.extend(element(ids::CUE_TRACK_POSITIONS, &positions));
+
+        let mut segment = tracks_el;
+        segment.extend(element(ids::CLUSTER, &cluster));
+        segment.extend(element(ids::CUES, &element(ids::CUE_POINT, &point)));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let file = parse(&data).unwrap();
+        assert_eq!(file.samples.len(), 1);
+        assert!(file.samples[0].is_keyframe);
+    }
+
+    #[test]
+    fn test_opus_codec_delay_and_discard_padding() {
+        let info = uint_element(ids::TIMESTAMP_SCALE, 1000);
+        let mut entry = uint_element(ids::TRACK_NUMBER, 1);
+        entry.extend(uint_element(ids::TRACK_TYPE, 2));
+        entry.extend(element(ids::CODEC_ID, b"A_OPUS"));
+        entry.extend(element(
+            ids::CODEC_PRIVATE,
+            &crate::decoder::opus::tests::opus_head(2, 312),
+        ));
+
+        // Two 20 ms packets, the second ending 10 ms early.
+        let mut cluster = uint_element(ids::TIMESTAMP, 0);
+        cluster.extend(simple_block(1, 0, 0x80, &[0xF8]));
+        let mut group = element(ids::BLOCK, &[0x81, 0x4E, 0x20, 0, 0xF8]);
+        group.extend(uint_element(ids::DISCARD_PADDING, 10_000_000));
+        cluster.extend(element(ids::BLOCK_GROUP, &group));
+
+        let mut segment = element(ids::INFO, &info);
+        segment.extend(element(ids::TRACKS, &element(ids::TRACK_ENTRY, &entry)));
+        segment.extend(element(ids::CLUSTER, &cluster));
+        let mut data = ebml_header("webm");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        // Without `CodecDelay`, the pre-skip of 312 samples (6.5 ms) is
+        // taken from the `OpusHead`.
+        let file = parse(&data).unwrap();
+        let pts: Vec<i64> = file.samples.iter().map(|s| s.pts).collect();
+        assert_eq!(pts, vec![-6500, 13500]);
+        assert_eq!(file.tracks[0].end_pts, Some(23500));
+
+        let padding = element(ids::DISCARD_PADDING, &[0xFF, 0x38]);
+        assert_eq!(elements(&padding, 0).next().unwrap().int(), -200);
+    }
+
+    #[test]
+    fn test_normalize_pcm_codec() {
+        assert_eq!(normalize_codec("A_PCM/INT/LIT", Some(24)), "pcm-s24");
+        assert_eq!(normalize_codec("A_PCM/INT/BIG", Some(16)), "pcm-s16be");
+        assert_eq!(normalize_codec("A_PCM/INT/LIT", Some(8)), "pcm-u8");
+        assert_eq!(normalize_codec("A_PCM/FLOAT/IEEE", Some(32)), "pcm-f32");
+        // Unsupported sizes keep the Matroska codec ID.
+        assert_eq!(
+            normalize_codec("A_PCM/FLOAT/IEEE", Some(64)),
+            "A_PCM/FLOAT/IEEE"
+        );
+        assert_eq!(normalize_codec("A_PCM/INT/LIT", None), "A_PCM/INT/LIT");
+        assert_eq!(normalize_codec("A_OPUS", Some(16)), "Opus");
+    }
+
+    #[test]
+    fn test_parse_track_properties() {
+        let mut colour = uint_element(ids::PRIMARIES, 9);
+        colour.extend(uint_element(ids::TRANSFER_CHARACTERISTICS, 16));
+        colour.extend(uint_element(ids::MATRIX_COEFFICIENTS, 9));
+        colour.extend(uint_element(ids::RANGE, 1));
+        colour.extend(uint_element(ids::BITS_PER_CHANNEL, 10));
+        let mut video = uint_element(ids::PIXEL_WIDTH, 1440);
+        video.extend(uint_element(ids::PIXEL_HEIGHT, 1080));
+        video.extend(uint_element(ids::DISPLAY_WIDTH, 16));
+        video.extend(uint_element(ids::DISPLAY_HEIGHT, 9));
+        video.extend(element(ids::COLOUR, &colour));
+        let mut video_track = uint_element(ids::TRACK_NUMBER, 1);
+        video_track.extend(uint_element(ids::TRACK_TYPE, 1));
+        video_track.extend(element(ids::CODEC_ID, b"V_MPEGH/ISO/HEVC"));
+        video_track.extend(uint_element(ids::DEFAULT_DURATION, 40_000_000));
+        video_track.extend(element(ids::VIDEO, &video));
+
+        let mut audio = element(ids::SAMPLING_FREQUENCY, &24000f64.to_be_bytes());
+        audio.extend(element(
+            ids::OUTPUT_SAMPLING_FREQUENCY,
+            &48000f64.to_be_bytes(),
+        ));
+        audio.extend(uint_element(ids::CHANNELS, 2));
+        let mut audio_track = uint_element(ids::TRACK_NUMBER, 2);
+        audio_track.extend(uint_element(ids::TRACK_TYPE, 2));
+        audio_track.extend(element(ids::CODEC_ID, b"A_AAC"));
+        audio_track.extend(element(ids::LANGUAGE, b"ger"));
+        audio_track.extend(element(ids::LANGUAGE_BCP47, b"de-CH"));
+        audio_track.extend(element(ids::NAME, b"Commentary"));
+        audio_track.extend(uint_element(ids::FLAG_DEFAULT, 0));
+        audio_track.extend(element(ids::AUDIO, &audio));
+
+        let mut subtitle_track = uint_element(ids::TRACK_NUMBER, 3);
+        subtitle_track.extend(uint_element(ids::TRACK_TYPE, 0x11));
+        subtitle_track.extend(element(ids::CODEC_ID, b"S_TEXT/UTF8"));
+        subtitle_track.extend(element(ids::LANGUAGE, b"und"));
+        subtitle_track.extend(uint_element(ids::FLAG_FORCED, 1));
+
+        let mut tracks = element(ids::TRACK_ENTRY, &video_track);
+        tracks.extend(element(ids::TRACK_ENTRY, &audio_track));
+        tracks.extend(element(ids::TRACK_ENTRY, &subtitle_track));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &element(ids::TRACKS, &tracks)));
+
+        let tracks = parse(&data).unwrap().tracks;
+        let video = tracks[0].video.as_ref().unwrap();
+        assert_eq!((video.width, video.height), (1440, 1080));
+        assert!((video.display_aspect_ratio.unwrap() - 16.0 / 9.0).abs() < 1e-9);
+        assert_eq!(video.frame_rate, Some(25.0));
+        assert_eq!(video.bit_depth, Some(10));
+        assert_eq!(video.color.unwrap().full_range, Some(false));
+        assert_eq!(tracks[0].language.as_deref(), Some("eng"));
+        assert!(tracks[0].is_default);
+
+        let audio = tracks[1].audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (48000, 2));
+        assert_eq!(tracks[1].language.as_deref(), Some("de-CH"));
+        assert_eq!(tracks[1].title.as_deref(), Some("Commentary"));
+        assert!(!tracks[1].is_default);
+
+        assert!(tracks[2].language.is_none());
+        assert!(tracks[2].is_forced);
+    }
+
+    /// Builds a `Tag` with the given target level and `(name, value)` pairs.
+    fn tag(level: u64, track_uid: Option<u64>, tags: &[(&str, &str)]) -> Vec<u8> {
+        let mut targets = uint_element(ids::TARGET_TYPE_VALUE, level);
+        if let Some(uid) = track_uid {
+            targets.extend(uint_element(ids::TAG_TRACK_UID, uid));
+        }
+        let mut p = element(ids::TARGETS, &targets);
+        for (name, value) in tags {
+            let mut simple = element(ids::TAG_NAME, name.as_bytes());
+            simple.extend(element(ids::TAG_STRING, value.as_bytes()));
+            p.extend(element(ids::SIMPLE_TAG, &simple));
+        }
+        element(ids::TAG, &p)
+    }
+
+    #[test]
+    fn test_parse_tags_and_attachments() {
+        let mut info = element(ids::TITLE, b"Segment title");
+        info.extend(element(ids::WRITING_APP, b"mkvmerge v80"));
+        let mut tags = tag(50, None, &[("TITLE", "Album"), ("ARTIST", "Band")]);
+        tags.extend(tag(
+            30,
+            None,
+            &[("TITLE", "Song"), ("DATE_RELEASED", "2020")],
+        ));
+        tags.extend(tag(30, Some(1), &[("TITLE", "Track-scoped")]));
+
+        let mut cover = element(ids::FILE_NAME, b"cover.jpg");
+        cover.extend(element(ids::FILE_MIME_TYPE, b"image/jpeg"));
+        cover.extend(element(ids::FILE_DATA, &[0xFF, 0xD8, 0xFF]));
+        let mut font = element(ids::FILE_NAME, b"font.ttf");
+        font.extend(element(ids::FILE_MIME_TYPE, b"font/ttf"));
+        font.extend(element(ids::FILE_DATA, &[0; 8]));
+        let mut attachments = element(ids::ATTACHED_FILE, &font);
+        attachments.extend(element(ids::ATTACHED_FILE, &cover));
+
+        let mut segment = element(ids::INFO, &info);
+        segment.extend(element(ids::TAGS, &tags));
+        segment.extend(element(ids::ATTACHMENTS, &attachments));
+        segment.extend(element(ids::TRACKS, &track_entry(1, 2, "A_OPUS")));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let metadata = parse(&data).unwrap().metadata;
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.album.as_deref(), Some("Album"));
+        assert_eq!(metadata.artist.as_deref(), Some("Band"));
+        assert_eq!(metadata.date.as_deref(), Some("2020"));
+        assert_eq!(metadata.encoder.as_deref(), Some("mkvmerge v80"));
+
+        assert_eq!(metadata.attachments.len(), 2);
+        assert!(!metadata.attachments[0].is_cover);
+        let cover = metadata.cover().unwrap();
+        assert_eq!(cover.name.as_deref(), Some("cover.jpg"));
+        assert_eq!(&data[cover.offset as usize..][..3], &[0xFF, 0xD8, 0xFF]);
+
+        // Without track-level tags the Info title is the fallback.
+        let mut segment = element(ids::INFO, &info);
+        segment.extend(element(ids::TRACKS, &track_entry(1, 2, "A_OPUS")));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &segment));
+        let metadata = parse(&data).unwrap().metadata;
+        assert_eq!(metadata.title.as_deref(), Some("Segment title"));
+        assert!(metadata.album.is_none());
+    }
+
+    /// Builds a `ChapterAtom` with `(language, title)` displays and nested atoms.
+    fn chapter_atom(start_ms: u64, displays: &[(&str, &str)], nested: &[u8]) -> Vec<u8> {
+        let mut p = uint_element(ids::CHAPTER_TIME_START, start_ms * 1_000_000);
+        for (language, title) in displays {
+            let mut display = element(ids::CHAP_STRING, title.as_bytes());
+            display.extend(element(ids::CHAP_LANGUAGE, language.as_bytes()));
+            p.extend(element(ids::CHAPTER_DISPLAY, &display));
+        }
+        p.extend_from_slice(nested);
+        element(ids::CHAPTER_ATOM, &p)
+    }
+
+    #[test]
+    fn test_parse_chapters() {
+        // A hidden edition, then the default one with a nested and a hidden chapter.
+        let mut hidden = uint_element(ids::EDITION_FLAG_HIDDEN, 1);
+        hidden.extend(chapter_atom(0, &[("eng", "Hidden edition")], &[]));
+        let mut edition = uint_element(ids::EDITION_FLAG_DEFAULT, 1);
+        let nested = chapter_atom(30_000, &[("eng", "Exercise")], &[]);
+        edition.extend(chapter_atom(
+            0,
+            &[("eng", "Lecture"), ("ger", "Vorlesung")],
+            &nested,
+        ));
+        let mut hidden_atom = uint_element(ids::CHAPTER_FLAG_HIDDEN, 1);
+        hidden_atom.extend(uint_element(ids::CHAPTER_TIME_START, 50_000_000_000));
+        edition.extend(element(ids::CHAPTER_ATOM, &hidden_atom));
+        edition.extend(chapter_atom(60_000, &[("eng", "Q&A")], &[]));
+        let mut chapters = element(ids::EDITION_ENTRY, &hidden);
+        chapters.extend(element(ids::EDITION_ENTRY, &edition));
+
+        let mut info = uint_element(ids::TIMESTAMP_SCALE, 1_000_000);
+        info.extend(element(ids::DURATION, &90_000f64.to_be_bytes()));
+        let mut segment = element(ids::INFO, &info);
+        segment.extend(element(ids::CHAPTERS, &chapters));
+        segment.extend(element(ids::TRACKS, &track_entry(1, 2, "A_OPUS")));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let chapters = parse(&data).unwrap().chapters;
+        let summary: Vec<_> = chapters
+            .iter()
+            .map(|c| (c.title.as_deref().unwrap(), c.start_ms, c.end_ms, c.depth))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Lecture", 0, Some(60_000), 0),
+                ("Exercise", 30_000, Some(60_000), 1),
+                ("Q&A", 60_000, Some(90_000), 0),
+            ]
+        );
+        assert_eq!(chapters[0].titles[1].title, "Vorlesung");
+        assert_eq!(chapters[0].titles[1].language.as_deref(), Some("ger"));
+    }
+}
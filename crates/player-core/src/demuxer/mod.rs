@@ -3,8 +3,40 @@
 //! This module provides functionality to demux video container formats
 //! (MP4, MKV, WebM, etc.) and extract audio/video streams.
 
+use crate::decoder::aac::AudioSpecificConfig;
+use crate::decoder::av1::Av1DecoderConfig;
+use crate::decoder::flac::FlacStreamInfo;
+use crate::decoder::h264::AvcDecoderConfig;
+use crate::decoder::h265::HevcDecoderConfig;
+use crate::decoder::opus::OpusHead;
+use crate::decoder::pcm::{self, WaveFormat};
+use crate::decoder::vorbis::VorbisHeaders;
+use crate::decoder::vp9::{self, VpcDecoderConfig};
 use crate::error::{PlayerError, Result};
+use crate::time::{Timebase, Timestamp};
 use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+mod avi;
+mod flac;
+mod fmp4;
+mod metadata;
+mod mkv;
+mod mp3;
+mod mp4;
+mod mp4meta;
+mod mpegts;
+mod ogg;
+mod reader;
+mod riff;
+mod seek;
+mod source;
+mod wav;
+
+pub use metadata::{Attachment, Chapter, ChapterTitle, Metadata};
+use seek::SeekIndex;
+use source::SourceReader;
+pub use source::{BlobSource, ByteSource, MemorySource};
 
 /// Supported container formats.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -15,12 +47,25 @@ pub enum ContainerFormat {
     Mkv,
     /// WebM container (subset of Matroska).
     WebM,
+    /// MPEG-2 Transport Stream (including 192-byte M2TS).
+    MpegTs,
+    /// Ogg container (Opus, Vorbis, FLAC or Theora).
+    Ogg,
+    /// Audio Video Interleave (RIFF) container, including OpenDML.
+    Avi,
+    /// MP3 elementary stream, optionally with ID3 tags.
+    Mp3,
+    /// Native FLAC stream.
+    Flac,
+    /// WAVE (RIFF) file, including RF64 and BW64.
+    Wav,
     /// Unknown or unsupported format.
     Unknown,
 }
 
 /// Information about a media stream.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct StreamInfo {
     /// Stream index within the container.
     pub index: u32,
@@ -28,11 +73,255 @@ pub struct StreamInfo {
     pub stream_type: StreamType,
     /// Codec identifier.
     pub codec: String,
+    /// RFC 6381 codec string for WebCodecs (e.g. `avc1.64001f`), when the
+    /// codec configuration could be parsed.
+    pub codec_string: Option<String>,
     /// Duration in milliseconds.
     pub duration_ms: Option<u64>,
+    /// Presentation time at which the last sample ends, in timescale units,
+    /// when the container records it exactly (the granule position of an
+    /// Ogg end-of-stream page, or a Matroska `DiscardPadding`). Decoded
+    /// samples past it are encoder padding.
+    pub end_pts: Option<i64>,
+    /// Number of timestamp units per second in the container.
+    pub timescale: u32,
+    /// Language code (ISO 639-2 or BCP 47), if known.
+    pub language: Option<String>,
+    /// Track name, if stored.
+    pub title: Option<String>,
+    /// Whether the stream is selected by default among streams of its type.
+    pub is_default: bool,
+    /// Whether the stream must be shown regardless of user choice (forced subtitles).
+    pub is_forced: bool,
+    /// Average bitrate in bits per second, if known.
+    pub bitrate: Option<u64>,
+    /// Picture properties of a video stream.
+    pub video: Option<VideoInfo>,
+    /// Sound properties of an audio stream.
+    pub audio: Option<AudioInfo>,
+    /// Codec initialization data from the container (e.g. the `avcC`
+    /// record), to pass to the decoder.
+    #[serde(skip)]
+    pub extra_data: Option<Vec<u8>>,
+}
+
+impl StreamInfo {
+    /// Creates stream information with only the required fields set.
+    pub fn new(
+        index: u32,
+        stream_type: StreamType,
+        codec: impl Into<String>,
+        timescale: u32,
+    ) -> Self {
+        Self {
+            index,
+            stream_type,
+            codec: codec.into(),
+            codec_string: None,
+            duration_ms: None,
+            end_pts: None,
+            timescale,
+            language: None,
+            title: None,
+            is_default: false,
+            is_forced: false,
+            bitrate: None,
+            video: None,
+            audio: None,
+            extra_data: None,
+        }
+    }
+
+    /// Returns the time base of the stream's packet timestamps.
+    pub fn timebase(&self) -> Timebase {
+        Timebase::from_rate(self.timescale)
+    }
+}
+
+/// Picture properties of a video stream.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoInfo {
+    /// Coded width in pixels.
+    pub width: u32,
+    /// Coded height in pixels.
+    pub height: u32,
+    /// Width to height ratio the picture is displayed at, if known.
+    pub display_aspect_ratio: Option<f64>,
+    /// Average frame rate in frames per second, if known.
+    pub frame_rate: Option<f64>,
+    /// Clockwise rotation to apply for display, in degrees (0, 90, 180 or 270).
+    pub rotation: u16,
+    /// Colour description, if signalled by the container.
+    pub color: Option<ColorInfo>,
+    /// Bits per colour component, if known.
+    pub bit_depth: Option<u8>,
+}
+
+/// Colour description using the ISO/IEC 23091-2 (H.273) code points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorInfo {
+    /// Colour primaries (e.g. 1 for BT.709, 9 for BT.2020).
+    pub primaries: Option<u8>,
+    /// Transfer characteristics (e.g. 1 for BT.709, 16 for PQ, 18 for HLG).
+    pub transfer: Option<u8>,
+    /// Matrix coefficients (e.g. 1 for BT.709, 9 for BT.2020 non-constant).
+    pub matrix: Option<u8>,
+    /// Whether samples use the full range rather than the video range.
+    pub full_range: Option<bool>,
+}
+
+/// Sound properties of an audio stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioInfo {
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Number of channels.
+    pub channels: u16,
+    /// Channel layout name such as `stereo` or `5.1`, if known.
+    pub channel_layout: Option<String>,
+    /// Bits per sample, if known.
+    pub bit_depth: Option<u8>,
+}
+
+impl AudioInfo {
+    /// Creates audio information with the default layout for the channel count.
+    pub(crate) fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            channel_layout: default_channel_layout(channels).map(str::to_string),
+            bit_depth: None,
+        }
+    }
+}
+
+/// Returns the layout name of a WAVE speaker position mask.
+pub(crate) fn channel_mask_layout(mask: u32) -> Option<&'static str> {
+    Some(match mask {
+        0x4 => "mono",
+        0x3 => "stereo",
+        0xB => "2.1",
+        0x7 => "3.0",
+        0x33 => "quad",
+        0x107 => "4.0",
+        0x607 => "5.0",
+        0x37 => "5.0(back)",
+        0x60F => "5.1",
+        0x3F => "5.1(back)",
+        0x70F => "6.1",
+        0x63F => "7.1",
+        _ => return None,
+    })
+}
+
+/// Returns the conventional layout name for a channel count.
+pub(crate) fn default_channel_layout(channels: u16) -> Option<&'static str> {
+    Some(match channels {
+        1 => "mono",
+        2 => "stereo",
+        3 => "2.1",
+        4 => "4.0",
+        5 => "5.0",
+        6 => "5.1",
+        7 => "6.1",
+        8 => "7.1",
+        _ => return None,
+    })
+}
+
+/// Derives the codec string and missing picture properties from the codec
+/// initialization data. Unparseable data is left for the decoder to reject.
+fn apply_codec_config(stream: &mut StreamInfo) {
+    // WebCodecs names VP8, MP3 and FLAC without profile or level.
+    match stream.codec.as_str() {
+        "vp08" => stream.codec_string = Some("vp8".to_string()),
+        "mp3" => stream.codec_string = Some("mp3".to_string()),
+        "fLaC" => stream.codec_string = Some("flac".to_string()),
+        _ => {}
+    }
+    // PCM decoders are initialized from a WAVEFORMATEX, which only WAVE
+    // and AVI files store.
+    if let Some(codec) = pcm::codec_for_name(&stream.codec) {
+        stream.codec_string = pcm::codec_string(codec).map(str::to_string);
+        if let (None, Some(audio)) = (&stream.extra_data, &stream.audio) {
+            let format = WaveFormat::new(codec, audio.sample_rate, audio.channels);
+            stream.extra_data = Some(format.to_bytes());
+        }
+    }
+    let extra_data = match &stream.extra_data {
+        Some(data) => data,
+        None => return,
+    };
+    match stream.codec.as_str() {
+        "avc1" | "avc3" => {
+            if let Ok(config) = AvcDecoderConfig::parse(extra_data) {
+                stream.codec_string = Some(config.codec_string());
+                config
+                    .sps
+                    .fill_video_info(stream.video.get_or_insert_with(VideoInfo::default));
+            }
+        }
+        "hvc1" | "hev1" => {
+            if let Ok(config) = HevcDecoderConfig::parse(extra_data) {
+                stream.codec_string = Some(config.codec_string(&stream.codec));
+                config
+                    .sps
+                    .fill_video_info(stream.video.get_or_insert_with(VideoInfo::default));
+            }
+        }
+        "av01" => {
+            if let Ok(config) = Av1DecoderConfig::parse(extra_data) {
+                stream.codec_string = Some(config.codec_string());
+                config.fill_video_info(stream.video.get_or_insert_with(VideoInfo::default));
+            }
+        }
+        "mp4a" => {
+            if let Ok(config) = AudioSpecificConfig::parse(extra_data) {
+                stream.codec_string = Some(config.codec_string());
+                config.fill_audio_info(stream.audio.get_or_insert_with(AudioInfo::default));
+            }
+        }
+        "fLaC" => {
+            if let Ok(info) = FlacStreamInfo::parse(extra_data) {
+                info.fill_audio_info(stream.audio.get_or_insert_with(AudioInfo::default));
+            }
+        }
+        "Opus" => {
+            if let Ok(head) = OpusHead::parse(extra_data) {
+                stream.codec_string = Some("opus".to_string());
+                head.fill_audio_info(stream.audio.get_or_insert_with(AudioInfo::default));
+            }
+        }
+        "vorbis" => {
+            if let Ok(headers) = VorbisHeaders::parse(extra_data) {
+                stream.codec_string = Some("vorbis".to_string());
+                headers.fill_audio_info(stream.audio.get_or_insert_with(AudioInfo::default));
+                if stream.bitrate.is_none() {
+                    stream.bitrate = headers.identification.bitrate_nominal.map(u64::from);
+                }
+            }
+        }
+        "vp08" | "vp09" => {
+            if let Ok(mut config) = VpcDecoderConfig::parse(extra_data) {
+                let video = stream.video.get_or_insert_with(VideoInfo::default);
+                if config.level == 0 && video.width > 0 && video.height > 0 {
+                    config.level = vp9::level_for_picture_size(video.width, video.height);
+                }
+                if stream.codec == "vp09" {
+                    stream.codec_string = Some(config.codec_string("vp09"));
+                }
+                config.fill_video_info(video);
+            }
+        }
+        _ => {}
+    }
 }
 
 /// Type of media stream.
+#[wasm_bindgen]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StreamType {
     /// Video stream.
@@ -48,16 +337,85 @@ pub enum StreamType {
 pub struct Packet {
     /// Stream index this packet belongs to.
     pub stream_index: u32,
-    /// Presentation timestamp in milliseconds.
-    pub pts_ms: u64,
-    /// Decode timestamp in milliseconds.
-    pub dts_ms: u64,
+    /// Presentation timestamp, in the stream's time base.
+    pub pts: Timestamp,
+    /// Decode timestamp, in the stream's time base.
+    pub dts: Timestamp,
     /// Whether this is a keyframe.
     pub is_keyframe: bool,
     /// Encoded data.
     pub data: Vec<u8>,
 }
 
+/// Location and timing of a single sample within the container.
+///
+/// Timestamps are expressed in the owning stream's timescale.
+#[derive(Debug, Clone)]
+pub(crate) struct SampleEntry {
+    /// Stream index this sample belongs to.
+    pub stream_index: u32,
+    /// Absolute file offset of the sample data.
+    pub offset: u64,
+    /// Size of the sample data in bytes.
+    pub size: u32,
+    /// Further `(offset, size)` ranges for samples split across the container.
+    pub continuation: Option<Box<[(u64, u32)]>>,
+    /// Decode timestamp.
+    pub dts: i64,
+    /// Presentation timestamp.
+    pub pts: i64,
+    /// Whether this sample is a random access point.
+    pub is_keyframe: bool,
+}
+
+/// Converts a value in `timescale` units to milliseconds.
+pub(crate) fn to_ms(value: u64, timescale: u32) -> u64 {
+    if timescale == 0 {
+        return 0;
+    }
+    (value as u128 * 1000 / timescale as u128) as u64
+}
+
+/// Number of leading bytes inspected to detect the container format.
+const FORMAT_PROBE_LEN: u64 = 4096;
+
+/// Bytes of appended data needed before the format is detected; enough for
+/// three M2TS packets or an EBML header with its DocType.
+const APPEND_PROBE_LEN: u64 = 1024;
+
+/// How far into a chapter going back restarts it rather than moving to the
+/// previous chapter, in milliseconds.
+const CHAPTER_RESTART_MS: u64 = 3000;
+
+/// Streams, duration and sample index produced by a container parser.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Container {
+    /// Streams in container order.
+    pub streams: Vec<StreamInfo>,
+    /// Overall duration in milliseconds, if known.
+    pub duration_ms: Option<u64>,
+    /// Samples of all streams found since the previous call to
+    /// [`ContainerParser::container`].
+    pub samples: Vec<SampleEntry>,
+    /// Descriptive tags and attachments.
+    pub metadata: Metadata,
+    /// Chapters in start order.
+    pub chapters: Vec<Chapter>,
+}
+
+/// Parser for one container format.
+///
+/// Parsers read only the structures they need from the source and keep
+/// their position, so `parse` resumes where the previous call stopped.
+pub(crate) trait ContainerParser: std::fmt::Debug {
+    /// Parses every complete structure available from `reader`.
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()>;
+
+    /// Returns the streams and duration found so far, handing over the
+    /// samples found since the previous call.
+    fn container(&mut self) -> Container;
+}
+
 /// Demuxer for parsing container formats and extracting streams.
 #[derive(Debug)]
 pub struct Demuxer {
@@ -65,9 +423,36 @@ pub struct Demuxer {
     format: Option<ContainerFormat>,
     /// Information about available streams.
     streams: Vec<StreamInfo>,
-    /// Raw container data.
-    data: Vec<u8>,
-    /// Current read position.
+    /// Overall duration in milliseconds, if known.
+    duration_ms: Option<u64>,
+    /// Descriptive tags and attachments.
+    metadata: Metadata,
+    /// Chapters in start order.
+    chapters: Vec<Chapter>,
+    /// Sample index of all streams, in file order, apart from samples
+    /// found after the read position had passed them.
+    samples: Vec<SampleEntry>,
+    /// Source the container data is read from.
+    source: Box<dyn ByteSource>,
+    /// Whether data is still being appended to the source.
+    growing: bool,
+    /// Parser of the detected format, kept to resume as data is appended.
+    parser: Option<Box<dyn ContainerParser>>,
+    /// Keyframe index of every stream.
+    seek_index: SeekIndex,
+    /// Per-stream sample count and total size, for the derived bitrate.
+    sample_totals: Vec<(u64, u64)>,
+    /// Per-stream index of the first sample to emit after a seek.
+    resume_from: Vec<usize>,
+    /// Packet to emit in place of the listed sample at the given offset,
+    /// after a seek into the middle of a PCM packet: that sample cut to
+    /// start at the target sample frame.
+    seek_cut: Option<(u64, SampleEntry)>,
+    /// Streams chosen with [`Demuxer::select_stream`], by type.
+    selected: Vec<(StreamType, Option<u32>)>,
+    /// Whether packets of each stream are returned.
+    active: Vec<bool>,
+    /// Current read position (index into `samples`).
     position: usize,
     /// Whether the demuxer has been initialized.
     initialized: bool,
@@ -85,7 +470,19 @@ impl Demuxer {
         Self {
             format: None,
             streams: Vec::new(),
-            data: Vec::new(),
+            duration_ms: None,
+            metadata: Metadata::default(),
+            chapters: Vec::new(),
+            samples: Vec::new(),
+            seek_index: SeekIndex::default(),
+            sample_totals: Vec::new(),
+            resume_from: Vec::new(),
+            seek_cut: None,
+            selected: Vec::new(),
+            active: Vec::new(),
+            source: Box::new(MemorySource::default()),
+            growing: false,
+            parser: None,
             position: 0,
             initialized: false,
         }
@@ -99,21 +496,118 @@ impl Demuxer {
     /// # Errors
     /// Returns an error if the data is empty or the format cannot be detected.
     pub fn init(&mut self, data: Vec<u8>) -> Result<()> {
-        if data.is_empty() {
+        self.init_source(Box::new(MemorySource::new(data)))
+    }
+
+    /// Initializes the demuxer with a byte source.
+    ///
+    /// Packet data is read from the source as packets are requested. MP4,
+    /// AVI and WAV files are only read for their headers and indexes up
+    /// front; other formats are read through once to locate their frames.
+    ///
+    /// # Arguments
+    /// * `source` - Random-access source of the container file.
+    ///
+    /// # Errors
+    /// Returns an error if the source is empty, cannot be read, or the
+    /// format cannot be detected.
+    pub fn init_source(&mut self, source: Box<dyn ByteSource>) -> Result<()> {
+        if source.is_empty() {
             return Err(PlayerError::demuxer("Empty data provided"));
         }
+        self.source = source;
+        self.growing = false;
+        self.open()?;
+        self.parse_streams()
+    }
 
+    /// Appends a chunk of container data as it arrives.
+    ///
+    /// The demuxer parses whatever complete structures are available, so
+    /// playback can start before the whole file has been received. Call
+    /// [`Demuxer::end_of_stream`] after the last chunk.
+    ///
+    /// # Arguments
+    /// * `data` - Next chunk of the container file.
+    ///
+    /// # Returns
+    /// `true` once stream information is available, `false` if more data is
+    /// needed first.
+    ///
+    /// # Errors
+    /// Returns an error if the demuxer was initialized with a complete source
+    /// or the data is not a valid container.
+    pub fn append_data(&mut self, data: &[u8]) -> Result<bool> {
+        if !self.growing {
+            if self.initialized {
+                return Err(PlayerError::demuxer("Cannot append to a complete source"));
+            }
+            self.source = Box::new(MemorySource::default());
+            self.growing = true;
+        }
+        self.source.append(data)?;
+
+        if !self.initialized {
+            // Wait for enough data to tell the formats apart.
+            if self.source.len() < APPEND_PROBE_LEN {
+                return Ok(false);
+            }
+            self.open()?;
+        }
+        self.parse_streams()?;
+        Ok(!self.streams.is_empty())
+    }
+
+    /// Signals that all data has been appended.
+    ///
+    /// # Errors
+    /// Returns an error if no data was appended or the format cannot be detected.
+    pub fn end_of_stream(&mut self) -> Result<()> {
+        if !self.growing {
+            return Ok(());
+        }
+        self.growing = false;
+        if !self.initialized {
+            if self.source.is_empty() {
+                return Err(PlayerError::demuxer("Empty data provided"));
+            }
+            self.open()?;
+        }
+        self.parse_streams()
+    }
+
+    /// Detects the format of the current source and resets the read state.
+    fn open(&mut self) -> Result<()> {
         // Detect container format from magic bytes
-        let format = self.detect_format(&data)?;
+        let mut head = vec![0; self.source.len().min(FORMAT_PROBE_LEN) as usize];
+        self.source.read_at(0, &mut head)?;
+        let format = self.detect_format(&head)?;
 
-        self.data = data;
+        self.parser = match format {
+            ContainerFormat::Mp4 => Some(Box::new(mp4::Mp4Parser::new())),
+            ContainerFormat::Mkv | ContainerFormat::WebM => Some(Box::new(mkv::MkvParser::new())),
+            ContainerFormat::MpegTs => Some(Box::new(mpegts::TsParser::new())),
+            ContainerFormat::Avi => Some(Box::new(avi::AviParser::new())),
+            ContainerFormat::Ogg => Some(Box::new(ogg::OggParser::new())),
+            ContainerFormat::Mp3 => Some(Box::new(mp3::Mp3Parser::new())),
+            ContainerFormat::Flac => Some(Box::new(flac::FlacParser::new())),
+            ContainerFormat::Wav => Some(Box::new(wav::WavParser::new())),
+            ContainerFormat::Unknown => None,
+        };
         self.format = Some(format);
+        self.streams.clear();
+        self.samples.clear();
+        self.duration_ms = None;
+        self.metadata = Metadata::default();
+        self.chapters.clear();
+        self.seek_index = SeekIndex::default();
+        self.sample_totals.clear();
+        self.resume_from.clear();
+        self.seek_cut = None;
+        self.selected.clear();
+        self.active.clear();
         self.position = 0;
         self.initialized = true;
-
-        // Parse container structure (stub for now)
-        self.parse_streams()?;
-
         Ok(())
     }
 
@@ -131,33 +625,164 @@ impl Demuxer {
         }
 
         // Check for EBML header (MKV/WebM)
-        if data.len() >= 4 && &data[0..4] == &[0x1A, 0x45, 0xDF, 0xA3] {
-            // Further check for WebM vs MKV would require parsing EBML
-            return Ok(ContainerFormat::Mkv);
+        if data.len() >= 4 && data[0..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+            return Ok(match mkv::doc_type(data).as_deref() {
+                Some("webm") => ContainerFormat::WebM,
+                _ => ContainerFormat::Mkv,
+            });
+        }
+
+        // Check for RIFF AVI header
+        if &data[0..4] == b"RIFF" && &data[8..12] == b"AVI " {
+            return Ok(ContainerFormat::Avi);
+        }
+
+        // Check for RIFF, RF64 or BW64 WAVE headers
+        if wav::probe(data) {
+            return Ok(ContainerFormat::Wav);
+        }
+
+        // Check for Ogg capture pattern
+        if &data[0..4] == b"OggS" {
+            return Ok(ContainerFormat::Ogg);
+        }
+
+        // Check for the FLAC stream marker, which may follow an ID3v2 tag
+        if flac::probe(data) {
+            return Ok(ContainerFormat::Flac);
+        }
+
+        // Check for repeated TS sync bytes (MPEG-TS/M2TS)
+        if mpegts::probe(data).is_some() {
+            return Ok(ContainerFormat::MpegTs);
+        }
+
+        // Check for an ID3v2 tag or consecutive MPEG audio frames (MP3)
+        if mp3::probe(data) {
+            return Ok(ContainerFormat::Mp3);
         }
 
         Ok(ContainerFormat::Unknown)
     }
 
-    /// Parses stream information from the container.
+    /// Parses stream information from the data available so far.
     fn parse_streams(&mut self) -> Result<()> {
-        // Stub implementation - actual parsing would depend on format
-        // For now, create placeholder stream info
-        match self.format {
-            Some(ContainerFormat::Mp4) | Some(ContainerFormat::Mkv) | Some(ContainerFormat::WebM) => {
-                // Placeholder: assume one video stream
-                self.streams.push(StreamInfo {
-                    index: 0,
-                    stream_type: StreamType::Video,
-                    codec: "unknown".to_string(),
-                    duration_ms: None,
-                });
+        let parser = match self.parser.as_mut() {
+            Some(parser) => parser,
+            None => return Ok(()),
+        };
+        let mut reader = if self.growing {
+            SourceReader::growing(self.source.as_mut())
+        } else {
+            SourceReader::new(self.source.as_mut())
+        };
+        parser.parse(&mut reader)?;
+
+        let container = parser.container();
+        self.streams = container.streams;
+        self.duration_ms = container.duration_ms;
+        self.metadata = container.metadata;
+        self.chapters = container.chapters;
+
+        self.sample_totals.resize(self.streams.len(), (0, 0));
+        for sample in &container.samples {
+            if let Some(total) = self.sample_totals.get_mut(sample.stream_index as usize) {
+                total.0 += 1;
+                total.1 += sample.size as u64;
             }
-            _ => {}
         }
+        let from = self.merge_samples(container.samples);
+        self.seek_index
+            .extend(&self.samples, from, self.streams.len());
+        self.resume_from.resize(self.streams.len(), 0);
+        self.derive_stream_info();
+        self.update_active();
         Ok(())
     }
 
+    /// Merges newly parsed samples into the file-ordered sample list and
+    /// returns the index of the first sample that was added or moved.
+    ///
+    /// Samples that belong before the read position, such as a long PES
+    /// packet completed after later ones were read, are placed at the read
+    /// position so they are still returned.
+    fn merge_samples(&mut self, mut added: Vec<SampleEntry>) -> usize {
+        // The sort is stable so samples sharing an offset keep their decode order.
+        added.sort_by_key(|s| s.offset);
+        let first = match added.first() {
+            Some(first) => first.offset,
+            None => return self.samples.len(),
+        };
+        let from =
+            self.position + self.samples[self.position..].partition_point(|s| s.offset <= first);
+        if from == self.samples.len() {
+            self.samples.extend(added);
+            return from;
+        }
+
+        // Samples already listed stay ahead of added ones at the same offset.
+        for index in self.resume_from.iter_mut() {
+            if let Some(sample) = self.samples.get(*index).filter(|_| *index >= from) {
+                *index += added.partition_point(|s| s.offset < sample.offset);
+            }
+        }
+        let tail = self.samples.split_off(from);
+        self.samples.reserve(tail.len() + added.len());
+        let mut added = added.into_iter().peekable();
+        for sample in tail {
+            while let Some(next) = added.next_if(|s| s.offset < sample.offset) {
+                self.samples.push(next);
+            }
+            self.samples.push(sample);
+        }
+        self.samples.extend(added);
+        from
+    }
+
+    /// Recomputes which streams are read from the selection.
+    fn update_active(&mut self) {
+        self.active = self
+            .streams
+            .iter()
+            .map(|s| self.selected_stream(s.stream_type) == Some(s.index))
+            .collect();
+    }
+
+    /// Fills in stream properties the container doesn't store from the
+    /// sample index, and marks the first stream of each type as default
+    /// when none is.
+    fn derive_stream_info(&mut self) {
+        for stream in &mut self.streams {
+            apply_codec_config(stream);
+        }
+
+        for (stream, &(count, bytes)) in self.streams.iter_mut().zip(&self.sample_totals) {
+            let duration_ms = stream.duration_ms.filter(|&ms| ms > 0).or(self.duration_ms);
+            let seconds = match duration_ms {
+                Some(ms) if ms > 0 && count > 0 => ms as f64 / 1000.0,
+                _ => continue,
+            };
+            stream
+                .bitrate
+                .get_or_insert((bytes as f64 * 8.0 / seconds) as u64);
+            if let Some(video) = stream.video.as_mut() {
+                video.frame_rate.get_or_insert(count as f64 / seconds);
+            }
+        }
+
+        for stream_type in [StreamType::Video, StreamType::Audio, StreamType::Subtitle] {
+            let mut of_type = self
+                .streams
+                .iter_mut()
+                .filter(|s| s.stream_type == stream_type);
+            if let Some(first) = of_type.next() {
+                if !first.is_default && !of_type.any(|s| s.is_default) {
+                    first.is_default = true;
+                }
+            }
+        }
+    }
+
     /// Returns the detected container format.
     pub fn format(&self) -> Option<ContainerFormat> {
         self.format
@@ -168,6 +793,121 @@ impl Demuxer {
         &self.streams
     }
 
+    /// Returns the overall media duration in milliseconds, if known.
+    pub fn duration_ms(&self) -> Option<u64> {
+        self.duration_ms
+    }
+
+    /// Returns the streams of one type.
+    pub fn streams_of_type(&self, stream_type: StreamType) -> impl Iterator<Item = &StreamInfo> {
+        self.streams
+            .iter()
+            .filter(move |s| s.stream_type == stream_type)
+    }
+
+    /// Returns the stream of a type whose packets are read.
+    ///
+    /// Until [`Demuxer::select_stream`] is called for the type, this is the
+    /// first stream flagged as default.
+    pub fn selected_stream(&self, stream_type: StreamType) -> Option<u32> {
+        match self.selected.iter().find(|(t, _)| *t == stream_type) {
+            Some(&(_, index)) => index,
+            None => self
+                .streams_of_type(stream_type)
+                .find(|s| s.is_default)
+                .map(|s| s.index),
+        }
+    }
+
+    /// Selects the stream of a type whose packets are read.
+    ///
+    /// Packets of the other streams of that type are skipped. The selection
+    /// persists across seeks; a newly selected stream is read from the
+    /// current position.
+    ///
+    /// # Arguments
+    /// * `stream_type` - Type of stream to select.
+    /// * `index` - Index of the stream, or `None` to read no stream of the type.
+    ///
+    /// # Errors
+    /// Returns an error if there is no stream of the given type at `index`.
+    pub fn select_stream(&mut self, stream_type: StreamType, index: Option<u32>) -> Result<()> {
+        if let Some(index) = index {
+            match self.streams.get(index as usize) {
+                Some(stream) if stream.stream_type == stream_type => {}
+                Some(_) => return Err(PlayerError::demuxer("Stream type mismatch")),
+                None => return Err(PlayerError::demuxer("Stream not found")),
+            }
+        }
+        self.selected.retain(|(t, _)| *t != stream_type);
+        self.selected.push((stream_type, index));
+        self.update_active();
+        Ok(())
+    }
+
+    /// Returns the container's descriptive tags and attachments.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the chapters in start order.
+    ///
+    /// Nested chapters follow their parent, with a greater [`Chapter::depth`].
+    pub fn chapters(&self) -> &[Chapter] {
+        &self.chapters
+    }
+
+    /// Returns the first chapter starting after a position.
+    ///
+    /// # Arguments
+    /// * `position_ms` - Current playback position in milliseconds.
+    pub fn next_chapter(&self, position_ms: u64) -> Option<&Chapter> {
+        self.chapters.iter().find(|c| c.start_ms > position_ms)
+    }
+
+    /// Returns the chapter to go back to from a position.
+    ///
+    /// This is the start of the current chapter, or the chapter before it
+    /// when the position is within three seconds of that start.
+    ///
+    /// # Arguments
+    /// * `position_ms` - Current playback position in milliseconds.
+    pub fn previous_chapter(&self, position_ms: u64) -> Option<&Chapter> {
+        let current = self
+            .chapters
+            .iter()
+            .rposition(|c| c.start_ms <= position_ms)?;
+        if position_ms - self.chapters[current].start_ms < CHAPTER_RESTART_MS && current > 0 {
+            Some(&self.chapters[current - 1])
+        } else {
+            Some(&self.chapters[current])
+        }
+    }
+
+    /// Reads the data of an attachment, such as cover art.
+    ///
+    /// # Arguments
+    /// * `index` - Index into [`Metadata::attachments`].
+    ///
+    /// # Errors
+    /// Returns an error if there is no such attachment or its data cannot be read.
+    pub fn attachment_data(&mut self, index: usize) -> Result<Vec<u8>> {
+        let attachment = self
+            .metadata
+            .attachments
+            .get(index)
+            .ok_or_else(|| PlayerError::demuxer("Attachment not found"))?;
+        if attachment.offset.saturating_add(attachment.size) > self.source.len() {
+            if self.growing {
+                return Err(PlayerError::NeedMoreData);
+            }
+            return Err(PlayerError::demuxer("Attachment data out of range"));
+        }
+        let mut data = vec![0; attachment.size as usize];
+        self.source.read_at(attachment.offset, &mut data)?;
+        Ok(data)
+    }
+
     /// Returns whether the demuxer has been initialized.
     pub fn is_initialized(&self) -> bool {
         self.initialized
@@ -178,31 +918,173 @@ impl Demuxer {
     /// # Returns
     /// `Ok(Some(packet))` if a packet was read, `Ok(None)` if end of stream,
     /// or an error if demuxing fails.
+    ///
+    /// # Errors
+    /// Returns [`PlayerError::NeedMoreData`] while data is being appended and
+    /// the next packet has not fully arrived yet.
     pub fn read_packet(&mut self) -> Result<Option<Packet>> {
         if !self.initialized {
+            if self.growing {
+                return Err(PlayerError::NeedMoreData);
+            }
             return Err(PlayerError::demuxer("Demuxer not initialized"));
         }
 
-        // Stub implementation
-        // Real implementation would parse container structure and extract packets
-        Ok(None)
+        // Skip samples that precede a stream's seek landing point.
+        let sample = loop {
+            let sample = match self.samples.get(self.position) {
+                Some(sample) => sample,
+                None if self.growing => return Err(PlayerError::NeedMoreData),
+                None => return Ok(None),
+            };
+            let stream = sample.stream_index as usize;
+            if self.active[stream] && self.position >= self.resume_from[stream] {
+                break sample;
+            }
+            self.position += 1;
+        };
+        let cut = match self.seek_cut.take() {
+            Some((offset, cut)) if offset == sample.offset => Some(cut),
+            Some(cut) if cut.1.stream_index != sample.stream_index => {
+                self.seek_cut = Some(cut);
+                None
+            }
+            _ => None,
+        };
+        let sample = cut.as_ref().unwrap_or(sample);
+
+        let ranges = std::iter::once((sample.offset, sample.size))
+            .chain(sample.continuation.iter().flat_map(|c| c.iter().copied()));
+        // Check every range against the source before allocating for them.
+        let mut len = 0u64;
+        for (offset, size) in ranges.clone() {
+            match offset.checked_add(size as u64) {
+                Some(end) if end <= self.source.len() => len += size as u64,
+                Some(_) if self.growing => return Err(PlayerError::NeedMoreData),
+                _ => return Err(PlayerError::demuxer("Sample data out of range")),
+            }
+        }
+        let mut data = Vec::with_capacity(len as usize);
+        for (offset, size) in ranges {
+            let start = data.len();
+            data.resize(start + size as usize, 0);
+            self.source.read_at(offset, &mut data[start..])?;
+        }
+
+        let timebase = self.streams[sample.stream_index as usize].timebase();
+        let packet = Packet {
+            stream_index: sample.stream_index,
+            pts: Timestamp::new(sample.pts, timebase),
+            dts: Timestamp::new(sample.dts, timebase),
+            is_keyframe: sample.is_keyframe,
+            data,
+        };
+        self.position += 1;
+
+        Ok(Some(packet))
     }
 
-    /// Seeks to a position in the container.
+    /// Seeks to the keyframe at or before a timestamp.
+    ///
+    /// The read cursor is moved to the nearest preceding keyframe of the
+    /// selected video stream (or the first selected seekable stream if no
+    /// video is selected). Other streams resume from their last sample at or
+    /// before that keyframe so that audio stays aligned with video. Without
+    /// video, a PCM stream resumes at the target sample frame.
     ///
     /// # Arguments
-    /// * `timestamp_ms` - Target timestamp in milliseconds.
+    /// * `target` - Target timestamp, in any time base.
+    ///
+    /// # Returns
+    /// The presentation timestamp of the keyframe playback resumes from, in
+    /// the time base of its stream.
     ///
     /// # Errors
-    /// Returns an error if seeking fails or is not supported.
-    pub fn seek(&mut self, timestamp_ms: u64) -> Result<()> {
+    /// Returns an error if the demuxer is not initialized or has no seekable stream.
+    pub fn seek(&mut self, target: Timestamp) -> Result<Timestamp> {
         if !self.initialized {
             return Err(PlayerError::demuxer("Demuxer not initialized"));
         }
 
-        // Stub implementation
-        let _ = timestamp_ms;
-        Ok(())
+        let seekable =
+            |s: &&StreamInfo| self.active[s.index as usize] && self.seek_index.has_points(s.index);
+        let reference = self
+            .streams
+            .iter()
+            .filter(seekable)
+            .find(|s| s.stream_type == StreamType::Video)
+            .or_else(|| self.streams.iter().find(seekable))
+            .ok_or_else(|| PlayerError::demuxer("No seekable stream"))?;
+
+        let timebase = reference.timebase();
+        let landed = self
+            .seek_index
+            .lookup(reference.index, target.rescale(timebase).ticks)
+            .ok_or_else(|| PlayerError::demuxer("No seekable stream"))?;
+        self.seek_cut = self.cut_pcm_sample(reference, landed.sample, target);
+        let landed_at = match &self.seek_cut {
+            Some((_, cut)) => Timestamp::new(cut.pts, timebase),
+            None => Timestamp::new(landed.pts, timebase),
+        };
+
+        for stream in &self.streams {
+            let point = if stream.index == reference.index {
+                Some(landed)
+            } else {
+                self.seek_index
+                    .lookup(stream.index, landed_at.rescale(stream.timebase()).ticks)
+            };
+            self.resume_from[stream.index as usize] = point.map_or(landed.sample, |p| p.sample);
+        }
+        self.position = self
+            .resume_from
+            .iter()
+            .zip(&self.active)
+            .filter(|(_, &active)| active)
+            .map(|(&from, _)| from)
+            .min()
+            .unwrap_or(0);
+
+        Ok(landed_at)
+    }
+
+    /// Cuts the sample of a PCM stream at `index` to start at the last
+    /// sample frame at or before `target`, returning its offset and the cut
+    /// sample. Returns `None` for other codecs and targets outside the
+    /// sample.
+    fn cut_pcm_sample(
+        &self,
+        stream: &StreamInfo,
+        index: usize,
+        target: Timestamp,
+    ) -> Option<(u64, SampleEntry)> {
+        let codec = pcm::codec_for_name(&stream.codec)?;
+        let audio = stream.audio.as_ref()?;
+        let block = pcm::sample_size(codec)? as u64 * audio.channels as u64;
+        let sample = self.samples.get(index)?;
+        if sample.continuation.is_some() || block == 0 {
+            return None;
+        }
+
+        let timebase = stream.timebase();
+        let rate = Timebase::from_rate(audio.sample_rate);
+        let start = Timestamp::new(sample.pts, timebase);
+        let frames = Timestamp::new(target.rescale(rate).ticks - start.rescale(rate).ticks, rate);
+        let skip = u64::try_from(frames.ticks).ok()?.checked_mul(block)?;
+        if skip == 0 || skip >= sample.size as u64 {
+            return None;
+        }
+        let shift = frames.rescale(timebase).ticks;
+        Some((
+            sample.offset,
+            SampleEntry {
+                offset: sample.offset + skip,
+                size: sample.size - skip as u32,
+                dts: sample.dts + shift,
+                pts: sample.pts + shift,
+                ..sample.clone()
+            },
+        ))
     }
 }
 
@@ -267,6 +1149,605 @@ mod tests {
         assert_eq!(demuxer.format(), Some(ContainerFormat::Mkv));
     }
 
+    #[test]
+    fn test_demuxer_detect_webm() {
+        use mkv::ids;
+        use mkv::tests::{ebml_header, element, simple_block, track_entry};
+
+        let mut cluster = element(ids::TIMESTAMP, &[0]);
+        cluster.extend(simple_block(1, 0, 0x80, &[0xAA; 4]));
+        cluster.extend(simple_block(1, 33, 0x00, &[0xBB; 2]));
+        let mut segment = element(ids::TRACKS, &track_entry(1, 1, "V_VP8"));
+        segment.extend(element(ids::CLUSTER, &cluster));
+        let mut data = ebml_header("webm");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::WebM));
+        assert_eq!(demuxer.streams().len(), 1);
+        assert_eq!(demuxer.streams()[0].codec, "vp08");
+
+        let first = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(first.data, vec![0xAA; 4]);
+        assert!(first.is_keyframe);
+        let second = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(second.pts.as_ms(), 33);
+        assert!(!second.is_keyframe);
+        assert!(demuxer.read_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_demuxer_avc_codec_config() {
+        use crate::decoder::h264::tests::{avcc_record, high_profile_sps};
+        use mkv::ids;
+        use mkv::tests::{ebml_header, element, simple_block, uint_element};
+
+        let record = avcc_record(&high_profile_sps(), &[0x68, 0xEE, 0x3C, 0x80]);
+        let mut entry = uint_element(ids::TRACK_NUMBER, 1);
+        entry.extend(uint_element(ids::TRACK_TYPE, 1));
+        entry.extend(element(ids::CODEC_ID, b"V_MPEG4/ISO/AVC"));
+        entry.extend(element(ids::CODEC_PRIVATE, &record));
+        let mut cluster = element(ids::TIMESTAMP, &[0]);
+        cluster.extend(simple_block(1, 0, 0x80, &[0, 0, 0, 1, 0x65]));
+        let mut segment = element(ids::TRACKS, &element(ids::TRACK_ENTRY, &entry));
+        segment.extend(element(ids::CLUSTER, &cluster));
+        let mut data = ebml_header("matroska");
+        data.extend(element(ids::SEGMENT, &segment));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = &demuxer.streams()[0];
+        assert_eq!(stream.codec, "avc1");
+        assert_eq!(stream.codec_string.as_deref(), Some("avc1.640028"));
+        assert_eq!(stream.extra_data.as_deref(), Some(&record[..]));
+        let video = stream.video.as_ref().unwrap();
+        assert_eq!((video.width, video.height), (1920, 1080));
+        assert!((video.frame_rate.unwrap() - 23.976).abs() < 1e-3);
+        assert!(!serde_json::to_string(stream).unwrap().contains("extraData"));
+    }
+
+    #[test]
+    fn test_demuxer_mpegts() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(mpegts::tests::transport_stream()).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::MpegTs));
+        assert_eq!(demuxer.streams().len(), 2);
+        assert_eq!(demuxer.streams()[0].timescale, 90000);
+        assert_eq!(demuxer.duration_ms(), Some(100));
+
+        // The IDR frame is gathered from both TS packets.
+        let first = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(first.stream_index, 0);
+        assert_eq!(first.data.len(), 205);
+        assert_eq!(&first.data[..5], &[0, 0, 0, 1, 0x65]);
+        assert_eq!(first.pts.as_ms(), 33);
+        assert!(first.is_keyframe);
+
+        let audio = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(
+            audio.data,
+            vec![0xFF, 0xF1, 0x50, 0x80, 0x01, 0x3F, 0xFC, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_demuxer_ogg() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(ogg::tests::ogg_file()).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::Ogg));
+        assert_eq!(demuxer.streams().len(), 2);
+        assert_eq!(demuxer.streams()[0].stream_type, StreamType::Audio);
+        assert_eq!(demuxer.duration_ms(), Some(60));
+
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push((packet.stream_index, packet.pts.as_ms(), packet.data.len()));
+        }
+        // Only the default audio stream is read; the continued packet is
+        // gathered from both pages.
+        assert_eq!(packets, vec![(0, 0, 10), (0, 20, 10), (0, 40, 300)]);
+
+        demuxer.select_stream(StreamType::Audio, Some(1)).unwrap();
+        demuxer.seek(Timestamp::from_ms(0)).unwrap();
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push((packet.stream_index, packet.pts.as_ms(), packet.data.len()));
+        }
+        assert_eq!(packets, vec![(1, 0, 4), (1, 23, 4)]);
+    }
+
+    #[test]
+    fn test_demuxer_avi() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(avi::tests::avi_file()).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::Avi));
+        assert_eq!(demuxer.streams().len(), 2);
+        assert_eq!(demuxer.duration_ms(), Some(80));
+
+        let first = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(first.data, vec![0xA0; 6]);
+        assert!(first.is_keyframe);
+
+        // Seeking past the second (delta) frame lands on the keyframe.
+        assert_eq!(demuxer.seek(Timestamp::from_ms(60)).unwrap().as_ms(), 0);
+        let packet = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!((packet.stream_index, packet.pts.as_ms()), (0, 0));
+    }
+
+    #[test]
+    fn test_demuxer_mp3() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(mp3::tests::mp3_file()).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::Mp3));
+        assert_eq!(demuxer.streams().len(), 1);
+        assert_eq!(demuxer.streams()[0].codec_string.as_deref(), Some("mp3"));
+        assert_eq!(demuxer.duration_ms(), Some(68));
+
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push((packet.pts.ticks, packet.data.len()));
+        }
+        // The first frame carries the encoder and decoder delay.
+        assert_eq!(
+            packets,
+            vec![(-1105, 417), (47, 417), (1199, 417), (2351, 417)]
+        );
+
+        assert_eq!(demuxer.seek(Timestamp::from_ms(30)).unwrap().as_ms(), 27);
+    }
+
+    #[test]
+    fn test_demuxer_flac() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(flac::tests::flac_file()).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::Flac));
+        let stream = &demuxer.streams()[0];
+        assert_eq!(stream.codec_string.as_deref(), Some("flac"));
+        assert_eq!(stream.audio.as_ref().unwrap().bit_depth, Some(16));
+        assert_eq!(demuxer.duration_ms(), Some(400));
+
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push(packet.pts.as_ms());
+        }
+        assert_eq!(packets, vec![0, 100, 200, 300]);
+
+        // Every frame is a random access point.
+        assert_eq!(demuxer.seek(Timestamp::from_ms(250)).unwrap().as_ms(), 200);
+    }
+
+    #[test]
+    fn test_demuxer_wav() {
+        use crate::decoder::{AudioCodec, AudioDecoder, SampleFormat};
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(wav::tests::wav_file(10000)).unwrap();
+        assert_eq!(demuxer.format(), Some(ContainerFormat::Wav));
+        let stream = demuxer.streams()[0].clone();
+        assert_eq!(stream.codec_string.as_deref(), Some("pcm-s16"));
+        assert_eq!(demuxer.duration_ms(), Some(1250));
+
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push((packet.pts.ticks, packet.data.len()));
+        }
+        assert_eq!(packets, vec![(0, 16384), (4096, 16384), (8192, 7232)]);
+
+        // A seek inside a packet resumes at the target sample frame.
+        let landed = demuxer.seek(Timestamp::from_ms(700)).unwrap();
+        assert_eq!(landed.ticks, 5600);
+        let packet = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!((packet.pts.ticks, packet.data.len()), (5600, 2592 * 4));
+        assert_eq!(packet.data[0], (5600 * 4) as u8);
+        let mut decoder = AudioDecoder::default();
+        let codec = pcm::codec_for_name(&stream.codec).unwrap();
+        assert_eq!(codec, AudioCodec::PcmS16le);
+        decoder.init(codec, stream.extra_data.as_deref()).unwrap();
+        let frame = decoder.decode(&packet.data, packet.pts).unwrap().unwrap();
+        assert_eq!((frame.channels, frame.sample_rate), (2, 8000));
+        assert_eq!(frame.format, SampleFormat::S16);
+        assert_eq!(frame.pts.as_ms(), 700);
+        assert_eq!(frame.data, packet.data);
+        let packet = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(packet.pts.ticks, 8192);
+
+        // Targets on a packet boundary read the packet whole.
+        assert_eq!(demuxer.seek(Timestamp::from_ms(512)).unwrap().ticks, 4096);
+        let packet = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(packet.data.len(), 16384);
+    }
+
+    #[test]
+    fn test_demuxer_pcm_extra_data() {
+        let mut stream = StreamInfo::new(0, StreamType::Audio, "pcm-s24be", 1000);
+        stream.audio = Some(AudioInfo::new(48000, 2));
+        apply_codec_config(&mut stream);
+        // WebCodecs has no big-endian PCM, but the decoder gets a format.
+        assert!(stream.codec_string.is_none());
+        let format = WaveFormat::parse(stream.extra_data.as_deref().unwrap()).unwrap();
+        assert_eq!((format.sample_rate, format.channels), (48000, 2));
+        assert_eq!(format.block_align, 6);
+    }
+
+    #[test]
+    fn test_demuxer_ogg_opus_gapless() {
+        use crate::decoder::{AudioCodec, AudioDecoder};
+        use ogg::tests::{opus_head, page};
+
+        // Three 20 ms packets, the first starting with the 312-sample
+        // pre-skip and the last ending 500 samples in.
+        let mut data = page(1, 0x02, 0, &[&opus_head(312)], None);
+        data.extend(page(1, 0, 0, &[b"OpusTags"], None));
+        data.extend(page(1, 0, 1920, &[&[0xF8], &[0xF8]], None));
+        data.extend(page(1, 0x04, 1920 + 500, &[&[0xF8]], None));
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = demuxer.streams()[0].clone();
+        assert_eq!(stream.codec_string.as_deref(), Some("opus"));
+        assert_eq!(stream.end_pts, Some(2108));
+
+        let mut decoder = AudioDecoder::default();
+        decoder
+            .init(AudioCodec::Opus, stream.extra_data.as_deref())
+            .unwrap();
+        let end = stream
+            .end_pts
+            .map(|end| Timestamp::new(end, stream.timebase()));
+        decoder.set_end(end);
+        let mut frames = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            let frame = decoder.decode(&packet.data, packet.pts).unwrap().unwrap();
+            frames.push((frame.pts.ticks, frame.data.len() / 2 / 4));
+        }
+        assert_eq!(frames, vec![(0, 648), (648, 960), (1608, 500)]);
+    }
+
+    #[test]
+    fn test_demuxer_mp4_dops() {
+        use mp4::tests::{ftyp, mp4_box, mvhd, TrakSpec};
+
+        // Version 0, two channels and a pre-skip of 312 samples.
+        let mut dops = vec![0, 2, 0x01, 0x38];
+        dops.extend(48000u32.to_be_bytes());
+        dops.extend([0, 0, 0]);
+        let mut moov = mvhd(1000, 0);
+        moov.extend(
+            TrakSpec {
+                entry: mp4_box(b"dOps", &dops),
+                ..TrakSpec::audio(b"Opus")
+            }
+            .build(),
+        );
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = &demuxer.streams()[0];
+        assert_eq!(stream.codec_string.as_deref(), Some("opus"));
+        let head = OpusHead::parse(stream.extra_data.as_deref().unwrap()).unwrap();
+        assert_eq!((head.channels, head.pre_skip), (2, 312));
+    }
+
+    #[test]
+    fn test_demuxer_mp4_dfla() {
+        use crate::decoder::flac::tests::streaminfo;
+        use mp4::tests::{ftyp, mp4_box, mvhd, TrakSpec};
+
+        let mut dfla = vec![0, 0, 0, 0, 0x80, 0, 0, 34];
+        dfla.extend(streaminfo(4096, 96000, 2, 24, 0));
+        let mut moov = mvhd(1000, 0);
+        moov.extend(
+            TrakSpec {
+                entry: mp4_box(b"dfLa", &dfla),
+                ..TrakSpec::audio(b"fLaC")
+            }
+            .build(),
+        );
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = &demuxer.streams()[0];
+        assert_eq!(stream.codec, "fLaC");
+        assert_eq!(stream.codec_string.as_deref(), Some("flac"));
+        let extra_data = stream.extra_data.as_deref().unwrap();
+        assert_eq!(&extra_data[..4], b"fLaC");
+        assert_eq!(&extra_data[4..], &dfla[4..]);
+        let audio = stream.audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.bit_depth), (96000, Some(24)));
+    }
+
+    #[test]
+    fn test_demuxer_append_data() {
+        let data = mpegts::tests::transport_stream();
+        let mut demuxer = Demuxer::new();
+
+        // Not enough data to detect the format yet.
+        assert!(!demuxer.append_data(&data[..500]).unwrap());
+        assert!(!demuxer.is_initialized());
+        assert!(matches!(
+            demuxer.read_packet(),
+            Err(PlayerError::NeedMoreData)
+        ));
+
+        // PAT and PMT have arrived, but no PES packet is complete.
+        assert!(demuxer.append_data(&data[500..1100]).unwrap());
+        assert_eq!(demuxer.format(), Some(ContainerFormat::MpegTs));
+        assert_eq!(demuxer.streams().len(), 2);
+        assert!(matches!(
+            demuxer.read_packet(),
+            Err(PlayerError::NeedMoreData)
+        ));
+
+        // A PES packet is complete once the next one of its stream starts.
+        assert!(demuxer.append_data(&data[1100..]).unwrap());
+        let first = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(first.data.len(), 205);
+        let audio = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(
+            audio.data,
+            vec![0xFF, 0xF1, 0x50, 0x80, 0x01, 0x3F, 0xFC, 1, 2]
+        );
+        assert!(matches!(
+            demuxer.read_packet(),
+            Err(PlayerError::NeedMoreData)
+        ));
+
+        demuxer.end_of_stream().unwrap();
+        assert_eq!(demuxer.duration_ms(), Some(100));
+        assert_eq!(demuxer.read_packet().unwrap().unwrap().pts.as_ms(), 100);
+        assert_eq!(demuxer.read_packet().unwrap().unwrap().pts.as_ms(), 77);
+        assert!(demuxer.read_packet().unwrap().is_none());
+        assert!(demuxer.append_data(&[0]).is_err());
+    }
+
+    #[test]
+    fn test_demuxer_merge_samples() {
+        let sample = |stream_index: u32, offset: u64| SampleEntry {
+            stream_index,
+            offset,
+            size: 1,
+            continuation: None,
+            dts: offset as i64,
+            pts: offset as i64,
+            is_keyframe: true,
+        };
+        let mut demuxer = Demuxer::new();
+        demuxer.samples = vec![sample(0, 0), sample(0, 100), sample(1, 200)];
+        demuxer.position = 2;
+        demuxer.resume_from = vec![0, 2];
+
+        // A sample behind the read position is placed at it, the others in
+        // file order; the resume point follows its sample.
+        let from = demuxer.merge_samples(vec![sample(1, 300), sample(0, 50), sample(0, 150)]);
+        assert_eq!(from, 2);
+        let offsets: Vec<u64> = demuxer.samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0, 100, 50, 150, 200, 300]);
+        assert_eq!(demuxer.resume_from, vec![0, 4]);
+
+        assert_eq!(demuxer.merge_samples(vec![sample(0, 400)]), 6);
+        assert_eq!(demuxer.merge_samples(Vec::new()), 7);
+    }
+
+    #[test]
+    fn test_demuxer_metadata_and_attachment() {
+        use mp4::tests::{ftyp, mp4_box, mvhd};
+        use mp4meta::tests::{ilst_item, udta};
+
+        let mut items = ilst_item(b"\xA9nam", 1, b"Lecture");
+        items.extend(ilst_item(b"covr", 14, b"\x89PNG"));
+        let mut moov = mvhd(1000, 0);
+        moov.extend(udta(&items));
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let metadata = demuxer.metadata();
+        assert_eq!(metadata.title.as_deref(), Some("Lecture"));
+        assert_eq!(metadata.attachments[0].mime_type, "image/png");
+        assert_eq!(demuxer.attachment_data(0).unwrap(), b"\x89PNG");
+        assert!(demuxer.attachment_data(1).is_err());
+    }
+
+    #[test]
+    fn test_demuxer_hevc_codec_config() {
+        use crate::decoder::h265::tests::{hvcc_record, main_profile_sps, main_profile_vps};
+        use mp4::tests::{ftyp, mp4_box, mvhd, TrakSpec};
+
+        let record = hvcc_record(&main_profile_vps(), &main_profile_sps(), &[0x44, 1, 0xC1]);
+        let mut moov = mvhd(1000, 0);
+        moov.extend(
+            TrakSpec {
+                entry: mp4_box(b"hvcC", &record),
+                ..TrakSpec::video(b"hev1")
+            }
+            .build(),
+        );
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = &demuxer.streams()[0];
+        assert_eq!(stream.codec_string.as_deref(), Some("hev1.1.6.L120.B0"));
+        assert_eq!(stream.extra_data.as_deref(), Some(&record[..]));
+        let video = stream.video.as_ref().unwrap();
+        assert_eq!((video.width, video.height), (1920, 1080));
+        assert_eq!(video.bit_depth, Some(8));
+    }
+
+    #[test]
+    fn test_demuxer_av1_codec_config() {
+        use crate::decoder::av1::tests::av1c_record;
+        use mkv::ids;
+        use mkv::tests::{ebml_header, element, uint_element};
+
+        let mut entry = uint_element(ids::TRACK_NUMBER, 1);
+        entry.extend(uint_element(ids::TRACK_TYPE, 1));
+        entry.extend(element(ids::CODEC_ID, b"V_AV1"));
+        entry.extend(element(ids::CODEC_PRIVATE, &av1c_record()));
+        let mut data = ebml_header("webm");
+        data.extend(element(
+            ids::SEGMENT,
+            &element(ids::TRACKS, &element(ids::TRACK_ENTRY, &entry)),
+        ));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = &demuxer.streams()[0];
+        assert_eq!(
+            stream.codec_string.as_deref(),
+            Some("av01.0.13M.10.0.110.09.16.09.0")
+        );
+        let video = stream.video.as_ref().unwrap();
+        assert_eq!((video.width, video.height), (3840, 2160));
+        assert_eq!(video.bit_depth, Some(10));
+    }
+
+    #[test]
+    fn test_demuxer_vp9_codec_private() {
+        use mkv::ids;
+        use mkv::tests::{ebml_header, element, uint_element};
+
+        let mut video = uint_element(ids::PIXEL_WIDTH, 1920);
+        video.extend(uint_element(ids::PIXEL_HEIGHT, 1080));
+        let mut entry = uint_element(ids::TRACK_NUMBER, 1);
+        entry.extend(uint_element(ids::TRACK_TYPE, 1));
+        entry.extend(element(ids::CODEC_ID, b"V_VP9"));
+        // Profile 0 and 8-bit, without a level.
+        entry.extend(element(ids::CODEC_PRIVATE, &[1, 1, 0, 3, 1, 8]));
+        entry.extend(element(ids::VIDEO, &video));
+        let mut data = ebml_header("webm");
+        data.extend(element(
+            ids::SEGMENT,
+            &element(ids::TRACKS, &element(ids::TRACK_ENTRY, &entry)),
+        ));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        let stream = &demuxer.streams()[0];
+        assert_eq!(stream.codec_string.as_deref(), Some("vp09.00.40.08"));
+        assert_eq!(stream.extra_data.as_ref().unwrap().len(), 12);
+        assert_eq!(stream.video.as_ref().unwrap().bit_depth, Some(8));
+    }
+
+    #[test]
+    fn test_demuxer_mp4_esds() {
+        use mp4::tests::{ftyp, mp4_box, mvhd, TrakSpec};
+
+        // ES_Descriptor > DecoderConfigDescriptor > DecoderSpecificInfo,
+        // the outer size in the 4-byte form.
+        let demux = |object_type: u8, info: &[u8]| {
+            let mut decoder_config = vec![object_type, 0x15, 0, 0, 0];
+            decoder_config.extend([0; 8]);
+            if !info.is_empty() {
+                decoder_config.extend([0x05, info.len() as u8]);
+                decoder_config.extend(info);
+            }
+            let mut es = vec![0, 1, 0];
+            es.push(0x04);
+            es.push(decoder_config.len() as u8);
+            es.extend(decoder_config);
+            let mut esds = vec![0; 4];
+            esds.extend([0x03, 0x80, 0x80, 0x80, es.len() as u8]);
+            esds.extend(es);
+
+            let mut moov = mvhd(1000, 0);
+            moov.extend(
+                TrakSpec {
+                    entry: mp4_box(b"esds", &esds),
+                    ..TrakSpec::audio(b"mp4a")
+                }
+                .build(),
+            );
+            let mut data = ftyp();
+            data.extend(mp4_box(b"moov", &moov));
+
+            let mut demuxer = Demuxer::new();
+            demuxer.init(data).unwrap();
+            demuxer.streams()[0].clone()
+        };
+
+        // An HE-AAC v2 config.
+        let stream = demux(0x40, &[0xEB, 0x09, 0x88, 0x00]);
+        assert_eq!(stream.codec, "mp4a");
+        assert_eq!(
+            stream.extra_data.as_deref(),
+            Some(&[0xEB, 0x09, 0x88, 0x00][..])
+        );
+        assert_eq!(stream.codec_string.as_deref(), Some("mp4a.40.29"));
+        let audio = stream.audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (48000, 2));
+
+        // MPEG-1 audio has no DecoderSpecificInfo.
+        let stream = demux(0x6B, &[]);
+        assert_eq!(stream.codec, "mp3");
+        assert_eq!(stream.codec_string.as_deref(), Some("mp3"));
+        assert!(stream.extra_data.is_none());
+    }
+
+    #[test]
+    fn test_demuxer_mp4_chapter_track() {
+        use mp4::tests::{ftyp, mp4_box, mvhd, stco, stsc, stsz, stts, TrakSpec};
+
+        let samples: Vec<u8> = [&b"\0\x05Intro"[..], b"\0\x04Main", b"\0\x05Outro"].concat();
+        let build = |mdat_start: u32| {
+            let mut text_stbl = stsz(&[7, 6, 7]);
+            text_stbl.extend(stco(&[mdat_start]));
+            text_stbl.extend(stsc(&[(1, 3)]));
+            text_stbl.extend(stts(&[(1, 10_000), (1, 50_000), (1, 60_000)]));
+            let mut moov = mvhd(1000, 120_000);
+            moov.extend(
+                TrakSpec {
+                    tref: mp4_box(b"chap", &2u32.to_be_bytes()),
+                    ..TrakSpec::video(b"avc1")
+                }
+                .build(),
+            );
+            moov.extend(
+                TrakSpec {
+                    track_id: 2,
+                    handler: *b"text",
+                    codec: *b"text",
+                    stream_type: StreamType::Subtitle,
+                    timescale: 1000,
+                    stbl: text_stbl,
+                    ..TrakSpec::video(b"avc1")
+                }
+                .build(),
+            );
+            let mut data = ftyp();
+            data.extend(mp4_box(b"moov", &moov));
+            data
+        };
+        let mut data = build(build(0).len() as u32 + 8);
+        data.extend(mp4_box(b"mdat", &samples));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+        // The chapter track is not exposed as a subtitle stream.
+        assert_eq!(demuxer.streams().len(), 1);
+        let chapters = demuxer.chapters();
+        assert_eq!(chapters.len(), 3);
+        assert_eq!(chapters[1].title.as_deref(), Some("Main"));
+        assert_eq!(
+            (chapters[1].start_ms, chapters[1].end_ms),
+            (10_000, Some(60_000))
+        );
+        assert_eq!(chapters[2].end_ms, Some(120_000));
+
+        assert_eq!(demuxer.next_chapter(0).unwrap().start_ms, 10_000);
+        assert!(demuxer.next_chapter(60_000).is_none());
+        // Shortly after a chapter starts, going back moves to the previous one.
+        assert_eq!(demuxer.previous_chapter(11_000).unwrap().start_ms, 0);
+        assert_eq!(demuxer.previous_chapter(20_000).unwrap().start_ms, 10_000);
+        assert_eq!(demuxer.previous_chapter(1_000).unwrap().start_ms, 0);
+    }
+
     #[test]
     fn test_demuxer_unknown_format() {
         let mut demuxer = Demuxer::new();
@@ -291,17 +1772,20 @@ mod tests {
     #[test]
     fn test_demuxer_seek_without_init() {
         let mut demuxer = Demuxer::new();
-        let result = demuxer.seek(1000);
+        let result = demuxer.seek(Timestamp::from_ms(1000));
         assert!(result.is_err());
     }
 
     #[test]
     fn test_stream_info_serialization() {
         let info = StreamInfo {
-            index: 0,
-            stream_type: StreamType::Video,
-            codec: "h264".to_string(),
             duration_ms: Some(60000),
+            video: Some(VideoInfo {
+                width: 1920,
+                height: 1080,
+                ..VideoInfo::default()
+            }),
+            ..StreamInfo::new(0, StreamType::Video, "h264", 90000)
         };
 
         let json = serde_json::to_string(&info).unwrap();
@@ -311,5 +1795,235 @@ mod tests {
         assert_eq!(info.stream_type, deserialized.stream_type);
         assert_eq!(info.codec, deserialized.codec);
         assert_eq!(info.duration_ms, deserialized.duration_ms);
+        assert_eq!(info.timescale, deserialized.timescale);
+        assert!(json.contains("streamType"));
+        assert!(json.contains("\"isDefault\":false"));
+        assert_eq!(deserialized.video.unwrap().width, 1920);
+        assert!(deserialized.audio.is_none());
+    }
+
+    #[test]
+    fn test_to_ms() {
+        assert_eq!(to_ms(90000, 90000), 1000);
+        assert_eq!(to_ms(1001, 30000), 33);
+        assert_eq!(to_ms(5, 0), 0);
+    }
+
+    /// Builds an MP4 file with one video and one audio track whose samples
+    /// are interleaved in a single `mdat`.
+    fn interleaved_mp4() -> Vec<u8> {
+        use mp4::tests::{ftyp, mp4_box, mvhd, stco, stsc, stss, stsz, stts, TrakSpec};
+
+        // Layout after ftyp + moov: mdat header, then V0 V1 A0 V2 A1.
+        let build = |mdat_start: u32| {
+            let mut video_stbl = stsz(&[4, 4, 4]);
+            video_stbl.extend(stco(&[mdat_start, mdat_start + 10]));
+            video_stbl.extend(stsc(&[(1, 2), (2, 1)]));
+            video_stbl.extend(stts(&[(3, 3000)]));
+            video_stbl.extend(stss(&[1, 3]));
+            let mut audio_stbl = stsz(&[2, 2]);
+            audio_stbl.extend(stco(&[mdat_start + 8, mdat_start + 14]));
+            audio_stbl.extend(stsc(&[(1, 1)]));
+            audio_stbl.extend(stts(&[(2, 1024)]));
+
+            let mut moov = mvhd(1000, 100);
+            moov.extend(
+                TrakSpec {
+                    stbl: video_stbl,
+                    ..TrakSpec::video(b"avc1")
+                }
+                .build(),
+            );
+            moov.extend(
+                TrakSpec {
+                    timescale: 1024,
+                    stbl: audio_stbl,
+                    ..TrakSpec::audio(b"mp4a")
+                }
+                .build(),
+            );
+            let mut data = ftyp();
+            data.extend(mp4_box(b"moov", &moov));
+            data
+        };
+
+        let header_len = build(0).len() as u32 + 8;
+        let mut data = build(header_len);
+        data.extend(mp4_box(
+            b"mdat",
+            &[1, 1, 1, 1, 2, 2, 2, 2, 9, 9, 3, 3, 3, 3, 8, 8],
+        ));
+        data
+    }
+
+    #[test]
+    fn test_demuxer_derives_stream_info() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(interleaved_mp4()).unwrap();
+
+        // Neither track is flagged, so the first of each type becomes default.
+        let streams = demuxer.streams();
+        assert!(streams.iter().all(|s| s.is_default));
+        // Three 4-byte frames and two 2-byte audio frames over the 100 ms movie.
+        assert_eq!(streams[0].bitrate, Some(960));
+        assert_eq!(streams[0].video.as_ref().unwrap().frame_rate, Some(30.0));
+        assert_eq!(streams[1].bitrate, Some(320));
+
+        let json = serde_json::to_string(streams).unwrap();
+        assert!(json.contains("\"frameRate\":30.0"));
+        assert!(json.contains("\"isDefault\":true"));
+    }
+
+    #[test]
+    fn test_demuxer_select_stream() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(interleaved_mp4()).unwrap();
+        assert_eq!(demuxer.selected_stream(StreamType::Audio), Some(1));
+        assert_eq!(demuxer.streams_of_type(StreamType::Audio).count(), 1);
+        assert!(demuxer.select_stream(StreamType::Audio, Some(0)).is_err());
+        assert!(demuxer.select_stream(StreamType::Audio, Some(5)).is_err());
+
+        // Turning audio off persists across a seek.
+        demuxer.select_stream(StreamType::Audio, None).unwrap();
+        assert_eq!(demuxer.selected_stream(StreamType::Audio), None);
+        demuxer.seek(Timestamp::from_ms(100)).unwrap();
+        let mut streams = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            streams.push(packet.stream_index);
+        }
+        assert_eq!(streams, [0]);
+    }
+
+    #[test]
+    fn test_demuxer_read_packets_mp4() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(interleaved_mp4()).unwrap();
+
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push(packet);
+        }
+
+        let order: Vec<(u32, i64)> = packets
+            .iter()
+            .map(|p| (p.stream_index, p.pts.as_ms()))
+            .collect();
+        assert_eq!(order, vec![(0, 0), (0, 33), (1, 0), (0, 66), (1, 1000)]);
+        assert_eq!(packets[1].pts.timebase, Timebase::from_rate(90000));
+        assert_eq!(packets[0].data, vec![1, 1, 1, 1]);
+        assert_eq!(packets[2].data, vec![9, 9]);
+        assert_eq!(packets[4].data, vec![8, 8]);
+        assert!(packets[0].is_keyframe);
+        assert!(!packets[1].is_keyframe);
+        assert!(packets[2].is_keyframe);
+    }
+
+    #[test]
+    fn test_demuxer_seek_lands_on_keyframe() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(interleaved_mp4()).unwrap();
+
+        // Video keyframes are at 0ms and 66ms.
+        assert_eq!(demuxer.seek(Timestamp::from_ms(70)).unwrap().as_ms(), 66);
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push((packet.stream_index, packet.pts.as_ms()));
+        }
+        // Audio resumes from its last sample at or before the keyframe.
+        assert_eq!(packets, vec![(1, 0), (0, 66), (1, 1000)]);
+
+        assert_eq!(demuxer.seek(Timestamp::from_ms(50)).unwrap().as_ms(), 0);
+        let first = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!((first.stream_index, first.pts.as_ms()), (0, 0));
+        assert!(first.is_keyframe);
+    }
+
+    #[test]
+    fn test_demuxer_fragmented_mp4() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(fmp4::tests::fragmented_mp4()).unwrap();
+        assert_eq!(demuxer.duration_ms(), Some(160));
+
+        let mut packets = Vec::new();
+        while let Some(packet) = demuxer.read_packet().unwrap() {
+            packets.push(packet);
+        }
+        let pts: Vec<i64> = packets.iter().map(|p| p.pts.as_ms()).collect();
+        assert_eq!(pts, vec![0, 40, 80, 120]);
+        assert_eq!(packets[0].data, vec![0x10; 3]);
+        assert_eq!(packets[3].data, vec![0x21; 2]);
+
+        assert_eq!(demuxer.seek(Timestamp::from_ms(130)).unwrap().as_ms(), 80);
+        let packet = demuxer.read_packet().unwrap().unwrap();
+        assert_eq!(packet.data, vec![0x20; 3]);
+        assert!(packet.is_keyframe);
+    }
+
+    #[test]
+    fn test_demuxer_seek_without_streams() {
+        let mut demuxer = Demuxer::new();
+        demuxer.init(vec![0xFF; 20]).unwrap();
+        assert!(demuxer.seek(Timestamp::from_ms(0)).is_err());
+    }
+
+    #[test]
+    fn test_demuxer_read_packet_out_of_range() {
+        let mut data = interleaved_mp4();
+        data.truncate(data.len() - 4);
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+
+        // The sample cut off by the end of the file is not listed.
+        for _ in 0..3 {
+            assert!(demuxer.read_packet().unwrap().is_some());
+        }
+        assert!(demuxer.read_packet().unwrap().is_none());
+
+        // Oversized ranges are rejected before anything is allocated.
+        let mut demuxer = Demuxer::new();
+        demuxer.init(interleaved_mp4()).unwrap();
+        demuxer.samples[0].size = u32::MAX;
+        demuxer.samples[1].continuation = Some(vec![(u64::MAX, 2)].into());
+        assert!(demuxer.read_packet().is_err());
+        demuxer.position = 1;
+        assert!(demuxer.read_packet().is_err());
+    }
+
+    #[test]
+    fn test_demuxer_parses_mp4_streams() {
+        use mp4::tests::{ftyp, mp4_box, mvhd, TrakSpec};
+
+        let mut moov = mvhd(600, 3000);
+        moov.extend(
+            TrakSpec {
+                timescale: 15360,
+                duration: 76800,
+                ..TrakSpec::video(b"av01")
+            }
+            .build(),
+        );
+        moov.extend(
+            TrakSpec {
+                duration: 240000,
+                ..TrakSpec::audio(b"Opus")
+            }
+            .build(),
+        );
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let mut demuxer = Demuxer::new();
+        demuxer.init(data).unwrap();
+
+        assert_eq!(demuxer.duration_ms(), Some(5000));
+        let streams = demuxer.streams();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].index, 0);
+        assert_eq!(streams[0].codec, "av01");
+        assert_eq!(streams[0].timescale, 15360);
+        assert_eq!(streams[1].index, 1);
+        assert_eq!(streams[1].stream_type, StreamType::Audio);
+        assert_eq!(streams[1].codec, "Opus");
+        assert_eq!(streams[1].duration_ms, Some(5000));
     }
 }
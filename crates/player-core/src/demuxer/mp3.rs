@@ -0,0 +1,276 @@
+//! MP3 elementary stream parsing.
+//!
+//! Skips ID3v2 tags and walks MPEG audio Layer III frame headers, emitting
+//! one sample per frame. A leading Xing/Info tag frame is not emitted: it
+//! becomes the stream's initialization data, and its LAME tag shifts the
+//! timestamps so that the encoder and decoder delay is presented before
+//! zero.
+
+use super::source::SourceReader;
+use super::{AudioInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType};
+use crate::decoder::mp3::{self, FrameHeader, XingHeader, HEADER_LEN};
+use crate::error::Result;
+
+/// Bytes scanned per read when resynchronizing on a frame header.
+const RESYNC_WINDOW: usize = 64 * 1024;
+/// Length of an ID3v2 tag header.
+const ID3V2_HEADER_LEN: usize = 10;
+
+/// Returns whether `data` starts with an ID3v2 tag, or with a Layer III
+/// frame followed by a compatible frame or the end of the data.
+pub(crate) fn probe(data: &[u8]) -> bool {
+    if data.starts_with(b"ID3") {
+        return mp3::id3v2_len(data).is_some();
+    }
+    let first = match FrameHeader::parse(data) {
+        Ok(header) if header.layer == 3 => header,
+        _ => return false,
+    };
+    match data.get(first.frame_len..) {
+        Some(next) if next.len() >= HEADER_LEN => {
+            FrameHeader::parse(next).is_ok_and(|next| next.is_compatible(&first))
+        }
+        _ => true,
+    }
+}
+
+/// Returns whether `data` starts with a tag that may only follow the last
+/// frame (ID3v1, APEv2 or Lyrics3).
+fn is_trailing_tag(data: &[u8]) -> bool {
+    data.starts_with(b"TAG") || data.starts_with(b"APETAGEX") || data.starts_with(b"LYRICS")
+}
+
+/// Incremental MP3 parser.
+#[derive(Debug, Default)]
+pub(crate) struct Mp3Parser {
+    /// Offset of the next frame or tag.
+    pos: u64,
+    /// Header of the first frame; later frames must be compatible with it.
+    first: Option<FrameHeader>,
+    /// Xing/Info tag of the first frame.
+    xing: Option<XingHeader>,
+    /// The single audio stream, once the first frame has been read.
+    stream: Option<StreamInfo>,
+    /// Whether a trailing tag ended the frames.
+    done: bool,
+    /// Number of audio frames found.
+    frames: u64,
+    /// Frames not yet handed over.
+    samples: Vec<SampleEntry>,
+}
+
+impl Mp3Parser {
+    /// Creates a parser positioned at the start of the file.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a Layer III frame header belonging to this stream.
+    fn frame_header(&self, data: &[u8]) -> Option<FrameHeader> {
+        FrameHeader::parse(data)
+            .ok()
+            .filter(|h| h.layer == 3 && self.first.is_none_or(|first| first.is_compatible(h)))
+    }
+
+    /// Advances `pos` to the next frame header; returns `false` if there is
+    /// none in the available data.
+    fn resync(&mut self, reader: &mut SourceReader<'_>) -> Result<bool> {
+        loop {
+            let window = reader.bytes(self.pos + 1, RESYNC_WINDOW)?;
+            if window.len() < HEADER_LEN {
+                return Ok(false);
+            }
+            let found = (0..=window.len() - HEADER_LEN)
+                .find(|&i| self.frame_header(&window[i..]).is_some());
+            match found {
+                Some(skip) => {
+                    self.pos += 1 + skip as u64;
+                    return Ok(true);
+                }
+                None => self.pos += (window.len() - (HEADER_LEN - 1)) as u64,
+            }
+        }
+    }
+
+    /// Amount subtracted from every timestamp so playback starts at zero.
+    fn start_offset(&self) -> i64 {
+        self.xing
+            .as_ref()
+            .map_or(0, |xing| xing.start_padding() as i64)
+    }
+}
+
+impl ContainerParser for Mp3Parser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        let complete = reader.is_complete();
+        while !self.done {
+            let head = reader.bytes(self.pos, ID3V2_HEADER_LEN)?;
+            if head.len() < ID3V2_HEADER_LEN && !complete {
+                break;
+            }
+            if let Some(len) = mp3::id3v2_len(head) {
+                self.pos += len as u64;
+                continue;
+            }
+            if head.len() < HEADER_LEN {
+                break;
+            }
+            let header = match self.frame_header(head) {
+                Some(header) => header,
+                None if is_trailing_tag(head) => {
+                    self.done = true;
+                    break;
+                }
+                None => {
+                    if self.resync(reader)? {
+                        continue;
+                    }
+                    break;
+                }
+            };
+            // A frame cut off at the end of a complete file cannot be
+            // decoded and is dropped.
+            let frame = match reader.exact(self.pos, header.frame_len)? {
+                Some(frame) => frame,
+                None => break,
+            };
+            let offset = self.pos;
+            self.pos += header.frame_len as u64;
+
+            if self.first.is_none() {
+                self.first = Some(header);
+                self.xing = XingHeader::parse(frame);
+                let mut info = StreamInfo::new(0, StreamType::Audio, "mp3", header.sample_rate);
+                info.audio = Some(AudioInfo::new(header.sample_rate, header.channels() as u16));
+                info.extra_data = self.xing.as_ref().map(|_| frame.to_vec());
+                self.stream = Some(info);
+                if self.xing.is_some() {
+                    continue;
+                }
+            }
+
+            let pts = self.frames as i64 * header.samples_per_frame() as i64 - self.start_offset();
+            self.frames += 1;
+            self.samples.push(SampleEntry {
+                stream_index: 0,
+                offset,
+                size: header.frame_len as u32,
+                continuation: None,
+                dts: pts,
+                pts,
+                is_keyframe: true,
+            });
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        let mut streams: Vec<StreamInfo> = self.stream.iter().cloned().collect();
+        if let (Some(info), Some(first)) = (streams.first_mut(), self.first) {
+            let samples_per_frame = first.samples_per_frame();
+            let samples = self
+                .xing
+                .as_ref()
+                .and_then(|xing| xing.sample_count(samples_per_frame))
+                .unwrap_or_else(|| {
+                    let decoded = self.frames as i64 * samples_per_frame as i64;
+                    (decoded - self.start_offset()).max(0) as u64
+                });
+            info.duration_ms = Some(super::to_ms(samples, info.timescale));
+        }
+        Container {
+            duration_ms: streams.first().and_then(|s| s.duration_ms),
+            streams,
+            samples: std::mem::take(&mut self.samples),
+            ..Container::default()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::decoder::mp3::tests::{info_frame, silent_frame};
+    use crate::demuxer::MemorySource;
+
+    /// Parses a complete in-memory MP3 file.
+    fn parse(data: &[u8]) -> Container {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = Mp3Parser::new();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        parser.container()
+    }
+
+    /// Builds an ID3v2.4 tag with `len` bytes of frame data.
+    pub(crate) fn id3v2_tag(len: usize) -> Vec<u8> {
+        let mut tag = b"ID3\x04\x00\x00".to_vec();
+        tag.extend((0..4).rev().map(|i| (len >> (7 * i)) as u8 & 0x7F));
+        tag.resize(ID3V2_HEADER_LEN + len, 0);
+        tag
+    }
+
+    /// Builds a gapless file: an ID3v2 tag, an Info frame, four audio
+    /// frames and an ID3v1 tag.
+    pub(crate) fn mp3_file() -> Vec<u8> {
+        let mut data = id3v2_tag(300);
+        data.extend(info_frame(4, 576, 1000));
+        for _ in 0..4 {
+            data.extend(silent_frame());
+        }
+        data.extend(b"TAG");
+        data.resize(data.len() + 125, 0);
+        data
+    }
+
+    #[test]
+    fn test_probe() {
+        assert!(probe(&mp3_file()));
+        assert!(probe(&[silent_frame(), silent_frame()].concat()));
+        // A frame followed by something other than a frame is rejected.
+        let mut data = silent_frame();
+        data.extend([0x47; 8]);
+        assert!(!probe(&data));
+        // Layer II frames are not MP3.
+        assert!(!probe(&[0xFF, 0xFD, 0x90, 0x64, 0, 0, 0, 0, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_parse_gapless_file() {
+        let data = mp3_file();
+        let file = parse(&data);
+
+        assert_eq!(file.streams.len(), 1);
+        let stream = &file.streams[0];
+        assert_eq!(stream.codec, "mp3");
+        assert_eq!(stream.timescale, 44100);
+        let audio = stream.audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (44100, 2));
+        // The Info frame is initialization data, not a sample.
+        assert_eq!(stream.extra_data.as_deref(), Some(&data[310..727]));
+        // 4 * 1152 - 576 - 1000 samples.
+        assert_eq!(stream.duration_ms, Some(68));
+        assert_eq!(file.duration_ms, Some(68));
+
+        let samples: Vec<(u64, i64)> = file.samples.iter().map(|s| (s.offset, s.pts)).collect();
+        assert_eq!(
+            samples,
+            vec![(727, -1105), (1144, 47), (1561, 1199), (1978, 2351)]
+        );
+    }
+
+    #[test]
+    fn test_resync_after_garbage() {
+        let mut data = silent_frame();
+        data.extend([0xFF, 0x00, 0x12]);
+        data.extend(silent_frame());
+        data.extend(&silent_frame()[..100]);
+        let file = parse(&data);
+
+        // The truncated last frame is dropped.
+        let offsets: Vec<u64> = file.samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0, 420]);
+        assert!(file.streams[0].extra_data.is_none());
+        assert_eq!(file.samples[1].pts, 1152);
+        assert_eq!(file.duration_ms, Some(52));
+    }
+}
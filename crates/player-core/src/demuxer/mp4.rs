@@ -0,0 +1,1948 @@
+//! ISO Base Media File Format (MP4/MOV) parsing.
+//!
+//! Walks the box hierarchy (`ftyp`/`moov`/`trak`/`mdia`/`minf`/`stbl`/`stsd`)
+//! and extracts per-track codec, timescale and duration information along
+//! with the sample index. Movie fragments are handled by [`super::fmp4`].
+
+use super::fmp4::FragmentParser;
+use super::metadata::{close_chapters, Chapter, Metadata};
+use super::mp4meta::{chapter_text, parse_moov_metadata, parse_nero_chapters, titled_chapter};
+use super::reader::ByteReader;
+use super::source::SourceReader;
+use super::{
+    to_ms, AudioInfo, ColorInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType,
+    VideoInfo,
+};
+use crate::decoder::opus::OpusHead;
+use crate::decoder::pcm;
+use crate::decoder::AudioCodec;
+use crate::error::{PlayerError, Result};
+
+/// `lpcm` format flag of floating-point samples.
+const LPCM_FLOAT: u32 = 0x1;
+/// `lpcm` format flag of big-endian samples.
+const LPCM_BIG_ENDIAN: u32 = 0x2;
+/// `lpcm` format flag of signed integer samples.
+const LPCM_SIGNED: u32 = 0x4;
+
+/// A single box located inside a parent payload.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Mp4Box<'a> {
+    /// Four-character box type.
+    pub kind: [u8; 4],
+    /// Box payload (everything after the header).
+    pub payload: &'a [u8],
+    /// Absolute file offset of the box header.
+    pub offset: u64,
+    /// Absolute file offset of the first payload byte.
+    pub payload_offset: u64,
+}
+
+/// Iterator over sibling boxes in a payload.
+///
+/// Iteration stops at the first truncated or malformed box header, which
+/// lets callers tolerate trailing garbage and incomplete files.
+#[derive(Debug, Clone)]
+pub(crate) struct BoxIter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    base_offset: u64,
+}
+
+impl<'a> Iterator for BoxIter<'a> {
+    type Item = Mp4Box<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.data[self.pos..];
+        let (kind, size, header_len) = box_header(rest)?;
+        let size = size.unwrap_or(rest.len() as u64);
+        if size < header_len as u64 || size > rest.len() as u64 {
+            return None;
+        }
+        let size = size as usize;
+        let payload = &rest[header_len..size];
+        let offset = self.base_offset + self.pos as u64;
+        self.pos += size;
+        Some(Mp4Box {
+            kind,
+            payload,
+            offset,
+            payload_offset: offset + header_len as u64,
+        })
+    }
+}
+
+/// Decodes a box header at the start of `data`.
+///
+/// Returns the box type, its total size (`None` for a box extending to the
+/// end of the file) and the header length.
+fn box_header(data: &[u8]) -> Option<([u8; 4], Option<u64>, usize)> {
+    let mut r = ByteReader::new(data);
+    let size32 = r.u32().ok()?;
+    let kind = r.fourcc().ok()?;
+    let size = match size32 {
+        0 => None,
+        1 => Some(r.u64().ok()?),
+        n => Some(n as u64),
+    };
+    Some((kind, size, r.position()))
+}
+
+/// Returns the end of the data a box ending at `end` may refer to: the
+/// `mdat` that follows it, or else the end of the file.
+///
+/// Returns `None` while the source is still growing and the bound is not
+/// known yet.
+fn fragment_data_end(reader: &mut SourceReader<'_>, end: u64) -> Result<Option<u64>> {
+    if let Some((kind, Some(size), _)) = box_header(reader.bytes(end, 16)?) {
+        if &kind == b"mdat" {
+            return Ok(Some(end.saturating_add(size)));
+        }
+    }
+    Ok(reader.is_complete().then(|| reader.len()))
+}
+
+/// Iterates over the boxes contained in `data`, which starts at file offset `base_offset`.
+pub(crate) fn boxes(data: &[u8], base_offset: u64) -> BoxIter<'_> {
+    BoxIter {
+        data,
+        pos: 0,
+        base_offset,
+    }
+}
+
+impl<'a> Mp4Box<'a> {
+    /// Iterates over the child boxes of this box.
+    pub(crate) fn children(&self) -> BoxIter<'a> {
+        boxes(self.payload, self.payload_offset)
+    }
+
+    /// Iterates over child boxes starting `skip` bytes into the payload.
+    pub(crate) fn children_after(&self, skip: usize) -> BoxIter<'a> {
+        let skip = skip.min(self.payload.len());
+        boxes(&self.payload[skip..], self.payload_offset + skip as u64)
+    }
+
+    /// Returns the first child box of the given type.
+    pub(crate) fn child(&self, kind: &[u8; 4]) -> Option<Mp4Box<'a>> {
+        self.children().find(|b| &b.kind == kind)
+    }
+}
+
+/// Parsed movie-level information.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Mp4Movie {
+    /// Movie timescale from `mvhd`.
+    pub timescale: u32,
+    /// Movie duration in milliseconds, if known.
+    pub duration_ms: Option<u64>,
+    /// Tracks with a supported handler type.
+    pub tracks: Vec<Mp4Track>,
+    /// Tags and cover art from `udta`/`meta`.
+    pub metadata: Metadata,
+    /// Chapters from a Nero `chpl` box.
+    pub chapters: Vec<Chapter>,
+    /// Text track referenced by `tref/chap`, whose samples are chapter titles.
+    pub chapter_track: Option<Mp4Track>,
+}
+
+/// Parsed per-track information.
+#[derive(Debug, Clone)]
+pub(crate) struct Mp4Track {
+    /// Track identifier from `tkhd`, referenced by `trex`/`tfhd`.
+    pub track_id: u32,
+    /// Stream type derived from the `hdlr` handler.
+    pub stream_type: StreamType,
+    /// Sample entry four-character code (e.g. `avc1`, `mp4a`), or the PCM
+    /// codec name of uncompressed audio.
+    pub codec: String,
+    /// Media timescale from `mdhd`.
+    pub timescale: u32,
+    /// Media duration in milliseconds, if known.
+    pub duration_ms: Option<u64>,
+    /// Language from `elng` or `mdhd`, if not undetermined.
+    pub language: Option<String>,
+    /// Track name from `udta/name`.
+    pub title: Option<String>,
+    /// Whether the `tkhd` enabled flag is set.
+    pub enabled: bool,
+    /// Average bitrate from `btrt`, in bits per second.
+    pub bitrate: Option<u64>,
+    /// Picture properties of a video track.
+    pub video: Option<VideoInfo>,
+    /// Sound properties of an audio track.
+    pub audio: Option<AudioInfo>,
+    /// Decoder configuration box payload of the sample entry (e.g. `avcC`).
+    pub extra_data: Option<Vec<u8>>,
+    /// Edit list from `edts`, also applied to samples from movie fragments.
+    pub edits: EditList,
+    /// Samples not yet handed over by the parser, in decode order, with
+    /// timestamps in `timescale` units.
+    pub samples: Vec<SampleEntry>,
+    /// Decode time following the last sample of the sample table.
+    pub end_dts: i64,
+}
+
+/// Incremental MP4 parser walking the top-level boxes.
+///
+/// Only `moov`, `moof` and `sidx` are read; `mdat` and other boxes are
+/// skipped by their size. Samples described by movie fragments are appended
+/// to the tracks declared in `moov`.
+#[derive(Debug, Default)]
+pub(crate) struct Mp4Parser {
+    /// Offset of the next top-level box.
+    pos: u64,
+    /// Movie parsed from `moov`.
+    movie: Option<Mp4Movie>,
+    /// Fragment state, when `moov` declares `mvex`.
+    fragments: Option<FragmentParser>,
+    /// Titles read so far from the samples of the chapter track.
+    chapter_titles: Vec<String>,
+}
+
+impl Mp4Parser {
+    /// Creates a parser positioned at the start of the file.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the movie with fragment durations resolved, once `moov` has been read.
+    pub(crate) fn movie(&self) -> Option<Mp4Movie> {
+        let mut movie = self.movie.clone()?;
+        if let Some(fragments) = &self.fragments {
+            fragments.finish(&mut movie);
+        }
+        Some(movie)
+    }
+
+    /// Parses a top-level box; `data_end` bounds the sample data of a
+    /// `moof` or of the tracks in `moov`.
+    fn parse_box(&mut self, b: &Mp4Box<'_>, data_end: u64) -> Result<()> {
+        match (&b.kind, self.movie.as_mut(), self.fragments.as_mut()) {
+            (b"moov", None, _) => {
+                let movie = parse_moov(b, data_end)?;
+                if let Some(mvex) = b.child(b"mvex") {
+                    self.fragments = Some(FragmentParser::new(&mvex, &movie)?);
+                }
+                self.movie = Some(movie);
+            }
+            (b"moof", Some(movie), Some(fragments)) => fragments.parse_moof(b, data_end, movie)?,
+            (b"sidx", Some(_), Some(fragments)) => fragments.parse_sidx(b)?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl ContainerParser for Mp4Parser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        while let Some((kind, size, header_len)) = box_header(reader.bytes(self.pos, 16)?) {
+            // A box extending to the end of the file ends with the data
+            // only once all of it has arrived.
+            let size = match size {
+                Some(size) => size,
+                None if reader.is_complete() => reader.len().saturating_sub(self.pos),
+                None => break,
+            };
+            if size < header_len as u64 {
+                break;
+            }
+            if matches!(&kind, b"moov" | b"moof" | b"sidx") {
+                // Fragments wait until the extent of their sample data is known.
+                let data_end = match &kind {
+                    b"moof" => match fragment_data_end(reader, self.pos.saturating_add(size))? {
+                        Some(end) => end,
+                        None => break,
+                    },
+                    _ if reader.is_complete() => reader.len(),
+                    _ => u64::MAX,
+                };
+                let data = match reader.exact(self.pos, size as usize)? {
+                    Some(data) => data.to_vec(),
+                    None => break,
+                };
+                if let Some(b) = boxes(&data, self.pos).next() {
+                    self.parse_box(&b, data_end)?;
+                }
+            }
+            self.pos += size;
+        }
+
+        // Chapter titles are read from the source as their samples arrive.
+        if let Some(track) = self.movie.as_ref().and_then(|m| m.chapter_track.as_ref()) {
+            for sample in &track.samples[self.chapter_titles.len()..] {
+                match reader.exact(sample.offset, sample.size as usize)? {
+                    Some(data) => self.chapter_titles.push(chapter_text(data)),
+                    None => break,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        let samples: Vec<Vec<SampleEntry>> = match self.movie.as_mut() {
+            Some(movie) => movie
+                .tracks
+                .iter_mut()
+                .map(|track| std::mem::take(&mut track.samples))
+                .collect(),
+            None => return Container::default(),
+        };
+        let movie = match self.movie() {
+            Some(movie) => movie,
+            None => return Container::default(),
+        };
+        // A chapter track takes precedence over a Nero chapter list.
+        let mut chapters = match &movie.chapter_track {
+            Some(track) if !self.chapter_titles.is_empty() => track
+                .samples
+                .iter()
+                .zip(&self.chapter_titles)
+                .map(|(sample, title)| {
+                    titled_chapter(
+                        to_ms(sample.pts.max(0) as u64, track.timescale),
+                        title.clone(),
+                    )
+                })
+                .collect(),
+            _ => movie.chapters,
+        };
+        close_chapters(&mut chapters, movie.duration_ms);
+        let mut container = Container {
+            duration_ms: movie.duration_ms,
+            metadata: movie.metadata,
+            chapters,
+            ..Container::default()
+        };
+        for (index, (track, samples)) in movie.tracks.into_iter().zip(samples).enumerate() {
+            container.streams.push(StreamInfo {
+                duration_ms: track.duration_ms,
+                language: track.language,
+                title: track.title,
+                is_default: track.enabled,
+                bitrate: track.bitrate,
+                video: track.video,
+                audio: track.audio,
+                extra_data: track.extra_data,
+                ..StreamInfo::new(
+                    index as u32,
+                    track.stream_type,
+                    track.codec,
+                    track.timescale,
+                )
+            });
+            container.samples.extend(samples);
+        }
+        container
+    }
+}
+
+/// Parses a `moov` box whose samples end by `data_end`.
+pub(crate) fn parse_moov(moov: &Mp4Box<'_>, data_end: u64) -> Result<Mp4Movie> {
+    let mut movie = Mp4Movie::default();
+
+    if let Some(mvhd) = moov.child(b"mvhd") {
+        let (timescale, duration) = parse_header_times(mvhd.payload)?;
+        movie.timescale = timescale;
+        movie.duration_ms = duration.map(|d| to_ms(d, timescale));
+    }
+
+    // Text tracks referenced by `tref/chap` hold chapter titles, not subtitles.
+    let chapter_ids: Vec<u32> = moov
+        .children()
+        .filter(|b| &b.kind == b"trak")
+        .filter_map(|trak| trak.child(b"tref")?.child(b"chap"))
+        .flat_map(|chap| {
+            chap.payload
+                .chunks_exact(4)
+                .map(|id| u32::from_be_bytes([id[0], id[1], id[2], id[3]]))
+        })
+        .collect();
+
+    for trak in moov.children().filter(|b| &b.kind == b"trak") {
+        let stream_index = movie.tracks.len() as u32;
+        if let Some(track) = parse_trak(&trak, stream_index, movie.timescale, data_end)? {
+            if chapter_ids.contains(&track.track_id) {
+                movie.chapter_track.get_or_insert(track);
+            } else {
+                movie.tracks.push(track);
+            }
+        }
+    }
+
+    if movie.duration_ms.is_none() {
+        movie.duration_ms = movie.tracks.iter().filter_map(|t| t.duration_ms).max();
+    }
+    movie.metadata = parse_moov_metadata(moov);
+    movie.chapters = parse_nero_chapters(moov);
+
+    Ok(movie)
+}
+
+/// Reads the timescale and duration from an `mvhd` or `mdhd` payload.
+pub(crate) fn parse_header_times(payload: &[u8]) -> Result<(u32, Option<u64>)> {
+    let mut r = ByteReader::new(payload);
+    let version = r.u8()?;
+    r.skip(3)?;
+    let (timescale, duration) = if version == 1 {
+        r.skip(16)?;
+        let timescale = r.u32()?;
+        let duration = r.u64()?;
+        (timescale, (duration != u64::MAX).then_some(duration))
+    } else {
+        r.skip(8)?;
+        let timescale = r.u32()?;
+        let duration = r.u32()?;
+        (timescale, (duration != u32::MAX).then_some(duration as u64))
+    };
+    Ok((timescale, duration))
+}
+
+/// Track header fields from `tkhd`.
+#[derive(Debug, Clone, Copy, Default)]
+struct TrackHeader {
+    track_id: u32,
+    enabled: bool,
+    /// Clockwise display rotation in degrees, from the transformation matrix.
+    rotation: u16,
+    /// Display width and height in pixels, zero if unset.
+    width: u32,
+    height: u32,
+}
+
+/// Reads a `tkhd` payload.
+fn parse_tkhd(payload: &[u8]) -> Result<TrackHeader> {
+    let mut r = ByteReader::new(payload);
+    let version = r.u8()?;
+    let flags = r.u24()?;
+    r.skip(if version == 1 { 16 } else { 8 })?;
+    let mut header = TrackHeader {
+        track_id: r.u32()?,
+        enabled: flags & 0x1 != 0,
+        ..TrackHeader::default()
+    };
+
+    // Older writers truncate the header after the track ID.
+    let mut display = || -> Result<(u16, u32, u32)> {
+        // Reserved, duration, reserved, layer, alternate group and volume.
+        r.skip(if version == 1 { 12 } else { 8 } + 16)?;
+        let a = r.u32()? as i32;
+        let b = r.u32()? as i32;
+        r.skip(28)?;
+        let rotation = match (a.signum(), b.signum()) {
+            (0, 1) => 90,
+            (-1, 0) => 180,
+            (0, -1) => 270,
+            _ => 0,
+        };
+        Ok((rotation, r.u32()? >> 16, r.u32()? >> 16))
+    };
+    if let Ok((rotation, width, height)) = display() {
+        header.rotation = rotation;
+        header.width = width;
+        header.height = height;
+    }
+    Ok(header)
+}
+
+/// Reads the ISO 639-2 language of an `mdhd` payload, `None` if undetermined.
+fn parse_mdhd_language(payload: &[u8]) -> Option<String> {
+    let offset = if payload.first() == Some(&1) { 32 } else { 20 };
+    let code = u16::from_be_bytes(payload.get(offset..offset + 2)?.try_into().ok()?);
+    // Values below 0x400 are Macintosh language codes.
+    if code < 0x400 {
+        return None;
+    }
+    let language: String = [10, 5, 0]
+        .iter()
+        .map(|shift| (((code >> shift) & 0x1F) as u8 + 0x60) as char)
+        .collect();
+    (language != "und").then_some(language)
+}
+
+/// Decodes a NUL-terminated string.
+fn c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// Maps an `hdlr` handler type to a stream type.
+fn handler_stream_type(payload: &[u8]) -> Result<Option<StreamType>> {
+    let mut r = ByteReader::new(payload);
+    r.skip(8)?;
+    let handler = r.fourcc()?;
+    Ok(match &handler {
+        b"vide" => Some(StreamType::Video),
+        b"soun" => Some(StreamType::Audio),
+        b"sbtl" | b"subt" | b"text" | b"clcp" => Some(StreamType::Subtitle),
+        _ => None,
+    })
+}
+
+/// Parses a `trak` box, returning `None` for unsupported handler types.
+/// Samples past `data_end` are dropped.
+fn parse_trak(
+    trak: &Mp4Box<'_>,
+    stream_index: u32,
+    movie_timescale: u32,
+    data_end: u64,
+) -> Result<Option<Mp4Track>> {
+    let tkhd = trak
+        .child(b"tkhd")
+        .ok_or_else(|| PlayerError::demuxer("Track is missing tkhd box"))?;
+    let header = parse_tkhd(tkhd.payload)?;
+
+    let mdia = trak
+        .child(b"mdia")
+        .ok_or_else(|| PlayerError::demuxer("Track is missing mdia box"))?;
+
+    let stream_type = match mdia.child(b"hdlr") {
+        Some(hdlr) => match handler_stream_type(hdlr.payload)? {
+            Some(t) => t,
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+
+    let mdhd = mdia
+        .child(b"mdhd")
+        .ok_or_else(|| PlayerError::demuxer("Track is missing mdhd box"))?;
+    let (timescale, duration) = parse_header_times(mdhd.payload)?;
+
+    let stbl = mdia
+        .child(b"minf")
+        .and_then(|minf| minf.child(b"stbl"))
+        .ok_or_else(|| PlayerError::demuxer("Track is missing stbl box"))?;
+
+    let mut codec = stbl
+        .child(b"stsd")
+        .map(|stsd| parse_stsd_codec(&stsd, stream_type))
+        .transpose()?
+        .flatten()
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let mut properties = stbl
+        .child(b"stsd")
+        .map(|stsd| parse_entry_properties(&stsd, stream_type, &codec))
+        .unwrap_or_default();
+    // MPEG-1 and MPEG-2 audio (object types 0x6B and 0x69) in `mp4a`, and
+    // QuickTime's `.mp3`, carry MP3.
+    if (codec == "mp4a" && matches!(properties.object_type, Some(0x69 | 0x6B))) || codec == ".mp3" {
+        codec = "mp3".to_string();
+    }
+    // Uncompressed audio is named by its sample encoding, and its samples,
+    // each a single sample frame, are read a chunk at a time.
+    let pcm_frame_len = match (properties.pcm, &properties.audio) {
+        (Some(pcm), Some(audio)) => {
+            codec = pcm::codec_name(pcm).unwrap_or_default().to_string();
+            pcm::sample_size(pcm).map(|size| size as u32 * audio.channels as u32)
+        }
+        _ => None,
+    };
+    if let Some(video) = properties.video.as_mut() {
+        video.rotation = header.rotation;
+        if header.width > 0 && header.height > 0 {
+            video.display_aspect_ratio = Some(header.width as f64 / header.height as f64);
+        }
+    }
+
+    // The extended language tag supersedes the packed `mdhd` code.
+    let language = mdia
+        .child(b"elng")
+        .and_then(|elng| elng.payload.get(4..))
+        .map(c_string)
+        .filter(|l| !l.is_empty())
+        .or_else(|| parse_mdhd_language(mdhd.payload));
+    let title = trak
+        .child(b"udta")
+        .and_then(|udta| udta.child(b"name"))
+        .map(|name| c_string(name.payload));
+
+    let edits = trak
+        .child(b"edts")
+        .map(|edts| EditList::parse(&edts, movie_timescale, timescale))
+        .transpose()?
+        .unwrap_or_default();
+
+    let table = SampleTable::parse(&stbl)?;
+    let mut samples = match pcm_frame_len {
+        Some(frame_len) if frame_len > 0 => {
+            table.build_chunks(stream_index, frame_len, data_end)?
+        }
+        _ => table.build_samples(stream_index, data_end)?,
+    };
+    for sample in &mut samples {
+        edits.apply(sample);
+    }
+
+    Ok(Some(Mp4Track {
+        track_id: header.track_id,
+        stream_type,
+        codec,
+        timescale,
+        duration_ms: duration.map(|d| to_ms(d, timescale)),
+        language,
+        title,
+        enabled: header.enabled,
+        bitrate: properties.bitrate,
+        video: properties.video,
+        audio: properties.audio,
+        extra_data: properties.extra_data,
+        end_dts: table.total_duration(),
+        edits,
+        samples,
+    }))
+}
+
+/// One `elst` entry, with times in the media timescale.
+#[derive(Debug, Clone, Copy)]
+struct Edit {
+    /// Presentation time at which the edit starts.
+    start: i64,
+    /// First media time presented, or `None` for an empty edit.
+    media_time: Option<i64>,
+    /// Media playback rate; zero for a dwell.
+    rate: f64,
+}
+
+/// Edit list of a track, mapping media time onto the movie timeline.
+///
+/// Empty edits delay the track, and the media time of the first real edit
+/// trims encoder delay or priming samples, which end up before zero.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EditList {
+    edits: Vec<Edit>,
+}
+
+/// Converts a value between timescales.
+fn rescale(value: u64, from: u32, to: u32) -> i64 {
+    if from == 0 {
+        return 0;
+    }
+    (value as u128 * to as u128 / from as u128).min(i64::MAX as u128) as i64
+}
+
+impl EditList {
+    /// Reads the `elst` box of an `edts` box.
+    ///
+    /// Edit durations are converted from the movie to the media timescale.
+    fn parse(edts: &Mp4Box<'_>, movie_timescale: u32, media_timescale: u32) -> Result<Self> {
+        let elst = match edts.child(b"elst") {
+            Some(elst) => elst,
+            None => return Ok(Self::default()),
+        };
+        let mut r = ByteReader::new(elst.payload);
+        let version = r.u8()?;
+        r.skip(3)?;
+        let count = r.u32()?;
+        let entry_len = if version == 1 { 20 } else { 12 };
+        let mut edits = Vec::with_capacity(bounded_capacity(count, &r, entry_len));
+        let mut start = 0i64;
+        for _ in 0..count {
+            let (duration, media_time) = if version == 1 {
+                (r.u64()?, r.u64()? as i64)
+            } else {
+                (r.u32()? as u64, r.u32()? as i32 as i64)
+            };
+            let rate = r.u16()? as i16 as f64 + r.u16()? as f64 / 65536.0;
+            edits.push(Edit {
+                start,
+                media_time: (media_time >= 0).then_some(media_time),
+                rate,
+            });
+            start = start.saturating_add(rescale(duration, movie_timescale, media_timescale));
+        }
+        Ok(Self { edits })
+    }
+
+    /// Moves a sample from media time onto the presentation timeline.
+    ///
+    /// The sample is placed by the last edit starting at or before its
+    /// presentation time, or by the first edit if it precedes them all.
+    pub(crate) fn apply(&self, sample: &mut SampleEntry) {
+        let mut playing = self
+            .edits
+            .iter()
+            .filter(|e| e.media_time.is_some() && e.rate > 0.0);
+        let first = match playing.next() {
+            Some(edit) => edit,
+            None => return,
+        };
+        let edit = std::iter::once(first)
+            .chain(playing)
+            .take_while(|e| e.media_time.is_some_and(|t| t <= sample.pts))
+            .last()
+            .unwrap_or(first);
+        let media_time = edit.media_time.unwrap_or(0);
+        let map = |t: i64| {
+            let elapsed = t - media_time;
+            if edit.rate == 1.0 {
+                edit.start + elapsed
+            } else {
+                edit.start + (elapsed as f64 / edit.rate).round() as i64
+            }
+        };
+        sample.pts = map(sample.pts);
+        sample.dts = map(sample.dts);
+    }
+}
+
+/// Raw sample table boxes of a track (`stsz`/`stco`/`stsc`/`stts`/`ctts`/`stss`).
+#[derive(Debug, Default)]
+struct SampleTable {
+    /// Number of samples.
+    sample_count: u32,
+    /// Size shared by every sample, or 0 when `sizes` lists them.
+    sample_size: u32,
+    /// Size of every sample, in decode order, unless `sample_size` is set.
+    sizes: Vec<u32>,
+    /// File offset of every chunk.
+    chunk_offsets: Vec<u64>,
+    /// `(first_chunk, samples_per_chunk)` runs, 1-based chunk numbers.
+    sample_to_chunk: Vec<(u32, u32)>,
+    /// `(sample_count, sample_delta)` runs.
+    time_to_sample: Vec<(u32, u32)>,
+    /// `(sample_count, composition_offset)` runs.
+    composition_offsets: Vec<(u32, i64)>,
+    /// 1-based sync sample numbers; `None` means every sample is a sync sample.
+    sync_samples: Option<Vec<u32>>,
+}
+
+/// Caps a declared entry count by how many entries could actually fit.
+fn bounded_capacity(count: u32, r: &ByteReader<'_>, entry_len: usize) -> usize {
+    (count as usize).min(r.remaining() / entry_len)
+}
+
+impl SampleTable {
+    /// Reads the sample table boxes from an `stbl` box.
+    fn parse(stbl: &Mp4Box<'_>) -> Result<Self> {
+        let mut table = SampleTable::default();
+
+        for b in stbl.children() {
+            let mut r = ByteReader::new(b.payload);
+            match &b.kind {
+                b"stsz" => {
+                    r.skip(4)?;
+                    let sample_size = r.u32()?;
+                    let count = r.u32()?;
+                    table.sample_count = count;
+                    table.sample_size = sample_size;
+                    // A shared size is looked up per sample rather than
+                    // expanded, as the count is not backed by any data.
+                    if sample_size == 0 {
+                        let mut sizes = Vec::with_capacity(bounded_capacity(count, &r, 4));
+                        for _ in 0..count {
+                            sizes.push(r.u32()?);
+                        }
+                        table.sizes = sizes;
+                    }
+                }
+                b"stz2" => {
+                    r.skip(7)?;
+                    let field_size = r.u8()?;
+                    let count = r.u32()?;
+                    let mut sizes = Vec::with_capacity(bounded_capacity(count, &r, 1));
+                    let mut i = 0;
+                    while i < count {
+                        match field_size {
+                            4 => {
+                                let byte = r.u8()? as u32;
+                                sizes.push(byte >> 4);
+                                if i + 1 < count {
+                                    sizes.push(byte & 0x0F);
+                                }
+                                i += 2;
+                                continue;
+                            }
+                            8 => sizes.push(r.u8()? as u32),
+                            16 => sizes.push(r.u16()? as u32),
+                            _ => return Err(PlayerError::demuxer("Invalid stz2 field size")),
+                        }
+                        i += 1;
+                    }
+                    table.sample_count = count;
+                    table.sample_size = 0;
+                    table.sizes = sizes;
+                }
+                b"stco" | b"co64" => {
+                    r.skip(4)?;
+                    let count = r.u32()?;
+                    let entry_len = if &b.kind == b"co64" { 8 } else { 4 };
+                    let mut offsets = Vec::with_capacity(bounded_capacity(count, &r, entry_len));
+                    for _ in 0..count {
+                        offsets.push(if entry_len == 8 {
+                            r.u64()?
+                        } else {
+                            r.u32()? as u64
+                        });
+                    }
+                    table.chunk_offsets = offsets;
+                }
+                b"stsc" => {
+                    r.skip(4)?;
+                    let count = r.u32()?;
+                    let mut runs = Vec::with_capacity(bounded_capacity(count, &r, 12));
+                    for _ in 0..count {
+                        let first_chunk = r.u32()?;
+                        let samples_per_chunk = r.u32()?;
+                        r.skip(4)?;
+                        runs.push((first_chunk, samples_per_chunk));
+                    }
+                    table.sample_to_chunk = runs;
+                }
+                b"stts" => {
+                    r.skip(4)?;
+                    let count = r.u32()?;
+                    let mut runs = Vec::with_capacity(bounded_capacity(count, &r, 8));
+                    for _ in 0..count {
+                        runs.push((r.u32()?, r.u32()?));
+                    }
+                    table.time_to_sample = runs;
+                }
+                b"ctts" => {
+                    let version = r.u8()?;
+                    r.skip(3)?;
+                    let count = r.u32()?;
+                    let mut runs = Vec::with_capacity(bounded_capacity(count, &r, 8));
+                    for _ in 0..count {
+                        let sample_count = r.u32()?;
+                        let raw = r.u32()?;
+                        // Version 1 allows negative offsets.
+                        let offset = if version == 0 {
+                            raw as i64
+                        } else {
+                            raw as i32 as i64
+                        };
+                        runs.push((sample_count, offset));
+                    }
+                    table.composition_offsets = runs;
+                }
+                b"stss" => {
+                    r.skip(4)?;
+                    let count = r.u32()?;
+                    let mut sync = Vec::with_capacity(bounded_capacity(count, &r, 4));
+                    for _ in 0..count {
+                        sync.push(r.u32()?);
+                    }
+                    table.sync_samples = Some(sync);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Returns the size of a sample, which must be below `sample_count`.
+    fn size(&self, sample: usize) -> u32 {
+        if self.sample_size != 0 {
+            self.sample_size
+        } else {
+            self.sizes[sample]
+        }
+    }
+
+    /// Returns the sum of all sample durations.
+    fn total_duration(&self) -> i64 {
+        self.time_to_sample
+            .iter()
+            .map(|&(count, delta)| count as i64 * delta as i64)
+            .sum()
+    }
+
+    /// Builds one entry per chunk for uncompressed audio, whose samples are
+    /// sample frames of `frame_len` bytes. The sizes in `stsz` are ignored:
+    /// QuickTime sound descriptions before version 2 give a size of 1.
+    /// Chunks too large for one entry are split, and frames past `data_end`
+    /// are dropped.
+    fn build_chunks(
+        &self,
+        stream_index: u32,
+        frame_len: u32,
+        data_end: u64,
+    ) -> Result<Vec<SampleEntry>> {
+        let max_frames = (u32::MAX / frame_len) as u64;
+        let mut remaining = self.sample_count as u64;
+        let mut entries = Vec::new();
+        let mut deltas = self.time_to_sample.iter().copied();
+        let (mut run_left, mut delta) = (0u64, 0i64);
+        let mut dts = 0i64;
+        for (run_index, &(first_chunk, samples_per_chunk)) in
+            self.sample_to_chunk.iter().enumerate()
+        {
+            let last_chunk = match self.sample_to_chunk.get(run_index + 1) {
+                Some(&(next_first, _)) => next_first.saturating_sub(1),
+                None => self.chunk_offsets.len() as u32,
+            };
+            for chunk in first_chunk..=last_chunk {
+                let mut offset = match (chunk as usize)
+                    .checked_sub(1)
+                    .and_then(|i| self.chunk_offsets.get(i))
+                {
+                    Some(&offset) => offset,
+                    None => break,
+                };
+                let available = data_end.saturating_sub(offset) / frame_len as u64;
+                let mut count = (samples_per_chunk as u64).min(remaining);
+                if count > available {
+                    // The file ends inside this chunk, so no later frame
+                    // can be read.
+                    count = available;
+                    remaining = count;
+                }
+                if count == 0 {
+                    continue;
+                }
+                remaining -= count;
+                while count > 0 {
+                    let frames = count.min(max_frames);
+                    let size = frames as u32 * frame_len;
+                    entries.push(SampleEntry {
+                        stream_index,
+                        offset,
+                        size,
+                        dts,
+                        pts: dts,
+                        is_keyframe: true,
+                        continuation: None,
+                    });
+                    offset += size as u64;
+                    count -= frames;
+                    // Advance through the time-to-sample runs.
+                    let mut left = frames;
+                    while left > 0 {
+                        if run_left == 0 {
+                            match deltas.next() {
+                                Some((run_count, run_delta)) => {
+                                    run_left = run_count as u64;
+                                    delta = run_delta as i64;
+                                }
+                                None => {
+                                    run_left = u64::MAX;
+                                    delta = 0;
+                                }
+                            }
+                            continue;
+                        }
+                        let step = left.min(run_left);
+                        dts += step as i64 * delta;
+                        left -= step;
+                        run_left -= step;
+                    }
+                }
+            }
+        }
+
+        if remaining > 0 {
+            return Err(PlayerError::demuxer(
+                "Sample table does not cover all samples",
+            ));
+        }
+        Ok(entries)
+    }
+
+    /// Expands the run-length encoded tables into one entry per sample,
+    /// dropping the samples from the first one past `data_end` on.
+    fn build_samples(&self, stream_index: u32, data_end: u64) -> Result<Vec<SampleEntry>> {
+        let mut sample_count = self.sample_count as usize;
+        // Only listed sizes are backed by data in the file.
+        let mut samples = Vec::with_capacity(self.sizes.len());
+
+        // Resolve file offsets via the chunk tables.
+        let mut sample = 0usize;
+        for (run_index, &(first_chunk, samples_per_chunk)) in
+            self.sample_to_chunk.iter().enumerate()
+        {
+            let last_chunk = match self.sample_to_chunk.get(run_index + 1) {
+                Some(&(next_first, _)) => next_first.saturating_sub(1),
+                None => self.chunk_offsets.len() as u32,
+            };
+            for chunk in first_chunk..=last_chunk {
+                let chunk_offset = (chunk as usize)
+                    .checked_sub(1)
+                    .and_then(|i| self.chunk_offsets.get(i));
+                let mut offset = match chunk_offset {
+                    Some(&offset) => offset,
+                    None => break,
+                };
+                for _ in 0..samples_per_chunk {
+                    if sample >= sample_count {
+                        break;
+                    }
+                    let size = self.size(sample);
+                    let end = offset.checked_add(size as u64).ok_or_else(|| {
+                        PlayerError::demuxer("Sample offset overflows the file size")
+                    })?;
+                    if end > data_end {
+                        // The file ends inside this sample.
+                        sample_count = sample;
+                        break;
+                    }
+                    samples.push(SampleEntry {
+                        stream_index,
+                        offset,
+                        size,
+                        dts: 0,
+                        pts: 0,
+                        is_keyframe: self.sync_samples.is_none(),
+                        continuation: None,
+                    });
+                    offset = end;
+                    sample += 1;
+                }
+            }
+        }
+
+        if samples.len() != sample_count {
+            return Err(PlayerError::demuxer(
+                "Sample table does not cover all samples",
+            ));
+        }
+
+        // Decode timestamps from the time-to-sample runs.
+        let mut dts = 0i64;
+        let mut deltas = self
+            .time_to_sample
+            .iter()
+            .flat_map(|&(count, delta)| std::iter::repeat_n(delta, count as usize));
+        for s in samples.iter_mut() {
+            s.dts = dts;
+            s.pts = dts;
+            dts += deltas.next().unwrap_or(0) as i64;
+        }
+
+        // Composition offsets shift presentation time relative to decode time.
+        let offsets = self
+            .composition_offsets
+            .iter()
+            .flat_map(|&(count, offset)| std::iter::repeat_n(offset, count as usize));
+        for (s, offset) in samples.iter_mut().zip(offsets) {
+            s.pts = s.dts + offset;
+        }
+
+        if let Some(sync) = &self.sync_samples {
+            for &number in sync {
+                if let Some(s) = (number as usize)
+                    .checked_sub(1)
+                    .and_then(|i| samples.get_mut(i))
+                {
+                    s.is_keyframe = true;
+                }
+            }
+        }
+
+        Ok(samples)
+    }
+}
+
+/// Size of the fixed payload fields preceding child boxes in a sample entry.
+fn sample_entry_header_len(stream_type: StreamType) -> usize {
+    match stream_type {
+        // SampleEntry (8) + VisualSampleEntry fields (70)
+        StreamType::Video => 78,
+        // SampleEntry (8) + AudioSampleEntry fields (20)
+        StreamType::Audio => 28,
+        StreamType::Subtitle => 8,
+    }
+}
+
+/// Picture or sound properties and bitrate of a sample entry.
+#[derive(Debug, Default)]
+struct EntryProperties {
+    video: Option<VideoInfo>,
+    audio: Option<AudioInfo>,
+    bitrate: Option<u64>,
+    extra_data: Option<Vec<u8>>,
+    /// Object type indication of an `esds` DecoderConfigDescriptor.
+    object_type: Option<u8>,
+    /// Sample encoding of uncompressed audio.
+    pcm: Option<AudioCodec>,
+}
+
+/// Reads the properties of the first `stsd` sample entry and its
+/// `pasp`, `colr`, `btrt` and decoder configuration boxes.
+fn parse_entry_properties(
+    stsd: &Mp4Box<'_>,
+    stream_type: StreamType,
+    codec: &str,
+) -> EntryProperties {
+    let mut properties = EntryProperties::default();
+    let entry = match stsd.children_after(8).next() {
+        Some(entry) => entry,
+        None => return properties,
+    };
+    let p = entry.payload;
+    let be_u16 = |at: usize| u16::from_be_bytes([p[at], p[at + 1]]);
+    let be_u32 = |at: usize| u32::from_be_bytes([p[at], p[at + 1], p[at + 2], p[at + 3]]);
+    // Sample size, `lpcm` format flags and byte order of uncompressed audio.
+    let mut sample_bits = 0;
+    let mut lpcm_flags = 0;
+    let mut little_endian = None;
+
+    let header_len = match stream_type {
+        StreamType::Video if p.len() >= 78 => {
+            let (width, height) = (be_u16(24) as u32, be_u16(26) as u32);
+            properties.video = Some(VideoInfo {
+                width,
+                height,
+                display_aspect_ratio: (height > 0).then(|| width as f64 / height as f64),
+                ..VideoInfo::default()
+            });
+            sample_entry_header_len(stream_type)
+        }
+        StreamType::Audio if p.len() >= 28 => {
+            // QuickTime sound description versions 1 and 2 extend the entry.
+            let (rate, channels, bits, extra) = match be_u16(8) {
+                2 if p.len() >= 64 => {
+                    let rate = f64::from_bits(u64::from_be_bytes(p[32..40].try_into().unwrap()));
+                    lpcm_flags = be_u32(52);
+                    (rate as u32, be_u32(40) as u16, be_u32(48) as u16, 36)
+                }
+                version => (
+                    be_u32(24) >> 16,
+                    be_u16(16),
+                    be_u16(18),
+                    16 * (version == 1) as usize,
+                ),
+            };
+            let mut audio = AudioInfo::new(rate, channels);
+            // The sample size field is only meaningful for uncompressed audio.
+            if matches!(
+                codec,
+                "lpcm"
+                    | "sowt"
+                    | "twos"
+                    | "ipcm"
+                    | "fpcm"
+                    | "in24"
+                    | "in32"
+                    | "fl32"
+                    | "fl64"
+                    | "raw "
+            ) {
+                audio.bit_depth = Some(bits as u8);
+            }
+            properties.audio = Some(audio);
+            sample_entry_header_len(stream_type) + extra
+        }
+        _ => return properties,
+    };
+
+    for child in entry.children_after(header_len) {
+        let c = child.payload;
+        match &child.kind {
+            b"pasp" if c.len() >= 8 => {
+                let h_spacing = u32::from_be_bytes([c[0], c[1], c[2], c[3]]) as f64;
+                let v_spacing = u32::from_be_bytes([c[4], c[5], c[6], c[7]]) as f64;
+                if let Some(video) = properties.video.as_mut() {
+                    if h_spacing > 0.0 && v_spacing > 0.0 && video.height > 0 {
+                        video.display_aspect_ratio = Some(
+                            video.width as f64 * h_spacing / (video.height as f64 * v_spacing),
+                        );
+                    }
+                }
+            }
+            b"colr" if c.len() >= 10 && matches!(&c[..4], b"nclx" | b"nclc") => {
+                if let Some(video) = properties.video.as_mut() {
+                    video.color = Some(ColorInfo {
+                        primaries: Some(c[5]),
+                        transfer: Some(c[7]),
+                        matrix: Some(c[9]),
+                        full_range: c
+                            .get(10)
+                            .filter(|_| &c[..4] == b"nclx")
+                            .map(|f| f & 0x80 != 0),
+                    });
+                }
+            }
+            b"avcC" | b"hvcC" | b"av1C" | b"vpcC" => properties.extra_data = Some(c.to_vec()),
+            // FLAC metadata blocks after the version and flags; stored
+            // with the stream marker as in Matroska.
+            b"dfLa" if c.len() > 4 => properties.extra_data = Some([b"fLaC", &c[4..]].concat()),
+            // Opus decoders take an `OpusHead`, as stored in Ogg and Matroska.
+            b"dOps" => {
+                properties.extra_data = OpusHead::parse_dops(c).ok().map(|head| head.to_bytes())
+            }
+            b"esds" => {
+                if let Some((object_type, config)) = parse_esds(c) {
+                    properties.object_type = Some(object_type);
+                    properties.extra_data = config.map(<[u8]>::to_vec);
+                }
+            }
+            b"btrt" if c.len() >= 12 => {
+                let average = u32::from_be_bytes([c[8], c[9], c[10], c[11]]);
+                properties.bitrate = (average > 0).then_some(average as u64);
+            }
+            // QuickTime byte order, directly or in the `wave` extension.
+            b"enda" | b"wave" => {
+                let enda = if &child.kind == b"wave" {
+                    child.child(b"enda").map(|enda| enda.payload)
+                } else {
+                    Some(c)
+                };
+                if let Some(&[high, low, ..]) = enda {
+                    little_endian = Some(u16::from_be_bytes([high, low]) != 0);
+                }
+            }
+            // ISO/IEC 23003-5 format flags and sample size.
+            b"pcmC" if c.len() >= 6 => {
+                little_endian = Some(c[4] & 1 != 0);
+                sample_bits = c[5] as u16;
+                if let Some(audio) = properties.audio.as_mut() {
+                    audio.bit_depth = Some(c[5]);
+                }
+            }
+            _ => {}
+        }
+    }
+    if stream_type == StreamType::Audio {
+        properties.pcm = pcm_codec(codec, sample_bits, lpcm_flags, little_endian);
+    }
+    properties
+}
+
+/// Returns the PCM codec of an uncompressed sound sample entry, given its
+/// sample size, `lpcm` format flags and the byte order from `enda` or
+/// `pcmC`. Signed 8-bit samples are not supported.
+fn pcm_codec(
+    codec: &str,
+    bits: u16,
+    lpcm_flags: u32,
+    little_endian: Option<bool>,
+) -> Option<AudioCodec> {
+    let big_endian = little_endian != Some(true);
+    match codec {
+        "raw " if bits == 8 => Some(AudioCodec::PcmU8),
+        "sowt" if bits != 8 => pcm::integer_codec(bits, false),
+        "twos" if bits != 8 => pcm::integer_codec(bits, true),
+        "in24" => pcm::integer_codec(24, big_endian),
+        "in32" => pcm::integer_codec(32, big_endian),
+        "fl32" => pcm::float_codec(32, big_endian),
+        "ulaw" => Some(AudioCodec::PcmMulaw),
+        "alaw" => Some(AudioCodec::PcmAlaw),
+        "lpcm" if lpcm_flags & LPCM_FLOAT != 0 => {
+            pcm::float_codec(bits, lpcm_flags & LPCM_BIG_ENDIAN != 0)
+        }
+        "lpcm" if bits != 8 || lpcm_flags & LPCM_SIGNED == 0 => {
+            pcm::integer_codec(bits, lpcm_flags & LPCM_BIG_ENDIAN != 0)
+        }
+        "ipcm" if bits != 8 => pcm::integer_codec(bits, big_endian),
+        "fpcm" => pcm::float_codec(bits, big_endian),
+        _ => None,
+    }
+}
+
+/// Reads the tag and size of an MPEG-4 descriptor (ISO/IEC 14496-1,
+/// 8.3.3), whose size is coded in 7-bit groups.
+fn read_descriptor<'a>(r: &mut ByteReader<'a>) -> Result<(u8, &'a [u8])> {
+    let tag = r.u8()?;
+    let mut size = 0usize;
+    for _ in 0..4 {
+        let byte = r.u8()?;
+        size = (size << 7) | (byte & 0x7F) as usize;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok((tag, r.bytes(size)?))
+}
+
+/// Returns the object type indication and DecoderSpecificInfo of an `esds`
+/// box; the latter is the AudioSpecificConfig for AAC.
+fn parse_esds(payload: &[u8]) -> Option<(u8, Option<&[u8]>)> {
+    let mut r = ByteReader::new(payload);
+    r.skip(4).ok()?;
+    let (tag, es) = read_descriptor(&mut r).ok()?;
+    if tag != 0x03 {
+        return None;
+    }
+    let mut es = ByteReader::new(es);
+    es.skip(2).ok()?; // ES_ID
+    let flags = es.u8().ok()?;
+    if flags & 0x80 != 0 {
+        es.skip(2).ok()?; // dependsOn_ES_ID
+    }
+    if flags & 0x40 != 0 {
+        let url_len = es.u8().ok()?;
+        es.skip(url_len as usize).ok()?;
+    }
+    if flags & 0x20 != 0 {
+        es.skip(2).ok()?; // OCR_ES_Id
+    }
+    let (tag, decoder_config) = read_descriptor(&mut es).ok()?;
+    if tag != 0x04 {
+        return None;
+    }
+    let mut decoder_config = ByteReader::new(decoder_config);
+    let object_type = decoder_config.u8().ok()?;
+    decoder_config.skip(12).ok()?; // stream type, buffer and bitrates
+    let info = match read_descriptor(&mut decoder_config) {
+        Ok((0x05, info)) => Some(info),
+        _ => None,
+    };
+    Some((object_type, info))
+}
+
+/// Returns the codec four-character code of the first `stsd` sample entry.
+///
+/// Encrypted entries (`encv`/`enca`) are resolved to their original format
+/// through `sinf`/`frma`.
+fn parse_stsd_codec(stsd: &Mp4Box<'_>, stream_type: StreamType) -> Result<Option<String>> {
+    let mut r = ByteReader::new(stsd.payload);
+    r.skip(4)?;
+    let entry_count = r.u32()?;
+    if entry_count == 0 {
+        return Ok(None);
+    }
+
+    let entry = match stsd.children_after(8).next() {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let mut kind = entry.kind;
+    if &kind == b"encv" || &kind == b"enca" {
+        let original = entry
+            .children_after(sample_entry_header_len(stream_type))
+            .find(|b| &b.kind == b"sinf")
+            .and_then(|sinf| sinf.child(b"frma"))
+            .filter(|frma| frma.payload.len() >= 4);
+        if let Some(frma) = original {
+            kind.copy_from_slice(&frma.payload[..4]);
+        }
+    }
+
+    Ok(Some(String::from_utf8_lossy(&kind).into_owned()))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::demuxer::{ByteSource, MemorySource};
+
+    /// Runs the parser over a complete in-memory file.
+    pub(crate) fn parse(data: &[u8]) -> Option<Mp4Movie> {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = Mp4Parser::new();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        parser.movie()
+    }
+
+    /// Builds a box with the given type and payload.
+    pub(crate) fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Builds a full box (with version and flags) with the given payload.
+    pub(crate) fn full_box(kind: &[u8; 4], version: u8, payload: &[u8]) -> Vec<u8> {
+        let mut body = vec![version, 0, 0, 0];
+        body.extend_from_slice(payload);
+        mp4_box(kind, &body)
+    }
+
+    pub(crate) fn mvhd(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut p = vec![0; 8];
+        p.extend_from_slice(&timescale.to_be_bytes());
+        p.extend_from_slice(&duration.to_be_bytes());
+        p.extend_from_slice(&[0; 80]);
+        full_box(b"mvhd", 0, &p)
+    }
+
+    pub(crate) fn tkhd(track_id: u32) -> Vec<u8> {
+        let mut p = vec![0; 8];
+        p.extend_from_slice(&track_id.to_be_bytes());
+        p.extend_from_slice(&[0; 68]);
+        full_box(b"tkhd", 0, &p)
+    }
+
+    pub(crate) fn mdhd(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut p = vec![0; 8];
+        p.extend_from_slice(&timescale.to_be_bytes());
+        p.extend_from_slice(&duration.to_be_bytes());
+        p.extend_from_slice(&[0x55, 0xC4, 0, 0]);
+        full_box(b"mdhd", 0, &p)
+    }
+
+    pub(crate) fn hdlr(handler: &[u8; 4]) -> Vec<u8> {
+        let mut p = vec![0; 4];
+        p.extend_from_slice(handler);
+        p.extend_from_slice(&[0; 13]);
+        full_box(b"hdlr", 0, &p)
+    }
+
+    pub(crate) fn stsd(entry_kind: &[u8; 4], stream_type: StreamType, children: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0; sample_entry_header_len(stream_type)];
+        entry.extend_from_slice(children);
+        let entry = mp4_box(entry_kind, &entry);
+        let mut p = 1u32.to_be_bytes().to_vec();
+        p.extend_from_slice(&entry);
+        full_box(b"stsd", 0, &p)
+    }
+
+    /// Description of a synthetic `trak` box for tests.
+    pub(crate) struct TrakSpec {
+        pub track_id: u32,
+        pub handler: [u8; 4],
+        pub codec: [u8; 4],
+        pub stream_type: StreamType,
+        pub timescale: u32,
+        pub duration: u32,
+        /// Child boxes of the sample entry, such as `avcC`.
+        pub entry: Vec<u8>,
+        /// Extra boxes appended to `stbl` after `stsd`.
+        pub stbl: Vec<u8>,
+        /// Children of a `tref` box, which is omitted when empty.
+        pub tref: Vec<u8>,
+    }
+
+    impl TrakSpec {
+        pub(crate) fn video(codec: &[u8; 4]) -> Self {
+            Self {
+                track_id: 1,
+                handler: *b"vide",
+                codec: *codec,
+                stream_type: StreamType::Video,
+                timescale: 90000,
+                duration: 0,
+                entry: Vec::new(),
+                stbl: Vec::new(),
+                tref: Vec::new(),
+            }
+        }
+
+        pub(crate) fn audio(codec: &[u8; 4]) -> Self {
+            Self {
+                track_id: 2,
+                handler: *b"soun",
+                codec: *codec,
+                stream_type: StreamType::Audio,
+                timescale: 48000,
+                duration: 0,
+                entry: Vec::new(),
+                stbl: Vec::new(),
+                tref: Vec::new(),
+            }
+        }
+
+        pub(crate) fn build(&self) -> Vec<u8> {
+            let mut stbl = stsd(&self.codec, self.stream_type, &self.entry);
+            stbl.extend_from_slice(&self.stbl);
+            let minf = mp4_box(b"minf", &mp4_box(b"stbl", &stbl));
+            let mut mdia = mdhd(self.timescale, self.duration);
+            mdia.extend_from_slice(&hdlr(&self.handler));
+            mdia.extend_from_slice(&minf);
+            let mut trak = tkhd(self.track_id);
+            if !self.tref.is_empty() {
+                trak.extend_from_slice(&mp4_box(b"tref", &self.tref));
+            }
+            trak.extend_from_slice(&mp4_box(b"mdia", &mdia));
+            mp4_box(b"trak", &trak)
+        }
+    }
+
+    pub(crate) fn ftyp() -> Vec<u8> {
+        mp4_box(b"ftyp", b"isom\0\0\0\0isomavc1")
+    }
+
+    /// Builds a full box whose payload is an entry count followed by `entries`.
+    fn table_box(kind: &[u8; 4], version: u8, entries: &[&[u32]]) -> Vec<u8> {
+        let mut p = (entries.len() as u32).to_be_bytes().to_vec();
+        for entry in entries {
+            for v in entry.iter() {
+                p.extend_from_slice(&v.to_be_bytes());
+            }
+        }
+        full_box(kind, version, &p)
+    }
+
+    pub(crate) fn stsz(sizes: &[u32]) -> Vec<u8> {
+        let mut p = 0u32.to_be_bytes().to_vec();
+        p.extend_from_slice(&(sizes.len() as u32).to_be_bytes());
+        for size in sizes {
+            p.extend_from_slice(&size.to_be_bytes());
+        }
+        full_box(b"stsz", 0, &p)
+    }
+
+    pub(crate) fn stco(offsets: &[u32]) -> Vec<u8> {
+        let entries: Vec<[u32; 1]> = offsets.iter().map(|&o| [o]).collect();
+        let refs: Vec<&[u32]> = entries.iter().map(|e| &e[..]).collect();
+        table_box(b"stco", 0, &refs)
+    }
+
+    pub(crate) fn stsc(runs: &[(u32, u32)]) -> Vec<u8> {
+        let entries: Vec<[u32; 3]> = runs.iter().map(|&(f, n)| [f, n, 1]).collect();
+        let refs: Vec<&[u32]> = entries.iter().map(|e| &e[..]).collect();
+        table_box(b"stsc", 0, &refs)
+    }
+
+    pub(crate) fn stts(runs: &[(u32, u32)]) -> Vec<u8> {
+        let entries: Vec<[u32; 2]> = runs.iter().map(|&(c, d)| [c, d]).collect();
+        let refs: Vec<&[u32]> = entries.iter().map(|e| &e[..]).collect();
+        table_box(b"stts", 0, &refs)
+    }
+
+    pub(crate) fn ctts(runs: &[(u32, u32)]) -> Vec<u8> {
+        let entries: Vec<[u32; 2]> = runs.iter().map(|&(c, o)| [c, o]).collect();
+        let refs: Vec<&[u32]> = entries.iter().map(|e| &e[..]).collect();
+        table_box(b"ctts", 0, &refs)
+    }
+
+    pub(crate) fn stss(numbers: &[u32]) -> Vec<u8> {
+        let entries: Vec<[u32; 1]> = numbers.iter().map(|&n| [n]).collect();
+        let refs: Vec<&[u32]> = entries.iter().map(|e| &e[..]).collect();
+        table_box(b"stss", 0, &refs)
+    }
+
+    /// Parses the single `stbl` of a synthetic track and expands its samples.
+    fn samples_of(stbl_children: &[u8]) -> Vec<SampleEntry> {
+        let stbl = mp4_box(b"stbl", stbl_children);
+        let b = boxes(&stbl, 0).next().unwrap();
+        SampleTable::parse(&b)
+            .unwrap()
+            .build_samples(0, u64::MAX)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_box_iter_handles_truncation() {
+        let mut data = mp4_box(b"free", &[1, 2, 3]);
+        data.extend_from_slice(&[0, 0, 0, 100, b'm', b'd', b'a', b't']);
+        let kinds: Vec<_> = boxes(&data, 0).map(|b| b.kind).collect();
+        assert_eq!(kinds, vec![*b"free"]);
+    }
+
+    #[test]
+    fn test_box_iter_large_size() {
+        let mut data = 1u32.to_be_bytes().to_vec();
+        data.extend_from_slice(b"mdat");
+        data.extend_from_slice(&20u64.to_be_bytes());
+        data.extend_from_slice(&[9, 9, 9, 9]);
+        let b = boxes(&data, 100).next().unwrap();
+        assert_eq!(&b.kind, b"mdat");
+        assert_eq!(b.payload, &[9, 9, 9, 9]);
+        assert_eq!(b.payload_offset, 116);
+    }
+
+    #[test]
+    fn test_parse_movie_tracks() {
+        let mut moov = mvhd(1000, 10_000);
+        moov.extend(
+            TrakSpec {
+                duration: 900_000,
+                ..TrakSpec::video(b"avc1")
+            }
+            .build(),
+        );
+        moov.extend(
+            TrakSpec {
+                duration: 480_000,
+                ..TrakSpec::audio(b"mp4a")
+            }
+            .build(),
+        );
+        moov.extend(
+            TrakSpec {
+                track_id: 3,
+                handler: *b"hint",
+                ..TrakSpec::video(b"rtp ")
+            }
+            .build(),
+        );
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let movie = parse(&data).unwrap();
+        assert_eq!(movie.timescale, 1000);
+        assert_eq!(movie.duration_ms, Some(10_000));
+        assert_eq!(movie.tracks.len(), 2);
+        assert_eq!(movie.tracks[0].track_id, 1);
+        assert_eq!(movie.tracks[0].codec, "avc1");
+        assert_eq!(movie.tracks[0].timescale, 90000);
+        assert_eq!(movie.tracks[0].duration_ms, Some(10_000));
+        assert_eq!(movie.tracks[1].stream_type, StreamType::Audio);
+        assert_eq!(movie.tracks[1].codec, "mp4a");
+    }
+
+    #[test]
+    fn test_parse_box_to_end_of_growing_file() {
+        let mut moov = mvhd(1000, 10_000);
+        moov.extend(TrakSpec::video(b"avc1").build());
+        moov.extend(TrakSpec::audio(b"mp4a").build());
+        let mut data = ftyp();
+        let moov_at = data.len();
+        data.extend(mp4_box(b"moov", &moov));
+        data[moov_at..moov_at + 4].copy_from_slice(&[0; 4]);
+
+        // The `moov` extends to the end of the file, which has not arrived.
+        let cut = data.len() - 10;
+        let mut source = MemorySource::new(data[..cut].to_vec());
+        let mut parser = Mp4Parser::new();
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        assert!(parser.movie().is_none());
+
+        source.append(&data[cut..]).unwrap();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        assert_eq!(parser.movie().unwrap().tracks.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_track_properties() {
+        let trak = |tkhd: Vec<u8>, mdhd: Vec<u8>, handler: &[u8; 4], entry: Vec<u8>| {
+            let mut stsd = 1u32.to_be_bytes().to_vec();
+            stsd.extend(entry);
+            let stbl = mp4_box(b"stbl", &full_box(b"stsd", 0, &stsd));
+            let mut mdia = mdhd;
+            mdia.extend(hdlr(handler));
+            mdia.extend(mp4_box(b"minf", &stbl));
+            let mut trak = tkhd;
+            trak.extend(mp4_box(b"mdia", &mdia));
+            trak.extend(mp4_box(b"udta", &mp4_box(b"name", b"Main\0")));
+            mp4_box(b"trak", &trak)
+        };
+
+        // Enabled, rotated by 90 degrees, displayed at 1920x1080.
+        let mut header = vec![0, 0, 0, 1];
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&1u32.to_be_bytes());
+        header.extend_from_slice(&[0; 24]);
+        for value in [0, 0x10000, 0, -0x10000, 0, 0, 0, 0, 0x4000_0000i32] {
+            header.extend_from_slice(&value.to_be_bytes());
+        }
+        header.extend_from_slice(&(1920u32 << 16).to_be_bytes());
+        header.extend_from_slice(&(1080u32 << 16).to_be_bytes());
+        let mut media_header = mdhd(90000, 0);
+        // Packed "eng".
+        media_header[28..30].copy_from_slice(&0x15C7u16.to_be_bytes());
+
+        let mut entry = vec![0; 24];
+        entry.extend_from_slice(&1440u16.to_be_bytes());
+        entry.extend_from_slice(&1080u16.to_be_bytes());
+        entry.extend_from_slice(&[0; 50]);
+        entry.extend(mp4_box(b"pasp", &[0, 0, 0, 4, 0, 0, 0, 3]));
+        entry.extend(mp4_box(b"colr", b"nclx\0\x09\0\x10\0\x09\x80"));
+        let mut btrt = vec![0; 8];
+        btrt.extend_from_slice(&5_000_000u32.to_be_bytes());
+        entry.extend(mp4_box(b"btrt", &btrt));
+        let mut moov = mvhd(1000, 0);
+        moov.extend(trak(
+            mp4_box(b"tkhd", &header),
+            media_header,
+            b"vide",
+            mp4_box(b"avc1", &entry),
+        ));
+
+        // 16-bit 5.1 PCM at 48 kHz.
+        let mut entry = vec![0; 16];
+        entry.extend_from_slice(&6u16.to_be_bytes());
+        entry.extend_from_slice(&16u16.to_be_bytes());
+        entry.extend_from_slice(&[0; 4]);
+        entry.extend_from_slice(&(48000u32 << 16).to_be_bytes());
+        moov.extend(trak(
+            tkhd(2),
+            mdhd(48000, 0),
+            b"soun",
+            mp4_box(b"ipcm", &entry),
+        ));
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+
+        let movie = parse(&data).unwrap();
+        let video_track = &movie.tracks[0];
+        assert!(video_track.enabled);
+        assert_eq!(video_track.language.as_deref(), Some("eng"));
+        assert_eq!(video_track.title.as_deref(), Some("Main"));
+        assert_eq!(video_track.bitrate, Some(5_000_000));
+        let video = video_track.video.as_ref().unwrap();
+        assert_eq!(
+            (video.width, video.height, video.rotation),
+            (1440, 1080, 90)
+        );
+        assert!((video.display_aspect_ratio.unwrap() - 16.0 / 9.0).abs() < 1e-9);
+        let color = video.color.unwrap();
+        assert_eq!(
+            (color.primaries, color.transfer, color.matrix),
+            (Some(9), Some(16), Some(9))
+        );
+        assert_eq!(color.full_range, Some(true));
+
+        let audio_track = &movie.tracks[1];
+        assert!(!audio_track.enabled);
+        assert!(audio_track.language.is_none());
+        let audio = audio_track.audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (48000, 6));
+        assert_eq!(audio.channel_layout.as_deref(), Some("5.1"));
+        assert_eq!(audio.bit_depth, Some(16));
+    }
+
+    #[test]
+    fn test_parse_pcm_chunks() {
+        // QuickTime version 1 sound description of 24-bit stereo, with a
+        // stale sample size and the little-endian flag in `wave`/`enda`.
+        let mut entry = vec![0; 8];
+        entry.extend_from_slice(&1u16.to_be_bytes());
+        entry.extend_from_slice(&[0; 6]);
+        entry.extend_from_slice(&2u16.to_be_bytes());
+        entry.extend_from_slice(&16u16.to_be_bytes());
+        entry.extend_from_slice(&[0; 4]);
+        entry.extend_from_slice(&(48000u32 << 16).to_be_bytes());
+        for value in [1u32, 3, 6, 3] {
+            entry.extend_from_slice(&value.to_be_bytes());
+        }
+        let mut wave = mp4_box(b"frma", b"in24");
+        wave.extend(mp4_box(b"enda", &[0, 1]));
+        entry.extend(mp4_box(b"wave", &wave));
+        let mut stsd = 1u32.to_be_bytes().to_vec();
+        stsd.extend(mp4_box(b"in24", &entry));
+
+        // 1500 samples of the constant size 1, in chunks of 1000 and 500.
+        let mut stbl = full_box(b"stsd", 0, &stsd);
+        let mut sizes = 1u32.to_be_bytes().to_vec();
+        sizes.extend_from_slice(&1500u32.to_be_bytes());
+        stbl.extend(full_box(b"stsz", 0, &sizes));
+        stbl.extend(stsc(&[(1, 1000), (2, 500)]));
+        stbl.extend(stco(&[5000, 9000]));
+        stbl.extend(stts(&[(1500, 1)]));
+        let mut mdia = mdhd(48000, 1500);
+        mdia.extend(hdlr(b"soun"));
+        mdia.extend(mp4_box(b"minf", &mp4_box(b"stbl", &stbl)));
+        let mut trak = tkhd(1);
+        trak.extend(mp4_box(b"mdia", &mdia));
+        let mut moov = mvhd(1000, 0);
+        moov.extend(mp4_box(b"trak", &trak));
+        let mut data = ftyp();
+        data.extend(mp4_box(b"moov", &moov));
+        data.resize(12000, 0);
+
+        let track = &parse(&data).unwrap().tracks[0];
+        assert_eq!(track.codec, "pcm-s24");
+        assert_eq!(
+            track
+                .samples
+                .iter()
+                .map(|s| (s.offset, s.size, s.pts, s.is_keyframe))
+                .collect::<Vec<_>>(),
+            vec![(5000, 6000, 0, true), (9000, 3000, 1000, true)]
+        );
+
+        assert_eq!(pcm_codec("twos", 16, 0, None), Some(AudioCodec::PcmS16be));
+        assert_eq!(pcm_codec("twos", 8, 0, None), None);
+        assert_eq!(
+            pcm_codec("lpcm", 32, LPCM_FLOAT, None),
+            Some(AudioCodec::PcmF32le)
+        );
+        assert_eq!(
+            pcm_codec("lpcm", 24, LPCM_BIG_ENDIAN | LPCM_SIGNED, None),
+            Some(AudioCodec::PcmS24be)
+        );
+        assert_eq!(
+            pcm_codec("ipcm", 32, 0, Some(true)),
+            Some(AudioCodec::PcmS32le)
+        );
+        assert_eq!(pcm_codec("mp4a", 16, 0, None), None);
+    }
+
+    #[test]
+    fn test_parse_encrypted_entry_uses_frma() {
+        let frma = mp4_box(b"frma", b"hvc1");
+        let sinf = mp4_box(b"sinf", &frma);
+        let mut entry_payload = vec![0; 78];
+        entry_payload.extend_from_slice(&sinf);
+        let mut stsd_payload = 1u32.to_be_bytes().to_vec();
+        stsd_payload.extend(mp4_box(b"encv", &entry_payload));
+        let stsd_box = full_box(b"stsd", 0, &stsd_payload);
+
+        let b = boxes(&stsd_box, 0).next().unwrap();
+        let codec = parse_stsd_codec(&b, StreamType::Video).unwrap();
+        assert_eq!(codec.as_deref(), Some("hvc1"));
+    }
+
+    #[test]
+    fn test_parse_without_moov() {
+        assert!(parse(&ftyp()).is_none());
+    }
+
+    #[test]
+    fn test_sample_table_chunk_layout() {
+        let mut stbl = stsz(&[10, 20, 30, 40, 50]);
+        stbl.extend(stco(&[1000, 2000, 3000]));
+        // Chunks 1-2 hold two samples each, chunk 3 holds one.
+        stbl.extend(stsc(&[(1, 2), (3, 1)]));
+        stbl.extend(stts(&[(5, 512)]));
+        let samples = samples_of(&stbl);
+
+        let offsets: Vec<u64> = samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![1000, 1010, 2000, 2030, 3000]);
+        let dts: Vec<i64> = samples.iter().map(|s| s.dts).collect();
+        assert_eq!(dts, vec![0, 512, 1024, 1536, 2048]);
+        assert!(samples.iter().all(|s| s.is_keyframe));
+    }
+
+    #[test]
+    fn test_sample_table_composition_and_sync() {
+        let mut stbl = stsz(&[1, 1, 1, 1]);
+        stbl.extend(stco(&[0]));
+        stbl.extend(stsc(&[(1, 4)]));
+        stbl.extend(stts(&[(4, 100)]));
+        stbl.extend(ctts(&[(1, 200), (2, 0), (1, 100)]));
+        stbl.extend(stss(&[1, 4]));
+        let samples = samples_of(&stbl);
+
+        let pts: Vec<i64> = samples.iter().map(|s| s.pts).collect();
+        assert_eq!(pts, vec![200, 100, 200, 400]);
+        let keys: Vec<bool> = samples.iter().map(|s| s.is_keyframe).collect();
+        assert_eq!(keys, vec![true, false, false, true]);
+    }
+
+    #[test]
+    fn test_sample_table_negative_composition_offsets() {
+        let mut stbl = stsz(&[1, 1, 1]);
+        stbl.extend(stco(&[0]));
+        stbl.extend(stsc(&[(1, 3)]));
+        stbl.extend(stts(&[(3, 100)]));
+        stbl.extend(table_box(b"ctts", 1, &[&[1, 100], &[2, -100i32 as u32]]));
+        let samples = samples_of(&stbl);
+
+        let pts: Vec<i64> = samples.iter().map(|s| s.pts).collect();
+        assert_eq!(pts, vec![100, 0, 100]);
+    }
+
+    #[test]
+    fn test_edit_list() {
+        // 500 ms empty edit, then media from 1024 at normal rate.
+        let mut p = 2u32.to_be_bytes().to_vec();
+        for (duration, media_time) in [(500u32, -1i32), (2000, 1024)] {
+            p.extend_from_slice(&duration.to_be_bytes());
+            p.extend_from_slice(&media_time.to_be_bytes());
+            p.extend_from_slice(&[0, 1, 0, 0]);
+        }
+        let edts = mp4_box(b"edts", &full_box(b"elst", 0, &p));
+        let edits = EditList::parse(&boxes(&edts, 0).next().unwrap(), 1000, 48000).unwrap();
+
+        let shifted = |pts: i64| {
+            let mut sample = SampleEntry {
+                stream_index: 0,
+                offset: 0,
+                size: 0,
+                continuation: None,
+                dts: pts,
+                pts,
+                is_keyframe: true,
+            };
+            edits.apply(&mut sample);
+            sample.pts
+        };
+        assert_eq!(shifted(1024), 24000);
+        assert_eq!(shifted(2048), 25024);
+        // Priming samples before the edit land before the movie start.
+        assert_eq!(shifted(0), 22976);
+
+        // Half-rate edit without a delay stretches media time.
+        let mut p = 1u32.to_be_bytes().to_vec();
+        p.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0]);
+        let edts = mp4_box(b"edts", &full_box(b"elst", 0, &p));
+        let edits = EditList::parse(&boxes(&edts, 0).next().unwrap(), 1000, 48000).unwrap();
+        let mut sample = SampleEntry {
+            stream_index: 0,
+            offset: 0,
+            size: 0,
+            continuation: None,
+            dts: 100,
+            pts: 100,
+            is_keyframe: true,
+        };
+        edits.apply(&mut sample);
+        assert_eq!((sample.dts, sample.pts), (200, 200));
+    }
+
+    #[test]
+    fn test_sample_table_co64_and_compact_sizes() {
+        let mut p = vec![0, 0, 0, 8];
+        p.extend_from_slice(&3u32.to_be_bytes());
+        p.extend_from_slice(&[5, 6, 7]);
+        let mut stbl = full_box(b"stz2", 0, &p);
+        let mut co64 = 1u32.to_be_bytes().to_vec();
+        co64.extend_from_slice(&0x1_0000_0000u64.to_be_bytes());
+        stbl.extend(full_box(b"co64", 0, &co64));
+        stbl.extend(stsc(&[(1, 3)]));
+        let samples = samples_of(&stbl);
+
+        let offsets: Vec<u64> = samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![0x1_0000_0000, 0x1_0000_0005, 0x1_0000_000B]);
+    }
+
+    #[test]
+    fn test_sample_table_constant_size_and_offset_overflow() {
+        // A shared size with a count no chunk covers is not expanded up front.
+        let mut p = 4u32.to_be_bytes().to_vec();
+        p.extend_from_slice(&u32::MAX.to_be_bytes());
+        let mut stbl = full_box(b"stsz", 0, &p);
+        stbl.extend(stco(&[0]));
+        stbl.extend(stsc(&[(1, 2)]));
+        let b = mp4_box(b"stbl", &stbl);
+        let b = boxes(&b, 0).next().unwrap();
+        let table = SampleTable::parse(&b).unwrap();
+        assert!(table.sizes.is_empty());
+        assert!(table.build_samples(0, u64::MAX).is_err());
+
+        let mut stbl = stsz(&[5, 5]);
+        let mut co64 = 1u32.to_be_bytes().to_vec();
+        co64.extend_from_slice(&(u64::MAX - 2).to_be_bytes());
+        stbl.extend(full_box(b"co64", 0, &co64));
+        stbl.extend(stsc(&[(1, 2)]));
+        let b = mp4_box(b"stbl", &stbl);
+        let b = boxes(&b, 0).next().unwrap();
+        assert!(SampleTable::parse(&b)
+            .unwrap()
+            .build_samples(0, u64::MAX)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sample_table_bounded_by_data_end() {
+        // A shared size with huge counts stops at the end of the file.
+        let mut p = 4u32.to_be_bytes().to_vec();
+        p.extend_from_slice(&u32::MAX.to_be_bytes());
+        let mut stbl = full_box(b"stsz", 0, &p);
+        stbl.extend(stco(&[0]));
+        stbl.extend(stsc(&[(1, u32::MAX)]));
+        stbl.extend(stts(&[(u32::MAX, 1)]));
+        let b = mp4_box(b"stbl", &stbl);
+        let b = boxes(&b, 0).next().unwrap();
+        let table = SampleTable::parse(&b).unwrap();
+        let samples = table.build_samples(0, 523).unwrap();
+        assert_eq!(samples.len(), 130);
+        assert_eq!(samples.last().map(|s| (s.offset, s.dts)), Some((516, 129)));
+
+        // PCM chunks are cut at the end of the file, and split when their
+        // size does not fit an entry.
+        let chunks = table.build_chunks(0, 4, 523).unwrap();
+        let sizes: Vec<_> = chunks.iter().map(|s| (s.offset, s.size)).collect();
+        assert_eq!(sizes, vec![(0, 520)]);
+        let chunks = table.build_chunks(0, 4, u64::MAX).unwrap();
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(
+            (chunks[1].offset, chunks[1].size),
+            (0xFFFF_FFFC, 0xFFFF_FFFC)
+        );
+        assert_eq!(chunks[1].dts, 0x3FFF_FFFF);
+        assert_eq!(chunks[4].size, 12);
+    }
+
+    #[test]
+    fn test_sample_table_missing_chunks() {
+        let mut stbl = stsz(&[1, 1, 1]);
+        stbl.extend(stco(&[0]));
+        stbl.extend(stsc(&[(1, 2)]));
+        let b = mp4_box(b"stbl", &stbl);
+        let b = boxes(&b, 0).next().unwrap();
+        let result = SampleTable::parse(&b).unwrap().build_samples(0, u64::MAX);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! MP4 metadata parsing.
+//!
+//! Reads iTunes-style `ilst` items from `udta`/`meta` (including `covr`
+//! cover art) and falls back to the QuickTime `©xxx` user data strings.
+//! Chapters come from Nero `chpl` lists or QuickTime chapter text tracks.
+
+use super::metadata::{image_mime_type, Attachment, Chapter, ChapterTitle, Metadata};
+use super::mp4::Mp4Box;
+use super::reader::ByteReader;
+use crate::error::Result;
+
+/// `ilst` data type: UTF-8 text.
+const DATA_TYPE_UTF8: u32 = 1;
+/// `ilst` data type: JPEG image.
+const DATA_TYPE_JPEG: u32 = 13;
+/// `ilst` data type: PNG image.
+const DATA_TYPE_PNG: u32 = 14;
+/// `ilst` data type: BMP image.
+const DATA_TYPE_BMP: u32 = 27;
+
+/// Collects tags and cover art from the `udta` and `meta` boxes of `moov`.
+pub(crate) fn parse_moov_metadata(moov: &Mp4Box<'_>) -> Metadata {
+    let mut metadata = Metadata::default();
+    for b in moov.children() {
+        match &b.kind {
+            b"udta" => parse_udta(&b, &mut metadata),
+            b"meta" => parse_meta(&b, &mut metadata),
+            _ => {}
+        }
+    }
+    metadata
+}
+
+/// Parses a `udta` box: `meta` item lists first, then QuickTime strings.
+fn parse_udta(udta: &Mp4Box<'_>, metadata: &mut Metadata) {
+    if let Some(meta) = udta.child(b"meta") {
+        parse_meta(&meta, metadata);
+    }
+    for b in udta.children().filter(|b| b.kind[0] == 0xA9) {
+        // Counted string: 16-bit length, 16-bit language, then the text.
+        if b.payload.len() < 4 {
+            continue;
+        }
+        let len = u16::from_be_bytes([b.payload[0], b.payload[1]]) as usize;
+        let text = &b.payload[4..(4 + len).min(b.payload.len())];
+        if let Some(field) = text_field(metadata, &b.kind) {
+            field.get_or_insert_with(|| text_value(text));
+        }
+    }
+}
+
+/// Parses a `meta` box holding an `ilst` item list.
+fn parse_meta(meta: &Mp4Box<'_>, metadata: &mut Metadata) {
+    // ISO `meta` is a full box; QuickTime's starts directly with `hdlr`.
+    let skip = if meta.payload.get(4..8) == Some(b"hdlr") {
+        0
+    } else {
+        4
+    };
+    let ilst = match meta.children_after(skip).find(|b| &b.kind == b"ilst") {
+        Some(ilst) => ilst,
+        None => return,
+    };
+    for item in ilst.children() {
+        for data in item.children().filter(|b| &b.kind == b"data") {
+            if data.payload.len() < 8 {
+                continue;
+            }
+            let data_type =
+                u32::from_be_bytes([0, data.payload[1], data.payload[2], data.payload[3]]);
+            let value = &data.payload[8..];
+            if &item.kind == b"covr" {
+                let mime_type = match data_type {
+                    DATA_TYPE_JPEG => Some("image/jpeg"),
+                    DATA_TYPE_PNG => Some("image/png"),
+                    DATA_TYPE_BMP => Some("image/bmp"),
+                    _ => image_mime_type(value),
+                };
+                metadata.attachments.push(Attachment {
+                    name: None,
+                    mime_type: mime_type.unwrap_or("application/octet-stream").to_string(),
+                    description: None,
+                    is_cover: true,
+                    size: value.len() as u64,
+                    offset: data.payload_offset + 8,
+                });
+            } else if data_type == DATA_TYPE_UTF8 {
+                if let Some(field) = text_field(metadata, &item.kind) {
+                    field.get_or_insert_with(|| text_value(value));
+                }
+            }
+        }
+    }
+}
+
+/// Returns the metadata field an `ilst` item or QuickTime string maps to.
+fn text_field<'a>(metadata: &'a mut Metadata, kind: &[u8; 4]) -> Option<&'a mut Option<String>> {
+    match kind {
+        b"\xA9nam" => Some(&mut metadata.title),
+        b"\xA9ART" | b"aART" => Some(&mut metadata.artist),
+        b"\xA9alb" => Some(&mut metadata.album),
+        b"\xA9day" => Some(&mut metadata.date),
+        b"\xA9too" | b"\xA9enc" | b"\xA9swr" => Some(&mut metadata.encoder),
+        b"\xA9cmt" | b"desc" => Some(&mut metadata.comment),
+        _ => None,
+    }
+}
+
+/// Reads the chapters of a Nero `chpl` box in `moov/udta`.
+pub(crate) fn parse_nero_chapters(moov: &Mp4Box<'_>) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    if let Some(chpl) = moov.child(b"udta").and_then(|udta| udta.child(b"chpl")) {
+        // A truncated list keeps the chapters read so far.
+        let _ = read_chpl(chpl.payload, &mut chapters);
+    }
+    chapters
+}
+
+fn read_chpl(payload: &[u8], chapters: &mut Vec<Chapter>) -> Result<()> {
+    let mut r = ByteReader::new(payload);
+    let version = r.u8()?;
+    r.skip(3)?;
+    if version > 0 {
+        r.skip(4)?;
+    }
+    let count = r.u8()?;
+    for _ in 0..count {
+        // Start times are in 100 ns units.
+        let start = r.u64()?;
+        let len = r.u8()? as usize;
+        chapters.push(titled_chapter(start / 10_000, text_value(r.bytes(len)?)));
+    }
+    Ok(())
+}
+
+/// Decodes the title held by a sample of a QuickTime chapter text track.
+pub(crate) fn chapter_text(sample: &[u8]) -> String {
+    // 16-bit length, then UTF-8 or BOM-prefixed UTF-16 text; style boxes may follow.
+    let (len, text) = match sample {
+        [a, b, text @ ..] => (u16::from_be_bytes([*a, *b]) as usize, text),
+        _ => return String::new(),
+    };
+    let text = &text[..len.min(text.len())];
+    match text.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => text_value(text),
+    }
+}
+
+/// Builds a chapter with a single title of unknown language.
+pub(crate) fn titled_chapter(start_ms: u64, title: String) -> Chapter {
+    Chapter {
+        start_ms,
+        title: Some(title.clone()),
+        titles: vec![ChapterTitle {
+            title,
+            language: None,
+        }],
+        ..Chapter::default()
+    }
+}
+
+/// Decodes a text value, trimming trailing NUL padding.
+fn text_value(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::mp4::boxes;
+    use super::super::mp4::tests::{full_box, mp4_box};
+    use super::*;
+
+    /// Builds an `ilst` item with a single `data` box.
+    pub(crate) fn ilst_item(kind: &[u8; 4], data_type: u32, value: &[u8]) -> Vec<u8> {
+        let mut data = data_type.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(value);
+        mp4_box(kind, &mp4_box(b"data", &data))
+    }
+
+    /// Builds a `udta` box with an ISO `meta` holding the given items.
+    pub(crate) fn udta(items: &[u8]) -> Vec<u8> {
+        let mut meta = mp4_box(b"hdlr", &[0; 25]);
+        meta.extend(mp4_box(b"ilst", items));
+        mp4_box(b"udta", &full_box(b"meta", 0, &meta))
+    }
+
+    fn parse(moov_children: &[u8]) -> Metadata {
+        let moov = mp4_box(b"moov", moov_children);
+        parse_moov_metadata(&boxes(&moov, 0).next().unwrap())
+    }
+
+    #[test]
+    fn test_parse_ilst_tags_and_cover() {
+        let mut items = ilst_item(b"\xA9nam", DATA_TYPE_UTF8, b"Lecture 1");
+        items.extend(ilst_item(b"\xA9ART", DATA_TYPE_UTF8, b"Speaker"));
+        items.extend(ilst_item(b"\xA9too", DATA_TYPE_UTF8, b"Lavf60.3.100"));
+        items.extend(ilst_item(b"covr", 0, &[0xFF, 0xD8, 0xFF, 0xE0]));
+        let moov = mp4_box(b"moov", &udta(&items));
+        let metadata = parse_moov_metadata(&boxes(&moov, 0).next().unwrap());
+
+        assert_eq!(metadata.title.as_deref(), Some("Lecture 1"));
+        assert_eq!(metadata.artist.as_deref(), Some("Speaker"));
+        assert_eq!(metadata.encoder.as_deref(), Some("Lavf60.3.100"));
+        assert!(metadata.album.is_none());
+
+        let cover = metadata.cover().unwrap();
+        assert_eq!(cover.mime_type, "image/jpeg");
+        assert_eq!(cover.size, 4);
+        assert_eq!(
+            &moov[cover.offset as usize..][..4],
+            &[0xFF, 0xD8, 0xFF, 0xE0]
+        );
+    }
+
+    #[test]
+    fn test_parse_quicktime_strings() {
+        let string = |kind: &[u8; 4], text: &str| {
+            let mut p = (text.len() as u16).to_be_bytes().to_vec();
+            p.extend_from_slice(&[0x55, 0xC4]);
+            p.extend_from_slice(text.as_bytes());
+            mp4_box(kind, &p)
+        };
+        let mut udta = string(b"\xA9nam", "Intro");
+        udta.extend(string(b"\xA9alb", "Course"));
+        // QuickTime `meta` has no version/flags; its item list takes precedence.
+        let mut meta = mp4_box(b"hdlr", &[0; 25]);
+        meta.extend(mp4_box(
+            b"ilst",
+            &ilst_item(b"\xA9nam", DATA_TYPE_UTF8, b"Lecture"),
+        ));
+        udta.extend(mp4_box(b"meta", &meta));
+        let metadata = parse(&mp4_box(b"udta", &udta));
+
+        assert_eq!(metadata.title.as_deref(), Some("Lecture"));
+        assert_eq!(metadata.album.as_deref(), Some("Course"));
+    }
+
+    #[test]
+    fn test_parse_nero_chapters() {
+        let mut chpl = vec![0; 4];
+        chpl.push(2);
+        for (start, title) in [(0u64, "Intro"), (600_000_000, "Part 1")] {
+            chpl.extend_from_slice(&start.to_be_bytes());
+            chpl.push(title.len() as u8);
+            chpl.extend_from_slice(title.as_bytes());
+        }
+        // Truncated third entry.
+        chpl.extend_from_slice(&[0; 4]);
+        let moov = mp4_box(b"moov", &mp4_box(b"udta", &full_box(b"chpl", 1, &chpl)));
+        let chapters = parse_nero_chapters(&boxes(&moov, 0).next().unwrap());
+
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[1].start_ms, 60_000);
+        assert_eq!(chapters[1].title.as_deref(), Some("Part 1"));
+        assert!(chapters[1].titles[0].language.is_none());
+    }
+
+    #[test]
+    fn test_chapter_text() {
+        assert_eq!(chapter_text(b"\0\x05Intro\0\0\0\x0cencd"), "Intro");
+        assert_eq!(chapter_text(b"\0\x06\xFE\xFF\0H\0i"), "Hi");
+        assert_eq!(chapter_text(b"\0"), "");
+    }
+}
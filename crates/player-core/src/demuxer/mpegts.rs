@@ -0,0 +1,666 @@
+//! MPEG-2 Transport Stream parsing.
+//!
+//! Reads the PAT and the PMT of the first program to discover elementary
+//! streams, then reassembles PES packets into samples. PES payloads are
+//! referenced in place as byte ranges across the 188-byte TS packets, and
+//! 33-bit PTS/DTS values are unwrapped into a continuous timeline.
+
+use super::source::SourceReader;
+use super::{AudioInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType};
+use crate::decoder::aac::AdtsHeader;
+use crate::error::Result;
+
+/// Sync byte starting every TS packet.
+const SYNC_BYTE: u8 = 0x47;
+/// Size of a TS packet.
+const TS_PACKET_SIZE: usize = 188;
+/// PID carrying the Program Association Table.
+const PAT_PID: u16 = 0x0000;
+/// PTS/DTS clock rate.
+const TIMESCALE: u32 = 90_000;
+/// Period of the 33-bit PTS/DTS counters.
+const TIMESTAMP_WRAP: i64 = 1 << 33;
+/// Bytes of PES payload inspected when looking for an IDR/IRAP NAL unit.
+const KEYFRAME_SCAN_LIMIT: usize = 2048;
+
+/// Detects a transport stream and returns its packet stride.
+///
+/// Plain TS uses 188-byte packets; M2TS (Blu-ray/AVCHD) prefixes each packet
+/// with a 4-byte timestamp for a 192-byte stride.
+pub(crate) fn probe(data: &[u8]) -> Option<usize> {
+    [(TS_PACKET_SIZE, 0), (192, 4)]
+        .into_iter()
+        .find(|&(stride, lead)| {
+            let packets = (data.len().saturating_sub(lead) / stride).min(3);
+            packets >= 2 && (0..packets).all(|i| data[lead + i * stride] == SYNC_BYTE)
+        })
+        .map(|(stride, _)| stride)
+}
+
+/// Elementary stream declared in the PMT.
+#[derive(Debug, Clone)]
+pub(crate) struct TsStream {
+    /// PID carrying the stream.
+    pub pid: u16,
+    /// Stream type derived from the PMT `stream_type`.
+    pub stream_type: StreamType,
+    /// Codec name, normalized to the MP4 sample entry code where one exists.
+    pub codec: String,
+    /// Language from an ISO 639 or DVB subtitling descriptor.
+    pub language: Option<String>,
+}
+
+/// Returns the language code of the first ISO 639 language (0x0A) or DVB
+/// subtitling (0x59) descriptor.
+fn descriptor_language(descriptors: &[u8]) -> Option<String> {
+    let mut pos = 0;
+    while pos + 2 <= descriptors.len() {
+        let tag = descriptors[pos];
+        let len = descriptors[pos + 1] as usize;
+        let body = descriptors.get(pos + 2..pos + 2 + len).unwrap_or(&[]);
+        if matches!(tag, 0x0A | 0x59) && body.len() >= 3 {
+            let code = String::from_utf8_lossy(&body[..3]).into_owned();
+            return (code != "und").then_some(code);
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+/// Maps a PMT `stream_type` and its descriptors to a stream description.
+fn classify(stream_type: u8, descriptors: &[u8]) -> Option<(StreamType, &'static str)> {
+    let classified = match stream_type {
+        0x01 | 0x02 => (StreamType::Video, "mp2v"),
+        0x1B => (StreamType::Video, "avc1"),
+        0x24 => (StreamType::Video, "hvc1"),
+        0x03 | 0x04 => (StreamType::Audio, "mp3"),
+        0x0F | 0x11 => (StreamType::Audio, "mp4a"),
+        0x81 => (StreamType::Audio, "ac-3"),
+        0x87 => (StreamType::Audio, "ec-3"),
+        0x90 => (StreamType::Subtitle, "pgs"),
+        0x06 => {
+            let mut pos = 0;
+            while pos + 2 <= descriptors.len() {
+                let tag = descriptors[pos];
+                let len = descriptors[pos + 1] as usize;
+                let body = descriptors.get(pos + 2..pos + 2 + len).unwrap_or(&[]);
+                match tag {
+                    0x05 if body.starts_with(b"Opus") => return Some((StreamType::Audio, "Opus")),
+                    0x05 if body.starts_with(b"AC-3") => return Some((StreamType::Audio, "ac-3")),
+                    0x6A => return Some((StreamType::Audio, "ac-3")),
+                    0x7A => return Some((StreamType::Audio, "ec-3")),
+                    0x59 => return Some((StreamType::Subtitle, "dvbsub")),
+                    _ => {}
+                }
+                pos += 2 + len;
+            }
+            return None;
+        }
+        _ => return None,
+    };
+    Some(classified)
+}
+
+/// A PES packet being reassembled.
+#[derive(Debug, Default)]
+struct PendingPes {
+    ranges: Vec<(u64, u32)>,
+    random_access: bool,
+}
+
+/// Per-stream reassembly and timestamp state.
+#[derive(Debug)]
+struct StreamState {
+    stream_index: u32,
+    is_h26x: bool,
+    hevc: bool,
+    /// Whether the stream carries ADTS-framed AAC.
+    is_adts: bool,
+    /// Header of the first ADTS frame, describing the stream.
+    adts: Option<AdtsHeader>,
+    pending: Option<PendingPes>,
+    last_pts: Option<i64>,
+    last_dts: Option<i64>,
+}
+
+/// Undoes 33-bit wraparound relative to the previous timestamp of a stream.
+fn unwrap_timestamp(raw: u64, previous: Option<i64>) -> i64 {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return raw as i64,
+    };
+    let mut value = previous - previous.rem_euclid(TIMESTAMP_WRAP) + raw as i64;
+    if value - previous > TIMESTAMP_WRAP / 2 {
+        value -= TIMESTAMP_WRAP;
+    } else if previous - value > TIMESTAMP_WRAP / 2 {
+        value += TIMESTAMP_WRAP;
+    }
+    value
+}
+
+/// Decodes a 5-byte PES timestamp field.
+fn read_timestamp(b: &[u8]) -> u64 {
+    (((b[0] >> 1) & 0x07) as u64) << 30
+        | (b[1] as u64) << 22
+        | ((b[2] >> 1) as u64) << 15
+        | (b[3] as u64) << 7
+        | (b[4] >> 1) as u64
+}
+
+/// Returns whether an Annex B payload contains an IDR (H.264) or IRAP (HEVC) NAL unit.
+fn has_random_access_nal(data: &[u8], hevc: bool) -> bool {
+    data.windows(4).any(|w| {
+        if w[0] != 0 || w[1] != 0 || w[2] != 1 {
+            return false;
+        }
+        if hevc {
+            (16..=21).contains(&((w[3] >> 1) & 0x3F))
+        } else {
+            w[3] & 0x1F == 5
+        }
+    })
+}
+
+/// Incremental transport stream parser.
+#[derive(Debug, Default)]
+pub(crate) struct TsParser {
+    /// Packet stride, once probed.
+    stride: Option<usize>,
+    /// Offset of the next packet, including any M2TS prefix.
+    pos: u64,
+    pmt_pid: Option<u16>,
+    /// Elementary streams of the first program.
+    streams: Vec<TsStream>,
+    states: Vec<StreamState>,
+    /// First timestamp in the file; each stream's first timestamp is unwrapped
+    /// against it so streams straddling a wrap share one timeline.
+    anchor: Option<i64>,
+    /// Unwrapped timestamp rebased to zero, fixed when samples are first
+    /// handed over.
+    start: Option<i64>,
+    /// Latest rebased presentation timestamp handed over.
+    end: i64,
+    /// Completed PES packets not yet handed over, on the unwrapped 90 kHz clock.
+    samples: Vec<SampleEntry>,
+}
+
+impl TsParser {
+    /// Creates a parser positioned at the start of the stream.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles the payload of one TS packet.
+    fn parse_packet(
+        &mut self,
+        reader: &mut SourceReader<'_>,
+        packet: &[u8],
+        packet_offset: u64,
+    ) -> Result<()> {
+        if packet[0] != SYNC_BYTE || packet[1] & 0x80 != 0 {
+            // Lost sync or transport error: skip the packet.
+            return Ok(());
+        }
+        let unit_start = packet[1] & 0x40 != 0;
+        let pid = u16::from_be_bytes([packet[1] & 0x1F, packet[2]]);
+        let scrambled = packet[3] & 0xC0 != 0;
+        let adaptation = packet[3] & 0x20 != 0;
+        let has_payload = packet[3] & 0x10 != 0;
+
+        let mut payload_start = 4;
+        let mut random_access = false;
+        if adaptation {
+            let len = packet[4] as usize;
+            random_access = len > 0 && packet[5] & 0x40 != 0;
+            payload_start = 5 + len;
+        }
+        if !has_payload || scrambled || payload_start >= TS_PACKET_SIZE {
+            return Ok(());
+        }
+        let payload = &packet[payload_start..];
+
+        if pid == PAT_PID {
+            if unit_start {
+                self.pmt_pid = parse_pat(payload).or(self.pmt_pid);
+            }
+            return Ok(());
+        }
+        if Some(pid) == self.pmt_pid {
+            if unit_start && self.streams.is_empty() {
+                for stream in parse_pmt(payload) {
+                    self.states.push(StreamState {
+                        stream_index: self.streams.len() as u32,
+                        is_h26x: matches!(stream.codec.as_str(), "avc1" | "hvc1"),
+                        hevc: stream.codec == "hvc1",
+                        is_adts: stream.codec == "mp4a",
+                        adts: None,
+                        pending: None,
+                        last_pts: None,
+                        last_dts: None,
+                    });
+                    self.streams.push(stream);
+                }
+            }
+            return Ok(());
+        }
+
+        let index = match self.streams.iter().position(|s| s.pid == pid) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        let state = &mut self.states[index];
+        if unit_start {
+            if let Some(pes) = state.pending.take() {
+                if let Some(sample) = finish_pes(reader, pes, state, &mut self.anchor)? {
+                    self.samples.push(sample);
+                }
+            }
+            state.pending = Some(PendingPes::default());
+        }
+        if let Some(pes) = state.pending.as_mut() {
+            pes.random_access |= random_access;
+            pes.ranges
+                .push((packet_offset + payload_start as u64, payload.len() as u32));
+        }
+        Ok(())
+    }
+}
+
+impl ContainerParser for TsParser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        let stride = match self.stride {
+            Some(stride) => stride,
+            None => match probe(reader.bytes(0, 3 * 192)?) {
+                Some(stride) => *self.stride.insert(stride),
+                None => return Ok(()),
+            },
+        };
+        let lead = (stride - TS_PACKET_SIZE) as u64;
+
+        let mut packet = [0u8; TS_PACKET_SIZE];
+        loop {
+            let packet_offset = self.pos + lead;
+            match reader.exact(packet_offset, TS_PACKET_SIZE)? {
+                Some(bytes) => packet.copy_from_slice(bytes),
+                None => break,
+            }
+            self.pos += stride as u64;
+            self.parse_packet(reader, &packet, packet_offset)?;
+        }
+
+        // The last PES packet of each stream ends with the stream.
+        if !reader.is_complete() {
+            return Ok(());
+        }
+        for state in self.states.iter_mut() {
+            if let Some(pes) = state.pending.take() {
+                if let Some(sample) = finish_pes(reader, pes, state, &mut self.anchor)? {
+                    self.samples.push(sample);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        // Rebase the timeline so the earliest timestamp of the first
+        // samples is zero; samples already handed over keep their times.
+        if self.start.is_none() {
+            self.start = self.samples.iter().map(|s| s.dts.min(s.pts)).min();
+        }
+        let start = self.start.unwrap_or(0);
+        let mut samples = std::mem::take(&mut self.samples);
+        for sample in samples.iter_mut() {
+            sample.pts -= start;
+            sample.dts -= start;
+            self.end = self.end.max(sample.pts);
+        }
+        samples.sort_by_key(|s| s.offset);
+        let duration_ms = self.start.map(|_| super::to_ms(self.end as u64, TIMESCALE));
+
+        Container {
+            streams: self
+                .streams
+                .iter()
+                .enumerate()
+                .zip(&self.states)
+                .map(|((index, stream), state)| StreamInfo {
+                    duration_ms,
+                    language: stream.language.clone(),
+                    codec_string: state
+                        .adts
+                        .map(|header| header.audio_specific_config().codec_string()),
+                    audio: state.adts.map(|header| {
+                        let config = header.audio_specific_config();
+                        AudioInfo::new(config.sample_rate, config.channels)
+                    }),
+                    ..StreamInfo::new(
+                        index as u32,
+                        stream.stream_type,
+                        stream.codec.clone(),
+                        TIMESCALE,
+                    )
+                })
+                .collect(),
+            duration_ms,
+            samples,
+            ..Container::default()
+        }
+    }
+}
+
+/// Returns the PMT PID of the first program in a PAT section.
+fn parse_pat(payload: &[u8]) -> Option<u16> {
+    let section = psi_section(payload, 0x00)?;
+    section
+        .chunks_exact(4)
+        .find(|entry| u16::from_be_bytes([entry[0], entry[1]]) != 0)
+        .map(|entry| u16::from_be_bytes([entry[2] & 0x1F, entry[3]]))
+}
+
+/// Returns the elementary streams listed in a PMT section.
+fn parse_pmt(payload: &[u8]) -> Vec<TsStream> {
+    let section = match psi_section(payload, 0x02) {
+        Some(section) if section.len() >= 4 => section,
+        _ => return Vec::new(),
+    };
+    let program_info_len = (u16::from_be_bytes([section[2], section[3]]) & 0x0FFF) as usize;
+    let mut pos = 4 + program_info_len;
+    let mut streams = Vec::new();
+    while pos + 5 <= section.len() {
+        let stream_type = section[pos];
+        let pid = u16::from_be_bytes([section[pos + 1] & 0x1F, section[pos + 2]]);
+        let info_len = (u16::from_be_bytes([section[pos + 3], section[pos + 4]]) & 0x0FFF) as usize;
+        let descriptors = section.get(pos + 5..pos + 5 + info_len).unwrap_or(&[]);
+        if let Some((kind, codec)) = classify(stream_type, descriptors) {
+            streams.push(TsStream {
+                pid,
+                stream_type: kind,
+                codec: codec.to_string(),
+                language: descriptor_language(descriptors),
+            });
+        }
+        pos += 5 + info_len;
+    }
+    streams
+}
+
+/// Extracts the table body of a PSI section (after the 5-byte extended
+/// header, excluding the CRC), if it carries the expected `table_id` and
+/// fits in this packet.
+fn psi_section(payload: &[u8], table_id: u8) -> Option<&[u8]> {
+    let pointer = *payload.first()? as usize;
+    let section = payload.get(1 + pointer..)?;
+    if section.len() < 3 || section[0] != table_id {
+        return None;
+    }
+    let length = (u16::from_be_bytes([section[1], section[2]]) & 0x0FFF) as usize;
+    // Skip table_id_extension, version and section numbers; drop the CRC32.
+    section.get(8..3 + length.checked_sub(4)?)
+}
+
+/// Parses the PES header of a reassembled packet and converts it to a sample.
+fn finish_pes(
+    reader: &mut SourceReader<'_>,
+    pes: PendingPes,
+    state: &mut StreamState,
+    anchor: &mut Option<i64>,
+) -> Result<Option<SampleEntry>> {
+    let header = reader.gather(&pes.ranges, 19)?;
+    if header.len() < 9 || header[..3] != [0, 0, 1] {
+        return Ok(None);
+    }
+    let flags = header[7];
+    let header_len = 9 + header[8] as usize;
+
+    let pts_raw = (flags & 0x80 != 0 && header.len() >= 14).then(|| read_timestamp(&header[9..14]));
+    let dts_raw =
+        (flags & 0x40 != 0 && header.len() >= 19).then(|| read_timestamp(&header[14..19]));
+    let pts = match pts_raw {
+        Some(raw) => unwrap_timestamp(raw, state.last_pts.or(*anchor)),
+        None => match state.last_pts {
+            Some(pts) => pts,
+            None => return Ok(None),
+        },
+    };
+    let dts = match dts_raw {
+        Some(raw) => unwrap_timestamp(raw, Some(pts)),
+        None => pts,
+    };
+    anchor.get_or_insert(dts);
+    state.last_pts = Some(pts);
+    state.last_dts = Some(dts);
+
+    // Drop the PES header from the front of the ranges.
+    let mut skip = header_len as u64;
+    let mut ranges: Vec<(u64, u32)> = Vec::with_capacity(pes.ranges.len());
+    for (offset, size) in pes.ranges {
+        if skip >= size as u64 {
+            skip -= size as u64;
+            continue;
+        }
+        ranges.push((offset + skip, size - skip as u32));
+        skip = 0;
+    }
+    let (&(offset, size), rest) = match ranges.split_first() {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+
+    if state.is_adts && state.adts.is_none() {
+        state.adts = AdtsHeader::parse(&reader.gather(&ranges, 9)?).ok();
+    }
+
+    let is_keyframe = if state.is_h26x {
+        pes.random_access
+            || has_random_access_nal(&reader.gather(&ranges, KEYFRAME_SCAN_LIMIT)?, state.hevc)
+    } else {
+        true
+    };
+
+    Ok(Some(SampleEntry {
+        stream_index: state.stream_index,
+        offset,
+        size,
+        continuation: (!rest.is_empty()).then(|| rest.to_vec().into_boxed_slice()),
+        dts,
+        pts,
+        is_keyframe,
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::demuxer::MemorySource;
+
+    /// Parses a complete in-memory transport stream.
+    fn parse(data: &[u8]) -> Container {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = TsParser::new();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        parser.container()
+    }
+
+    /// Builds one TS packet, padding the payload with an adaptation field.
+    pub(crate) fn ts_packet(
+        pid: u16,
+        unit_start: bool,
+        random_access: bool,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut p = vec![
+            SYNC_BYTE,
+            ((unit_start as u8) << 6) | (pid >> 8) as u8,
+            pid as u8,
+            0x30,
+        ];
+        let stuffing = TS_PACKET_SIZE - 4 - payload.len();
+        p.push((stuffing - 1) as u8);
+        if stuffing > 1 {
+            p.push(if random_access { 0x40 } else { 0x00 });
+            p.extend(vec![0xFF; stuffing - 2]);
+        }
+        p.extend_from_slice(payload);
+        p
+    }
+
+    fn section(table_id: u8, body: &[u8]) -> Vec<u8> {
+        let length = (5 + body.len() + 4) as u16;
+        let mut s = vec![
+            0,
+            table_id,
+            0xB0 | (length >> 8) as u8,
+            length as u8,
+            0,
+            1,
+            0xC1,
+            0,
+            0,
+        ];
+        s.extend_from_slice(body);
+        s.extend_from_slice(&[0; 4]);
+        s
+    }
+
+    pub(crate) fn pat(pmt_pid: u16) -> Vec<u8> {
+        section(0x00, &[0, 1, 0xE0 | (pmt_pid >> 8) as u8, pmt_pid as u8])
+    }
+
+    pub(crate) fn pmt(streams: &[(u8, u16)]) -> Vec<u8> {
+        let mut body = vec![0xE1, 0x00, 0xF0, 0x00];
+        for &(stream_type, pid) in streams {
+            body.extend_from_slice(&[stream_type, 0xE0 | (pid >> 8) as u8, pid as u8, 0xF0, 0x00]);
+        }
+        section(0x02, &body)
+    }
+
+    fn encode_timestamp(marker: u8, ts: u64) -> [u8; 5] {
+        [
+            (marker << 4) | (((ts >> 30) as u8 & 0x07) << 1) | 1,
+            (ts >> 22) as u8,
+            (((ts >> 15) as u8) << 1) | 1,
+            (ts >> 7) as u8,
+            ((ts as u8) << 1) | 1,
+        ]
+    }
+
+    pub(crate) fn pes(stream_id: u8, pts: u64, dts: Option<u64>, body: &[u8]) -> Vec<u8> {
+        let mut p = vec![0, 0, 1, stream_id, 0, 0, 0x80];
+        match dts {
+            Some(dts) => {
+                p.extend_from_slice(&[0xC0, 10]);
+                p.extend_from_slice(&encode_timestamp(3, pts));
+                p.extend_from_slice(&encode_timestamp(1, dts));
+            }
+            None => {
+                p.extend_from_slice(&[0x80, 5]);
+                p.extend_from_slice(&encode_timestamp(2, pts));
+            }
+        }
+        p.extend_from_slice(body);
+        p
+    }
+
+    /// Builds a stream with one H.264 video PID (0x100) and one AAC PID (0x101).
+    pub(crate) fn transport_stream() -> Vec<u8> {
+        let mut data = ts_packet(0, true, false, &pat(0x1000));
+        data.extend(ts_packet(
+            0x1000,
+            true,
+            false,
+            &pmt(&[(0x1B, 0x100), (0x0F, 0x101)]),
+        ));
+
+        // IDR frame split over two TS packets, just before the 33-bit wrap.
+        let wrap = TIMESTAMP_WRAP as u64;
+        let mut idr = vec![0, 0, 0, 1, 0x65];
+        idr.extend(vec![0xAB; 200]);
+        let video = pes(0xE0, wrap - 3000, Some(wrap - 6000), &idr);
+        data.extend(ts_packet(0x100, true, false, &video[..150]));
+        data.extend(ts_packet(0x100, false, false, &video[150..]));
+        // 44.1 kHz stereo AAC LC in a 9-byte ADTS frame.
+        let adts = [0xFF, 0xF1, 0x50, 0x80, 0x01, 0x3F, 0xFC, 1, 2];
+        let audio = pes(0xC0, wrap - 6000, None, &adts);
+        data.extend(ts_packet(0x101, true, false, &audio));
+        // Frames after the counters wrapped.
+        let video = pes(0xE0, 3000, Some(0), &[0, 0, 1, 0x41, 7]);
+        data.extend(ts_packet(0x100, true, false, &video));
+        let audio = pes(0xC0, 1000, None, &[0xFF, 0xF1, 3, 4]);
+        data.extend(ts_packet(0x101, true, true, &audio));
+        data
+    }
+
+    #[test]
+    fn test_probe() {
+        let data = transport_stream();
+        assert_eq!(probe(&data), Some(188));
+        assert_eq!(probe(&data[..100]), None);
+
+        let mut m2ts = Vec::new();
+        for packet in data.chunks(188) {
+            m2ts.extend_from_slice(&[0, 0, 0, 0]);
+            m2ts.extend_from_slice(packet);
+        }
+        assert_eq!(probe(&m2ts), Some(192));
+        assert_eq!(parse(&m2ts).samples.len(), 4);
+    }
+
+    #[test]
+    fn test_unwrap_timestamp() {
+        assert_eq!(unwrap_timestamp(100, None), 100);
+        assert_eq!(
+            unwrap_timestamp(10, Some(TIMESTAMP_WRAP - 10)),
+            TIMESTAMP_WRAP + 10
+        );
+        assert_eq!(unwrap_timestamp(TIMESTAMP_WRAP as u64 - 10, Some(5)), -10);
+        assert_eq!(unwrap_timestamp(500, Some(400)), 500);
+    }
+
+    #[test]
+    fn test_parse_streams_and_pes() {
+        let data = transport_stream();
+        let file = parse(&data);
+
+        assert_eq!(file.streams.len(), 2);
+        assert_eq!(file.streams[0].codec, "avc1");
+        assert_eq!(file.streams[0].stream_type, StreamType::Video);
+        assert_eq!(file.streams[1].codec, "mp4a");
+        assert_eq!(file.streams[1].codec_string.as_deref(), Some("mp4a.40.2"));
+        assert_eq!(file.streams[1].audio, Some(AudioInfo::new(44100, 2)));
+
+        let summary: Vec<(u32, i64, i64, bool)> = file
+            .samples
+            .iter()
+            .map(|s| (s.stream_index, s.pts, s.dts, s.is_keyframe))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 3000, 0, true),
+                (1, 0, 0, true),
+                (0, 9000, 6000, false),
+                (1, 7000, 7000, true),
+            ]
+        );
+        assert_eq!(file.duration_ms, Some(100));
+
+        // The IDR frame spans two packets.
+        let idr = &file.samples[0];
+        let continuation = idr.continuation.as_ref().unwrap();
+        let total: u32 = idr.size + continuation.iter().map(|r| r.1).sum::<u32>();
+        assert_eq!(total, 205);
+    }
+
+    #[test]
+    fn test_classify_private_stream() {
+        let opus = [0x05, 4, b'O', b'p', b'u', b's'];
+        assert_eq!(classify(0x06, &opus), Some((StreamType::Audio, "Opus")));
+        assert_eq!(
+            classify(0x06, &[0x59, 0]),
+            Some((StreamType::Subtitle, "dvbsub"))
+        );
+        assert_eq!(classify(0x06, &[]), None);
+        assert_eq!(classify(0x15, &[]), None);
+    }
+}
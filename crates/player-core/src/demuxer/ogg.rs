@@ -0,0 +1,720 @@
+//! Ogg container parsing.
+//!
+//! Walks `OggS` pages, reassembles packets that span page boundaries and
+//! assigns timestamps from each page's granule position. Multiplexed and
+//! chained logical bitstreams are exposed as separate streams; headers are
+//! identified per codec (Opus, Vorbis, FLAC and Theora) and are not emitted
+//! as samples.
+
+use super::source::SourceReader;
+use super::{
+    AudioInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType, VideoInfo,
+};
+use crate::decoder::opus;
+use crate::decoder::vorbis::{self, VorbisHeaders};
+use crate::error::Result;
+
+/// Capture pattern starting every page.
+const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+/// Size of the fixed page header, before the segment table.
+const PAGE_HEADER_LEN: usize = 27;
+/// Header type flag: page continues a packet from the previous page.
+const FLAG_CONTINUED: u8 = 0x01;
+/// Header type flag: last page of a logical bitstream.
+const FLAG_END_OF_STREAM: u8 = 0x04;
+/// Opus granule positions always count 48 kHz samples.
+const OPUS_RATE: u32 = 48_000;
+/// Bytes scanned per read when resynchronizing on a capture pattern.
+const RESYNC_WINDOW: usize = 64 * 1024;
+
+/// Codec carried by a logical bitstream, with its timing parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Codec {
+    /// Opus; the first `pre_skip` samples are decoder priming.
+    Opus { pre_skip: i64 },
+    /// Vorbis at the given sample rate.
+    Vorbis { rate: u32 },
+    /// FLAC at the given sample rate, followed by `headers` metadata packets.
+    Flac { rate: u32, headers: usize },
+    /// Theora at `fps_num / fps_den` frames per second.
+    Theora {
+        fps_num: u32,
+        fps_den: u32,
+        granule_shift: u32,
+        /// Bitstream 3.2.1 and later count granules from one instead of zero.
+        granule_offset: i64,
+    },
+}
+
+impl Codec {
+    /// Identifies the codec from the first packet of a logical bitstream.
+    fn identify(packet: &[u8]) -> Option<Self> {
+        if packet.starts_with(b"OpusHead") && packet.len() >= 12 {
+            let pre_skip = u16::from_le_bytes([packet[10], packet[11]]);
+            return Some(Codec::Opus {
+                pre_skip: pre_skip as i64,
+            });
+        }
+        if packet.starts_with(b"\x01vorbis") && packet.len() >= 16 {
+            let rate = u32::from_le_bytes([packet[12], packet[13], packet[14], packet[15]]);
+            return Some(Codec::Vorbis { rate });
+        }
+        if packet.starts_with(b"\x7FFLAC") && packet.len() >= 30 {
+            let headers = u16::from_be_bytes([packet[7], packet[8]]) as usize;
+            // STREAMINFO follows the "fLaC" marker and a metadata block header;
+            // the sample rate is its first 20 bits at offset 10.
+            let rate =
+                (packet[27] as u32) << 12 | (packet[28] as u32) << 4 | (packet[29] as u32) >> 4;
+            return Some(Codec::Flac { rate, headers });
+        }
+        if packet.starts_with(b"\x80theora") && packet.len() >= 42 {
+            let version = (packet[7], packet[8], packet[9]);
+            let fps_num = u32::from_be_bytes([packet[22], packet[23], packet[24], packet[25]]);
+            let fps_den = u32::from_be_bytes([packet[26], packet[27], packet[28], packet[29]]);
+            let granule_shift = ((packet[40] & 0x03) << 3 | packet[41] >> 5) as u32;
+            return Some(Codec::Theora {
+                fps_num,
+                fps_den: fps_den.max(1),
+                granule_shift,
+                granule_offset: (version >= (3, 2, 1)) as i64,
+            });
+        }
+        None
+    }
+
+    /// Fills in the stream properties carried by the identification header.
+    fn describe(&self, info: &mut StreamInfo, packet: &[u8]) {
+        let le_u32 = |at: usize| {
+            u32::from_le_bytes([packet[at], packet[at + 1], packet[at + 2], packet[at + 3]])
+        };
+        let be_u24 =
+            |at: usize| u32::from_be_bytes([0, packet[at], packet[at + 1], packet[at + 2]]);
+        match *self {
+            // Opus always decodes at 48 kHz, whatever the input rate was.
+            Codec::Opus { .. } => {
+                info.audio = Some(AudioInfo::new(OPUS_RATE, packet[9] as u16));
+                info.extra_data = Some(packet.to_vec());
+            }
+            Codec::Vorbis { rate } if packet.len() >= 24 => {
+                info.audio = Some(AudioInfo::new(rate, packet[11] as u16));
+                let nominal = le_u32(20) as i32;
+                info.bitrate = (nominal > 0).then_some(nominal as u64);
+            }
+            Codec::Flac { rate, .. } if packet.len() >= 31 => {
+                let mut audio = AudioInfo::new(rate, ((packet[29] >> 1) & 0x07) as u16 + 1);
+                audio.bit_depth = Some(((packet[29] & 0x01) << 4 | packet[30] >> 4) + 1);
+                info.audio = Some(audio);
+                // The "fLaC" marker and STREAMINFO block, as in Matroska.
+                info.extra_data = packet.get(9..51).map(<[u8]>::to_vec);
+            }
+            Codec::Theora {
+                fps_num, fps_den, ..
+            } => {
+                let (width, height) = (be_u24(14), be_u24(17));
+                let (par_num, par_den) = (be_u24(30), be_u24(33));
+                let par = if par_num > 0 && par_den > 0 {
+                    par_num as f64 / par_den as f64
+                } else {
+                    1.0
+                };
+                info.video = Some(VideoInfo {
+                    width,
+                    height,
+                    display_aspect_ratio: (height > 0).then(|| width as f64 * par / height as f64),
+                    frame_rate: Some(fps_num as f64 / fps_den as f64),
+                    ..VideoInfo::default()
+                });
+                let nominal = be_u24(37);
+                info.bitrate = (nominal > 0).then_some(nominal as u64);
+            }
+            _ => {}
+        }
+    }
+
+    /// Codec name, normalized to the MP4 sample entry code where one exists.
+    fn name(&self) -> &'static str {
+        match self {
+            Codec::Opus { .. } => "Opus",
+            Codec::Vorbis { .. } => "vorbis",
+            Codec::Flac { .. } => "fLaC",
+            Codec::Theora { .. } => "theora",
+        }
+    }
+
+    fn stream_type(&self) -> StreamType {
+        match self {
+            Codec::Theora { .. } => StreamType::Video,
+            _ => StreamType::Audio,
+        }
+    }
+
+    fn timescale(&self) -> u32 {
+        match *self {
+            Codec::Opus { .. } => OPUS_RATE,
+            Codec::Vorbis { rate } | Codec::Flac { rate, .. } => rate,
+            Codec::Theora { fps_num, .. } => fps_num,
+        }
+    }
+
+    /// Number of header packets, including the identification packet.
+    fn header_count(&self) -> usize {
+        match *self {
+            Codec::Opus { .. } => 2,
+            Codec::Vorbis { .. } | Codec::Theora { .. } => 3,
+            Codec::Flac { headers, .. } => 1 + headers,
+        }
+    }
+
+    /// Amount subtracted from every timestamp so playback starts at zero.
+    fn start_offset(&self) -> i64 {
+        match *self {
+            Codec::Opus { pre_skip } => pre_skip,
+            _ => 0,
+        }
+    }
+
+    /// Converts a page granule position to the end time of its last packet,
+    /// in timescale units.
+    fn granule_end(&self, granule: i64) -> i64 {
+        match *self {
+            Codec::Theora {
+                fps_den,
+                granule_shift,
+                granule_offset,
+                ..
+            } => {
+                let keyframe = granule >> granule_shift;
+                let delta = granule & ((1i64 << granule_shift) - 1);
+                (keyframe + delta - granule_offset + 1) * fps_den as i64
+            }
+            _ => granule,
+        }
+    }
+
+    /// Returns the duration of a data packet in timescale units, when it can
+    /// be derived from the packet alone.
+    fn packet_duration(&self, packet: &[u8]) -> Option<i64> {
+        match *self {
+            Codec::Opus { .. } => opus::packet_samples(packet).map(i64::from),
+            Codec::Theora { fps_den, .. } => Some(fps_den as i64),
+            _ => None,
+        }
+    }
+
+    fn is_keyframe(&self, packet: &[u8]) -> bool {
+        match self {
+            // Theora data packets clear the top bit; intra frames clear the next.
+            Codec::Theora { .. } => packet.first().is_some_and(|b| b & 0x40 == 0),
+            _ => true,
+        }
+    }
+}
+
+/// Reassembly and timing state of one logical bitstream.
+#[derive(Debug)]
+struct LogicalStream {
+    serial: u32,
+    /// Codec, once the identification packet has been read; `None` after an
+    /// unrecognized first packet means the stream is ignored.
+    codec: Option<Codec>,
+    /// Whether the identification packet has been seen.
+    identified: bool,
+    stream_index: u32,
+    headers_left: usize,
+    /// Ranges of a packet continuing onto the next page.
+    partial: Option<Vec<(u64, u32)>>,
+    /// End time of the last page with a granule position.
+    last_end: Option<i64>,
+    /// Whether the end-of-stream page has been read.
+    ended: bool,
+    /// Vorbis header packets read so far.
+    headers: Vec<Vec<u8>>,
+    /// Vorbis headers, once all three have been read.
+    vorbis: Option<VorbisHeaders>,
+    /// Block size of the last Vorbis packet.
+    vorbis_block: Option<u16>,
+}
+
+impl LogicalStream {
+    /// Returns the duration of a data packet in timescale units, when it can
+    /// be derived from the packet and those before it. A Vorbis packet ends
+    /// halfway through its block, so the first one has no duration.
+    fn packet_duration(&mut self, codec: Codec, packet: &[u8]) -> Option<i64> {
+        match &self.vorbis {
+            Some(headers) => {
+                let blocksize = headers.packet_blocksize(packet)?;
+                let previous = self.vorbis_block.replace(blocksize);
+                Some(previous.map_or(0, |p| vorbis::overlap_samples(p, blocksize) as i64))
+            }
+            None => codec.packet_duration(packet),
+        }
+    }
+}
+
+/// A data packet completed on the current page, awaiting its timestamp.
+struct PagePacket {
+    ranges: Vec<(u64, u32)>,
+    duration: Option<i64>,
+    is_keyframe: bool,
+}
+
+/// Incremental Ogg parser.
+#[derive(Debug, Default)]
+pub(crate) struct OggParser {
+    /// Offset of the next page.
+    pos: u64,
+    /// Identified logical bitstreams, in order of appearance.
+    streams: Vec<StreamInfo>,
+    logical: Vec<LogicalStream>,
+    /// Data packets of all streams not yet handed over.
+    samples: Vec<SampleEntry>,
+}
+
+impl OggParser {
+    /// Creates a parser positioned at the start of the file.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advances `pos` to the next capture pattern; returns `false` if there is none.
+    fn resync(&mut self, reader: &mut SourceReader<'_>) -> Result<bool> {
+        loop {
+            let window = reader.bytes(self.pos + 1, RESYNC_WINDOW)?;
+            if window.len() < CAPTURE_PATTERN.len() {
+                return Ok(false);
+            }
+            match window.windows(4).position(|w| w == CAPTURE_PATTERN) {
+                Some(skip) => {
+                    self.pos += 1 + skip as u64;
+                    return Ok(true);
+                }
+                None => self.pos += (window.len() - 3) as u64,
+            }
+        }
+    }
+}
+
+impl ContainerParser for OggParser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        loop {
+            let pos = self.pos;
+            let header = match reader.exact(pos, PAGE_HEADER_LEN)? {
+                Some(header) => header,
+                None => break,
+            };
+            if &header[..4] != CAPTURE_PATTERN {
+                if self.resync(reader)? {
+                    continue;
+                }
+                break;
+            }
+            let header_type = header[5];
+            let granule = i64::from_le_bytes(header[6..14].try_into().unwrap());
+            let serial = u32::from_le_bytes(header[14..18].try_into().unwrap());
+            let segments = header[26] as usize;
+            let lacing = match reader.exact(pos + PAGE_HEADER_LEN as u64, segments)? {
+                Some(lacing) => lacing.to_vec(),
+                None => break,
+            };
+            let body_start = pos + (PAGE_HEADER_LEN + segments) as u64;
+            let body_len: u64 = lacing.iter().map(|&l| l as u64).sum();
+            if body_start + body_len > reader.len() {
+                break;
+            }
+            self.pos = body_start + body_len;
+
+            let index = match self.logical.iter().position(|s| s.serial == serial) {
+                Some(index) => index,
+                None => {
+                    self.logical.push(LogicalStream {
+                        serial,
+                        codec: None,
+                        identified: false,
+                        stream_index: 0,
+                        headers_left: 0,
+                        partial: None,
+                        last_end: None,
+                        ended: false,
+                        headers: Vec::new(),
+                        vorbis: None,
+                        vorbis_block: None,
+                    });
+                    self.logical.len() - 1
+                }
+            };
+            let stream = &mut self.logical[index];
+
+            // A packet continued from a page we never saw cannot be recovered,
+            // nor can one whose continuation page is missing.
+            let mut current = if header_type & FLAG_CONTINUED != 0 {
+                stream.partial.take()
+            } else {
+                stream.partial = None;
+                Some(Vec::new())
+            };
+
+            let mut completed = Vec::new();
+            let mut offset = body_start;
+            let mut run_start = offset;
+            for &lace in &lacing {
+                offset += lace as u64;
+                if lace < 255 {
+                    if let Some(mut ranges) = current.take() {
+                        if offset > run_start {
+                            ranges.push((run_start, (offset - run_start) as u32));
+                        }
+                        completed.push(ranges);
+                    }
+                    current = Some(Vec::new());
+                    run_start = offset;
+                }
+            }
+            if let Some(mut ranges) = current {
+                if offset > run_start {
+                    ranges.push((run_start, (offset - run_start) as u32));
+                }
+                stream.partial = Some(ranges);
+            }
+
+            let mut packets = Vec::new();
+            for ranges in completed {
+                // Headers are kept whole as codec initialization data.
+                let limit = if !stream.identified || stream.headers_left > 0 {
+                    ranges.iter().map(|&(_, size)| size as usize).sum()
+                } else {
+                    64
+                };
+                let head = reader.gather(&ranges, limit)?;
+                if !stream.identified {
+                    stream.identified = true;
+                    stream.codec = Codec::identify(&head);
+                    if let Some(codec) = stream.codec {
+                        stream.stream_index = self.streams.len() as u32;
+                        stream.headers_left = codec.header_count() - 1;
+                        let mut info = StreamInfo::new(
+                            stream.stream_index,
+                            codec.stream_type(),
+                            codec.name(),
+                            codec.timescale(),
+                        );
+                        codec.describe(&mut info, &head);
+                        self.streams.push(info);
+                        if let Codec::Vorbis { .. } = codec {
+                            stream.headers.push(head);
+                        }
+                    }
+                    continue;
+                }
+                let codec = match stream.codec {
+                    Some(codec) => codec,
+                    None => continue,
+                };
+                if stream.headers_left > 0 {
+                    stream.headers_left -= 1;
+                    if let Codec::Vorbis { .. } = codec {
+                        stream.headers.push(head);
+                        if stream.headers.len() == 3 {
+                            let headers = std::mem::take(&mut stream.headers);
+                            let [id, comment, setup] = [&headers[0], &headers[1], &headers[2]];
+                            let info = &mut self.streams[stream.stream_index as usize];
+                            info.extra_data = Some(vorbis::codec_private([id, comment, setup]));
+                            stream.vorbis = VorbisHeaders::from_packets(id, comment, setup).ok();
+                        }
+                    }
+                    continue;
+                }
+                if ranges.is_empty() {
+                    continue;
+                }
+                packets.push(PagePacket {
+                    duration: stream.packet_duration(codec, &head),
+                    is_keyframe: codec.is_keyframe(&head),
+                    ranges,
+                });
+            }
+
+            let codec = match stream.codec {
+                Some(codec) => codec,
+                None => continue,
+            };
+            let end = if granule >= 0 {
+                Some(codec.granule_end(granule))
+            } else {
+                None
+            };
+            let last = header_type & FLAG_END_OF_STREAM != 0;
+            let timestamps = page_timestamps(&packets, stream.last_end, end, last);
+            if end.is_some() {
+                stream.last_end = end;
+                stream.ended = last;
+            }
+            for (packet, pts) in packets.into_iter().zip(timestamps) {
+                let pts = pts - codec.start_offset();
+                let (&(offset, size), rest) = packet.ranges.split_first().unwrap();
+                self.samples.push(SampleEntry {
+                    stream_index: stream.stream_index,
+                    offset,
+                    size,
+                    continuation: (!rest.is_empty()).then(|| rest.to_vec().into_boxed_slice()),
+                    dts: pts,
+                    pts,
+                    is_keyframe: packet.is_keyframe,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        let mut streams = self.streams.clone();
+        for stream in &self.logical {
+            if let (Some(codec), Some(end)) = (stream.codec, stream.last_end) {
+                let info = &mut streams[stream.stream_index as usize];
+                let duration = (end - codec.start_offset()).max(0) as u64;
+                info.duration_ms = Some(super::to_ms(duration, info.timescale));
+                info.end_pts = stream.ended.then(|| end - codec.start_offset());
+            }
+        }
+        Container {
+            duration_ms: streams.iter().filter_map(|s| s.duration_ms).max(),
+            streams,
+            samples: std::mem::take(&mut self.samples),
+            ..Container::default()
+        }
+    }
+}
+
+/// Assigns start timestamps to the packets completed on a page.
+///
+/// When every packet duration is known the timestamps are counted back from
+/// the page end, or forward from the previous one on the `last` page, whose
+/// end may cut the last packet short; otherwise they are spread evenly
+/// between the previous page end and this one.
+fn page_timestamps(
+    packets: &[PagePacket],
+    start: Option<i64>,
+    end: Option<i64>,
+    last: bool,
+) -> Vec<i64> {
+    let durations: Option<Vec<i64>> = packets.iter().map(|p| p.duration).collect();
+    match (durations, end) {
+        (Some(durations), Some(end)) => {
+            let mut t = match start {
+                Some(start) if last => start,
+                _ => end - durations.iter().sum::<i64>(),
+            };
+            durations
+                .iter()
+                .map(|d| {
+                    let pts = t;
+                    t += d;
+                    pts
+                })
+                .collect()
+        }
+        (Some(durations), None) => {
+            let mut t = start.unwrap_or(0);
+            durations
+                .iter()
+                .map(|d| {
+                    let pts = t;
+                    t += d;
+                    pts
+                })
+                .collect()
+        }
+        (None, end) => {
+            let start = start.unwrap_or(0);
+            let end = end.unwrap_or(start).max(start);
+            let count = packets.len() as i64;
+            (0..count)
+                .map(|i| start + (end - start) * i / count)
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::demuxer::MemorySource;
+
+    /// Parses a complete in-memory Ogg file.
+    fn parse(data: &[u8]) -> Container {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = OggParser::new();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        parser.container()
+    }
+
+    /// Builds one page holding whole packets, plus an optional trailing
+    /// packet fragment that continues on the next page.
+    pub(crate) fn page(
+        serial: u32,
+        header_type: u8,
+        granule: i64,
+        packets: &[&[u8]],
+        trailing: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        let mut body = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255u8, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+            body.extend_from_slice(packet);
+        }
+        if let Some(fragment) = trailing {
+            assert!(fragment.len().is_multiple_of(255));
+            lacing.extend(std::iter::repeat_n(255u8, fragment.len() / 255));
+            body.extend_from_slice(fragment);
+        }
+        let mut p = CAPTURE_PATTERN.to_vec();
+        p.push(0);
+        p.push(header_type);
+        p.extend_from_slice(&granule.to_le_bytes());
+        p.extend_from_slice(&serial.to_le_bytes());
+        p.extend_from_slice(&[0; 8]);
+        p.push(lacing.len() as u8);
+        p.extend(lacing);
+        p.extend(body);
+        p
+    }
+
+    pub(crate) fn opus_head(pre_skip: u16) -> Vec<u8> {
+        let mut h = b"OpusHead".to_vec();
+        h.extend_from_slice(&[1, 2]);
+        h.extend_from_slice(&pre_skip.to_le_bytes());
+        h.extend_from_slice(&48000u32.to_le_bytes());
+        h.extend_from_slice(&[0, 0, 0]);
+        h
+    }
+
+    fn vorbis_id(rate: u32) -> Vec<u8> {
+        let mut h = b"\x01vorbis".to_vec();
+        h.extend_from_slice(&[0; 4]);
+        h.push(2);
+        h.extend_from_slice(&rate.to_le_bytes());
+        h.extend_from_slice(&[0; 14]);
+        h
+    }
+
+    /// Builds an Opus stream (serial 1) multiplexed with a Vorbis stream (serial 2).
+    pub(crate) fn ogg_file() -> Vec<u8> {
+        // 20 ms CELT frames (config 31, one frame) = 960 samples.
+        let frame = |fill: u8| {
+            let mut f = vec![0xF8];
+            f.extend(vec![fill; 9]);
+            f
+        };
+        let long: Vec<u8> = vec![0xF8; 300];
+        let mut data = page(1, 0x02, 0, &[&opus_head(312)], None);
+        data.extend(page(2, 0x02, 0, &[&vorbis_id(44100)], None));
+        data.extend(page(1, 0, 0, &[b"OpusTags"], None));
+        data.extend(page(2, 0, 0, &[b"\x03vorbis", b"\x05vorbis"], None));
+        // The long packet starts on one page and finishes on the next.
+        data.extend(page(
+            1,
+            0,
+            312 + 1920,
+            &[&frame(1), &frame(2)],
+            Some(&long[..255]),
+        ));
+        data.extend(page(2, 0, 2048, &[&[0xA0; 4], &[0xA1; 4]], None));
+        data.extend(page(1, 0x05, 312 + 2880, &[&long[255..]], None));
+        data
+    }
+
+    #[test]
+    fn test_parse_multiplexed_streams() {
+        let data = ogg_file();
+        let file = parse(&data);
+
+        assert_eq!(file.streams.len(), 2);
+        assert_eq!(file.streams[0].codec, "Opus");
+        assert_eq!(file.streams[0].timescale, 48000);
+        assert_eq!(file.streams[1].codec, "vorbis");
+        assert_eq!(file.streams[1].timescale, 44100);
+        let opus = file.streams[0].audio.as_ref().unwrap();
+        assert_eq!((opus.sample_rate, opus.channels), (48000, 2));
+        assert_eq!(file.streams[1].audio.as_ref().unwrap().sample_rate, 44100);
+        assert_eq!(file.streams[0].duration_ms, Some(60));
+        assert_eq!(file.duration_ms, Some(60));
+
+        let summary: Vec<(u32, i64)> = file
+            .samples
+            .iter()
+            .map(|s| (s.stream_index, s.pts))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(0, 0), (0, 960), (1, 0), (1, 1024), (0, 1920)]
+        );
+
+        // The continued packet keeps both of its byte ranges.
+        let long = &file.samples[4];
+        assert_eq!(long.size, 255);
+        let continuation = long.continuation.as_deref().unwrap();
+        assert_eq!(continuation.len(), 1);
+        assert_eq!(continuation[0].1, 45);
+    }
+
+    #[test]
+    fn test_codec_headers_and_end_trimming() {
+        use crate::decoder::vorbis::tests::{comment, identification, setup};
+
+        let id = identification(2, 44100);
+        let tags = comment("test", &["TITLE=Loop"]);
+        let modes = setup(&[false, true]);
+        let mut data = page(1, 0x02, 0, &[&opus_head(312)], None);
+        data.extend(page(2, 0x02, 0, &[&id], None));
+        data.extend(page(1, 0, 0, &[b"OpusTags"], None));
+        data.extend(page(2, 0, 0, &[&tags, &modes], None));
+        data.extend(page(1, 0, 960, &[&[0xF8]], None));
+        // A short block primes the decoder, then long blocks follow.
+        data.extend(page(2, 0, 1600, &[&[0b00], &[0b10], &[0b10]], None));
+        // The last pages end 500 and 1000 samples into their packet.
+        data.extend(page(1, 0x04, 960 + 500, &[&[0xF8]], None));
+        data.extend(page(2, 0x04, 2600, &[&[0b10]], None));
+        let file = parse(&data);
+
+        assert_eq!(file.streams[0].extra_data, Some(opus_head(312)));
+        assert_eq!(file.streams[0].end_pts, Some(1148));
+        assert_eq!(
+            file.streams[1].extra_data,
+            Some(vorbis::codec_private([&id, &tags, &modes]))
+        );
+        assert_eq!(file.streams[1].end_pts, Some(2600));
+        let summary: Vec<(u32, i64)> = file
+            .samples
+            .iter()
+            .map(|s| (s.stream_index, s.pts))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(0, -312), (1, 0), (1, 0), (1, 576), (0, 648), (1, 1600)]
+        );
+    }
+
+    #[test]
+    fn test_theora_granule() {
+        let codec = Codec::Theora {
+            fps_num: 25,
+            fps_den: 1,
+            granule_shift: 6,
+            granule_offset: 1,
+        };
+        // Keyframe 10 plus 2 frames: the 12th frame (index 11) ends at 12.
+        assert_eq!(codec.granule_end(10 << 6 | 2), 12);
+        assert!(codec.is_keyframe(&[0x00]));
+        assert!(!codec.is_keyframe(&[0x40]));
+    }
+
+    #[test]
+    fn test_unknown_stream_is_ignored() {
+        let mut data = page(7, 0x02, 0, &[b"fishead\0"], None);
+        data.extend(page(7, 0, 0, &[b"fisbone\0"], None));
+        let file = parse(&data);
+        assert!(file.streams.is_empty());
+        assert!(file.samples.is_empty());
+    }
+}
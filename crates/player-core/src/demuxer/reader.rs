@@ -0,0 +1,105 @@
+//! Byte-level reading helpers shared by the container parsers.
+
+use crate::error::{PlayerError, Result};
+
+/// Cursor over a byte slice with bounds-checked big-endian reads.
+#[derive(Debug, Clone)]
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    /// Creates a reader positioned at the start of `data`.
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Returns the current read position.
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns the number of unread bytes.
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    /// Advances the cursor by `n` bytes.
+    pub(crate) fn skip(&mut self, n: usize) -> Result<()> {
+        self.bytes(n).map(|_| ())
+    }
+
+    /// Reads `n` raw bytes.
+    pub(crate) fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.remaining() < n {
+            return Err(PlayerError::demuxer("Unexpected end of data"));
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    /// Reads a four-character code.
+    pub(crate) fn fourcc(&mut self) -> Result<[u8; 4]> {
+        let b = self.bytes(4)?;
+        Ok([b[0], b[1], b[2], b[3]])
+    }
+
+    /// Reads an unsigned 8-bit integer.
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// Reads a big-endian unsigned 16-bit integer.
+    pub(crate) fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// Reads a big-endian unsigned 24-bit integer.
+    pub(crate) fn u24(&mut self) -> Result<u32> {
+        let b = self.bytes(3)?;
+        Ok(u32::from_be_bytes([0, b[0], b[1], b[2]]))
+    }
+
+    /// Reads a big-endian unsigned 32-bit integer.
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Reads a big-endian unsigned 64-bit integer.
+    pub(crate) fn u64(&mut self) -> Result<u64> {
+        let hi = self.u32()? as u64;
+        let lo = self.u32()? as u64;
+        Ok((hi << 32) | lo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_big_endian() {
+        let data = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
+        let mut r = ByteReader::new(&data);
+        assert_eq!(r.u8().unwrap(), 0x01);
+        assert_eq!(r.u16().unwrap(), 0x0203);
+        assert_eq!(r.u24().unwrap(), 0x04_0506);
+        assert_eq!(r.remaining(), 3);
+        assert!(r.u32().is_err());
+        assert_eq!(r.position(), 6);
+    }
+
+    #[test]
+    fn test_reader_u64_and_skip() {
+        let mut data = vec![0xAA, 0xBB];
+        data.extend_from_slice(&0x0102_0304_0506_0708u64.to_be_bytes());
+        let mut r = ByteReader::new(&data);
+        r.skip(2).unwrap();
+        assert_eq!(r.u64().unwrap(), 0x0102_0304_0506_0708);
+        assert!(r.skip(1).is_err());
+    }
+}
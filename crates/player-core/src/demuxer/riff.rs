@@ -0,0 +1,241 @@
+//! RIFF chunk structure shared by RIFF-based containers.
+//!
+//! Chunks are little-endian `id`/`size` headers followed by a payload padded
+//! to an even length. `RIFF` and `LIST` chunks start with a form type and
+//! contain further chunks.
+
+use super::source::SourceReader;
+use crate::error::Result;
+
+/// A single chunk located inside a parent payload.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Chunk<'a> {
+    /// Four-character chunk identifier.
+    pub id: [u8; 4],
+    /// Chunk payload, truncated to the available data.
+    pub payload: &'a [u8],
+    /// Absolute file offset of the first payload byte.
+    pub payload_offset: u64,
+}
+
+impl<'a> Chunk<'a> {
+    /// Returns the form type of a `RIFF` or `LIST` chunk.
+    pub(crate) fn list_type(&self) -> Option<&'a [u8]> {
+        if matches!(&self.id, b"RIFF" | b"LIST" | b"RF64" | b"BW64") {
+            self.payload.get(..4)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether this is a `LIST` chunk of the given form type.
+    pub(crate) fn is_list(&self, list_type: &[u8; 4]) -> bool {
+        self.id == *b"LIST" && self.list_type() == Some(&list_type[..])
+    }
+
+    /// Iterates over the chunks of a `RIFF` or `LIST` chunk.
+    pub(crate) fn children(&self) -> ChunkIter<'a> {
+        let start = self.payload.len().min(4);
+        chunks(&self.payload[start..], self.payload_offset + start as u64)
+    }
+
+    /// Returns the first child chunk with the given identifier.
+    pub(crate) fn child(&self, id: &[u8; 4]) -> Option<Chunk<'a>> {
+        self.children().find(|c| c.id == *id)
+    }
+}
+
+/// Iterator over sibling chunks.
+///
+/// A chunk whose size runs past the end of the data is returned truncated
+/// and ends iteration, so that partially written files remain readable.
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkIter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    base_offset: u64,
+}
+
+impl<'a> Iterator for ChunkIter<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.data.get(self.pos..)?;
+        if rest.len() < 8 {
+            return None;
+        }
+        let id = [rest[0], rest[1], rest[2], rest[3]];
+        let size = le_u32(&rest[4..8]) as usize;
+        let end = 8usize.saturating_add(size).min(rest.len());
+        let payload_offset = self.base_offset + self.pos as u64 + 8;
+        // Payloads are padded to an even length.
+        self.pos = self
+            .pos
+            .saturating_add(8)
+            .saturating_add(size)
+            .saturating_add(size & 1);
+        Some(Chunk {
+            id,
+            payload: &rest[8..end],
+            payload_offset,
+        })
+    }
+}
+
+/// Iterates over the chunks in `data`, which starts at file offset `base_offset`.
+pub(crate) fn chunks(data: &[u8], base_offset: u64) -> ChunkIter<'_> {
+    ChunkIter {
+        data,
+        pos: 0,
+        base_offset,
+    }
+}
+
+/// Location of a chunk read through a [`SourceReader`], without its payload.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ChunkHeader {
+    /// Four-character chunk identifier.
+    pub id: [u8; 4],
+    /// Form type of a `RIFF` or `LIST` chunk.
+    pub list_type: Option<[u8; 4]>,
+    /// Absolute file offset of the first payload byte.
+    pub payload_offset: u64,
+    /// Payload size as declared; may run past the end of a truncated file.
+    pub size: u64,
+}
+
+impl ChunkHeader {
+    /// Returns whether this is a `LIST` chunk of the given form type.
+    pub(crate) fn is_list(&self, list_type: &[u8; 4]) -> bool {
+        self.id == *b"LIST" && self.list_type == Some(*list_type)
+    }
+
+    /// Returns the offset just past the payload.
+    pub(crate) fn end(&self) -> u64 {
+        self.payload_offset + self.size
+    }
+
+    /// Reads the child chunk headers of a `RIFF` or `LIST` chunk.
+    pub(crate) fn children(&self, reader: &mut SourceReader<'_>) -> Result<Vec<ChunkHeader>> {
+        read_chunks(reader, self.payload_offset + 4, self.end())
+    }
+
+    /// Reads the whole chunk, header included, for walking with [`chunks`].
+    pub(crate) fn load(&self, reader: &mut SourceReader<'_>) -> Result<Vec<u8>> {
+        Ok(reader
+            .bytes(
+                self.payload_offset - 8,
+                (self.size as usize).saturating_add(8),
+            )?
+            .to_vec())
+    }
+}
+
+/// Reads the chunk header at `offset`, or `None` if the source ends first.
+pub(crate) fn read_chunk_at(
+    reader: &mut SourceReader<'_>,
+    offset: u64,
+) -> Result<Option<ChunkHeader>> {
+    let header = match reader.exact(offset, 8)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let id = [header[0], header[1], header[2], header[3]];
+    let size = le_u32(&header[4..]) as u64;
+    let payload_offset = offset + 8;
+    let list_type = if matches!(&id, b"RIFF" | b"LIST" | b"RF64" | b"BW64") && size >= 4 {
+        reader
+            .exact(payload_offset, 4)?
+            .map(|t| [t[0], t[1], t[2], t[3]])
+    } else {
+        None
+    };
+    Ok(Some(ChunkHeader {
+        id,
+        list_type,
+        payload_offset,
+        size,
+    }))
+}
+
+/// Reads the headers of the sibling chunks between `start` and `end`.
+pub(crate) fn read_chunks(
+    reader: &mut SourceReader<'_>,
+    start: u64,
+    end: u64,
+) -> Result<Vec<ChunkHeader>> {
+    let mut headers = Vec::new();
+    let mut pos = start;
+    while pos + 8 <= end {
+        let header = match read_chunk_at(reader, pos)? {
+            Some(header) => header,
+            None => break,
+        };
+        // Payloads are padded to an even length.
+        pos = header.end() + (header.size & 1);
+        headers.push(header);
+    }
+    Ok(headers)
+}
+
+/// Reads a little-endian `u16` from the start of `b`.
+pub(crate) fn le_u16(b: &[u8]) -> u16 {
+    u16::from_le_bytes([b[0], b[1]])
+}
+
+/// Reads a little-endian `u32` from the start of `b`.
+pub(crate) fn le_u32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+/// Reads a little-endian `u64` from the start of `b`.
+pub(crate) fn le_u64(b: &[u8]) -> u64 {
+    u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a chunk, padding odd payloads.
+    pub(crate) fn chunk(id: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut c = id.to_vec();
+        c.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        c.extend_from_slice(payload);
+        if payload.len() % 2 == 1 {
+            c.push(0);
+        }
+        c
+    }
+
+    /// Builds a `LIST` (or `RIFF`) chunk of the given form type.
+    pub(crate) fn list(id: &[u8; 4], list_type: &[u8; 4], children: &[u8]) -> Vec<u8> {
+        let mut payload = list_type.to_vec();
+        payload.extend_from_slice(children);
+        chunk(id, &payload)
+    }
+
+    #[test]
+    fn test_chunk_iter_padding_and_lists() {
+        let mut children = chunk(b"abcd", &[1, 2, 3]);
+        children.extend(chunk(b"efgh", &[4]));
+        let data = list(b"LIST", b"test", &children);
+
+        let root = chunks(&data, 100).next().unwrap();
+        assert!(root.is_list(b"test"));
+        let kids: Vec<_> = root.children().collect();
+        assert_eq!(kids.len(), 2);
+        assert_eq!(kids[0].payload, &[1, 2, 3]);
+        assert_eq!(kids[1].payload_offset, 100 + 12 + 12 + 8);
+        assert_eq!(root.child(b"efgh").unwrap().payload, &[4]);
+    }
+
+    #[test]
+    fn test_chunk_iter_truncated() {
+        let mut data = chunk(b"abcd", &[0; 16]);
+        data.truncate(12);
+        let only = chunks(&data, 0).next().unwrap();
+        assert_eq!(only.payload.len(), 4);
+        assert_eq!(chunks(&data, 0).count(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+//! Keyframe index used to reposition the demuxer read cursor.
+
+use super::SampleEntry;
+
+/// A random access point of a single stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SeekPoint {
+    /// Presentation timestamp in the stream's timescale.
+    pub pts: i64,
+    /// Index of the sample in the demuxer's file-ordered sample list.
+    pub sample: usize,
+}
+
+/// Per-stream keyframe index, sorted by presentation time.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SeekIndex {
+    streams: Vec<Vec<SeekPoint>>,
+    /// Number of samples indexed so far.
+    len: usize,
+}
+
+impl SeekIndex {
+    /// Indexes the samples from `from` on, which were added to the list or
+    /// moved by samples merged in before them.
+    pub(crate) fn extend(&mut self, samples: &[SampleEntry], from: usize, stream_count: usize) {
+        self.streams.resize(stream_count, Vec::new());
+        if from < self.len {
+            for points in self.streams.iter_mut() {
+                points.retain(|p| p.sample < from);
+            }
+        }
+        for (sample, entry) in samples.iter().enumerate().skip(from) {
+            if !entry.is_keyframe {
+                continue;
+            }
+            if let Some(points) = self.streams.get_mut(entry.stream_index as usize) {
+                // Points sharing a timestamp stay in file order.
+                let at = points.partition_point(|p| p.pts <= entry.pts);
+                points.insert(
+                    at,
+                    SeekPoint {
+                        pts: entry.pts,
+                        sample,
+                    },
+                );
+            }
+        }
+        self.len = samples.len();
+    }
+
+    /// Returns whether the given stream has any random access points.
+    pub(crate) fn has_points(&self, stream_index: u32) -> bool {
+        self.streams
+            .get(stream_index as usize)
+            .is_some_and(|points| !points.is_empty())
+    }
+
+    /// Returns the last random access point at or before `pts`.
+    ///
+    /// Falls back to the first point when `pts` precedes every keyframe.
+    pub(crate) fn lookup(&self, stream_index: u32, pts: i64) -> Option<SeekPoint> {
+        let points = self.streams.get(stream_index as usize)?;
+        let after = points.partition_point(|p| p.pts <= pts);
+        points.get(after.saturating_sub(1)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(samples: &[SampleEntry], stream_count: usize) -> SeekIndex {
+        let mut index = SeekIndex::default();
+        index.extend(samples, 0, stream_count);
+        index
+    }
+
+    fn sample(stream_index: u32, pts: i64, is_keyframe: bool) -> SampleEntry {
+        SampleEntry {
+            stream_index,
+            offset: 0,
+            size: 0,
+            dts: pts,
+            pts,
+            is_keyframe,
+            continuation: None,
+        }
+    }
+
+    #[test]
+    fn test_seek_index_lookup() {
+        let samples = vec![
+            sample(0, 0, true),
+            sample(1, 0, true),
+            sample(0, 100, false),
+            sample(0, 200, true),
+            sample(1, 150, true),
+        ];
+        let index = build(&samples, 2);
+
+        assert_eq!(index.lookup(0, 150), Some(SeekPoint { pts: 0, sample: 0 }));
+        assert_eq!(
+            index.lookup(0, 200),
+            Some(SeekPoint {
+                pts: 200,
+                sample: 3
+            })
+        );
+        assert_eq!(index.lookup(0, 9999).unwrap().sample, 3);
+        assert_eq!(index.lookup(1, 160).unwrap().sample, 4);
+        assert!(index.lookup(2, 0).is_none());
+    }
+
+    #[test]
+    fn test_seek_index_extend() {
+        let mut samples = vec![sample(0, 0, true), sample(0, 200, true)];
+        let mut index = build(&samples, 1);
+
+        // A new stream, and a sample merged in ahead of the second one.
+        samples.insert(1, sample(0, 100, true));
+        samples.push(sample(1, 50, true));
+        index.extend(&samples, 1, 2);
+        assert_eq!(index.lookup(0, 150).unwrap().sample, 1);
+        assert_eq!(index.lookup(0, 200).unwrap().sample, 2);
+        assert_eq!(index.lookup(1, 50).unwrap().sample, 3);
+        assert_eq!(index.streams[0].len(), 3);
+    }
+
+    #[test]
+    fn test_seek_index_before_first_keyframe() {
+        let samples = vec![sample(0, 500, true), sample(0, 600, false)];
+        let index = build(&samples, 1);
+        assert_eq!(index.lookup(0, 0).unwrap().pts, 500);
+        assert!(index.has_points(0));
+        assert!(!index.has_points(1));
+    }
+}
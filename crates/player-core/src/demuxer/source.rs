@@ -0,0 +1,264 @@
+//! Random-access byte sources the demuxer pulls container data from.
+//!
+//! Parsers never hold the whole file: they read headers and index structures
+//! through a [`SourceReader`], and packet payloads are fetched on demand when
+//! they are read. This keeps memory bounded for multi-gigabyte files.
+
+use crate::error::{PlayerError, Result};
+
+/// Size of the read-ahead window used by [`SourceReader`].
+const WINDOW_SIZE: usize = 1 << 20;
+
+/// A random-access source of container bytes with a known length.
+pub trait ByteSource: std::fmt::Debug {
+    /// Returns the total length of the source in bytes.
+    fn len(&self) -> u64;
+
+    /// Returns whether the source is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills `buf` with the bytes starting at `offset`.
+    ///
+    /// # Errors
+    /// Returns an error if the range extends past the end of the source or
+    /// the underlying read fails.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()>;
+
+    /// Appends bytes to the end of a growing source.
+    ///
+    /// # Errors
+    /// Returns an error if the source cannot grow, which is the default.
+    fn append(&mut self, _data: &[u8]) -> Result<()> {
+        Err(PlayerError::io("Source does not support appending"))
+    }
+}
+
+/// Byte source backed by an in-memory buffer.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    data: Vec<u8>,
+}
+
+impl MemorySource {
+    /// Creates a source over `data`.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+impl ByteSource for MemorySource {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let bytes = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.data.get(start..start.checked_add(buf.len())?))
+            .ok_or_else(|| PlayerError::io("Read past end of source"))?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.data.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Byte source reading slices of a JavaScript `File` or `Blob` on demand.
+///
+/// Reads are synchronous through `FileReaderSync`, which is only available in
+/// Web Workers, so the player core must run off the main thread.
+#[derive(Debug)]
+pub struct BlobSource {
+    blob: web_sys::Blob,
+    reader: web_sys::FileReaderSync,
+}
+
+impl BlobSource {
+    /// Creates a source over `blob`.
+    ///
+    /// # Errors
+    /// Returns an error if `FileReaderSync` is unavailable (outside a worker).
+    pub fn new(blob: web_sys::Blob) -> Result<Self> {
+        let reader = web_sys::FileReaderSync::new()
+            .map_err(|_| PlayerError::io("FileReaderSync is unavailable"))?;
+        Ok(Self { blob, reader })
+    }
+}
+
+impl ByteSource for BlobSource {
+    fn len(&self) -> u64 {
+        self.blob.size() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= self.len())
+            .ok_or_else(|| PlayerError::io("Read past end of source"))?;
+        let slice = self
+            .blob
+            .slice_with_f64_and_f64(offset as f64, end as f64)
+            .map_err(|_| PlayerError::io("Failed to slice blob"))?;
+        let buffer = self
+            .reader
+            .read_as_array_buffer(&slice)
+            .map_err(|_| PlayerError::io("Failed to read blob"))?;
+        js_sys::Uint8Array::new(&buffer).copy_to(buf);
+        Ok(())
+    }
+}
+
+/// Windowed reader used by the container parsers.
+///
+/// Small reads are served from a read-ahead window so that walking headers
+/// does not issue one source read per field.
+pub(crate) struct SourceReader<'a> {
+    source: &'a mut dyn ByteSource,
+    window: Vec<u8>,
+    window_offset: u64,
+    /// Whether the source may still grow.
+    growing: bool,
+}
+
+impl<'a> SourceReader<'a> {
+    /// Creates a reader over a complete `source`.
+    pub(crate) fn new(source: &'a mut dyn ByteSource) -> Self {
+        Self {
+            source,
+            window: Vec::new(),
+            window_offset: 0,
+            growing: false,
+        }
+    }
+
+    /// Creates a reader over a `source` that more data may still be appended to.
+    pub(crate) fn growing(source: &'a mut dyn ByteSource) -> Self {
+        Self {
+            growing: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Returns whether the end of the source is the end of the file.
+    ///
+    /// Parsers must not treat data cut off at the end of an incomplete
+    /// source as truncated; it will be available on a later call.
+    pub(crate) fn is_complete(&self) -> bool {
+        !self.growing
+    }
+
+    /// Returns the length of the underlying source.
+    pub(crate) fn len(&self) -> u64 {
+        self.source.len()
+    }
+
+    /// Returns up to `len` bytes at `offset`; fewer if the source ends first.
+    pub(crate) fn bytes(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        let total = self.source.len();
+        let end = offset.saturating_add(len as u64).min(total);
+        if offset >= end {
+            return Ok(&[]);
+        }
+        let window_end = self.window_offset + self.window.len() as u64;
+        if offset < self.window_offset || end > window_end {
+            let fill = (end - offset).max(WINDOW_SIZE as u64).min(total - offset);
+            self.window.resize(fill as usize, 0);
+            self.source.read_at(offset, &mut self.window)?;
+            self.window_offset = offset;
+        }
+        let start = (offset - self.window_offset) as usize;
+        Ok(&self.window[start..start + (end - offset) as usize])
+    }
+
+    /// Returns exactly `len` bytes at `offset`, or `None` if the source ends first.
+    pub(crate) fn exact(&mut self, offset: u64, len: usize) -> Result<Option<&[u8]>> {
+        let bytes = self.bytes(offset, len)?;
+        Ok((bytes.len() == len).then_some(bytes))
+    }
+
+    /// Copies up to `limit` bytes from the start of a list of `(offset, size)` ranges.
+    pub(crate) fn gather(&mut self, ranges: &[(u64, u32)], limit: usize) -> Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(limit);
+        for &(offset, size) in ranges {
+            let take = (size as usize).min(limit - out.len());
+            out.extend_from_slice(self.bytes(offset, take)?);
+            if out.len() == limit {
+                break;
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl std::fmt::Debug for SourceReader<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceReader")
+            .field("len", &self.source.len())
+            .field("window_offset", &self.window_offset)
+            .field("window_len", &self.window.len())
+            .field("growing", &self.growing)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Source that counts how often it is read.
+    #[derive(Debug)]
+    struct CountingSource {
+        inner: MemorySource,
+        reads: usize,
+    }
+
+    impl ByteSource for CountingSource {
+        fn len(&self) -> u64 {
+            self.inner.len()
+        }
+
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+            self.reads += 1;
+            self.inner.read_at(offset, buf)
+        }
+    }
+
+    #[test]
+    fn test_memory_source_read_at() {
+        let mut source = MemorySource::new((0..10).collect());
+        let mut buf = [0; 3];
+        source.read_at(4, &mut buf).unwrap();
+        assert_eq!(buf, [4, 5, 6]);
+        assert!(source.read_at(8, &mut buf).is_err());
+        assert!(source.read_at(u64::MAX, &mut buf).is_err());
+        source.append(&[10, 11]).unwrap();
+        source.read_at(8, &mut buf).unwrap();
+        assert_eq!(buf, [8, 9, 10]);
+    }
+
+    #[test]
+    fn test_reader_window_and_bounds() {
+        let mut source = CountingSource {
+            inner: MemorySource::new((0..=255).collect()),
+            reads: 0,
+        };
+        let mut reader = SourceReader::new(&mut source);
+        assert_eq!(reader.bytes(0, 2).unwrap(), &[0, 1]);
+        assert_eq!(reader.bytes(200, 4).unwrap(), &[200, 201, 202, 203]);
+        assert_eq!(reader.bytes(254, 8).unwrap(), &[254, 255]);
+        assert!(reader.bytes(300, 1).unwrap().is_empty());
+        assert!(reader.exact(254, 8).unwrap().is_none());
+        assert_eq!(
+            reader.gather(&[(1, 2), (100, 5)], 4).unwrap(),
+            vec![1, 2, 100, 101]
+        );
+        drop(reader);
+        // The whole source fits in the first window.
+        assert_eq!(source.reads, 1);
+    }
+}
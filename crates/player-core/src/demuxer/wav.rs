@@ -0,0 +1,467 @@
+//! WAVE (RIFF) file parsing, including RF64 and BW64 files past 4 GB.
+//!
+//! The `data` chunk is cut into packets of whole sample frames (or blocks,
+//! for compressed formats, and frames for MP3). Every packet is a random
+//! access point with an exact timestamp, so a seek into PCM data can resume
+//! at any sample frame.
+
+use super::riff::{le_u16, le_u64, read_chunk_at, ChunkHeader};
+use super::source::SourceReader;
+use super::{to_ms, AudioInfo, Container, ContainerParser, SampleEntry, StreamInfo, StreamType};
+use crate::decoder::mp3::{FrameHeader, HEADER_LEN};
+use crate::decoder::pcm::{self, WaveFormat};
+use crate::error::{PlayerError, Result};
+
+/// Approximate size of a packet; packets hold as many whole blocks as fit.
+const PACKET_LEN: u64 = 16 * 1024;
+/// Format tag of MPEG audio Layer III.
+const WAVE_FORMAT_MPEGLAYER3: u16 = 0x0055;
+/// 32-bit chunk size of RF64 chunks whose size is given by `ds64`, also
+/// written by streaming encoders that do not know the size in advance.
+const SIZE_IN_DS64: u64 = 0xFFFF_FFFF;
+
+/// Returns whether `data` starts with a WAVE RIFF header.
+pub(crate) fn probe(data: &[u8]) -> bool {
+    matches!(&data[0..4], b"RIFF" | b"RF64" | b"BW64") && &data[8..12] == b"WAVE"
+}
+
+/// Returns the codec name of a `WAVEFORMATEX`: the PCM codec name, or a
+/// name for the format tag of compressed audio.
+pub(crate) fn audio_codec(format: &[u8]) -> String {
+    let parsed = WaveFormat::parse(format).ok();
+    if let Some(name) = parsed
+        .as_ref()
+        .and_then(WaveFormat::codec)
+        .and_then(pcm::codec_name)
+    {
+        return name.to_string();
+    }
+    let name = match parsed.map_or(le_u16(format), |f| f.format_tag) {
+        pcm::WAVE_FORMAT_PCM | pcm::WAVE_FORMAT_IEEE_FLOAT => "pcm",
+        0x0050 => "mp2",
+        WAVE_FORMAT_MPEGLAYER3 => "mp3",
+        0x00FF | 0x1610 | 0x706D => "mp4a",
+        0x2000 => "ac-3",
+        0x2001 => "dtsc",
+        0x674F..=0x6751 => "vorbis",
+        tag => return format!("0x{:04x}", tag),
+    };
+    name.to_string()
+}
+
+/// Format and location of the audio data of a WAVE file.
+#[derive(Debug)]
+struct WaveHeader {
+    /// Raw `fmt ` payload.
+    fmt: Vec<u8>,
+    format: WaveFormat,
+    /// File offset of the first sample.
+    data_offset: u64,
+    /// Size of the `data` payload, or `None` if the writer did not know it.
+    data_size: Option<u64>,
+}
+
+/// Reads the `fmt ` chunk and locates the `data` chunk, taking its size
+/// from `ds64` in RF64 files. Returns `None` until both have arrived.
+fn read_header(reader: &mut SourceReader<'_>, riff: &ChunkHeader) -> Result<Option<WaveHeader>> {
+    let mut ds64_data_size = None;
+    let mut fmt = None;
+    let mut pos = riff.payload_offset + 4;
+    while let Some(chunk) = read_chunk_at(reader, pos)? {
+        match &chunk.id {
+            b"ds64" | b"fmt " => {
+                let payload = match reader.exact(chunk.payload_offset, chunk.size as usize)? {
+                    Some(payload) => payload,
+                    None => return Ok(None),
+                };
+                if chunk.id == *b"ds64" {
+                    // RIFF size, data size, sample count, then a table of
+                    // other chunk sizes.
+                    ds64_data_size = payload.get(8..16).map(le_u64);
+                } else {
+                    let format = WaveFormat::parse(payload)
+                        .map_err(|_| PlayerError::invalid_format("Invalid WAVE fmt chunk"))?;
+                    fmt = Some((payload.to_vec(), format));
+                }
+            }
+            b"data" => {
+                let (fmt, format) = fmt.ok_or_else(|| {
+                    PlayerError::invalid_format("WAVE data chunk precedes the fmt chunk")
+                })?;
+                let data_size = match ds64_data_size {
+                    Some(size) if riff.id != *b"RIFF" && chunk.size == SIZE_IN_DS64 => Some(size),
+                    _ if chunk.size == SIZE_IN_DS64 => None,
+                    _ => Some(chunk.size),
+                };
+                return Ok(Some(WaveHeader {
+                    fmt,
+                    format,
+                    data_offset: chunk.payload_offset,
+                    data_size,
+                }));
+            }
+            _ => {}
+        }
+        // Payloads are padded to an even length.
+        pos = chunk.end() + (chunk.size & 1);
+    }
+    Ok(None)
+}
+
+/// WAVE parser.
+///
+/// Each call re-reads the header and continues cutting the `data` chunk up
+/// to the end of the data received so far.
+#[derive(Debug, Default)]
+pub(crate) struct WavParser {
+    /// The single audio stream, once `fmt ` and `data` have been found.
+    stream: Option<StreamInfo>,
+    /// Position in the `data` payload up to which packets have been cut.
+    data_pos: u64,
+    /// Timestamp of the next MP3 frame.
+    frame_pts: i64,
+    /// Packets not yet handed over.
+    samples: Vec<SampleEntry>,
+}
+
+impl WavParser {
+    /// Creates a parser.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cuts one packet per MP3 frame from the `data` payload up to `end`,
+    /// skipping bytes that do not start a frame.
+    fn cut_frames(
+        &mut self,
+        reader: &mut SourceReader<'_>,
+        header: &WaveHeader,
+        end: u64,
+    ) -> Result<()> {
+        let mut pos = self.data_pos;
+        while pos < end {
+            let head = reader.bytes(header.data_offset + pos, HEADER_LEN)?;
+            if head.len() < HEADER_LEN {
+                break;
+            }
+            let frame = match FrameHeader::parse(head) {
+                Ok(frame) if frame.layer == 3 => frame,
+                _ => {
+                    pos += 1;
+                    continue;
+                }
+            };
+            if pos + frame.frame_len as u64 > end {
+                break;
+            }
+            self.samples.push(SampleEntry {
+                stream_index: 0,
+                offset: header.data_offset + pos,
+                size: frame.frame_len as u32,
+                continuation: None,
+                dts: self.frame_pts,
+                pts: self.frame_pts,
+                is_keyframe: true,
+            });
+            // Timestamps count samples at the rate of the format record.
+            self.frame_pts += (frame.samples_per_frame() as u64 * header.format.sample_rate as u64
+                / frame.sample_rate as u64) as i64;
+            pos += frame.frame_len as u64;
+        }
+        self.data_pos = pos;
+        Ok(())
+    }
+}
+
+impl ContainerParser for WavParser {
+    fn parse(&mut self, reader: &mut SourceReader<'_>) -> Result<()> {
+        let riff = read_chunk_at(reader, 0)?
+            .filter(|c| c.list_type == Some(*b"WAVE"))
+            .ok_or_else(|| PlayerError::invalid_format("Missing WAVE RIFF header"))?;
+        let header = match read_header(reader, &riff)? {
+            Some(header) => header,
+            None if !reader.is_complete() => return Ok(()),
+            None => return Err(PlayerError::invalid_format("Missing WAVE data chunk")),
+        };
+        let format = &header.format;
+        if format.sample_rate == 0 {
+            return Err(PlayerError::invalid_format("WAVE sample rate is zero"));
+        }
+
+        let codec = format.codec();
+        let framed = codec.is_none() && format.format_tag == WAVE_FORMAT_MPEGLAYER3;
+        // PCM blocks are single sample frames, whatever the header says.
+        let block = match codec.and_then(pcm::sample_size) {
+            Some(size) => size as u64 * format.channels as u64,
+            None if framed => 1,
+            None => format.block_align.max(1) as u64,
+        };
+        let ticks = |bytes: u64| -> i64 {
+            if codec.is_some() {
+                (bytes / block) as i64
+            } else {
+                (bytes as u128 * format.sample_rate as u128)
+                    .checked_div(format.byte_rate as u128)
+                    .unwrap_or(0) as i64
+            }
+        };
+
+        // Unknown or overstated sizes end with the data, once it is complete.
+        let available = reader.len().saturating_sub(header.data_offset);
+        let data_size = match header.data_size {
+            Some(size) if !reader.is_complete() || size <= available => Some(size),
+            _ if reader.is_complete() => Some(available),
+            _ => None,
+        };
+        // A cut-off block at the end of a truncated file is not played.
+        let data_end = data_size.map(|size| size / block * block);
+        let end = data_end.unwrap_or(u64::MAX).min(available / block * block);
+        if framed {
+            self.cut_frames(reader, &header, end)?;
+        } else {
+            let packet_len = (PACKET_LEN / block).max(1) * block;
+            let mut pos = self.data_pos;
+            while pos < end {
+                let size = packet_len.min(end - pos);
+                // Only the last packet of the data may be short.
+                if size < packet_len && Some(pos + size) != data_end {
+                    break;
+                }
+                self.samples.push(SampleEntry {
+                    stream_index: 0,
+                    offset: header.data_offset + pos,
+                    size: size as u32,
+                    continuation: None,
+                    dts: ticks(pos),
+                    pts: ticks(pos),
+                    is_keyframe: true,
+                });
+                pos += size;
+            }
+            self.data_pos = pos;
+        }
+
+        let name = audio_codec(&header.fmt);
+        let mut stream = StreamInfo::new(0, StreamType::Audio, name, format.sample_rate);
+        format.fill_audio_info(stream.audio.insert(AudioInfo::default()));
+        stream.bitrate = (format.byte_rate > 0).then_some(format.byte_rate as u64 * 8);
+        stream.duration_ms = data_size.map(|size| to_ms(ticks(size) as u64, format.sample_rate));
+        // PCM decoders are initialized from the format record.
+        if codec.is_some() {
+            stream.extra_data = Some(header.fmt);
+        }
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn container(&mut self) -> Container {
+        Container {
+            duration_ms: self.stream.as_ref().and_then(|s| s.duration_ms),
+            streams: self.stream.iter().cloned().collect(),
+            samples: std::mem::take(&mut self.samples),
+            ..Container::default()
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::riff::tests::{chunk, list};
+    use super::*;
+    use crate::decoder::mp3::tests::silent_frame;
+    use crate::decoder::AudioCodec;
+    use crate::demuxer::{ByteSource, MemorySource};
+
+    /// Parses a complete in-memory WAVE file.
+    fn parse(data: &[u8]) -> Result<Container> {
+        let mut source = MemorySource::new(data.to_vec());
+        let mut parser = WavParser::new();
+        parser.parse(&mut SourceReader::new(&mut source))?;
+        Ok(parser.container())
+    }
+
+    fn summary(file: &Container) -> Vec<(u64, u32, i64)> {
+        file.samples
+            .iter()
+            .map(|s| (s.offset, s.size, s.pts))
+            .collect()
+    }
+
+    /// Builds a 16-bit stereo 8 kHz file of `frames` sample frames, with a
+    /// `LIST` chunk between `fmt ` and `data`.
+    pub(crate) fn wav_file(frames: usize) -> Vec<u8> {
+        let format = WaveFormat::new(AudioCodec::PcmS16le, 8000, 2);
+        let mut body = chunk(b"fmt ", &format.to_bytes());
+        body.extend(list(b"LIST", b"INFO", &chunk(b"INAM", b"Tone\0")));
+        let samples: Vec<u8> = (0..frames * 4).map(|i| i as u8).collect();
+        body.extend(chunk(b"data", &samples));
+        list(b"RIFF", b"WAVE", &body)
+    }
+
+    #[test]
+    fn test_parse_pcm() {
+        let data = wav_file(10000);
+        assert!(probe(&data));
+        let file = parse(&data).unwrap();
+        let stream = &file.streams[0];
+        assert_eq!(stream.codec, "pcm-s16");
+        assert_eq!(stream.timescale, 8000);
+        assert_eq!(stream.bitrate, Some(256_000));
+        assert_eq!(file.duration_ms, Some(1250));
+        let audio = stream.audio.as_ref().unwrap();
+        assert_eq!((audio.sample_rate, audio.channels), (8000, 2));
+        assert_eq!(audio.bit_depth, Some(16));
+        let format = WaveFormat::parse(stream.extra_data.as_deref().unwrap()).unwrap();
+        assert_eq!(format.codec(), Some(AudioCodec::PcmS16le));
+
+        // 4096 frames of 4 bytes per packet.
+        let data_offset = 12 + 26 + 26 + 8;
+        assert_eq!(
+            summary(&file),
+            vec![
+                (data_offset, 16384, 0),
+                (data_offset + 16384, 16384, 4096),
+                (data_offset + 32768, 7232, 8192),
+            ]
+        );
+        assert!(file.samples.iter().all(|s| s.is_keyframe));
+    }
+
+    #[test]
+    fn test_parse_extensible_and_truncated() {
+        // 24-bit 5.1: 18-byte frames, 910 per packet.
+        let mut format = WaveFormat::new(AudioCodec::PcmS24le, 48000, 6);
+        format.channel_mask = Some(0x60F);
+        let mut body = chunk(b"fmt ", &format.to_bytes());
+        body.extend(chunk(b"data", &[0; 18 * 1000]));
+        let mut data = list(b"RIFF", b"WAVE", &body);
+        // Cut inside the last frame.
+        data.truncate(data.len() - 100);
+
+        let file = parse(&data).unwrap();
+        let stream = &file.streams[0];
+        assert_eq!(stream.codec, "pcm-s24");
+        let audio = stream.audio.as_ref().unwrap();
+        assert_eq!(audio.channel_layout.as_deref(), Some("5.1"));
+        assert_eq!(audio.bit_depth, Some(24));
+        assert_eq!(
+            file.samples
+                .iter()
+                .map(|s| (s.size, s.pts))
+                .collect::<Vec<_>>(),
+            vec![(16380, 0), (1512, 910)]
+        );
+    }
+
+    #[test]
+    fn test_parse_rf64() {
+        let format = WaveFormat::new(AudioCodec::PcmU8, 8000, 1);
+        let mut ds64 = u64::MAX.to_le_bytes().to_vec();
+        ds64.extend(20000u64.to_le_bytes());
+        ds64.extend(20000u64.to_le_bytes());
+        ds64.extend(0u32.to_le_bytes());
+        let mut body = chunk(b"ds64", &ds64);
+        body.extend(chunk(b"fmt ", &format.to_bytes()));
+        body.extend(b"data");
+        body.extend(u32::MAX.to_le_bytes());
+        body.extend(vec![0x80; 20000]);
+        let mut data = b"RF64".to_vec();
+        data.extend(u32::MAX.to_le_bytes());
+        data.extend(b"WAVE");
+        data.extend(body);
+
+        let file = parse(&data).unwrap();
+        assert_eq!(file.streams[0].codec, "pcm-u8");
+        assert_eq!(file.duration_ms, Some(2500));
+        assert_eq!(
+            file.samples.iter().map(|s| s.pts).collect::<Vec<_>>(),
+            vec![0, 16384]
+        );
+        assert_eq!(file.samples[1].size, 20000 - 16384);
+    }
+
+    #[test]
+    fn test_parse_incremental() {
+        let data = wav_file(10000);
+        let mut source = MemorySource::new(data[..20000].to_vec());
+        let mut parser = WavParser::new();
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        // The second packet has not fully arrived.
+        let file = parser.container();
+        assert_eq!(file.duration_ms, Some(1250));
+        assert_eq!(file.samples.len(), 1);
+
+        source.append(&data[20000..]).unwrap();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        // Only the packets cut since are handed over.
+        let pts: Vec<i64> = parser.container().samples.iter().map(|s| s.pts).collect();
+        assert_eq!(pts, vec![4096, 8192]);
+
+        // The header alone is not enough to describe the stream.
+        let mut parser = WavParser::new();
+        let mut source = MemorySource::new(data[..30].to_vec());
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        assert!(parser.container().streams.is_empty());
+        assert!(parse(&data[..30]).is_err());
+    }
+
+    #[test]
+    fn test_parse_mp3() {
+        let mut format = WaveFormat::new(AudioCodec::PcmS16le, 44100, 2);
+        format.format_tag = WAVE_FORMAT_MPEGLAYER3;
+        format.byte_rate = 16000;
+        let frame = silent_frame();
+        let mut payload = vec![0; 2];
+        for _ in 0..3 {
+            payload.extend_from_slice(&frame);
+        }
+        let mut body = chunk(b"fmt ", &format.to_bytes());
+        body.extend(chunk(b"data", &payload));
+        let data = list(b"RIFF", b"WAVE", &body);
+
+        // Frames are cut whole after the leading junk, and the format record
+        // is not passed to the MP3 decoder.
+        let file = parse(&data).unwrap();
+        assert_eq!(file.streams[0].codec, "mp3");
+        assert!(file.streams[0].extra_data.is_none());
+        let data_offset = 12 + 26 + 8 + 2;
+        assert_eq!(
+            summary(&file),
+            vec![
+                (data_offset, 417, 0),
+                (data_offset + 417, 417, 1152),
+                (data_offset + 834, 417, 2304),
+            ]
+        );
+
+        // A frame that has not fully arrived waits for the rest; the data
+        // chunk ends with a padding byte.
+        let mut source = MemorySource::new(data[..data.len() - 2].to_vec());
+        let mut parser = WavParser::new();
+        parser
+            .parse(&mut SourceReader::growing(&mut source))
+            .unwrap();
+        assert_eq!(parser.container().samples.len(), 2);
+        source.append(&data[data.len() - 2..]).unwrap();
+        parser.parse(&mut SourceReader::new(&mut source)).unwrap();
+        assert_eq!(
+            summary(&parser.container()),
+            vec![(data_offset + 834, 417, 2304)]
+        );
+    }
+
+    #[test]
+    fn test_audio_codec_names() {
+        let mut mp3 = WaveFormat::new(AudioCodec::PcmS16le, 44100, 2);
+        mp3.format_tag = 0x0055;
+        assert_eq!(audio_codec(&mp3.to_bytes()), "mp3");
+        let mut float64 = WaveFormat::new(AudioCodec::PcmF32le, 44100, 2);
+        float64.bits_per_sample = 64;
+        assert_eq!(audio_codec(&float64.to_bytes()), "pcm");
+        assert_eq!(audio_codec(&[0x34, 0x12]), "0x1234");
+    }
+}
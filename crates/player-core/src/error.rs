@@ -38,6 +38,10 @@ pub enum PlayerError {
     /// General internal error.
     #[error("Internal error: {0}")]
     Internal(String),
+
+    /// More input data must be appended before the operation can complete.
+    #[error("Need more data")]
+    NeedMoreData,
 }
 
 impl From<PlayerError> for JsValue {
@@ -112,6 +116,7 @@ mod tests {
             PlayerError::invalid_format("unknown format"),
             PlayerError::io("file not found"),
             PlayerError::internal("unexpected state"),
+            PlayerError::NeedMoreData,
         ];
 
         for error in errors {
@@ -5,6 +5,7 @@
 
 use crate::decoder::{AudioFrame, VideoFrame};
 use crate::error::{PlayerError, Result};
+use crate::time::Timestamp;
 use std::collections::VecDeque;
 
 /// Configuration for frame buffers.
@@ -94,13 +95,13 @@ impl VideoFrameBuffer {
     }
 
     /// Returns the PTS of the oldest frame, if any.
-    pub fn front_pts(&self) -> Option<u64> {
-        self.frames.front().map(|f| f.pts_ms)
+    pub fn front_pts(&self) -> Option<Timestamp> {
+        self.frames.front().map(|f| f.pts)
     }
 
     /// Returns the PTS of the newest frame, if any.
-    pub fn back_pts(&self) -> Option<u64> {
-        self.frames.back().map(|f| f.pts_ms)
+    pub fn back_pts(&self) -> Option<Timestamp> {
+        self.frames.back().map(|f| f.pts)
     }
 }
 
@@ -243,22 +244,22 @@ mod tests {
     use super::*;
     use crate::decoder::PixelFormat;
 
-    fn create_test_video_frame(pts_ms: u64) -> VideoFrame {
+    fn create_test_video_frame(pts_ms: i64) -> VideoFrame {
         VideoFrame {
             width: 1920,
             height: 1080,
-            pts_ms,
+            pts: Timestamp::from_ms(pts_ms),
             format: PixelFormat::Yuv420p,
             data: vec![0; 100],
         }
     }
 
-    fn create_test_audio_frame(pts_ms: u64) -> AudioFrame {
+    fn create_test_audio_frame(pts_ms: i64) -> AudioFrame {
         use crate::decoder::SampleFormat;
         AudioFrame {
             channels: 2,
             sample_rate: 48000,
-            pts_ms,
+            pts: Timestamp::from_ms(pts_ms),
             format: SampleFormat::F32,
             data: vec![0; 100],
         }
@@ -285,9 +286,9 @@ mod tests {
         assert_eq!(buffer.len(), 3);
 
         // Pop should return in FIFO order
-        assert_eq!(buffer.pop().unwrap().pts_ms, 100);
-        assert_eq!(buffer.pop().unwrap().pts_ms, 200);
-        assert_eq!(buffer.pop().unwrap().pts_ms, 300);
+        assert_eq!(buffer.pop().unwrap().pts.as_ms(), 100);
+        assert_eq!(buffer.pop().unwrap().pts.as_ms(), 200);
+        assert_eq!(buffer.pop().unwrap().pts.as_ms(), 300);
         assert!(buffer.pop().is_none());
     }
 
@@ -316,8 +317,8 @@ mod tests {
         buffer.push(create_test_video_frame(200)).unwrap();
 
         // Peek should not remove the frame
-        assert_eq!(buffer.peek().unwrap().pts_ms, 100);
-        assert_eq!(buffer.peek().unwrap().pts_ms, 100);
+        assert_eq!(buffer.peek().unwrap().pts.as_ms(), 100);
+        assert_eq!(buffer.peek().unwrap().pts.as_ms(), 100);
         assert_eq!(buffer.len(), 2);
     }
 
@@ -329,8 +330,8 @@ mod tests {
         buffer.push(create_test_video_frame(200)).unwrap();
         buffer.push(create_test_video_frame(300)).unwrap();
 
-        assert_eq!(buffer.front_pts(), Some(100));
-        assert_eq!(buffer.back_pts(), Some(300));
+        assert_eq!(buffer.front_pts(), Some(Timestamp::from_ms(100)));
+        assert_eq!(buffer.back_pts(), Some(Timestamp::from_ms(300)));
     }
 
     #[test]
@@ -354,9 +355,9 @@ mod tests {
         buffer.push(create_test_audio_frame(300)).unwrap();
 
         // Pop should return in FIFO order
-        assert_eq!(buffer.pop().unwrap().pts_ms, 100);
-        assert_eq!(buffer.pop().unwrap().pts_ms, 200);
-        assert_eq!(buffer.pop().unwrap().pts_ms, 300);
+        assert_eq!(buffer.pop().unwrap().pts.as_ms(), 100);
+        assert_eq!(buffer.pop().unwrap().pts.as_ms(), 200);
+        assert_eq!(buffer.pop().unwrap().pts.as_ms(), 300);
     }
 
     #[test]
@@ -38,12 +38,14 @@ pub mod demuxer;
 pub mod error;
 pub mod frame_buffer;
 pub mod subtitle;
+pub mod time;
 
 pub use decoder::{AudioDecoder, VideoDecoder};
-pub use demuxer::Demuxer;
+pub use demuxer::{BlobSource, ByteSource, Demuxer, MemorySource, StreamType};
 pub use error::{PlayerError, Result};
 pub use frame_buffer::{AudioFrameBuffer, FrameBufferManager, VideoFrameBuffer};
 pub use subtitle::{SubtitleParser, SubtitleTrack};
+pub use time::{Timebase, Timestamp};
 
 /// Library version string.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -120,6 +122,66 @@ impl PlayerCore {
         Ok(())
     }
 
+    /// Loads media from a `File` or `Blob`, reading slices on demand.
+    ///
+    /// Packet data is read as packets are requested. MP4, AVI and WAV files
+    /// are only read for their headers and indexes up front; other formats
+    /// are read through once to locate their frames. Must be called from a
+    /// Web Worker.
+    ///
+    /// # Arguments
+    /// * `blob` - Container file (MP4, MKV, etc.)
+    #[wasm_bindgen]
+    pub fn load_blob(&mut self, blob: web_sys::Blob) -> std::result::Result<(), JsValue> {
+        self.state = PlayerState::Loading;
+
+        let source = BlobSource::new(blob).map_err(|e| -> JsValue { e.into() })?;
+        self.demuxer
+            .init_source(Box::new(source))
+            .map_err(|e| -> JsValue { e.into() })?;
+
+        self.state = PlayerState::Ready;
+        Ok(())
+    }
+
+    /// Appends a chunk of media data as it arrives, like MSE's `appendBuffer`.
+    ///
+    /// # Arguments
+    /// * `data` - Next chunk of the container file.
+    ///
+    /// # Returns
+    /// `true` once enough data has arrived to start playback, `false` if more
+    /// data is needed.
+    #[wasm_bindgen]
+    pub fn append_data(&mut self, data: &[u8]) -> std::result::Result<bool, JsValue> {
+        if self.state == PlayerState::Idle {
+            self.state = PlayerState::Loading;
+        }
+
+        let ready = self
+            .demuxer
+            .append_data(data)
+            .map_err(|e| -> JsValue { e.into() })?;
+
+        if ready && self.state == PlayerState::Loading {
+            self.state = PlayerState::Ready;
+        }
+        Ok(ready)
+    }
+
+    /// Signals that the last chunk of media data has been appended.
+    #[wasm_bindgen]
+    pub fn end_of_stream(&mut self) -> std::result::Result<(), JsValue> {
+        self.demuxer
+            .end_of_stream()
+            .map_err(|e| -> JsValue { e.into() })?;
+
+        if self.state == PlayerState::Loading {
+            self.state = PlayerState::Ready;
+        }
+        Ok(())
+    }
+
     /// Starts playback.
     #[wasm_bindgen]
     pub fn play(&mut self) -> std::result::Result<(), JsValue> {
@@ -143,14 +205,20 @@ impl PlayerCore {
     /// Seeks to a specific timestamp.
     ///
     /// # Arguments
-    /// * `timestamp_ms` - Target timestamp in milliseconds.
+    /// * `timestamp_ms` - Target timestamp in milliseconds, with microsecond
+    ///   precision for frame stepping.
+    ///
+    /// # Returns
+    /// The timestamp of the keyframe playback resumes from, in fractional
+    /// milliseconds.
     #[wasm_bindgen]
-    pub fn seek(&mut self, timestamp_ms: u64) -> std::result::Result<(), JsValue> {
-        self.demuxer
-            .seek(timestamp_ms)
+    pub fn seek(&mut self, timestamp_ms: f64) -> std::result::Result<f64, JsValue> {
+        let landed = self
+            .demuxer
+            .seek(Timestamp::from_ms_f64(timestamp_ms))
             .map_err(|e| -> JsValue { e.into() })?;
         self.frame_buffer.clear();
-        Ok(())
+        Ok(landed.as_ms_f64().max(0.0))
     }
 
     /// Resets the player to idle state.
@@ -169,6 +237,108 @@ impl PlayerCore {
         self.demuxer.format().map(|f| format!("{:?}", f))
     }
 
+    /// Returns information about all streams in the container as JSON.
+    #[wasm_bindgen]
+    pub fn streams(&self) -> String {
+        serde_json::to_string(self.demuxer.streams()).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Returns the streams of one type as JSON, for track menus.
+    #[wasm_bindgen]
+    pub fn tracks(&self, stream_type: StreamType) -> String {
+        let tracks: Vec<_> = self.demuxer.streams_of_type(stream_type).collect();
+        serde_json::to_string(&tracks).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Returns the index of the active stream of a type, if any.
+    #[wasm_bindgen]
+    pub fn selected_track(&self, stream_type: StreamType) -> Option<u32> {
+        self.demuxer.selected_stream(stream_type)
+    }
+
+    /// Selects the active stream of a type.
+    ///
+    /// # Arguments
+    /// * `stream_type` - Type of track to select.
+    /// * `index` - Stream index from [`PlayerCore::tracks`], or `undefined`
+    ///   to turn the type off (e.g. hide subtitles).
+    #[wasm_bindgen]
+    pub fn select_track(
+        &mut self,
+        stream_type: StreamType,
+        index: Option<u32>,
+    ) -> std::result::Result<(), JsValue> {
+        self.demuxer
+            .select_stream(stream_type, index)
+            .map_err(|e| -> JsValue { e.into() })
+    }
+
+    /// Returns the media duration in milliseconds, if known.
+    #[wasm_bindgen]
+    pub fn duration_ms(&self) -> Option<f64> {
+        self.demuxer.duration_ms().map(|d| d as f64)
+    }
+
+    /// Returns the container's tags and attachment list as JSON.
+    #[wasm_bindgen]
+    pub fn metadata(&self) -> String {
+        serde_json::to_string(self.demuxer.metadata()).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Returns the data of an attachment, such as cover art.
+    ///
+    /// # Arguments
+    /// * `index` - Index into the `attachments` list of [`PlayerCore::metadata`].
+    #[wasm_bindgen]
+    pub fn attachment_data(&mut self, index: usize) -> std::result::Result<Vec<u8>, JsValue> {
+        self.demuxer
+            .attachment_data(index)
+            .map_err(|e| -> JsValue { e.into() })
+    }
+
+    /// Returns the chapters as JSON.
+    #[wasm_bindgen]
+    pub fn chapters(&self) -> String {
+        serde_json::to_string(self.demuxer.chapters()).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Seeks to the start of the chapter after the playback position.
+    ///
+    /// # Arguments
+    /// * `position_ms` - Current playback position in milliseconds.
+    ///
+    /// # Returns
+    /// The timestamp of the keyframe playback resumes from, in fractional
+    /// milliseconds.
+    #[wasm_bindgen]
+    pub fn seek_next_chapter(&mut self, position_ms: f64) -> std::result::Result<f64, JsValue> {
+        let start_ms = self
+            .demuxer
+            .next_chapter(position_ms as u64)
+            .map(|c| c.start_ms)
+            .ok_or_else(|| JsValue::from_str("No next chapter"))?;
+        self.seek(start_ms as f64)
+    }
+
+    /// Seeks to the start of the current chapter, or of the previous one when
+    /// playback is near the start of the current chapter.
+    ///
+    /// # Arguments
+    /// * `position_ms` - Current playback position in milliseconds.
+    ///
+    /// # Returns
+    /// The timestamp of the keyframe playback resumes from, in fractional
+    /// milliseconds.
+    #[wasm_bindgen]
+    pub fn seek_previous_chapter(&mut self, position_ms: f64) -> std::result::Result<f64, JsValue> {
+        let start_ms = self
+            .demuxer
+            .previous_chapter(position_ms as u64)
+            .map(|c| c.start_ms)
+            .ok_or_else(|| JsValue::from_str("No previous chapter"))?;
+        self.seek(start_ms as f64)
+    }
+
     /// Returns buffer statistics as JSON.
     #[wasm_bindgen]
     pub fn buffer_stats(&self) -> String {
@@ -316,6 +486,16 @@ mod tests {
         assert!(stats.contains("audioFrames"));
     }
 
+    #[test]
+    fn test_player_core_streams_empty() {
+        let player = PlayerCore::new();
+        assert_eq!(player.streams(), "[]");
+        assert_eq!(player.chapters(), "[]");
+        assert_eq!(player.tracks(StreamType::Audio), "[]");
+        assert!(player.selected_track(StreamType::Audio).is_none());
+        assert!(player.duration_ms().is_none());
+    }
+
     #[test]
     fn test_player_core_default() {
         let player = PlayerCore::default();
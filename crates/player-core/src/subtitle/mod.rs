@@ -4,6 +4,7 @@
 //! (SRT, ASS/SSA, VTT) and prepare them for rendering.
 
 use crate::error::{PlayerError, Result};
+use crate::time::Timestamp;
 use serde::{Deserialize, Serialize};
 
 /// Supported subtitle formats.
@@ -22,10 +23,10 @@ pub enum SubtitleFormat {
 pub struct SubtitleCue {
     /// Unique identifier for the cue.
     pub id: String,
-    /// Start time in milliseconds.
-    pub start_ms: u64,
-    /// End time in milliseconds.
-    pub end_ms: u64,
+    /// Start time.
+    pub start: Timestamp,
+    /// End time.
+    pub end: Timestamp,
     /// Subtitle text content.
     pub text: String,
     /// Optional styling information.
@@ -76,10 +77,10 @@ impl SubtitleTrack {
     }
 
     /// Returns cues that should be visible at the given timestamp.
-    pub fn cues_at(&self, timestamp_ms: u64) -> Vec<&SubtitleCue> {
+    pub fn cues_at(&self, timestamp: Timestamp) -> Vec<&SubtitleCue> {
         self.cues
             .iter()
-            .filter(|cue| timestamp_ms >= cue.start_ms && timestamp_ms < cue.end_ms)
+            .filter(|cue| timestamp >= cue.start && timestamp < cue.end)
             .collect()
     }
 
@@ -157,12 +158,12 @@ impl SubtitleParser {
 
             // Parse timing line (format: "00:00:01,000 --> 00:00:04,000")
             let timing_line = lines[1];
-            if let Some((start_ms, end_ms)) = self.parse_srt_timing(timing_line) {
+            if let Some((start, end)) = self.parse_srt_timing(timing_line) {
                 let text = lines[2..].join("\n");
                 track.add_cue(SubtitleCue {
                     id: format!("{}", index + 1),
-                    start_ms,
-                    end_ms,
+                    start,
+                    end,
                     text,
                     style: None,
                 });
@@ -172,8 +173,8 @@ impl SubtitleParser {
         Ok(track)
     }
 
-    /// Parses SRT timing string to milliseconds.
-    fn parse_srt_timing(&self, timing: &str) -> Option<(u64, u64)> {
+    /// Parses SRT timing string to start and end timestamps.
+    fn parse_srt_timing(&self, timing: &str) -> Option<(Timestamp, Timestamp)> {
         let parts: Vec<&str> = timing.split("-->").collect();
         if parts.len() != 2 {
             return None;
@@ -181,7 +182,10 @@ impl SubtitleParser {
 
         let start = self.parse_srt_timestamp(parts[0].trim())?;
         let end = self.parse_srt_timestamp(parts[1].trim())?;
-        Some((start, end))
+        Some((
+            Timestamp::from_ms(start as i64),
+            Timestamp::from_ms(end as i64),
+        ))
     }
 
     /// Parses a single SRT timestamp to milliseconds.
@@ -238,36 +242,36 @@ mod tests {
 
         track.add_cue(SubtitleCue {
             id: "1".to_string(),
-            start_ms: 1000,
-            end_ms: 3000,
+            start: Timestamp::from_ms(1000),
+            end: Timestamp::from_ms(3000),
             text: "First subtitle".to_string(),
             style: None,
         });
 
         track.add_cue(SubtitleCue {
             id: "2".to_string(),
-            start_ms: 2500,
-            end_ms: 5000,
+            start: Timestamp::from_ms(2500),
+            end: Timestamp::from_ms(5000),
             text: "Second subtitle".to_string(),
             style: None,
         });
 
         // Before first subtitle
-        assert!(track.cues_at(500).is_empty());
+        assert!(track.cues_at(Timestamp::from_ms(500)).is_empty());
 
         // Only first subtitle
-        assert_eq!(track.cues_at(1500).len(), 1);
-        assert_eq!(track.cues_at(1500)[0].id, "1");
+        assert_eq!(track.cues_at(Timestamp::from_ms(1500)).len(), 1);
+        assert_eq!(track.cues_at(Timestamp::from_ms(1500))[0].id, "1");
 
         // Both subtitles (overlapping)
-        assert_eq!(track.cues_at(2700).len(), 2);
+        assert_eq!(track.cues_at(Timestamp::from_ms(2700)).len(), 2);
 
         // Only second subtitle
-        assert_eq!(track.cues_at(4000).len(), 1);
-        assert_eq!(track.cues_at(4000)[0].id, "2");
+        assert_eq!(track.cues_at(Timestamp::from_ms(4000)).len(), 1);
+        assert_eq!(track.cues_at(Timestamp::from_ms(4000))[0].id, "2");
 
         // After all subtitles
-        assert!(track.cues_at(6000).is_empty());
+        assert!(track.cues_at(Timestamp::from_ms(6000)).is_empty());
     }
 
     #[test]
@@ -319,12 +323,12 @@ with multiple lines
         assert_eq!(track.format, SubtitleFormat::Srt);
         assert_eq!(track.cue_count(), 2);
 
-        assert_eq!(track.cues[0].start_ms, 1000);
-        assert_eq!(track.cues[0].end_ms, 4000);
+        assert_eq!(track.cues[0].start, Timestamp::from_ms(1000));
+        assert_eq!(track.cues[0].end, Timestamp::from_ms(4000));
         assert_eq!(track.cues[0].text, "Hello, World!");
 
-        assert_eq!(track.cues[1].start_ms, 5500);
-        assert_eq!(track.cues[1].end_ms, 8000);
+        assert_eq!(track.cues[1].start, Timestamp::from_ms(5500));
+        assert_eq!(track.cues[1].end, Timestamp::from_ms(8000));
         assert!(track.cues[1].text.contains("multiple lines"));
     }
 
@@ -358,8 +362,8 @@ with multiple lines
     fn test_subtitle_cue_serialization() {
         let cue = SubtitleCue {
             id: "1".to_string(),
-            start_ms: 1000,
-            end_ms: 3000,
+            start: Timestamp::from_ms(1000),
+            end: Timestamp::from_ms(3000),
             text: "Test".to_string(),
             style: None,
         };
@@ -368,8 +372,8 @@ with multiple lines
         let deserialized: SubtitleCue = serde_json::from_str(&json).unwrap();
 
         assert_eq!(cue.id, deserialized.id);
-        assert_eq!(cue.start_ms, deserialized.start_ms);
-        assert_eq!(cue.end_ms, deserialized.end_ms);
+        assert_eq!(cue.start, deserialized.start);
+        assert_eq!(cue.end, deserialized.end);
         assert_eq!(cue.text, deserialized.text);
     }
 }
@@ -0,0 +1,184 @@
+//! Rational timestamps.
+//!
+//! Containers count time in their own units (90 kHz for MPEG-TS, the sample
+//! rate for audio tracks, 1/1000 for Matroska by default). A [`Timestamp`]
+//! keeps the tick count together with its [`Timebase`], so 29.97 fps frame
+//! times and AAC frame durations survive the trip from demuxer to renderer
+//! without rounding to whole milliseconds.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Duration of one tick, as a fraction of a second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Timebase {
+    /// Numerator, in seconds.
+    pub num: u32,
+    /// Denominator, in seconds.
+    pub den: u32,
+}
+
+impl Timebase {
+    /// One tick per millisecond.
+    pub const MILLISECONDS: Self = Self::new(1, 1000);
+    /// One tick per microsecond.
+    pub const MICROSECONDS: Self = Self::new(1, 1_000_000);
+
+    /// Creates a time base of `num / den` seconds per tick.
+    pub const fn new(num: u32, den: u32) -> Self {
+        Self { num, den }
+    }
+
+    /// Creates the time base of a clock running at `rate` ticks per second,
+    /// such as an MP4 timescale or an audio sample rate.
+    pub const fn from_rate(rate: u32) -> Self {
+        Self::new(1, rate)
+    }
+}
+
+/// A point in time, counted in ticks of a [`Timebase`].
+///
+/// Timestamps in different time bases compare by the instant they denote.
+/// Ticks are signed: samples trimmed by an edit list or encoder priming are
+/// presented before zero.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Timestamp {
+    /// Number of ticks since the start of the presentation.
+    pub ticks: i64,
+    /// Duration of one tick.
+    pub timebase: Timebase,
+}
+
+impl Timestamp {
+    /// Time zero.
+    pub const ZERO: Self = Self::new(0, Timebase::MILLISECONDS);
+
+    /// Creates a timestamp of `ticks` in `timebase`.
+    pub const fn new(ticks: i64, timebase: Timebase) -> Self {
+        Self { ticks, timebase }
+    }
+
+    /// Creates a timestamp from whole milliseconds.
+    pub const fn from_ms(ms: i64) -> Self {
+        Self::new(ms, Timebase::MILLISECONDS)
+    }
+
+    /// Creates a timestamp from fractional milliseconds, as passed from
+    /// JavaScript, keeping microsecond precision.
+    pub fn from_ms_f64(ms: f64) -> Self {
+        Self::new((ms * 1000.0).round() as i64, Timebase::MICROSECONDS)
+    }
+
+    /// Returns the numerator and denominator of this instant in seconds.
+    fn seconds(self) -> (i128, i128) {
+        (
+            self.ticks as i128 * self.timebase.num as i128,
+            self.timebase.den as i128,
+        )
+    }
+
+    /// Converts to another time base, rounding towards negative infinity.
+    ///
+    /// A zero denominator yields zero ticks.
+    pub fn rescale(self, timebase: Timebase) -> Self {
+        let (num, den) = self.seconds();
+        let target_den = den * timebase.num as i128;
+        let ticks = if target_den == 0 {
+            0
+        } else {
+            (num * timebase.den as i128).div_euclid(target_den)
+        };
+        Self::new(
+            ticks.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+            timebase,
+        )
+    }
+
+    /// Returns whole milliseconds, rounding towards negative infinity.
+    pub fn as_ms(self) -> i64 {
+        self.rescale(Timebase::MILLISECONDS).ticks
+    }
+
+    /// Returns whole microseconds, rounding towards negative infinity.
+    pub fn as_us(self) -> i64 {
+        self.rescale(Timebase::MICROSECONDS).ticks
+    }
+
+    /// Returns fractional milliseconds, for passing to JavaScript.
+    pub fn as_ms_f64(self) -> f64 {
+        let (num, den) = self.seconds();
+        if den == 0 {
+            return 0.0;
+        }
+        (num * 1000) as f64 / den as f64
+    }
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a_num, a_den) = self.seconds();
+        let (b_num, b_den) = other.seconds();
+        (a_num * b_den).cmp(&(b_num * a_den))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_rescale() {
+        // Third frame at 29.97 fps.
+        let pts = Timestamp::new(2 * 1001, Timebase::new(1, 30000));
+        assert_eq!(pts.as_ms(), 66);
+        assert_eq!(pts.as_us(), 66733);
+        assert!((pts.as_ms_f64() - 66.7333).abs() < 1e-3);
+        assert_eq!(pts.rescale(Timebase::from_rate(90000)).ticks, 6006);
+
+        let priming = Timestamp::new(-1024, Timebase::from_rate(48000));
+        assert_eq!(priming.as_ms(), -22);
+        assert_eq!(Timestamp::new(5, Timebase::new(1, 0)).as_ms(), 0);
+    }
+
+    #[test]
+    fn test_timestamp_ordering_across_timebases() {
+        let a = Timestamp::new(45000, Timebase::from_rate(90000));
+        let b = Timestamp::from_ms(500);
+        assert_eq!(a, b);
+        assert!(Timestamp::new(1001, Timebase::new(1, 30000)) > Timestamp::from_ms(33));
+        assert!(Timestamp::new(-1, Timebase::from_rate(48000)) < Timestamp::ZERO);
+        assert_eq!(
+            Timestamp::from_ms_f64(33.3666),
+            Timestamp::new(33367, Timebase::MICROSECONDS)
+        );
+    }
+
+    #[test]
+    fn test_timestamp_serialization() {
+        let pts = Timestamp::new(3003, Timebase::new(1, 90000));
+        let json = serde_json::to_string(&pts).unwrap();
+        assert_eq!(json, r#"{"ticks":3003,"timebase":{"num":1,"den":90000}}"#);
+        let deserialized: Timestamp = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.ticks, 3003);
+    }
+}